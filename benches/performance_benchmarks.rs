@@ -1,5 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use extractor::{BioFilter, Config, FileIndex, FilterCondition, ColumnFilter, NumericCondition};
+use extractor::testdata::SyntheticData;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -35,7 +36,7 @@ fn bench_row_lookup(c: &mut Criterion) {
             
             filter.add_filter(Box::new(ColumnFilter::new(
                 "gene_id".to_string(),
-                FilterCondition::Equals("GENE_500000".to_string())
+                FilterCondition::Equals("ENSG00000500000".to_string())
             ).unwrap()));
             
             black_box(filter.process().unwrap())
@@ -51,7 +52,7 @@ fn bench_row_lookup(c: &mut Criterion) {
             
             filter.add_filter(Box::new(ColumnFilter::new(
                 "gene_id".to_string(),
-                FilterCondition::Equals("GENE_500000".to_string())
+                FilterCondition::Equals("ENSG00000500000".to_string())
             ).unwrap()));
             
             black_box(filter.process().unwrap())
@@ -178,30 +179,6 @@ fn bench_memory_usage(c: &mut Criterion) {
 
 /// Helper function to create benchmark data
 fn setup_benchmark_data(filename: &str, rows: usize) -> Result<(), Box<dyn std::error::Error>> {
-    use rand::Rng;
-    use std::fs::File;
-    use std::io::Write;
-
-    let mut file = File::create(filename)?;
-    
-    // Write header
-    writeln!(file, "gene_id,gene_name,chromosome,expression,p_value,condition,group")?;
-    
-    // Generate test data
-    let mut rng = rand::thread_rng();
-    let data = (0..rows).map(|i| {
-        let gene_id = format!("GENE_{}", i);
-        let gene_name = format!("Name_{}", i);
-        let chr = format!("chr{}", (i % 23) + 1);
-        let expression = rng.gen_range(0.0..100.0);
-        let p_value = rng.gen_range(0.0..1.0);
-        let condition = if i % 2 == 0 { "control" } else { "treated" };
-        let group = rng.gen_range(1..=5);
-        
-        format!("{},{},{},{:.2},{:.4},{},{}", gene_id, gene_name, chr, expression, p_value, condition, group)
-    }).collect::<Vec<_>>().join("\n");
-    
-    file.write_all(data.as_bytes())?;
-    
+    SyntheticData::expression(rows).write_file(filename)?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,20 @@
+//! Build script: writes the C header for the `ffi` feature.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+/// Regenerate `include/extractor.h` from `src/ffi.rs`
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+    cbindgen::generate(&crate_dir)
+        .expect("failed to generate include/extractor.h")
+        .write_to_file(format!("{crate_dir}/include/extractor.h"));
+}
@@ -53,7 +53,7 @@ where
 }
 
 fn build_filter(input: &str, output: &str, index: Option<&str>) -> DynResult<BioFilter> {
-    let mut b = BioFilter::builder(input, output);
+    let mut b = BioFilter::builder(input, output).overwrite(true);
     if let Some(idx) = index {
         b = b.with_index(idx);
     }
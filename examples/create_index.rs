@@ -1,4 +1,5 @@
-use extractor::index::{FileIndex, Position};
+use extractor::index::{FileIndex, Keep, Position, INDEX_FORMAT_VERSION};
+use extractor::utils::{calculate_file_checksum, ChecksumAlgorithm};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
@@ -89,6 +90,7 @@ fn create_index(
     }
 
     Ok(FileIndex {
+        format_version: INDEX_FORMAT_VERSION,
         metadata: IndexMetadata {
             source_file: PathBuf::from(file_path),
             file_size: std::fs::metadata(file_path)?.len(),
@@ -97,7 +99,9 @@ fn create_index(
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
-            file_checksum: calculate_checksum(file_path)?,
+            file_checksum: calculate_file_checksum(file_path.as_ref(), ChecksumAlgorithm::XxHash64)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?,
+            checksum_algorithm: ChecksumAlgorithm::XxHash64,
             row_count: positions.len() as u64,
             header_position: Position {
                 offset: header_pos,
@@ -108,11 +112,16 @@ fn create_index(
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            // Computed by FileIndex::load
+            key_summaries: HashMap::new(),
         },
         columns: headers,
         primary_column: primary_key.to_string(),
         positions,
+        duplicate_positions: HashMap::new(),
+        duplicate_policy: Keep::Error,
         secondary_indices,
+        row_checkpoints: Vec::new(),
     })
 }
 
@@ -127,25 +136,6 @@ fn read_row_at_position(file_path: &str, position: &Position) -> io::Result<Vec<
     Ok(buffer)
 }
 
-/// Calculate a checksum for the file
-fn calculate_checksum(file_path: &str) -> io::Result<u64> {
-    let mut file = File::open(file_path)?;
-    let mut buffer = [0; 8192];
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-    
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        use std::hash::Hasher;
-        hasher.write(&buffer[..bytes_read]);
-    }
-    
-    use std::hash::Hasher;
-    Ok(hasher.finish())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,6 +2,8 @@ use extractor::{
     BioFilter, Config, FileIndex, FilterCondition, 
     ColumnFilter, NumericCondition, RangeCondition
 };
+use extractor::index::IndexedReader;
+use extractor::testdata::SyntheticData;
 use std::path::PathBuf;
 use std::time::Instant;
 
@@ -38,6 +40,7 @@ fn primary_index_example() -> Result<(), Box<dyn std::error::Error>> {
     // Use index for filtering
     let mut filter = BioFilter::builder("large_dataset.csv", "output_primary.csv")
         .with_index("gene_index.json")
+        .overwrite(true)
         .build()?;
 
     filter.add_filter(Box::new(ColumnFilter::new(
@@ -62,6 +65,7 @@ fn secondary_index_example() -> Result<(), Box<dyn std::error::Error>> {
     // Use secondary index for chromosome-based query
     let mut filter = BioFilter::builder("large_dataset.csv", "output_secondary.csv")
         .with_index("multi_index.json")
+        .overwrite(true)
         .build()?;
 
     filter.add_filter(Box::new(ColumnFilter::new(
@@ -84,6 +88,7 @@ fn performance_comparison() -> Result<(), Box<dyn std::error::Error>> {
     // Without index
     let start = Instant::now();
     let mut filter_no_index = BioFilter::builder("large_dataset.csv", "output_no_index.csv")
+        .overwrite(true)
         .build()?;
 
     filter_no_index.add_filter(Box::new(ColumnFilter::new(
@@ -103,6 +108,7 @@ fn performance_comparison() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut filter_with_index = BioFilter::builder("large_dataset.csv", "output_with_index.csv")
         .with_index("expression_index.json")
+        .overwrite(true)
         .build()?;
 
     filter_with_index.add_filter(Box::new(ColumnFilter::new(
@@ -124,14 +130,15 @@ fn performance_comparison() -> Result<(), Box<dyn std::error::Error>> {
 fn random_access_example() -> Result<(), Box<dyn std::error::Error>> {
     let index = FileIndex::load("gene_index.json")?;
 
-    // Access specific genes by ID
-    let genes_of_interest = vec!["ENSG00000139618", "ENSG00000141510", "ENSG00000157764"];
-    
-    for gene_id in genes_of_interest {
-        if let Some(position) = index.get_position(gene_id) {
-            // Read the specific row using the position
-            let row = read_row_at_position("large_dataset.csv", position)?;
-            println!("Found gene {}: {}", gene_id, String::from_utf8_lossy(&row));
+    // Access specific genes by ID; the reader sorts and coalesces the reads
+    let genes_of_interest = ["ENSG00000139618", "ENSG00000141510", "ENSG00000157764"];
+    let mut reader = IndexedReader::new(&index)?;
+    let rows = reader.get_many(&genes_of_interest)?;
+
+    for (gene_id, row) in genes_of_interest.iter().zip(rows) {
+        if let Some(row) = row {
+            let fields: Vec<_> = row.iter().map(String::from_utf8_lossy).collect();
+            println!("Found gene {}: {}", gene_id, fields.join(","));
         }
     }
 
@@ -150,6 +157,7 @@ fn complex_query_example() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut filter = BioFilter::builder("large_dataset.csv", "output_complex.csv")
         .with_index("complex_index.json")
+        .overwrite(true)
         .build()?;
 
     // Complex filtering criteria
@@ -183,49 +191,7 @@ fn complex_query_example() -> Result<(), Box<dyn std::error::Error>> {
 
 /// Create a large sample dataset
 fn create_large_dataset() -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::Write;
-
-    let mut file = File::create("large_dataset.csv")?;
-    
-    // Write header
-    writeln!(file, "gene_id,gene_name,chromosome,start,end,strand,gene_type,tpm,fpkm")?;
-
-    // Generate 100,000 sample genes
-    for i in 0..100_000 {
-        let gene_id = format!("ENSG{:011}", i);
-        let gene_name = format!("GENE_{}", i);
-        let chr = format!("chr{}", (i % 23) + 1);
-        let start = i * 1000 + 1;
-        let end = start + 999;
-        let strand = if i % 2 == 0 { "+" } else { "-" };
-        let gene_type = match i % 5 {
-            0 => "protein_coding",
-            1 => "lncRNA",
-            2 => "miRNA",
-            3 => "pseudogene",
-            _ => "other",
-        };
-        let tpm = (i as f64 % 1000.0) + 0.1;
-        let fpkm = tpm * 1.2;
-
-        writeln!(file, "{},{},{},{},{},{},{},{:.2},{:.2}",
-            gene_id, gene_name, chr, start, end, strand, gene_type, tpm, fpkm)?;
-    }
-
+    // 100,000 sample genes
+    SyntheticData::expression(100_000).write_file("large_dataset.csv")?;
     Ok(())
 }
-
-/// Helper function to read a specific row using its position
-fn read_row_at_position(file_path: &str, position: &Position) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::{Read, Seek, SeekFrom};
-
-    let mut file = File::open(file_path)?;
-    let mut buffer = vec![0; position.length as usize];
-    
-    file.seek(SeekFrom::Start(position.offset))?;
-    file.read_exact(&mut buffer)?;
-    
-    Ok(buffer)
-}
\ No newline at end of file
@@ -0,0 +1,143 @@
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#ifndef EXTRACTOR_H
+#define EXTRACTOR_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result code of every `extractor_*` call
+ */
+typedef enum ExtractorStatus {
+  /**
+   * The call succeeded
+   */
+  EXTRACTOR_STATUS_OK = 0,
+  /**
+   * A pointer was null or a string was not valid UTF-8 / JSON
+   */
+  EXTRACTOR_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * Reading the input or writing the output failed
+   */
+  EXTRACTOR_STATUS_IO = 2,
+  /**
+   * A filter refers to a column the input does not have
+   */
+  EXTRACTOR_STATUS_COLUMN_NOT_FOUND = 3,
+  /**
+   * Any other library error
+   */
+  EXTRACTOR_STATUS_FAILED = 4,
+  /**
+   * The library panicked; the handle should be freed
+   */
+  EXTRACTOR_STATUS_PANIC = 5,
+} ExtractorStatus;
+
+/**
+ * Opaque extraction handle, created by `extractor_open`
+ */
+typedef struct ExtractorHandle ExtractorHandle;
+
+/**
+ * Counters from the most recent `extractor_process` call
+ */
+typedef struct ExtractorStats {
+  /**
+   * Number of rows processed
+   */
+  uint64_t rows_processed;
+  /**
+   * Number of rows matched
+   */
+  uint64_t rows_matched;
+  /**
+   * Processing time in milliseconds
+   */
+  uint64_t processing_time_ms;
+  /**
+   * Input file size in bytes
+   */
+  uint64_t input_size;
+  /**
+   * Output file size in bytes
+   */
+  uint64_t output_size;
+} ExtractorStats;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open `input_path` for filtering into `output_path` with the default
+ * configuration and store the new handle in `*out`.
+ *
+ * # Safety
+ *
+ * `input_path` and `output_path` must be NUL-terminated strings and `out`
+ * must point to writable storage for a handle pointer.
+ */
+enum ExtractorStatus extractor_open(const char *input_path,
+                                    const char *output_path,
+                                    struct ExtractorHandle **out);
+
+/**
+ * Add a filter on `column`. `condition_json` is a `FilterCondition` in its
+ * serde form, e.g. `{"Numeric":{"LessThan":5e-8}}` or `"NotEmpty"`.
+ *
+ * # Safety
+ *
+ * `handle` must come from `extractor_open` and not have been freed; the
+ * strings must be NUL-terminated.
+ */
+enum ExtractorStatus extractor_add_filter(struct ExtractorHandle *handle,
+                                          const char *column,
+                                          const char *condition_json);
+
+/**
+ * Run the extraction, writing matched rows to the output path
+ *
+ * # Safety
+ *
+ * `handle` must come from `extractor_open` and not have been freed.
+ */
+enum ExtractorStatus extractor_process(struct ExtractorHandle *handle);
+
+/**
+ * Copy the statistics of the last `extractor_process` call into `*out`
+ *
+ * # Safety
+ *
+ * `handle` must come from `extractor_open` and not have been freed, and
+ * `out` must point to a writable `ExtractorStats`.
+ */
+enum ExtractorStatus extractor_get_stats(struct ExtractorHandle *handle,
+                                         struct ExtractorStats *out);
+
+/**
+ * Release a handle. Passing null is a no-op.
+ *
+ * # Safety
+ *
+ * `handle` must be null or come from `extractor_open`, and must not be used
+ * again afterwards.
+ */
+void extractor_free(struct ExtractorHandle *handle);
+
+/**
+ * Message of the last failed call on this thread, or null if none has
+ * failed. The string stays valid until the next failing call on the thread.
+ */
+const char *extractor_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EXTRACTOR_H */
@@ -0,0 +1,41 @@
+/* Filter plugin ABI for builds with the `plugins` feature; mirrors
+ * `PluginVTable` in src/plugin.rs. */
+
+#ifndef EXTRACTOR_PLUGIN_H
+#define EXTRACTOR_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define EXTRACTOR_PLUGIN_ABI_VERSION 1
+
+/**
+ * Function table returned by `extractor_plugin_v1`. Every function must be
+ * set and thread-safe: `test` is called on one instance from several
+ * threads at once.
+ */
+typedef struct ExtractorPluginVTable {
+  /** EXTRACTOR_PLUGIN_ABI_VERSION */
+  uint32_t abi_version;
+  /** Plugin name, NUL-terminated, static */
+  const char *name;
+  /** Create an instance from a NUL-terminated configuration string; NULL if it is invalid */
+  void *(*create)(const char *config);
+  /** Test one field of `len` bytes: 1 keeps the row, 0 drops it, negative marks the field invalid */
+  int32_t (*test)(void *instance, const uint8_t *field, size_t len);
+  /** Free an instance from `create` */
+  void (*destroy)(void *instance);
+} ExtractorPluginVTable;
+
+#ifdef __cplusplus
+extern "C" {
+#endif  // __cplusplus
+
+/** Entry point every plugin library exports */
+const ExtractorPluginVTable *extractor_plugin_v1(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EXTRACTOR_PLUGIN_H */
@@ -0,0 +1,6 @@
+// Forward routine registration to Rust so the linker keeps the static library
+void R_init_extractor_extendr(void *dll);
+
+void R_init_extractor(void *dll) {
+    R_init_extractor_extendr(dll);
+}
@@ -0,0 +1,4 @@
+//! Static library linked into the R package. The exported functions are
+//! defined in extractor's `r` module.
+
+pub use extractor;
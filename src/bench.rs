@@ -0,0 +1,227 @@
+//! Self-benchmark on the caller's own data and hardware: times a plain scan
+//! of an input, a filtered run over it and a run writing to a file, and
+//! reports the throughput in a form that can be saved as JSON and compared
+//! across library versions.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use csv::ByteRecord;
+use serde::Serialize;
+
+use crate::core::BioFilter;
+use crate::filters::{ColumnFilter, FilterCondition};
+use crate::output::{ExistingOutput, RowSink, WriteMode};
+use crate::{Config, Result};
+
+/// Bench runs so far, so concurrent runs write to different output files
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Times runs over one input: the streaming path, and the file-to-file path
+/// `process` takes. Each measurement is the fastest of `iterations` runs, so
+/// a cold page cache only affects the first.
+#[derive(Debug, Clone)]
+pub struct Bench {
+    input: PathBuf,
+    config: Config,
+    filters: Vec<(String, FilterCondition)>,
+    iterations: usize,
+}
+
+/// Throughput of one kind of run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchTiming {
+    /// Wall time of the fastest run
+    pub seconds: f64,
+    /// Input megabytes (10^6 bytes) read per second
+    pub mb_per_second: f64,
+    /// Input rows read per second
+    pub rows_per_second: f64,
+    /// Rows that passed the filters
+    pub rows_matched: u64,
+}
+
+/// Result of `Bench::run`
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    /// Version of the library that ran the benchmark
+    pub version: String,
+    /// Size of the input in bytes
+    pub input_bytes: u64,
+    /// Data rows in the input
+    pub rows: u64,
+    /// Runs each timing is the best of
+    pub iterations: usize,
+    /// Reading and parsing every row, with no filters
+    pub scan: BenchTiming,
+    /// The same with the filters, when any were given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered: Option<BenchTiming>,
+    /// Extra time the filters take as a share of the scan time
+    /// (0.25 is 25% slower)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_overhead: Option<f64>,
+    /// The filters run from file to file, as `process` does: memory mapped
+    /// and split across threads when `Config::parallel` is set. The output
+    /// goes to a temporary file that is removed afterwards.
+    pub to_file: BenchTiming,
+}
+
+impl BenchReport {
+    /// The report as one line of JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl Bench {
+    /// Benchmark `input` read with the default configuration, best of 3 runs
+    pub fn new(input: PathBuf) -> Self {
+        Self { input, config: Config::default(), filters: Vec::new(), iterations: 3 }
+    }
+
+    /// Read the input with `config`, e.g. for its delimiter and aliases
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Time a run with this filter as well as the plain scan
+    pub fn filter(mut self, column: &str, condition: FilterCondition) -> Self {
+        self.filters.push((column.to_string(), condition));
+        self
+    }
+
+    /// Set how many runs each timing is the best of (default: 3)
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Run the benchmark
+    pub fn run(&self) -> Result<BenchReport> {
+        let (scan_time, rows, input_bytes, _) = self.best(&[], None)?;
+        let scan = timing(scan_time, rows, input_bytes, rows);
+        let filtered = if self.filters.is_empty() {
+            None
+        } else {
+            let (time, _, _, matched) = self.best(&self.filters, None)?;
+            Some(timing(time, rows, input_bytes, matched))
+        };
+        let output = std::env::temp_dir().join(format!(
+            "extractor-bench-{}-{}.out",
+            std::process::id(),
+            RUNS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = self.best(&self.filters, Some(&output));
+        let _ = std::fs::remove_file(&output);
+        let (time, _, _, matched) = written?;
+        let to_file = timing(time, rows, input_bytes, matched);
+        let filter_overhead = filtered
+            .as_ref()
+            .filter(|_| scan.seconds > 0.0)
+            .map(|filtered| filtered.seconds / scan.seconds - 1.0);
+        Ok(BenchReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            input_bytes,
+            rows,
+            iterations: self.iterations,
+            scan,
+            filtered,
+            filter_overhead,
+            to_file,
+        })
+    }
+
+    /// Fastest of `iterations` runs with `filters`, with the rows read,
+    /// bytes read and rows matched. Rows are written to `output` by `process`
+    /// when given, and streamed and dropped otherwise.
+    fn best(&self, filters: &[(String, FilterCondition)], output: Option<&Path>) -> Result<(Duration, u64, u64, u64)> {
+        let config = match output {
+            Some(_) => Config {
+                write_mode: WriteMode::Direct,
+                on_existing_output: ExistingOutput::Overwrite,
+                ..self.config.clone()
+            },
+            None => self.config.clone(),
+        };
+        let output_path = output.map_or_else(PathBuf::new, Path::to_path_buf);
+        let mut filter = BioFilter::new(self.input.clone(), output_path, config, None)?;
+        for (column, condition) in filters {
+            filter.add_filter(Box::new(ColumnFilter::new(column.clone(), condition.clone())?));
+        }
+        let mut best: Option<(Duration, u64, u64, u64)> = None;
+        for _ in 0..self.iterations {
+            let started = Instant::now();
+            let stats = match output {
+                Some(_) => filter.process()?,
+                None => filter.process_into(&mut Discard)?,
+            };
+            let elapsed = started.elapsed();
+            if best.is_none_or(|(time, ..)| elapsed < time) {
+                best = Some((elapsed, stats.rows_processed, stats.input_size, stats.rows_matched));
+            }
+        }
+        Ok(best.expect("at least one iteration"))
+    }
+}
+
+fn timing(time: Duration, rows: u64, bytes: u64, rows_matched: u64) -> BenchTiming {
+    let seconds = time.as_secs_f64();
+    let per_second = |n: f64| if seconds > 0.0 { n / seconds } else { 0.0 };
+    BenchTiming {
+        seconds,
+        mb_per_second: per_second(bytes as f64 / 1e6),
+        rows_per_second: per_second(rows as f64),
+        rows_matched,
+    }
+}
+
+/// Sink that drops every row, so only reading and filtering are timed
+struct Discard;
+
+impl RowSink for Discard {
+    fn begin(&mut self, _header: &ByteRecord) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_row(&mut self, _record: &ByteRecord) -> Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::NumericCondition;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_bench_report() -> Result<()> {
+        let input = NamedTempFile::new()?;
+        let rows: String = (0..1000).map(|i| format!("rs{i},{}\n", i as f64 / 1000.0)).collect();
+        std::fs::write(input.path(), format!("rsid,p\n{rows}"))?;
+
+        let report = Bench::new(input.path().into())
+            .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(0.05)))
+            .iterations(2)
+            .run()?;
+        assert_eq!(report.rows, 1000);
+        assert_eq!(report.input_bytes, std::fs::metadata(input.path())?.len());
+        assert_eq!(report.scan.rows_matched, 1000);
+        assert_eq!(report.filtered.as_ref().map(|t| t.rows_matched), Some(50));
+        assert_eq!(report.to_file.rows_matched, 50);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["scan"]["mb_per_second"].is_number());
+        assert!(json.get("filter_overhead").is_some());
+        assert!(json["to_file"]["seconds"].is_number());
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! BGZF, the blocked gzip format of `bgzip`, and tabix indexes over it.
+//! See the SAM/BAM and tabix specifications for the layouts.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+
+use crate::compression::TabixColumns;
+
+/// Uncompressed bytes per block, as written by htslib
+const BLOCK_SIZE: usize = 0xff00;
+
+/// Empty block that marks the end of a BGZF file
+const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0x1b, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Writes BGZF blocks, optionally indexing each line as it goes
+pub(crate) struct BgzfWriter<W: Write> {
+    inner: W,
+    /// Uncompressed bytes of the current block
+    block: Vec<u8>,
+    /// Compressed bytes written so far, i.e. where the next block starts
+    offset: u64,
+    index: Option<TabixBuilder>,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub(crate) fn new(inner: W, index: Option<TabixBuilder>) -> Self {
+        Self { inner, block: Vec::with_capacity(BLOCK_SIZE), offset: 0, index }
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let compressed = compress_block(&self.block)?;
+        self.inner.write_all(&compressed)?;
+        let block_end = virtual_offset(self.offset, self.block.len());
+        self.offset += compressed.len() as u64;
+        if let Some(index) = &mut self.index {
+            index.block_written(block_end, virtual_offset(self.offset, 0));
+        }
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Write the last block and the end-of-file marker, returning the inner
+    /// writer and the serialized index
+    pub(crate) fn finish(mut self) -> io::Result<(W, Option<Vec<u8>>)> {
+        let end = virtual_offset(self.offset, self.block.len());
+        if let Some(index) = &mut self.index {
+            index.end_line(end)?;
+        }
+        self.flush_block()?;
+        self.inner.write_all(&EOF_BLOCK)?;
+        Ok((self.inner, self.index.map(TabixBuilder::serialize)))
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let piece = &buf[..buf.len().min(BLOCK_SIZE - self.block.len())];
+        if let Some(index) = &mut self.index {
+            index.feed(piece, self.offset, self.block.len())?;
+        }
+        self.block.extend_from_slice(piece);
+        if self.block.len() == BLOCK_SIZE {
+            self.flush_block()?;
+        }
+        Ok(piece.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner.flush()
+    }
+}
+
+/// Position in a BGZF file: the compressed offset of a block and an offset
+/// into its uncompressed data
+fn virtual_offset(block: u64, within: usize) -> u64 {
+    (block << 16) | within as u64
+}
+
+/// One complete gzip member holding `data`
+fn compress_block(data: &[u8]) -> io::Result<Vec<u8>> {
+    let deflate = |level| -> io::Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len()), level);
+        encoder.write_all(data)?;
+        encoder.finish()
+    };
+    let mut compressed = deflate(Compression::default())?;
+    // Incompressible data can grow past the 64KB a block may take up
+    if compressed.len() + 26 > 1 << 16 {
+        compressed = deflate(Compression::none())?;
+    }
+    let mut crc = Crc::new();
+    crc.update(data);
+
+    let block_size = (compressed.len() + 26 - 1) as u16;
+    let mut block = Vec::with_capacity(compressed.len() + 26);
+    block.extend_from_slice(&[0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0]);
+    block.extend_from_slice(&block_size.to_le_bytes());
+    block.extend_from_slice(&compressed);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    Ok(block)
+}
+
+/// Bins and linear index of one chromosome
+#[derive(Default)]
+struct Reference {
+    /// Chunks of virtual offsets per bin
+    bins: BTreeMap<u32, Vec<(u64, u64)>>,
+    /// Smallest start offset of a line overlapping each 16kb window
+    linear: Vec<u64>,
+}
+
+/// Builds a tabix index from the lines written to a BGZF file
+pub(crate) struct TabixBuilder {
+    columns: TabixColumns,
+    /// Chromosome, start and end field indices, once the header is read
+    fields: Option<(usize, usize, usize)>,
+    line: Vec<u8>,
+    /// Virtual offset where `line` starts
+    line_start: u64,
+    names: Vec<String>,
+    references: Vec<Reference>,
+    /// Start of the previous line, to check the output is sorted
+    last_start: u64,
+}
+
+impl TabixBuilder {
+    pub(crate) fn new(columns: TabixColumns) -> Self {
+        Self {
+            columns,
+            fields: None,
+            line: Vec::new(),
+            line_start: 0,
+            names: Vec::new(),
+            references: Vec::new(),
+            last_start: 0,
+        }
+    }
+
+    /// Index the lines in `data`, about to be appended at `within` in the
+    /// block that will start at compressed offset `block`
+    fn feed(&mut self, mut data: &[u8], block: u64, mut within: usize) -> io::Result<()> {
+        while let Some(newline) = data.iter().position(|&b| b == b'\n') {
+            self.line.extend_from_slice(&data[..newline]);
+            within += newline + 1;
+            self.end_line(virtual_offset(block, within))?;
+            data = &data[newline + 1..];
+        }
+        self.line.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// A block ending at `end` was written; a line starting there really
+    /// starts at the beginning of the next block
+    fn block_written(&mut self, end: u64, next: u64) {
+        if self.line_start == end {
+            self.line_start = next;
+        }
+    }
+
+    /// Index the buffered line, which ends just before virtual offset `end`
+    fn end_line(&mut self, end: u64) -> io::Result<()> {
+        let line = std::mem::take(&mut self.line);
+        let start = std::mem::replace(&mut self.line_start, end);
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<&[u8]> = line.split(|&b| b == b'\t').collect();
+        let Some((chrom, beg, stop)) = self.fields else {
+            self.fields = Some(self.resolve(&fields)?);
+            return Ok(());
+        };
+
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let position = |idx: usize| -> io::Result<u64> {
+            let value = fields.get(idx).copied().unwrap_or_default();
+            std::str::from_utf8(value.trim_ascii())
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid(format!("tabix index: invalid position {:?}", String::from_utf8_lossy(value))))
+        };
+        let beg = position(beg)?.saturating_sub(1);
+        let stop = position(stop)?.max(beg + 1);
+        let name = String::from_utf8_lossy(fields.get(chrom).copied().unwrap_or_default().trim_ascii());
+
+        if self.names.last().is_none_or(|last| *last != name) {
+            if self.names.iter().any(|seen| *seen == name) {
+                return Err(invalid(format!("tabix index: rows for {name} are not contiguous")));
+            }
+            self.names.push(name.into_owned());
+            self.references.push(Reference::default());
+        } else if beg < self.last_start {
+            return Err(invalid(format!("tabix index: rows for {name} are not sorted by position")));
+        }
+        self.last_start = beg;
+
+        let reference = self.references.last_mut().expect("pushed above");
+        let chunks = reference.bins.entry(region_bin(beg, stop)).or_default();
+        match chunks.last_mut() {
+            Some(chunk) if chunk.1 == start => chunk.1 = end,
+            _ => chunks.push((start, end)),
+        }
+        let last_window = ((stop - 1) >> 14) as usize;
+        if reference.linear.len() <= last_window {
+            reference.linear.resize(last_window + 1, 0);
+        }
+        for window in &mut reference.linear[(beg >> 14) as usize..=last_window] {
+            if *window == 0 || start < *window {
+                *window = start;
+            }
+        }
+        Ok(())
+    }
+
+    /// Field indices of the indexed columns in the header row
+    fn resolve(&self, header: &[&[u8]]) -> io::Result<(usize, usize, usize)> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|field| field.trim_ascii() == name.as_bytes())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("tabix index: no column {name}")))
+        };
+        let start = find(&self.columns.start)?;
+        let end = match &self.columns.end {
+            Some(end) => find(end)?,
+            None => start,
+        };
+        Ok((find(&self.columns.chrom)?, start, end))
+    }
+
+    /// The index in `.tbi` layout, before BGZF compression
+    fn serialize(self) -> Vec<u8> {
+        let (chrom, start, end) = self.fields.unwrap_or_default();
+        let mut out = b"TBI\x01".to_vec();
+        let int = |out: &mut Vec<u8>, value: i32| out.extend_from_slice(&value.to_le_bytes());
+        int(&mut out, self.names.len() as i32);
+        // Generic format, 1-based columns, `#` comments and one header line
+        for value in [0, chrom as i32 + 1, start as i32 + 1, end as i32 + 1, b'#' as i32, 1] {
+            int(&mut out, value);
+        }
+        int(&mut out, self.names.iter().map(|name| name.len() as i32 + 1).sum());
+        for name in &self.names {
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+        }
+
+        for reference in self.references {
+            int(&mut out, reference.bins.len() as i32);
+            for (bin, chunks) in reference.bins {
+                out.extend_from_slice(&bin.to_le_bytes());
+                int(&mut out, chunks.len() as i32);
+                for (chunk_start, chunk_end) in chunks {
+                    out.extend_from_slice(&chunk_start.to_le_bytes());
+                    out.extend_from_slice(&chunk_end.to_le_bytes());
+                }
+            }
+            int(&mut out, reference.linear.len() as i32);
+            // Windows without lines point at the previous window's offset
+            let mut previous = 0;
+            for offset in reference.linear {
+                previous = if offset == 0 { previous } else { offset };
+                out.extend_from_slice(&previous.to_le_bytes());
+            }
+        }
+        out
+    }
+}
+
+/// Smallest bin of the UCSC binning scheme holding the 0-based half-open
+/// region `beg..end`
+fn region_bin(beg: u64, end: u64) -> u32 {
+    let end = end - 1;
+    for (shift, first) in [(14, 4681), (17, 585), (20, 73), (23, 9), (26, 1)] {
+        if beg >> shift == end >> shift {
+            return (first + (beg >> shift)) as u32;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_bgzf_with_tabix_index() -> io::Result<()> {
+        let mut text = b"chrom\tpos\trsid\n".to_vec();
+        for i in 0..20_000u64 {
+            let chrom = if i < 12_000 { "1" } else { "2" };
+            text.extend_from_slice(format!("{chrom}\t{}\trs{i}\n", 1000 + i * 37).as_bytes());
+        }
+        let mut writer = BgzfWriter::new(Vec::new(), Some(TabixBuilder::new(TabixColumns::new("chrom", "pos"))));
+        // Uneven writes, as rows arrive from the output writer
+        for piece in text.chunks(997) {
+            writer.write_all(piece)?;
+        }
+        let (bgzf, index) = writer.finish()?;
+        assert!(bgzf.ends_with(&EOF_BLOCK));
+
+        let mut decoded = Vec::new();
+        flate2::read::MultiGzDecoder::new(&bgzf[..]).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, text);
+
+        let index = index.expect("index built");
+        assert!(index.starts_with(b"TBI\x01"));
+        assert_eq!(i32::from_le_bytes(index[4..8].try_into().unwrap()), 2);
+        assert_eq!(&index[32..40], b"\x04\0\0\x001\x002\x00");
+
+        let mut unsorted = BgzfWriter::new(Vec::new(), Some(TabixBuilder::new(TabixColumns::new("chrom", "pos"))));
+        assert!(unsorted.write_all(b"chrom\tpos\n1\t500\n1\t100\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! Coarse block index ("zone maps") for range scans.
+//! Records the byte range of every block of rows with a summary of selected
+//! columns, so a scan can skip the blocks a filter rules out without the
+//! memory cost of a per-key index.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use csv::{ByteRecord, ReaderBuilder};
+use serde::{Deserialize, Serialize};
+use crate::core::header_map;
+use crate::error::{ExtractorError, IndexErrorKind};
+use crate::filters::{parse_number, Filter};
+use crate::index::{read_checked, save_checked};
+use crate::utils::{self, ChecksumAlgorithm};
+use crate::Result;
+
+/// Rows per block by default
+pub const DEFAULT_ROWS_PER_BLOCK: u64 = 16_384;
+
+/// Distinct values kept per block and column by default
+pub const DEFAULT_MAX_DISTINCT: usize = 16;
+
+/// Version of the block index file format written by `BlockIndex::save`
+pub const BLOCK_INDEX_FORMAT_VERSION: u32 = 1;
+
+/// What one block holds in one indexed column
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnZone {
+    /// Smallest finite number in the column
+    pub min: Option<f64>,
+    /// Largest finite number in the column
+    pub max: Option<f64>,
+    /// Some value wasn't a finite number. Numeric filters fail on such values
+    /// rather than reject them, so `min` and `max` can't rule the block out.
+    pub non_numeric: bool,
+    /// Some row had no field for the column
+    pub missing: bool,
+    /// Distinct values, sorted, or `None` if there were more than the
+    /// index's `max_distinct` or some weren't UTF-8
+    pub values: Option<Vec<String>>,
+}
+
+impl ColumnZone {
+    fn new(max_distinct: usize) -> Self {
+        Self { values: (max_distinct > 0).then(Vec::new), ..Self::default() }
+    }
+
+    /// Add one row's field, `None` if the row is too short to have one
+    fn add(&mut self, value: Option<&[u8]>, max_distinct: usize) {
+        let Some(value) = value else {
+            self.missing = true;
+            return;
+        };
+        match parse_number(value).filter(|x| x.is_finite()) {
+            Some(x) => {
+                self.min = Some(self.min.map_or(x, |min| min.min(x)));
+                self.max = Some(self.max.map_or(x, |max| max.max(x)));
+            }
+            None => self.non_numeric = true,
+        }
+        let Some(values) = &mut self.values else {
+            return;
+        };
+        let text = std::str::from_utf8(value).ok();
+        match text.map(|s| (s, values.binary_search_by(|v| v.as_str().cmp(s)))) {
+            Some((_, Ok(_))) => {}
+            Some((s, Err(at))) if values.len() < max_distinct => values.insert(at, s.to_string()),
+            _ => self.values = None,
+        }
+    }
+}
+
+/// A run of consecutive rows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    /// Byte offset of the first row
+    pub offset: u64,
+    /// Length of the block in bytes, up to the end of its last row
+    pub length: u64,
+    /// Row number of the first row (the first data row is 1)
+    pub first_row: u64,
+    /// Number of rows in the block
+    pub rows: u64,
+    /// Summary of each indexed column, parallel to `BlockIndex::columns`
+    pub zones: Vec<ColumnZone>,
+}
+
+/// Block index of a delimited file with a header row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIndex {
+    /// Format version the index was built as
+    pub format_version: u32,
+    /// Original file path
+    pub source_file: PathBuf,
+    /// File size at index creation
+    pub file_size: u64,
+    /// Checksum of the file, computed with `checksum_algorithm`
+    pub file_checksum: u64,
+    /// Algorithm used for `file_checksum`
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Field delimiter the file was read with
+    pub delimiter: u8,
+    /// Length of the header row in bytes, including a byte order mark
+    pub header_length: u64,
+    /// Indexed columns
+    pub columns: Vec<String>,
+    /// Blocks in file order
+    pub blocks: Vec<Block>,
+}
+
+impl BlockIndex {
+    /// Create a new block index builder
+    pub fn builder(source_file: PathBuf) -> BlockIndexBuilder {
+        BlockIndexBuilder::new(source_file)
+    }
+
+    /// Load a block index saved with `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = read_checked(path)?;
+        let index: Self = serde_json::from_slice(&json)
+            .map_err(|_| ExtractorError::index_error(IndexErrorKind::InvalidFormat, Some(path)))?;
+        if index.format_version != BLOCK_INDEX_FORMAT_VERSION {
+            return Err(ExtractorError::index_error(
+                IndexErrorKind::UnsupportedVersion {
+                    found: index.format_version,
+                    supported: BLOCK_INDEX_FORMAT_VERSION,
+                },
+                Some(path),
+            ));
+        }
+        Ok(index)
+    }
+
+    /// Save the index atomically, with a checksum footer like `FileIndex::save`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(self).map_err(|e| ExtractorError::index_error(
+            IndexErrorKind::BuildError(e.to_string()),
+            Some(path.to_owned())
+        ))?;
+        save_checked(path, json)
+    }
+
+    /// Verify the index against the current state of the file
+    pub fn verify(&self, file: &File) -> Result<bool> {
+        let size = file.metadata().map_err(|e| ExtractorError::io_error(e, &self.source_file))?.len();
+        Ok(size == self.file_size
+            && utils::checksum_file(file, &self.source_file, self.checksum_algorithm)? == self.file_checksum)
+    }
+
+    /// Blocks that could hold a row passing every filter. `filters` pairs
+    /// each filter with the position of its column in `columns`; filters on
+    /// columns that aren't indexed can't rule anything out and are left out.
+    pub fn candidate_blocks<'a>(&'a self, filters: &'a [(usize, &dyn Filter)]) -> impl Iterator<Item = &'a Block> + 'a {
+        self.blocks
+            .iter()
+            .filter(|block| filters.iter().all(|(column, filter)| filter.may_match(&block.zones[*column])))
+    }
+}
+
+/// Builder for block indices
+pub struct BlockIndexBuilder {
+    source_file: PathBuf,
+    columns: Vec<String>,
+    rows_per_block: u64,
+    max_distinct: usize,
+    delimiter: u8,
+    checksum_algorithm: ChecksumAlgorithm,
+}
+
+impl BlockIndexBuilder {
+    /// Create a new block index builder
+    pub fn new(source_file: PathBuf) -> Self {
+        Self {
+            source_file,
+            columns: Vec::new(),
+            rows_per_block: DEFAULT_ROWS_PER_BLOCK,
+            max_distinct: DEFAULT_MAX_DISTINCT,
+            delimiter: b',',
+            checksum_algorithm: ChecksumAlgorithm::Sampled { blocks: 16 },
+        }
+    }
+
+    /// Summarize `column` in every block
+    pub fn add_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.push(column.into());
+        self
+    }
+
+    /// Set the number of rows per block (default: 16384). Smaller blocks
+    /// skip more precisely at the cost of index size.
+    pub fn with_rows_per_block(mut self, rows: u64) -> Self {
+        self.rows_per_block = rows.max(1);
+        self
+    }
+
+    /// Keep up to `n` distinct values per block and column (default: 16).
+    /// Columns with more values in a block only get their numeric range.
+    pub fn with_max_distinct(mut self, n: usize) -> Self {
+        self.max_distinct = n;
+        self
+    }
+
+    /// Set the field delimiter (default: `,`)
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set the checksum used to detect changes to the source file
+    /// (default: 16 sampled blocks)
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum_algorithm = algorithm;
+        self
+    }
+
+    /// Build the index in one pass over the file
+    pub fn build(self) -> Result<BlockIndex> {
+        let path = &self.source_file;
+        let mut file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let file_size = file.metadata().map_err(|e| ExtractorError::io_error(e, path))?.len();
+        let file_checksum = utils::checksum_file(&file, path, self.checksum_algorithm)?;
+        file.rewind().map_err(|e| ExtractorError::io_error(e, path))?;
+
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(BufReader::new(file));
+        let headers: HashMap<String, usize> = header_map(reader.byte_headers()?);
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                headers.get(column).copied().ok_or_else(|| ExtractorError::column_not_in_headers(column, &headers))
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        let header_length = reader.position().byte();
+
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut record = ByteRecord::new();
+        let mut row = 0;
+        while reader.read_byte_record(&mut record)? {
+            row += 1;
+            let start = record.position().map_or(0, |p| p.byte());
+            let end = reader.position().byte();
+            let full = blocks.last().is_none_or(|block| block.rows == self.rows_per_block);
+            if full {
+                blocks.push(Block {
+                    offset: start,
+                    length: 0,
+                    first_row: row,
+                    rows: 0,
+                    zones: vec![ColumnZone::new(self.max_distinct); columns.len()],
+                });
+            }
+            let block = blocks.last_mut().expect("block pushed above");
+            block.length = end - block.offset;
+            block.rows += 1;
+            for (zone, &idx) in block.zones.iter_mut().zip(&columns) {
+                zone.add(record.get(idx), self.max_distinct);
+            }
+        }
+
+        Ok(BlockIndex {
+            format_version: BLOCK_INDEX_FORMAT_VERSION,
+            source_file: self.source_file,
+            file_size,
+            file_checksum,
+            checksum_algorithm: self.checksum_algorithm,
+            delimiter: self.delimiter,
+            header_length,
+            columns: self.columns,
+            blocks,
+        })
+    }
+}
+
+/// Reads byte ranges of a file one after another, as if they were one file
+pub(crate) struct RangeReader {
+    file: File,
+    ranges: std::vec::IntoIter<(u64, u64)>,
+    /// Bytes left in the current range
+    remaining: u64,
+}
+
+impl RangeReader {
+    /// Read `ranges`, each an offset and length, in order
+    pub(crate) fn new(file: File, ranges: Vec<(u64, u64)>) -> Self {
+        Self { file, ranges: ranges.into_iter(), remaining: 0 }
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((offset, length)) = self.ranges.next() else {
+                return Ok(0);
+            };
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.remaining = length;
+        }
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.file.read(&mut buf[..len])?;
+        if n == 0 && len > 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file is shorter than its block index"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_block_zones() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,chrom,p").unwrap();
+        for i in 0..100 {
+            writeln!(temp_file, "rs{i},chr{},{}", 1 + i / 50, i as f64 / 100.0).unwrap();
+        }
+        writeln!(temp_file, "rs100,chr2,NA").unwrap();
+        let index = BlockIndex::builder(temp_file.path().to_owned())
+            .add_column("p")
+            .add_column("chrom")
+            .with_rows_per_block(25)
+            .build()?;
+        assert_eq!(index.blocks.len(), 5);
+        let first = &index.blocks[0];
+        assert_eq!((first.first_row, first.rows), (1, 25));
+        assert_eq!((first.zones[0].min, first.zones[0].max), (Some(0.0), Some(0.24)));
+        assert_eq!(first.zones[1].values, Some(vec!["chr1".to_string()]));
+        assert!(index.blocks[4].zones[0].non_numeric);
+
+        let data = std::fs::read(temp_file.path())?;
+        let block = &index.blocks[1];
+        let bytes = &data[block.offset as usize..(block.offset + block.length) as usize];
+        assert!(bytes.starts_with(b"rs25,") && bytes.ends_with(b"rs49,chr1,0.49\n"));
+
+        let p = ColumnFilter::new("p".to_string(), FilterCondition::Numeric(NumericCondition::GreaterOrEqual(0.6)))?;
+        let chrom = ColumnFilter::new("chrom".to_string(), FilterCondition::Equals("chr2".to_string()))?;
+        let filters: [(usize, &dyn Filter); 2] = [(0, &p), (1, &chrom)];
+        let kept: Vec<u64> = index.candidate_blocks(&filters).map(|block| block.first_row).collect();
+        // The last block can't be skipped on `p`: "NA" would fail the filter
+        assert_eq!(kept, vec![51, 76, 101]);
+
+        let path = temp_file.path().with_extension("blocks");
+        index.save(&path)?;
+        let loaded = BlockIndex::load(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(loaded.blocks, index.blocks);
+        assert!(loaded.verify(&File::open(temp_file.path())?)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Chromosome names: natural ordering (chr1 < chr2 < … < chr10 < chrX < chrY
+//! < chrM) and comparison that ignores how a source writes them.
+
+use std::cmp::Ordering;
+
+/// A chromosome name reduced to what identifies it. Ordered autosomes first
+/// (numerically), then X, Y, the mitochondrial chromosome, and any other
+/// contig by its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChromKey<'a> {
+    /// Numbered chromosome
+    Autosome(u32),
+    /// X chromosome
+    X,
+    /// Y chromosome
+    Y,
+    /// Mitochondrial chromosome (`M` or `MT`)
+    M,
+    /// Unplaced contigs, scaffolds and anything else, as written
+    Other(&'a str),
+}
+
+impl<'a> ChromKey<'a> {
+    /// Parse a chromosome name. A `chr` prefix (any case) and surrounding
+    /// whitespace are ignored, so `chr7`, `Chr7` and `7` are the same key.
+    pub fn parse(name: &'a str) -> Self {
+        let name = name.trim();
+        let bare = match name.get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("chr") => &name[3..],
+            _ => name,
+        };
+        let digits = !bare.is_empty() && bare.bytes().all(|b| b.is_ascii_digit());
+        if let Some(n) = digits.then(|| bare.parse().ok()).flatten() {
+            return ChromKey::Autosome(n);
+        }
+        let is = |other: &str| bare.eq_ignore_ascii_case(other);
+        if is("X") {
+            ChromKey::X
+        } else if is("Y") {
+            ChromKey::Y
+        } else if is("M") || is("MT") {
+            ChromKey::M
+        } else {
+            ChromKey::Other(name)
+        }
+    }
+}
+
+/// Order two chromosome names naturally
+pub fn compare_chrom(a: &str, b: &str) -> Ordering {
+    ChromKey::parse(a).cmp(&ChromKey::parse(b))
+}
+
+/// Whether two names refer to the same chromosome, e.g. `chrX` and `X`, or
+/// `chrM` and `MT`
+pub fn chrom_eq(a: &str, b: &str) -> bool {
+    ChromKey::parse(a) == ChromKey::parse(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrom_order() {
+        let mut names = vec!["chrM", "chr10", "Y", "chr2", "chrUn_gl000220", "chrX", "1", "chr22"];
+        names.sort_by(|a, b| compare_chrom(a, b));
+        assert_eq!(names, ["1", "chr2", "chr10", "chr22", "chrX", "Y", "chrM", "chrUn_gl000220"]);
+
+        assert!(chrom_eq("chr7", "7"));
+        assert!(chrom_eq("CHRx", "X"));
+        assert!(chrom_eq("chrM", "MT"));
+        assert!(chrom_eq(" 07", "chr7"));
+        assert!(!chrom_eq("chr1", "chr11"));
+        assert!(!chrom_eq("chr1_random", "1"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Clumping: keep only the most significant row per genomic window, or per
+//! LD-independent locus from a precomputed map, like PLINK's `--clump`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use csv::ByteRecord;
+
+use crate::chrom::ChromKey;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::group::{field, Prepass};
+use crate::lookup::LookupTable;
+use crate::Result;
+
+/// How rows are grouped into regions that keep one lead row each
+enum Regions {
+    /// Rows within `window_bp` of a more significant lead on the same
+    /// chromosome are clumped into it
+    Window { chrom_column: String, pos_column: String, window_bp: u64 },
+    /// Rows are grouped by their locus in `loci`, looked up by variant ID
+    Locus { id_column: String, loci: Arc<LookupTable>, locus_idx: usize },
+}
+
+/// Where a candidate row sits
+enum Place {
+    Position(String, u64),
+    Locus(Vec<u8>),
+    /// Not in the locus map, so a locus of its own
+    Unmapped,
+}
+
+/// A row seen in the first pass
+struct Candidate {
+    p: f64,
+    key: Vec<u8>,
+    place: Place,
+}
+
+/// Keeps the row with the smallest p-value in each region and drops the rest.
+/// Leads are chosen greedily, most significant first; rows with the same
+/// coordinates (or ID) and p-value as a lead are kept with it. Add it with
+/// `BioFilter::add_clump_filter`, which runs the first pass.
+pub struct ClumpFilter {
+    p_column: String,
+    regions: Regions,
+    /// Rows that may become leads; every row with a numeric p-value when `None`
+    among: Option<Box<dyn Filter>>,
+    candidates: Mutex<Vec<Candidate>>,
+    /// Keys of the lead rows
+    leads: RwLock<HashSet<Vec<u8>>>,
+}
+
+impl ClumpFilter {
+    /// Keep the best row within every `window_kb` kilobases (either side of
+    /// each lead)
+    pub fn window(chrom_column: String, pos_column: String, p_column: String, window_kb: u64) -> Self {
+        Self::with_regions(
+            p_column,
+            Regions::Window { chrom_column, pos_column, window_bp: window_kb.saturating_mul(1000) },
+        )
+    }
+
+    /// Keep the best row per locus, where `loci` maps the variant IDs in
+    /// `id_column` to a locus in its `locus_column`. Variants missing from the
+    /// map are loci of their own.
+    pub fn by_locus(id_column: String, p_column: String, loci: Arc<LookupTable>, locus_column: &str) -> Result<Self> {
+        let locus_idx = loci
+            .columns()
+            .iter()
+            .position(|column| column == locus_column)
+            .ok_or_else(|| ExtractorError::column_not_found(locus_column, loci.columns()))?;
+        Ok(Self::with_regions(p_column, Regions::Locus { id_column, loci, locus_idx }))
+    }
+
+    fn with_regions(p_column: String, regions: Regions) -> Self {
+        Self {
+            p_column,
+            regions,
+            among: None,
+            candidates: Mutex::new(Vec::new()),
+            leads: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Only consider rows matching `predicate` as leads, e.g. p < 5e-8
+    pub fn among(mut self, predicate: Box<dyn Filter>) -> Self {
+        self.among = Some(predicate);
+        self
+    }
+
+    /// Identity of a row: its coordinates (or ID) and p-value
+    fn key(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<Vec<u8>> {
+        let mut key = match &self.regions {
+            Regions::Window { chrom_column, pos_column, .. } => {
+                let mut key = field(row, chrom_column, headers)?.to_vec();
+                key.push(0);
+                key.extend_from_slice(field(row, pos_column, headers)?);
+                key
+            }
+            Regions::Locus { id_column, .. } => field(row, id_column, headers)?.to_vec(),
+        };
+        key.push(0);
+        key.extend_from_slice(field(row, &self.p_column, headers)?);
+        Ok(key)
+    }
+
+    fn place(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<Option<Place>> {
+        Ok(match &self.regions {
+            Regions::Window { chrom_column, pos_column, .. } => {
+                let chrom = std::str::from_utf8(field(row, chrom_column, headers)?).ok();
+                let pos = std::str::from_utf8(field(row, pos_column, headers)?)
+                    .ok()
+                    .and_then(|pos| pos.parse().ok());
+                chrom.zip(pos).map(|(chrom, pos)| Place::Position(chrom.to_string(), pos))
+            }
+            Regions::Locus { id_column, loci, locus_idx } => {
+                let locus = loci.get(field(row, id_column, headers)?);
+                Some(match locus.and_then(|values| values.get(*locus_idx)) {
+                    Some(locus) => Place::Locus(locus.to_vec()),
+                    None => Place::Unmapped,
+                })
+            }
+        })
+    }
+}
+
+impl Prepass for ClumpFilter {
+    fn observe(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<()> {
+        let eligible = match &self.among {
+            Some(predicate) => predicate.apply(row, headers)?,
+            None => true,
+        };
+        let p = std::str::from_utf8(field(row, &self.p_column, headers)?)
+            .ok()
+            .and_then(|p| p.parse::<f64>().ok())
+            .filter(|p| !p.is_nan());
+        let place = if eligible { self.place(row, headers)? } else { None };
+        if let Some((p, place)) = p.zip(place) {
+            let candidate = Candidate { p, key: self.key(row, headers)?, place };
+            self.candidates.lock().unwrap_or_else(|e| e.into_inner()).push(candidate);
+        }
+        Ok(())
+    }
+
+    fn finish(&self) {
+        let mut candidates = std::mem::take(&mut *self.candidates.lock().unwrap_or_else(|e| e.into_inner()));
+        // Stable, so ties go to the row that came first
+        candidates.sort_by(|a, b| a.p.total_cmp(&b.p));
+
+        let window = match &self.regions {
+            Regions::Window { window_bp, .. } => *window_bp,
+            Regions::Locus { .. } => 0,
+        };
+        let mut taken_positions: HashMap<ChromKey, BTreeSet<u64>> = HashMap::new();
+        let mut taken_loci = HashSet::new();
+        let mut leads = HashSet::new();
+        for candidate in &candidates {
+            let lead = match &candidate.place {
+                Place::Position(chrom, pos) => {
+                    let taken = taken_positions.entry(ChromKey::parse(chrom)).or_default();
+                    let nearby = pos.saturating_sub(window)..=pos.saturating_add(window);
+                    taken.range(nearby).next().is_none() && taken.insert(*pos)
+                }
+                Place::Locus(locus) => taken_loci.insert(locus),
+                Place::Unmapped => true,
+            };
+            if lead {
+                leads.insert(candidate.key.clone());
+            }
+        }
+        *self.leads.write().unwrap_or_else(|e| e.into_inner()) = leads;
+    }
+}
+
+impl Filter for ClumpFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let key = self.key(row, headers)?;
+        Ok(self.leads.read().unwrap_or_else(|e| e.into_inner()).contains(&key))
+    }
+
+    fn column_name(&self) -> &str {
+        &self.p_column
+    }
+
+    fn description(&self) -> String {
+        match &self.regions {
+            Regions::Window { window_bp, .. } => {
+                format!("lowest {} per {} kb window", self.p_column, window_bp / 1000)
+            }
+            Regions::Locus { .. } => format!("lowest {} per locus", self.p_column),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(filter: &ClumpFilter, header: &[&str], rows: &[&[&str]]) -> Result<Vec<usize>> {
+        let headers: HashMap<String, usize> =
+            header.iter().enumerate().map(|(i, name)| (name.to_string(), i)).collect();
+        let rows: Vec<ByteRecord> = rows.iter().map(|row| ByteRecord::from(row.to_vec())).collect();
+        for row in &rows {
+            filter.observe(row, &headers)?;
+        }
+        filter.finish();
+        let mut kept = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            if filter.apply(row, &headers)? {
+                kept.push(i);
+            }
+        }
+        Ok(kept)
+    }
+
+    #[test]
+    fn test_clumping() -> Result<()> {
+        let window = ClumpFilter::window("chrom".into(), "pos".into(), "p".into(), 250);
+        let rows: &[&[&str]] = &[
+            &["1", "1000000", "1e-8"],
+            &["chr1", "1200000", "1e-12"],
+            &["1", "1600000", "1e-9"],
+            &["2", "1000000", "NA"],
+            &["2", "1000500", "0.01"],
+        ];
+        assert_eq!(run(&window, &["chrom", "pos", "p"], rows)?, [1, 2, 4]);
+
+        let map = "rsid,locus\nrs1,L1\nrs2,L1\nrs3,L2\n";
+        let loci = Arc::new(LookupTable::from_reader(map.as_bytes(), "rsid", b',')?);
+        let by_locus = ClumpFilter::by_locus("rsid".into(), "p".into(), loci, "locus")?;
+        let rows: &[&[&str]] = &[&["rs1", "0.2"], &["rs2", "0.1"], &["rs3", "0.5"], &["rs4", "0.9"]];
+        assert_eq!(run(&by_locus, &["rsid", "p"], rows)?, [1, 2, 3]);
+        Ok(())
+    }
+}
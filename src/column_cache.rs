@@ -0,0 +1,232 @@
+//! Columnar cache sidecar for repeated numeric filtering.
+//! Stores where every row is and the values of selected numeric columns in a
+//! compact binary layout, so later runs can test numeric filters against the
+//! stored numbers and only read the rows that pass.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use csv::{ByteRecord, ReaderBuilder};
+use serde::{Deserialize, Serialize};
+use crate::core::header_map;
+use crate::error::{ExtractorError, IndexErrorKind};
+use crate::filters::{parse_number, Filter};
+use crate::index::{read_checked, save_checked};
+use crate::utils::{self, ChecksumAlgorithm};
+use crate::Result;
+
+/// Starts every column cache file
+const MAGIC: &[u8; 8] = b"EXCOLS\r\n";
+
+/// Version of the column cache file format written by `ColumnCache::save`
+pub const COLUMN_CACHE_FORMAT_VERSION: u32 = 1;
+
+/// What a column cache was built from, stored as JSON ahead of its columns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheMetadata {
+    /// Original file path
+    pub source_file: PathBuf,
+    /// File size when the cache was built
+    pub file_size: u64,
+    /// Checksum of the file, computed with `checksum_algorithm`
+    pub file_checksum: u64,
+    /// Algorithm used for `file_checksum`
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Field delimiter the file was read with
+    pub delimiter: u8,
+    /// Length of the header row in bytes, including a byte order mark
+    pub header_length: u64,
+    /// Cached columns
+    pub columns: Vec<String>,
+    /// Number of rows
+    pub rows: u64,
+}
+
+/// Row positions and numeric columns of a delimited file with a header row
+#[derive(Debug, Clone)]
+pub struct ColumnCache {
+    /// What the cache was built from
+    pub metadata: CacheMetadata,
+    /// Byte offset of each row
+    offsets: Vec<u64>,
+    /// Length of each row in bytes
+    lengths: Vec<u32>,
+    /// Values of each column, parallel to `metadata.columns`. `None` where
+    /// the row has no field or it isn't a number.
+    values: Vec<Vec<Option<f64>>>,
+}
+
+impl ColumnCache {
+    /// Cache `columns` of `source_file` in one pass over it
+    pub fn build(source_file: PathBuf, columns: Vec<String>, delimiter: u8) -> Result<Self> {
+        let path = &source_file;
+        let checksum_algorithm = ChecksumAlgorithm::Sampled { blocks: 16 };
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let file_size = file.metadata().map_err(|e| ExtractorError::io_error(e, path))?.len();
+        let file_checksum = utils::checksum_file(&file, path, checksum_algorithm)?;
+
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(BufReader::new(file));
+        let headers = header_map(reader.byte_headers()?);
+        let positions = columns
+            .iter()
+            .map(|column| {
+                headers.get(column).copied().ok_or_else(|| ExtractorError::column_not_in_headers(column, &headers))
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        let header_length = reader.position().byte();
+
+        let mut offsets = Vec::new();
+        let mut lengths = Vec::new();
+        let mut values = vec![Vec::new(); columns.len()];
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let start = record.position().map_or(0, |p| p.byte());
+            offsets.push(start);
+            lengths.push((reader.position().byte() - start) as u32);
+            for (column, &idx) in values.iter_mut().zip(&positions) {
+                column.push(record.get(idx).and_then(parse_number));
+            }
+        }
+
+        Ok(Self {
+            metadata: CacheMetadata {
+                source_file,
+                file_size,
+                file_checksum,
+                checksum_algorithm,
+                delimiter,
+                header_length,
+                columns,
+                rows: offsets.len() as u64,
+            },
+            offsets,
+            lengths,
+            values,
+        })
+    }
+
+    /// Load a cache saved with `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = read_checked(path)?;
+        let invalid = || ExtractorError::index_error(IndexErrorKind::InvalidFormat, Some(path));
+        let mut data = Bytes(data.strip_prefix(MAGIC.as_slice()).ok_or_else(invalid)?);
+        let version = data.u32().ok_or_else(invalid)?;
+        if version != COLUMN_CACHE_FORMAT_VERSION {
+            return Err(ExtractorError::index_error(
+                IndexErrorKind::UnsupportedVersion { found: version, supported: COLUMN_CACHE_FORMAT_VERSION },
+                Some(path),
+            ));
+        }
+        let metadata_length = data.u32().ok_or_else(invalid)? as usize;
+        let metadata: CacheMetadata =
+            serde_json::from_slice(data.take(metadata_length).ok_or_else(invalid)?).map_err(|_| invalid())?;
+        let rows = metadata.rows as usize;
+        let offsets = (0..rows).map(|_| data.u64()).collect::<Option<Vec<_>>>().ok_or_else(invalid)?;
+        let lengths = (0..rows).map(|_| data.u32()).collect::<Option<Vec<_>>>().ok_or_else(invalid)?;
+        let values = metadata
+            .columns
+            .iter()
+            .map(|_| {
+                let numbers = (0..rows).map(|_| data.u64().map(f64::from_bits)).collect::<Option<Vec<_>>>()?;
+                let valid = data.take(rows.div_ceil(8))?;
+                Some(numbers.into_iter().enumerate().map(|(i, x)| ((valid[i / 8] >> (i % 8)) & 1 == 1).then_some(x)).collect())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        if !data.0.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { metadata, offsets, lengths, values })
+    }
+
+    /// Save the cache atomically, with a checksum footer like `FileIndex::save`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let metadata = serde_json::to_vec(&self.metadata).map_err(|e| ExtractorError::index_error(
+            IndexErrorKind::BuildError(e.to_string()),
+            Some(path.to_owned())
+        ))?;
+        let rows = self.offsets.len();
+        let mut contents = Vec::with_capacity(MAGIC.len() + 8 + metadata.len() + rows * (12 + 9 * self.values.len()));
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&COLUMN_CACHE_FORMAT_VERSION.to_le_bytes());
+        contents.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        contents.extend_from_slice(&metadata);
+        for offset in &self.offsets {
+            contents.extend_from_slice(&offset.to_le_bytes());
+        }
+        for length in &self.lengths {
+            contents.extend_from_slice(&length.to_le_bytes());
+        }
+        for column in &self.values {
+            for x in column {
+                contents.extend_from_slice(&x.unwrap_or_default().to_bits().to_le_bytes());
+            }
+            let mut valid = vec![0u8; rows.div_ceil(8)];
+            for (i, x) in column.iter().enumerate() {
+                valid[i / 8] |= u8::from(x.is_some()) << (i % 8);
+            }
+            contents.extend_from_slice(&valid);
+        }
+        save_checked(path, contents)
+    }
+
+    /// Verify the cache against the current state of the file
+    pub fn verify(&self, file: &File) -> Result<bool> {
+        let metadata = &self.metadata;
+        let size = file.metadata().map_err(|e| ExtractorError::io_error(e, &metadata.source_file))?.len();
+        Ok(size == metadata.file_size
+            && utils::checksum_file(file, &metadata.source_file, metadata.checksum_algorithm)? == metadata.file_checksum)
+    }
+
+    /// Cached values of `column`, one per row; `None` where the row has no
+    /// field or it isn't a number
+    pub fn column(&self, column: &str) -> Option<&[Option<f64>]> {
+        let idx = self.metadata.columns.iter().position(|c| c == column)?;
+        Some(&self.values[idx])
+    }
+
+    /// Byte ranges of the rows that pass every filter, with adjacent rows
+    /// merged. `filters` pairs filters that `test_number` with the position
+    /// of their column in `metadata.columns`. Rows whose value isn't a number
+    /// are kept, so reading them reports the error the filter raises.
+    pub fn passing_ranges(&self, filters: &[(usize, &dyn Filter)]) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for (row, (&offset, &length)) in self.offsets.iter().zip(&self.lengths).enumerate() {
+            let pass = filters.iter().all(|(column, filter)| {
+                self.values[*column][row].is_none_or(|x| filter.test_number(x).unwrap_or(true))
+            });
+            if !pass {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((start, len)) if *start + *len == offset => *len += u64::from(length),
+                _ => ranges.push((offset, u64::from(length))),
+            }
+        }
+        ranges
+    }
+}
+
+/// Reads little-endian fields from the front of a byte slice
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = self.0.split_at_checked(n)?;
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes(b.try_into().expect("8 bytes")))
+    }
+}
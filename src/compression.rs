@@ -0,0 +1,170 @@
+//! Compressed output: gzip, zstd, or bgzip with an optional tabix index.
+//! Anything but plain output needs the `compression` feature.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "compression")]
+use crate::bgzf::{BgzfWriter, TabixBuilder};
+use crate::error::ExtractorError;
+use crate::Result;
+
+/// Compression applied to the output file
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutputCompression {
+    /// Plain text (default)
+    #[default]
+    None,
+    /// gzip
+    Gzip,
+    /// Zstandard
+    Zstd,
+    /// BGZF, the blocked gzip written by `bgzip`. Any gzip reader can read
+    /// it; with `tabix` set, a `.tbi` index is written next to the output.
+    Bgzip {
+        /// Columns to index, or `None` for no index
+        tabix: Option<TabixColumns>,
+    },
+}
+
+/// Columns a tabix index is built on, by header name. Positions are 1-based
+/// and inclusive, as in VCF files and GWAS summary statistics, and the output
+/// must be tab-delimited and grouped by chromosome with ascending starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabixColumns {
+    /// Chromosome column
+    pub chrom: String,
+    /// Start position column
+    pub start: String,
+    /// End position column; the start is used when `None`
+    pub end: Option<String>,
+}
+
+impl TabixColumns {
+    /// Index on a chromosome and a position column
+    pub fn new<S: Into<String>>(chrom: S, start: S) -> Self {
+        Self { chrom: chrom.into(), start: start.into(), end: None }
+    }
+
+    /// Also use an end position column
+    pub fn with_end<S: Into<String>>(mut self, end: S) -> Self {
+        self.end = Some(end.into());
+        self
+    }
+}
+
+/// Output writer that compresses whatever is written to it
+pub(crate) enum CompressedWriter<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compression")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "compression")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    #[cfg(feature = "compression")]
+    Bgzf(BgzfWriter<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Wrap `inner`. `delimiter` and `header` (whether the first line written
+    /// is a header row) are needed to build a tabix index.
+    pub(crate) fn new(inner: W, compression: &OutputCompression, delimiter: u8, header: bool) -> Result<Self> {
+        if let OutputCompression::Bgzip { tabix: Some(_) } = compression {
+            if delimiter != b'\t' {
+                return Err(ExtractorError::Config("A tabix index needs tab-delimited output".to_string()));
+            }
+            if !header {
+                return Err(ExtractorError::Config(
+                    "A tabix index needs a header row naming its columns".to_string()
+                ));
+            }
+        }
+        Self::wrap(inner, compression)
+    }
+
+    #[cfg(feature = "compression")]
+    fn wrap(inner: W, compression: &OutputCompression) -> Result<Self> {
+        Ok(match compression {
+            OutputCompression::None => CompressedWriter::Plain(inner),
+            OutputCompression::Gzip => {
+                CompressedWriter::Gzip(flate2::write::GzEncoder::new(inner, flate2::Compression::default()))
+            }
+            OutputCompression::Zstd => CompressedWriter::Zstd(zstd::stream::write::Encoder::new(inner, 0)?),
+            OutputCompression::Bgzip { tabix } => {
+                CompressedWriter::Bgzf(BgzfWriter::new(inner, tabix.clone().map(TabixBuilder::new)))
+            }
+        })
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn wrap(inner: W, compression: &OutputCompression) -> Result<Self> {
+        match compression {
+            OutputCompression::None => Ok(CompressedWriter::Plain(inner)),
+            other => Err(ExtractorError::Config(format!(
+                "{other:?} output requires the `compression` feature"
+            ))),
+        }
+    }
+
+    /// Write any trailer and return the flushed inner writer, with the tabix
+    /// index if one was built
+    pub(crate) fn finish(self) -> Result<(W, Option<Vec<u8>>)> {
+        let (mut inner, index) = match self {
+            CompressedWriter::Plain(inner) => (inner, None),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Gzip(encoder) => (encoder.finish()?, None),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Zstd(encoder) => (encoder.finish()?, None),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Bgzf(writer) => writer.finish()?,
+        };
+        inner.flush()?;
+        Ok((inner, index))
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.write(buf),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Bgzf(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.flush(),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            CompressedWriter::Bgzf(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Write a tabix index for the output at `output` to `<output>.tbi`
+#[cfg(feature = "compression")]
+pub(crate) fn write_tabix(output: &Path, index: &[u8]) -> Result<()> {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".tbi");
+    let path = std::path::PathBuf::from(path);
+    let file = std::fs::File::create(&path).map_err(|e| ExtractorError::io_error(e, &path))?;
+    let mut writer = BgzfWriter::new(io::BufWriter::new(file), None);
+    writer.write_all(index)?;
+    writer.finish()?.0.flush()?;
+    Ok(())
+}
+
+/// Without the `compression` feature no index is ever built
+#[cfg(not(feature = "compression"))]
+pub(crate) fn write_tabix(_output: &Path, _index: &[u8]) -> Result<()> {
+    Ok(())
+}
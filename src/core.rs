@@ -1,36 +1,155 @@
 //! Core processing logic for the Extractor library.
 //! Implements the main filtering and processing functionality.
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+#[cfg(feature = "parallel")]
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use rayon::prelude::*;
-use csv::{ReaderBuilder, WriterBuilder};
-use crossbeam_channel::{bounded, Sender};
-
-use crate::{Config, ProcessingStats};
-use crate::error::{ExtractorError, FilterErrorKind};
-use crate::filters::Filter;
-use crate::index::FileIndex;
-use crate::utils::{self, Progress, SafeMmapOptions};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+#[cfg(feature = "parallel")]
+use std::time::Duration;
+use csv::{ByteRecord, ReaderBuilder, StringRecord};
+#[cfg(feature = "parallel")]
+use crossbeam_channel::{unbounded, Receiver, TryRecvError};
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::{Config, MemoryStats, PipelineStats, ProcessingStats, VerifyMode, ZeroMatchGuard};
+use crate::blocks::{BlockIndex, RangeReader};
+use crate::column_cache::ColumnCache;
+use crate::encoding::{decode_reader, Decoded, InputEncoding, UTF8_BOM};
+use crate::error::{ExtractorError, FilterErrorKind, IndexErrorKind, RowErrorPolicy, RowErrorSummary};
+use crate::explain::{ExplainTarget, FilterOutcome, FilterTrace, RowExplanation};
+use crate::filters::{is_default_empty, Filter, FilterCounters, FilterStats};
+use crate::fixed_width::FixedWidthReader;
+use crate::clump::ClumpFilter;
+use crate::compression::{write_tabix, CompressedWriter, OutputCompression};
+use crate::digest::{digest_bytes, DigestAlgorithm, DigestReader, DigestWriter, InputDigest};
+use crate::group::{GroupFilter, Prepass};
+use crate::index::{ChunkIndex, FileIndex, IndexColumns, Keep};
+use crate::lookup::{LookupFilter, LookupTable};
+use crate::observers::{save_gene_summary, ColumnObserver, DistinctValues, GeneSummaryObserver, ObserverSet};
+use crate::retry::{self, RetryingReader};
+use crate::schema::{HeaderFingerprint, Schema};
+use crate::transform::{ConstantColumns, RowTransform};
+#[cfg(feature = "object-store")]
+use crate::remote;
+#[cfg(feature = "parquet")]
+use crate::parquet::ParquetInput;
+#[cfg(feature = "parallel")]
+use crate::output::row_content;
+use crate::output::{
+    check_appended_header, ends_mid_line, AppendWriter, ExistingOutput, OutputFile, PartitionedWriter, RawCapture,
+    RowSink, RowWriter, ShardManifest, ShardedWriter, ThreadOutput, PARTITION_MANIFEST,
+};
+#[cfg(feature = "parallel")]
+use crate::output::ThreadFiles;
+#[cfg(feature = "parallel")]
+use crate::progress::Progress;
+use crate::utils;
+use crate::validate::{self, ValidationIssue};
+#[cfg(feature = "parallel")]
+use crate::utils::SafeMmapOptions;
 use crate::Result;
 
-/// Chunk of data to be processed
-struct Chunk {
-    data: Vec<u8>,
-    start_offset: u64,
-    chunk_index: usize,
+/// Writer for the output file of single-file runs
+type OutputWriter = CompressedWriter<AppendWriter<DigestWriter<BufWriter<File>>>>;
+
+/// Byte range of the input handled by one worker
+#[cfg(feature = "parallel")]
+struct Chunk<'a> {
+    /// The whole input; rows may run past `end`
+    data: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+/// A named set of filters with its own output file. Several pipelines added to
+/// one `BioFilter` are all evaluated during a single scan of the input.
+pub struct Pipeline {
+    name: String,
+    output_path: PathBuf,
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline writing to `output_path`
+    pub fn new<S: Into<String>, P: Into<PathBuf>>(name: S, output_path: P) -> Self {
+        Self {
+            name: name.into(),
+            output_path: output_path.into(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Add a filter, builder style
+    pub fn with_filter(mut self, filter: Box<dyn Filter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Add a filter to the pipeline
+    pub fn add_filter(&mut self, filter: Box<dyn Filter>) {
+        self.filters.push(filter);
+    }
+
+    /// Pipeline name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Output path of the pipeline
+    pub fn output_path(&self) -> &std::path::Path {
+        &self.output_path
+    }
+
+    fn matches(&self, record: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for filter in &self.filters {
+            if !filter.apply(record, headers)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Rows returned by `BioFilter::preview`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preview {
+    /// Header the output would start with, if it has one
+    pub header: Option<StringRecord>,
+    /// Selected rows as they would be written
+    pub rows: Vec<StringRecord>,
+    /// Input rows read to find them
+    pub rows_scanned: u64,
+    /// Rows skipped under `RowErrorPolicy::Skip`
+    pub row_errors: Vec<RowErrorSummary>,
 }
 
 /// Main processing engine
 pub struct BioFilter {
     config: Config,
     filters: Vec<Box<dyn Filter>>,
+    /// Per-filter match counters, parallel to `filters`
+    filter_counters: Vec<FilterCounters>,
+    pipelines: Vec<Pipeline>,
+    observers: Vec<ColumnObserver>,
+    gene_summary: Option<GeneSummaryObserver>,
+    /// Cross-row filters, also in `filters`, that need a first pass over the input
+    prepass_filters: Vec<Arc<dyn Prepass>>,
+    transforms: Vec<Box<dyn RowTransform>>,
     input_path: PathBuf,
     output_path: PathBuf,
     index: Option<Arc<FileIndex>>,
+    /// Block index used to skip blocks of rows the filters rule out
+    block_index: Option<Arc<BlockIndex>>,
+    /// Column cache sidecar numeric filters are tested against
+    column_cache: Option<PathBuf>,
+    /// Index built on `index_column` by the last run
+    built_index: Mutex<Option<Arc<FileIndex>>>,
     stats: Arc<ProcessingStats>,
 }
 
@@ -42,8 +161,8 @@ impl BioFilter {
         config: Config,
         index_path: Option<PathBuf>,
     ) -> Result<Self> {
-        // Validate input file
-        if !input_path.exists() {
+        // Validate input file; remote objects are checked when they are opened
+        if !input_path.exists() && !is_remote(&input_path) {
             return Err(ExtractorError::io_error(
                 std::io::Error::new(
                     std::io::ErrorKind::NotFound,
@@ -53,463 +172,4150 @@ impl BioFilter {
             ));
         }
 
+        check_column_names(&config)?;
+
         // Load index if specified
         let index = if let Some(idx_path) = index_path {
-            Some(Arc::new(FileIndex::load(&idx_path)?))
+            Some(Arc::new(retry::retry(&config.io_retry, || FileIndex::load(&idx_path))?))
         } else {
             None
         };
 
-        Ok(Self {
+        let mut filter = Self::unchecked(input_path, output_path, config, index);
+        let columns = &filter.config.filename_columns;
+        if let Some(transform) = ConstantColumns::for_input(columns, &filter.input_path, &filter.config.column_aliases) {
+            filter.transforms.push(Box::new(transform));
+        }
+        if let Some(schema) = &filter.config.schema {
+            filter.validate_schema(schema)?;
+        }
+        Ok(filter)
+    }
+
+    /// Create a BioFilter without checking its input or config, for `validate`
+    pub(crate) fn unchecked(
+        input_path: PathBuf,
+        output_path: PathBuf,
+        config: Config,
+        index: Option<Arc<FileIndex>>,
+    ) -> Self {
+        Self {
             config,
             filters: Vec::new(),
+            filter_counters: Vec::new(),
+            pipelines: Vec::new(),
+            observers: Vec::new(),
+            gene_summary: None,
+            prepass_filters: Vec::new(),
+            transforms: Vec::new(),
             input_path,
             output_path,
             index,
+            block_index: None,
+            column_cache: None,
+            built_index: Mutex::new(None),
             stats: Arc::new(ProcessingStats::default()),
-        })
+        }
+    }
+
+    /// Check the declared column types against the start of the input
+    fn validate_schema(&self, schema: &Schema) -> Result<()> {
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        schema.validate(&mut reader, &headers)
+    }
+
+    /// Create a BioFilter without input or output files, for use with
+    /// `process_stream` on data that is already in memory
+    pub fn streaming(config: Config) -> Self {
+        Self::unchecked(PathBuf::new(), PathBuf::new(), config, None)
     }
 
     /// Add a filter to the processing pipeline
     pub fn add_filter(&mut self, filter: Box<dyn Filter>) {
         self.filters.push(filter);
+        self.filter_counters.push(FilterCounters::default());
     }
 
-    /// Process the input file
-    pub fn process(&self) -> Result<ProcessingStats> {
-        if self.config.use_index && self.index.is_none() {
-            return Err(ExtractorError::Config(
-                "Index required but not loaded".to_string()
+    /// Per-filter match statistics from the most recent `process()` call, in
+    /// pipeline order. Shows which filter rejects the most rows and what each
+    /// costs per evaluation, to help decide how filters should be ordered.
+    ///
+    /// Covers filters added with `add_filter`; pipeline-specific filters are not tracked.
+    pub fn filter_stats(&self) -> Vec<FilterStats> {
+        self.filters
+            .iter()
+            .zip(&self.filter_counters)
+            .enumerate()
+            .map(|(position, (filter, counters))| counters.snapshot(position, filter.as_ref()))
+            .collect()
+    }
+
+    /// Render `filter_stats()` as a plain-text selectivity table
+    pub fn filter_report(&self) -> String {
+        let mut report = format!(
+            "{:>3}  {:<40} {:>12} {:>12} {:>9} {:>10}\n",
+            "#", "filter", "evaluated", "rejected", "rejected%", "avg ns"
+        );
+        for stats in self.filter_stats() {
+            report.push_str(&format!(
+                "{:>3}  {:<40} {:>12} {:>12} {:>8.2}% {:>10.1}\n",
+                stats.position,
+                stats.description,
+                stats.evaluated,
+                stats.rejected,
+                stats.rejection_rate() * 100.0,
+                stats.avg_eval_ns
             ));
         }
+        report
+    }
 
-        let input_file = File::open(&self.input_path)
-            .map_err(|e| ExtractorError::io_error(e, &self.input_path))?;
-
-        let output_file = File::create(&self.output_path)
-            .map_err(|e| ExtractorError::io_error(e, &self.output_path))?;
+    /// Skip blocks of rows that `index` shows no row of could pass the
+    /// filters. Runs then read only the header and the remaining blocks, so
+    /// `rows_processed` counts only rows in those blocks, row numbers in
+    /// errors count from the first row read, and errors filters would have
+    /// raised on rows in skipped blocks aren't reported. Needs a local UTF-8
+    /// input with a header row and a single output file.
+    pub fn set_block_index(&mut self, index: BlockIndex) {
+        self.block_index = Some(Arc::new(index));
+    }
 
-        if self.config.parallel {
-            self.process_parallel(input_file, output_file)
-        } else {
-            self.process_sequential(input_file, output_file)
-        }
+    /// Test numeric filters against the column cache sidecar at `path` and
+    /// read only the rows that pass. The cache holds the position of every
+    /// row and the numeric columns filtered on; it is built by the first run
+    /// and rebuilt when the input changes or a run filters another column,
+    /// so repeated threshold sweeps skip the full scan. Runs see the same
+    /// caveats as with `set_block_index`.
+    pub fn set_column_cache(&mut self, path: PathBuf) {
+        self.column_cache = Some(path);
     }
 
-    /// Process file in parallel using multiple threads
-    fn process_parallel(&self, input: File, output: File) -> Result<ProcessingStats> {
-        let file_size = input.metadata()?.len();
-        let chunk_size = self.config.chunk_size;
-        let num_chunks = (file_size + chunk_size as u64 - 1) / chunk_size as u64;
-
-        // Set up progress tracking
-        #[cfg(feature = "progress-bars")]
-        let progress = Arc::new(Progress::new(
-            file_size,
-            "Processing file"
-        ));
+    /// Collect statistics (min/max/mean/unique count) for a column of the
+    /// matched rows; results are reported in `ProcessingStats::column_stats`
+    pub fn add_observer(&mut self, observer: ColumnObserver) {
+        self.observers.push(observer);
+    }
 
-        // Create channels for collecting results
-        let (tx, rx) = bounded(self.config.num_threads.unwrap_or_else(num_cpus::get));
-        let output = Arc::new(std::sync::Mutex::new(BufWriter::new(output)));
+    /// Summarize the matched rows per gene; results are reported in
+    /// `ProcessingStats::gene_summary`
+    pub fn set_gene_summary(&mut self, observer: GeneSummaryObserver) {
+        self.gene_summary = Some(observer);
+    }
 
-        // Process chunks in parallel
-        let processed_rows = Arc::new(AtomicU64::new(0));
-        let matched_rows = Arc::new(AtomicU64::new(0));
+    /// Accumulators for the observers and gene summary, reading output rows
+    fn observer_set(&self, output_columns: &HashMap<String, usize>) -> Result<ObserverSet> {
+        ObserverSet::new(&self.observers, self.gene_summary.as_ref(), output_columns)
+    }
 
-        let mmap = unsafe {
-            utils::create_mmap(&input, &SafeMmapOptions::default())?
-        };
-        let mmap = Arc::new(mmap);
-        // Spawn processing threads
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(self.config.num_threads.unwrap_or_else(num_cpus::get))
-            .build()?;
+    /// Add a named pipeline with its own filters and output file.
+    ///
+    /// Once any pipeline is added, `process()` scans the input once and writes
+    /// each pipeline's matches to its own output. Filters added with
+    /// `add_filter` act as a shared pre-filter for every pipeline, and the
+    /// BioFilter's own output path is not written.
+    pub fn add_pipeline(&mut self, pipeline: Pipeline) {
+        self.pipelines.push(pipeline);
+    }
 
-        pool.scope(|s| {
-            // Split file into chunks and process
-            for chunk_index in 0..num_chunks {
-                let start = chunk_index as u64 * chunk_size as u64;
-                let end = std::cmp::min(start + chunk_size as u64, file_size);
-                
-                let tx = tx.clone();
-                let mmap = mmap.clone();
-                let filters = &self.filters;
-                let processed_rows = processed_rows.clone();
-                let matched_rows = matched_rows.clone();
-                
-                #[cfg(feature = "progress-bars")]
-                let progress = progress.clone();
+    /// Add a cross-row filter. Processing then reads the input twice: once to
+    /// count rows per group and once to filter.
+    pub fn add_group_filter(&mut self, filter: GroupFilter) {
+        let filter = Arc::new(filter);
+        self.prepass_filters.push(filter.clone());
+        self.add_filter(Box::new(filter));
+    }
 
-                s.spawn(move |_| {
-                    let chunk_data = &mmap[start as usize..end as usize];
-                    let chunk = Chunk {
-                        data: chunk_data.to_vec(),
-                        start_offset: start,
-                        chunk_index: chunk_index as usize,
-                    };
-
-                    if let Ok(results) = self.process_chunk(&chunk, filters) {
-                        processed_rows.fetch_add(results.rows_processed, Ordering::Relaxed);
-                        matched_rows.fetch_add(results.rows_matched, Ordering::Relaxed);
-                        
-                        #[cfg(feature = "progress-bars")]
-                        progress.inc(chunk_data.len() as u64);
-
-                        // Send processed results
-                        let _ = tx.send((chunk_index, results));
-                    }
-                });
-            }
-        });
+    /// Add a clumping filter that keeps the best row per window or locus.
+    /// Like group filters, it reads the input twice.
+    pub fn add_clump_filter(&mut self, filter: ClumpFilter) {
+        let filter = Arc::new(filter);
+        self.prepass_filters.push(filter.clone());
+        self.add_filter(Box::new(filter));
+    }
 
-        // Collect and write results in order
-        drop(tx);
-        let mut results = vec![];
-        while let Ok(result) = rx.recv() {
-            results.push(result);
-        }
-        results.sort_by_key(|(idx, _)| *idx);
+    /// Drop rows whose `column` value appears in the key list at `path` (one
+    /// key per line, e.g. withdrawn rsIDs or flagged samples)
+    pub fn exclude_by_key<P: AsRef<std::path::Path>>(&mut self, path: P, column: &str) -> Result<()> {
+        let keys = Arc::new(LookupTable::load_keys(path.as_ref())?);
+        self.add_filter(Box::new(LookupFilter::absent(column.to_string(), keys)));
+        Ok(())
+    }
 
-        let mut output = output.lock().unwrap();
-        for (_, chunk_result) in results {
-            output.write_all(&chunk_result.output_data)?;
-        }
-        output.flush()?;
+    /// Add a transform that rewrites matched rows before they are written.
+    /// Transforms run in the order they were added.
+    pub fn add_transform(&mut self, transform: Box<dyn RowTransform>) {
+        self.transforms.push(transform);
+    }
 
-        #[cfg(feature = "progress-bars")]
-        progress.finish();
+    /// Reorder the filters added with `add_filter` using the first `sample_rows`
+    /// rows of the input, so that cheap, highly selective filters run first.
+    ///
+    /// Every filter is evaluated on every sampled row and ranked by average cost
+    /// divided by rejection rate; filters that rejected nothing keep their
+    /// relative order at the end. Returns the new order as indices into the
+    /// previous order. Call it once before `process()`.
+    pub fn optimize_filter_order(&mut self, sample_rows: usize) -> Result<Vec<usize>> {
+        let order = self.plan_filter_order(sample_rows)?;
 
-        Ok(ProcessingStats {
-            rows_processed: processed_rows.load(Ordering::Relaxed),
-            rows_matched: matched_rows.load(Ordering::Relaxed),
-            processing_time_ms: 0, // TODO: Add timing
-            input_size: file_size,
-            output_size: self.output_path.metadata()?.len(),
-        })
+        let mut filters: Vec<Option<Box<dyn Filter>>> =
+            std::mem::take(&mut self.filters).into_iter().map(Some).collect();
+        let mut counters: Vec<Option<FilterCounters>> =
+            std::mem::take(&mut self.filter_counters).into_iter().map(Some).collect();
+        for &i in &order {
+            self.filters.push(filters[i].take().expect("order is a permutation"));
+            self.filter_counters.push(counters[i].take().expect("order is a permutation"));
+        }
+        Ok(order)
     }
 
-    /// Process file sequentially in a single thread
-    fn process_sequential(&self, input: File, output: File) -> Result<ProcessingStats> {
+    /// Measure each filter on a sample of rows and rank them
+    fn plan_filter_order(&self, sample_rows: usize) -> Result<Vec<usize>> {
+        let identity: Vec<usize> = (0..self.filters.len()).collect();
+        if self.filters.len() < 2 || sample_rows == 0 {
+            return Ok(identity);
+        }
+
+        let (input, _) = self.open_stream()?;
         let mut reader = ReaderBuilder::new()
             .delimiter(self.config.delimiter)
             .has_headers(self.config.has_headers)
-            .from_reader(input);
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
 
-        let mut writer = WriterBuilder::new()
-            .delimiter(self.config.delimiter)
-            .from_writer(output);
+        let mut sample = Vec::with_capacity(sample_rows.min(64 * 1024));
+        let mut record = ByteRecord::new();
+        while sample.len() < sample_rows && reader.read_byte_record(&mut record)? {
+            sample.push(record.clone());
+        }
+        if sample.is_empty() {
+            return Ok(identity);
+        }
 
-        let headers = reader.headers()?.clone();
-        writer.write_record(&headers)?;
+        // Rank by expected cost per rejected row; errors count as rejections
+        let scores: Vec<f64> = self
+            .filters
+            .iter()
+            .map(|filter| {
+                let start = Instant::now();
+                let rejected = sample
+                    .iter()
+                    .filter(|row| !matches!(filter.apply(row, &headers), Ok(true)))
+                    .count();
+                let cost = start.elapsed().as_nanos() as f64 / sample.len() as f64;
+                if rejected == 0 {
+                    f64::INFINITY
+                } else {
+                    cost / (rejected as f64 / sample.len() as f64)
+                }
+            })
+            .collect();
 
-        let mut stats = ProcessingStats::default();
-        stats.input_size = self.input_path.metadata()?.len();
+        let mut order = identity;
+        order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+        Ok(order)
+    }
 
-        for result in reader.records() {
-            let record = result?;
-            stats.rows_processed += 1;
+    /// Process the input file
+    pub fn process(&self) -> Result<ProcessingStats> {
+        self.run(self.config.verify_mode)
+    }
+
+    /// Filter CSV read from `input` and write the matched rows to `output`.
+    ///
+    /// Runs the single-threaded streaming path and ignores the configured
+    /// input and output paths, pipelines and partitioning, so it also works
+    /// where files and threads are unavailable (e.g. in the browser).
+    /// `input_size` and `output_size` count the bytes read and written.
+    pub fn process_stream<R: Read, W: Write>(&self, input: R, output: W) -> Result<ProcessingStats> {
+        if !self.prepass_filters.is_empty() {
+            return Err(ExtractorError::Config(
+                "Group and clump filters read the input twice and cannot be used with process_stream".to_string()
+            ));
+        }
+        for counters in &self.filter_counters {
+            counters.reset();
+        }
+        let (stats, output) = self.filter_stream(input, ByteCounter { inner: output, bytes: 0 }, None, None, None)?;
+        Ok(ProcessingStats { output_size: output.bytes, ..stats })
+    }
+
+    /// Filter the input file into `sink` instead of the output path.
+    ///
+    /// Runs a single streaming pass; pipelines and partitioning are ignored.
+    /// Without a header row or `column_names` the columns are named
+    /// `column_1`, `column_2`, ... and `output_size` is 0.
+    pub fn process_into(&self, sink: &mut dyn RowSink) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        for counters in &self.filter_counters {
+            counters.reset();
+        }
+        self.run_prepass()?;
+        let (input, input_size) = self.open_stream()?;
+        let (encoding, bom) = (input.encoding, input.bom);
+        let mut reader = self.stream_reader(input.reader);
+
+        let mut header_record = self.column_header(reader.byte_headers()?);
+        if !self.writes_header() {
+            header_record = (1..=header_record.len())
+                .map(|i| format!("column_{i}"))
+                .collect();
+        }
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        let mut observed = self.observer_set(&self.output_columns(&header_record)?)?;
+        sink.begin(&self.output_header(&header_record))?;
+
+        let mut stats = ProcessingStats { input_size, encoding, bom, ..ProcessingStats::default() };
+        let mut guard = MatchGuard::new(&self.config, Some(input_size))?;
 
-            if self.apply_filters(&record)? {
-                writer.write_record(&record)?;
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            guard.check(reader.position().byte(), stats.rows_processed, stats.rows_matched, &mut stats.warnings)?;
+            stats.rows_processed += 1;
+            let row = self.select_row(&mut stats, &record, &headers, &excluded)?;
+            if let Some(row) = row {
+                sink.write_row(&row)?;
+                observed.observe(&row);
                 stats.rows_matched += 1;
             }
+            release_span(&mut reader);
         }
 
-        writer.flush()?;
-        stats.output_size = self.output_path.metadata()?.len();
+        sink.finish()?;
+        (stats.column_stats, stats.gene_summary) = observed.finish();
+        stats.processing_time_ms = start.elapsed().as_millis() as u64;
         Ok(stats)
     }
 
-    /// Process a single chunk of data
-    fn process_chunk(
-        &self,
-        chunk: &Chunk,
-        filters: &[Box<dyn Filter>],
-    ) -> Result<ChunkResult> {
-        let mut result = ChunkResult {
-            rows_processed: 0,
-            rows_matched: 0,
-            output_data: Vec::with_capacity(chunk.data.len()),
+    /// Process the input file, then prove the output is byte-identical to what
+    /// the sequential reference path writes for the same input, whatever
+    /// `verify_mode` is set to. The input is read a second time for the check,
+    /// and any difference fails with `ExtractorError::VerificationFailed`.
+    /// Sequential runs are the reference path and are not checked again.
+    pub fn process_verified(&self) -> Result<ProcessingStats> {
+        self.run(VerifyMode::Full)
+    }
+
+    fn run(&self, verify: VerifyMode) -> Result<ProcessingStats> {
+        self.check_outputs(verify)?;
+        let header = self.header_fingerprint()?;
+        let baseline = self.config.header_baseline.as_deref();
+        let drift = match (baseline, &header) {
+            (Some(path), Some(header)) => HeaderFingerprint::load(path)?.and_then(|previous| header.drift_from(&previous)),
+            _ => None,
         };
+        if let Some(drift) = &drift {
+            tracing::warn!("{drift}");
+        }
+
+        let mut stats = self.run_to_outputs(verify)?;
+        if let (Some(path), Some(header)) = (baseline, &header) {
+            header.save(path)?;
+        }
+        if let Some(path) = self.gene_summary.as_ref().and_then(GeneSummaryObserver::output) {
+            save_gene_summary(path, &stats.gene_summary)?;
+        }
+        stats.warnings.splice(0..0, drift);
+        stats.memory.peak_rss_bytes = utils::peak_rss_bytes();
+        Ok(ProcessingStats { config: Some(self.config.to_json()), header, ..stats })
+    }
 
-        // Create a writer that writes to our output buffer
-        let mut writer = WriterBuilder::new()
+    /// Fingerprint of the input's header row, read ahead of the run
+    fn header_fingerprint(&self) -> Result<Option<HeaderFingerprint>> {
+        if !self.config.has_headers {
+            return Ok(None);
+        }
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
             .delimiter(self.config.delimiter)
-            .from_writer(&mut result.output_data);
+            .flexible(true)
+            .from_reader(input.reader);
+        Ok(Some(HeaderFingerprint::of(reader.byte_headers()?)))
+    }
 
-        // Find complete rows in the chunk
-        let mut start = 0;
-        let mut in_quoted_field = false;
-        let mut row_start = 0;
-        
-        // Skip incomplete row at start if this isn't the first chunk
-        if chunk.chunk_index > 0 {
-            while start < chunk.data.len() && chunk.data[start] != b'\n' {
-                start += 1;
-            }
-            start += 1;
-            row_start = start;
+    fn run_to_outputs(&self, verify: VerifyMode) -> Result<ProcessingStats> {
+        *self.built_index.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        let sharded = self.config.max_output_rows.is_some() || self.config.max_output_bytes.is_some();
+
+        for counters in &self.filter_counters {
+            counters.reset();
         }
+        self.run_prepass()?;
 
-        // Process each row in the chunk
-        for (i, &byte) in chunk.data[start..].iter().enumerate() {
-            let pos = start + i;
+        #[cfg(feature = "object-store")]
+        if is_remote(&self.input_path) {
+            return self.process_remote();
+        }
 
-            // Handle quoted fields
-            if byte == b'"' {
-                in_quoted_field = !in_quoted_field;
-                continue;
-            }
+        #[cfg(feature = "parquet")]
+        if is_parquet(&self.input_path) {
+            return self.process_parquet();
+        }
 
-            // Only process row endings outside of quotes
-            if !in_quoted_field && byte == b'\n' {
-                let row_data = &chunk.data[row_start..pos];
-                result.rows_processed += 1;
+        let input_file = self.open_input()?;
 
-                // Skip empty rows
-                if row_data.is_empty() {
-                    row_start = pos + 1;
-                    continue;
-                }
+        if !self.pipelines.is_empty() {
+            return self.process_pipelines(input_file);
+        }
 
-                // Parse the row
-                if let Ok(should_keep) = self.process_row(row_data, filters) {
-                    if should_keep {
-                        // Write the row to output
-                        writer.write_record(row_data.split(|&b| b == self.config.delimiter))?;
-                        result.rows_matched += 1;
-                    }
-                }
+        if let Some(column) = &self.config.partition_by {
+            return self.process_partitioned(input_file, column);
+        }
 
-                row_start = pos + 1;
-            }
+        if sharded {
+            return self.process_sharded(input_file);
         }
 
-        // Flush the writer to ensure all data is written to our buffer
-        writer.flush()?;
-        Ok(result)
+        if self.config.thread_output == ThreadOutput::Partitioned {
+            return if self.config.parallel {
+                self.check_shard_output()?;
+                self.process_parallel(input_file, None, None, None)
+            } else {
+                self.process_sharded(input_file)
+            };
+        }
+
+        let (output, output_file) =
+            OutputFile::create(&self.output_path, self.config.write_mode, self.config.on_existing_output)?;
+        let appended_to = output.existing_header(self.config.delimiter)?;
+        let mut index_chunks = Vec::new();
+        let index = self.config.index_column.is_some().then_some(&mut index_chunks);
+        let mut stats = if let Some(path) = &self.column_cache {
+            let stats = self.process_cached(path, input_file, output_file, appended_to.as_ref())?;
+            self.verify_output(verify, output.path())?;
+            stats
+        } else if let Some(blocks) = &self.block_index {
+            let stats = self.process_blocks(blocks, input_file, output_file, appended_to.as_ref())?;
+            self.verify_output(verify, output.path())?;
+            stats
+        } else if self.config.parallel {
+            let stats = self.process_parallel(input_file, Some(output_file), appended_to.as_ref(), index)?;
+            self.verify_output(verify, output.path())?;
+            stats
+        } else {
+            self.process_sequential(input_file, output_file, appended_to.as_ref(), index)?
+        };
+        output.commit()?;
+        if let Some(column) = &self.config.index_column {
+            stats.memory.index_bytes = index_chunks.iter().map(ChunkIndex::approx_bytes).sum();
+            let index = FileIndex::builder(self.input_path.clone(), column.clone())
+                .on_duplicate(Keep::All)
+                .build_from_chunks(index_chunks)?;
+            *self.built_index.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(index));
+        }
+        Ok(stats)
     }
 
-    /// Process a single row of data
-    fn process_row(&self, row_data: &[u8], filters: &[Box<dyn Filter>]) -> Result<bool> {
-        // Get cached headers
-        let headers = self.get_headers()?;
+    /// Index of the input built on `index_column` by the last successful
+    /// run, if one was requested with `build_index_while_filtering`
+    pub fn built_index(&self) -> Option<Arc<FileIndex>> {
+        self.built_index.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
 
-        // Apply all filters
-        for filter in filters {
-            if !filter.apply(row_data, &headers)? {
-                return Ok(false);
-            }
-        }
+    /// Where `index_column` is in the input
+    fn index_columns(&self, headers: &HashMap<String, usize>) -> Result<Option<IndexColumns>> {
+        self.config
+            .index_column
+            .as_ref()
+            .map(|column| {
+                let idx = *headers
+                    .get(column)
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))?;
+                Ok(IndexColumns::primary(idx))
+            })
+            .transpose()
+    }
 
-        Ok(true)
+    /// Reject output settings that can't be used together
+    fn check_outputs(&self, verify: VerifyMode) -> Result<()> {
+        if self.config.use_index && self.index.is_none() {
+            return Err(ExtractorError::Config(
+                "Index required but not loaded".to_string()
+            ));
+        }
+        let split = self.config.max_output_rows.is_some() || self.config.max_output_bytes.is_some();
+        if self.config.thread_output != ThreadOutput::Shared
+            && (!self.pipelines.is_empty() || self.config.partition_by.is_some() || split)
+        {
+            return Err(ExtractorError::Config(
+                "Per-thread output can't be combined with pipelines, partitioned or sharded output".to_string()
+            ));
+        }
+        let sharded = split || self.config.thread_output == ThreadOutput::Partitioned;
+        if verify != VerifyMode::Off
+            && (!self.pipelines.is_empty() || self.config.partition_by.is_some() || sharded)
+        {
+            return Err(ExtractorError::Config(
+                "Output verification requires a single output file".to_string()
+            ));
+        }
+        if verify != VerifyMode::Off && self.config.on_existing_output == ExistingOutput::Append {
+            return Err(ExtractorError::Config(
+                "Output verification cannot be used when appending".to_string()
+            ));
+        }
+        if self.config.output_compression != OutputCompression::None
+            && (verify != VerifyMode::Off
+                || self.config.on_existing_output == ExistingOutput::Append
+                || !self.pipelines.is_empty()
+                || self.config.partition_by.is_some()
+                || sharded)
+        {
+            return Err(ExtractorError::Config(
+                "Compressed output needs a single new output file without verification".to_string()
+            ));
+        }
+        if self.config.index_column.is_some()
+            && (!self.pipelines.is_empty()
+                || self.config.partition_by.is_some()
+                || sharded
+                || is_remote(&self.input_path)
+                || is_parquet(&self.input_path))
+        {
+            return Err(ExtractorError::Config(
+                "An index can only be built while filtering a local delimited input into a single output file".to_string()
+            ));
+        }
+        if (!self.config.preserve_order || self.config.thread_output != ThreadOutput::Shared)
+            && (verify != VerifyMode::Off || matches!(self.config.output_compression, OutputCompression::Bgzip { tabix: Some(_) }))
+        {
+            return Err(ExtractorError::Config(
+                "Verified output and tabix indices need preserve_order and a shared writer".to_string()
+            ));
+        }
+        if self.block_index.is_some() && self.column_cache.is_some() {
+            return Err(ExtractorError::Config(
+                "A block index and a column cache can't be used together".to_string()
+            ));
+        }
+        if (self.block_index.is_some() || self.column_cache.is_some())
+            && (!self.pipelines.is_empty()
+                || self.config.partition_by.is_some()
+                || sharded
+                || is_remote(&self.input_path)
+                || is_parquet(&self.input_path)
+                || self.config.index_column.is_some()
+                || !self.config.has_headers)
+        {
+            return Err(ExtractorError::Config(
+                "A block index or column cache can only be used to filter a local delimited input with a header row into a single output file, without building an index".to_string()
+            ));
+        }
+        if self.pipelines.is_empty() && self.config.partition_by.is_some() && sharded {
+            return Err(ExtractorError::Config(
+                "Partitioned output cannot also be split into shards".to_string()
+            ));
+        }
+        Ok(())
     }
 
-    /// Helper method to parse a row into fields
-    fn parse_row<'a>(&self, row: &'a [u8]) -> Vec<&'a [u8]> {
-        let mut fields = Vec::new();
-        let mut start = 0;
-        let mut in_quotes = false;
-        
-        for (i, &byte) in row.iter().enumerate() {
-            match byte {
-                b'"' => in_quotes = !in_quotes,
-                b',' if !in_quotes => {
-                    fields.push(&row[start..i]);
-                    start = i + 1;
-                }
-                _ => {}
+    /// Check the whole setup without processing anything: the input and its
+    /// header, the columns filters and settings refer to, the index and the
+    /// output location. Every problem found is returned, rather than the run
+    /// failing part-way on the first one.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        for check in [check_column_names(&self.config), self.check_outputs(self.config.verify_mode)] {
+            if let Err(e) = check {
+                issues.push(ValidationIssue::Config(config_message(e)));
             }
         }
-        
-        // Add the last field
-        if start < row.len() {
-            fields.push(&row[start..]);
+
+        if is_remote(&self.input_path) || self.input_path.is_file() {
+            self.validate_input(&mut issues);
+        } else {
+            issues.push(ValidationIssue::Input(format!("{} does not exist", self.input_path.display())));
         }
-        
-        fields
-    }
 
-    /// Apply filters to a record
-    fn apply_filters(&self, record: &csv::StringRecord) -> Result<bool> {
-        for filter in &self.filters {
-            if !filter.apply(record.as_bytes(), &self.get_headers()?)? {
-                return Ok(false);
+        if let Some(blocks) = &self.block_index {
+            let file = File::open(&self.input_path).map_err(|e| ExtractorError::io_error(e, &self.input_path));
+            match file.and_then(|file| blocks.verify(&file)) {
+                Ok(true) => {}
+                Ok(false) => issues.push(ValidationIssue::Index(format!(
+                    "block index built from a different version of {}",
+                    self.input_path.display()
+                ))),
+                Err(_) if !self.input_path.is_file() => {}
+                Err(e) => issues.push(ValidationIssue::Index(e.to_string())),
             }
         }
-        Ok(true)
+        if let Some(index) = &self.index {
+            let file = File::open(&self.input_path).map_err(|e| ExtractorError::io_error(e, &self.input_path));
+            match file.and_then(|file| index.verify(&file)) {
+                Ok(true) => {}
+                Ok(false) => issues.push(ValidationIssue::Index(format!(
+                    "built from a different version of {}",
+                    self.input_path.display()
+                ))),
+                // Already reported as an input issue when the file is missing
+                Err(_) if !self.input_path.is_file() => {}
+                Err(e) => issues.push(ValidationIssue::Index(e.to_string())),
+            }
+        }
+
+        let existing = self.config.on_existing_output;
+        if !self.pipelines.is_empty() {
+            issues.extend(self.pipelines.iter().filter_map(|p| validate::check_output_file(&p.output_path, existing)));
+        } else if self.config.partition_by.is_some() {
+            issues.extend(validate::check_output_dir(&self.output_path));
+        } else {
+            issues.extend(validate::check_output_file(&self.output_path, existing));
+        }
+        issues
     }
 
-    /// Get CSV headers as a map of column names to indices
-    fn get_headers(&self) -> Result<std::collections::HashMap<String, usize>> {
-        let file = File::open(&self.input_path)?;
+    /// Check the input's header against the columns in use, and its first rows
+    /// against the schema
+    fn validate_input(&self, issues: &mut Vec<ValidationIssue>) {
+        let input = match self.open_stream() {
+            Ok((input, _)) => input,
+            Err(e) => return issues.push(ValidationIssue::Input(e.to_string())),
+        };
         let mut reader = ReaderBuilder::new()
             .delimiter(self.config.delimiter)
-            .has_headers(true)
-            .from_reader(file);
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = match reader.byte_headers() {
+            Ok(first) => self.column_header(first),
+            Err(e) => return issues.push(ValidationIssue::InputFormat(e.to_string())),
+        };
+        let headers = match resolve_columns(&header_record, &self.config.column_aliases) {
+            Ok(headers) => headers,
+            Err(e) => return issues.push(ValidationIssue::InputFormat(config_message(e))),
+        };
 
-        let headers = reader.headers()?;
-        Ok(headers
+        let mut used: Vec<(&str, String)> = self.filters
             .iter()
-            .enumerate()
-            .map(|(i, name)| (name.to_string(), i))
-            .collect())
+            .map(|filter| (filter.column_name(), filter.description()))
+            .collect();
+        for pipeline in &self.pipelines {
+            used.extend(pipeline.filters.iter().map(|filter| {
+                (filter.column_name(), format!("pipeline '{}': {}", pipeline.name, filter.description()))
+            }));
+        }
+        if let Some(column) = &self.config.index_column {
+            used.push((column, "index_column".to_string()));
+        }
+        // Observers and partitioning read output rows, which transforms may have changed
+        let output_columns = self.output_columns(&header_record).unwrap_or_default();
+        let mut used_in_output: Vec<(&str, String)> = self.observers
+            .iter()
+            .map(|observer| (observer.column_name(), "a column observer".to_string()))
+            .collect();
+        if let Some(column) = &self.config.partition_by {
+            used_in_output.push((column, "partition_by".to_string()));
+        }
+        if let Some(observer) = &self.gene_summary {
+            used_in_output.extend(observer.columns().map(|column| (column, "the gene summary".to_string())));
+        }
+        issues.extend(
+            used.into_iter()
+                .filter(|(column, _)| !headers.contains_key(*column))
+                .chain(used_in_output.into_iter().filter(|(column, _)| !output_columns.contains_key(*column)))
+                .map(|(column, used_by)| ValidationIssue::UnknownColumn { column: column.to_string(), used_by }),
+        );
+
+        let schema_check = self.config.schema.as_ref().map(|schema| schema.validate(&mut reader, &headers));
+        if let Some(Err(e)) = schema_check {
+            issues.push(ValidationIssue::InputFormat(e.to_string()));
+        }
     }
-}
 
-#[derive(Debug)]
-pub(crate) struct ChunkProcessingStats {
-    pub rows_processed: u64,
-    pub rows_matched: u64,
-    pub bytes_processed: u64,
-}
+    /// The first `n` rows the filters select, after transforms, without
+    /// writing any output. The input is read only until `n` rows are found,
+    /// apart from the first pass group and clump filters need. Per-filter
+    /// statistics are left alone; bad rows are handled as `on_row_error` says.
+    pub fn preview(&self, n: usize) -> Result<Preview> {
+        self.run_prepass()?;
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
 
-/// Helper for managing chunk boundaries
-struct ChunkBoundary {
-    start: usize,
-    end: usize,
-    is_complete: bool,
+        let mut preview = Preview {
+            header: self.writes_header().then(|| {
+                StringRecord::from_byte_record_lossy(self.output_header(&header_record).into_owned())
+            }),
+            ..Preview::default()
+        };
+        let mut record = ByteRecord::new();
+        while preview.rows.len() < n
+            && reader.read_byte_record(&mut record).map_err(|e| {
+                ExtractorError::from(e).at_row(preview.rows_scanned + 1, &record, self.config.delimiter)
+            })?
+        {
+            preview.rows_scanned += 1;
+            let row = match self.matches_uncounted(&record, &headers) {
+                Ok(true) => self.transform_row(&record, &headers, &excluded),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            };
+            match row {
+                Ok(Some(row)) => preview.rows.push(StringRecord::from_byte_record_lossy(row.into_owned())),
+                Ok(None) => {}
+                Err(e) => self.skip_row(
+                    e.at_row(preview.rows_scanned, &record, self.config.delimiter),
+                    &mut preview.row_errors,
+                )?,
+            }
+        }
+        Ok(preview)
+    }
+
+    /// Distinct non-empty values of `column` over the whole input, ignoring
+    /// filters, with the number of rows holding each. A secondary index on
+    /// `column` answers without reading the input; its `limit` most common
+    /// values are returned. A scan instead counts only the first `limit`
+    /// distinct values it meets, so memory stays bounded on columns such as
+    /// rsIDs. Either way `capped` says whether there were more.
+    pub fn distinct_values(&self, column: &str, limit: usize) -> Result<DistinctValues> {
+        if let Some(values) = self.index.as_ref().and_then(|index| index.secondary_indices.get(column)) {
+            let counts = values.iter().map(|(value, positions)| (value.clone(), positions.len() as u64)).collect();
+            return Ok(DistinctValues::from_counts(counts, limit, false));
+        }
+
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let idx = *headers
+            .get(column)
+            .ok_or_else(|| ExtractorError::column_not_in_headers(column, &headers))?;
+
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        let mut capped = false;
+        let mut record = ByteRecord::new();
+        let mut row = 0;
+        while reader
+            .read_byte_record(&mut record)
+            .map_err(|e| ExtractorError::from(e).at_row(row + 1, &record, self.config.delimiter))?
+        {
+            row += 1;
+            let value = String::from_utf8_lossy(record.get(idx).unwrap_or_default().trim_ascii());
+            if value.is_empty() {
+                continue;
+            }
+            if let Some(count) = counts.get_mut(value.as_ref()) {
+                *count += 1;
+            } else if counts.len() < limit {
+                counts.insert(value.into_owned(), 1);
+            } else {
+                capped = true;
+            }
+        }
+        Ok(DistinctValues::from_counts(counts, limit, capped))
+    }
+
+    /// Evaluate every filter against the rows picked by `target` and report
+    /// what each one looked at and decided, e.g. to find out why a variant is
+    /// missing from the output. Unlike a run, evaluation doesn't stop at the
+    /// first failing filter and the per-filter statistics are left alone.
+    /// Group and clump filters get their first pass over the input beforehand.
+    /// No rows are returned when nothing matches `target`.
+    pub fn explain(&self, target: &ExplainTarget) -> Result<Vec<RowExplanation>> {
+        self.run_prepass()?;
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let key = match target {
+            ExplainTarget::Row(_) => None,
+            ExplainTarget::Key { column, value } => {
+                let idx = *headers
+                    .get(column)
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(column, &headers))?;
+                Some((idx, value.trim().as_bytes()))
+            }
+        };
+
+        let mut explained = Vec::new();
+        let mut record = ByteRecord::new();
+        let mut row = 0;
+        while reader
+            .read_byte_record(&mut record)
+            .map_err(|e| ExtractorError::from(e).at_row(row + 1, &record, self.config.delimiter))?
+        {
+            row += 1;
+            let picked = match (target, key) {
+                (ExplainTarget::Row(n), _) => row == *n,
+                (_, Some((idx, value))) => record.get(idx).is_some_and(|field| field.trim_ascii() == value),
+                _ => false,
+            };
+            if picked {
+                explained.push(self.explain_row(row, &record, &headers));
+            }
+            if matches!(target, ExplainTarget::Row(n) if row >= *n) {
+                break;
+            }
+        }
+        Ok(explained)
+    }
+
+    /// Trace every top-level and pipeline filter for one row
+    fn explain_row(&self, row: u64, record: &ByteRecord, headers: &HashMap<String, usize>) -> RowExplanation {
+        let trace = |pipeline: Option<&str>, filter: &dyn Filter| FilterTrace {
+            pipeline: pipeline.map(str::to_string),
+            filter: filter.description(),
+            outcome: match filter.apply(record, headers) {
+                Ok(true) => FilterOutcome::Passed,
+                Ok(false) => FilterOutcome::Failed,
+                Err(e) => FilterOutcome::Error(e.to_string()),
+            },
+            detail: filter.explain(record, headers),
+        };
+        let mut filters: Vec<FilterTrace> = self.filters.iter().map(|filter| trace(None, filter.as_ref())).collect();
+        let selected = filters.iter().all(|trace| trace.outcome == FilterOutcome::Passed);
+        for pipeline in &self.pipelines {
+            filters.extend(pipeline.filters.iter().map(|filter| trace(Some(&pipeline.name), filter.as_ref())));
+        }
+        RowExplanation { row, selected, filters }
+    }
+
+    /// First pass for group and clump filters
+    fn run_prepass(&self) -> Result<()> {
+        if self.prepass_filters.is_empty() {
+            return Ok(());
+        }
+        let (input, _) = self.open_stream()?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(input.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            for filter in &self.prepass_filters {
+                filter.observe(&record, &headers)?;
+            }
+        }
+        for filter in &self.prepass_filters {
+            filter.finish();
+        }
+        Ok(())
+    }
+
+    /// Check the output written to `path` against the sequential reference path
+    fn verify_output(&self, mode: VerifyMode, path: &std::path::Path) -> Result<()> {
+        match mode {
+            VerifyMode::Off => Ok(()),
+            VerifyMode::Full => {
+                let expected = self.reference_output_hash()?;
+                let actual = utils::calculate_file_checksum(
+                    path,
+                    utils::ChecksumAlgorithm::XxHash64,
+                )?;
+                if expected != actual {
+                    return Err(ExtractorError::VerificationFailed(format!(
+                        "output hash {actual:016x} differs from sequential reference {expected:016x}"
+                    )));
+                }
+                Ok(())
+            }
+            VerifyMode::Sampled { every } => {
+                let every = every.max(1);
+                let expected = self.reference_row_digest(every)?;
+                let actual = self.output_row_digest(every, path)?;
+                if expected != actual {
+                    return Err(ExtractorError::VerificationFailed(format!(
+                        "{} sampled output rows (digest {:016x}) differ from {} in the sequential reference (digest {:016x})",
+                        actual.rows, actual.sum, expected.rows, expected.sum
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Hash of the output the sequential path would write, without writing it
+    fn reference_output_hash(&self) -> Result<u64> {
+        let input = self.open_input()?;
+        let mut reader = self.stream_reader(self.decode(self.retrying(input))?.reader);
+        let mut writer = RowWriter::new(
+            HashWriter(Xxh64::new(0)),
+            self.config.output_mode,
+            self.config.delimiter,
+            self.config.quoting,
+        );
+
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        if self.writes_header() {
+            let header = self.output_header(&header_record);
+            writer.write(&header, kept_span(&header, &self.header_span(&reader)))?;
+        }
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let row = if self.or_skipped(self.matches_uncounted(&record, &headers))? {
+                self.or_skipped(self.transform_row(&record, &headers, &excluded))?
+            } else {
+                None
+            };
+            if let Some(row) = row {
+                writer.write(&row, kept_span(&row, record_span(&reader, &record)))?;
+            }
+            release_span(&mut reader);
+        }
+        Ok(writer.into_inner()?.0.digest())
+    }
+
+    /// Digest of the sampled input rows that the filters match
+    fn reference_row_digest(&self, every: u64) -> Result<RowSetDigest> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(self.open_stream()?.0.reader);
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+
+        let mut digest = RowSetDigest::default();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let hash = if !self.rewrites_rows() {
+                let hash = row_hash(&record);
+                (hash.is_multiple_of(every) && self.or_skipped(self.matches_uncounted(&record, &headers))?)
+                    .then_some(hash)
+            } else if self.or_skipped(self.matches_uncounted(&record, &headers))? {
+                // Rows are sampled as written, so transformed rows are hashed after filtering
+                self.or_skipped(self.transform_row(&record, &headers, &excluded))?
+                    .map(|row| row_hash(&row))
+                    .filter(|hash| hash.is_multiple_of(every))
+            } else {
+                None
+            };
+            if let Some(hash) = hash {
+                digest.add(hash);
+            }
+        }
+        Ok(digest)
+    }
+
+    /// Digest of the sampled rows in the output written to `path`
+    fn output_row_digest(&self, every: u64, path: &std::path::Path) -> Result<RowSetDigest> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.writes_header())
+            .flexible(true)
+            .from_path(path)?;
+
+        let mut digest = RowSetDigest::default();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let hash = row_hash(&record);
+            if hash.is_multiple_of(every) {
+                digest.add(hash);
+            }
+        }
+        Ok(digest)
+    }
+
+    /// Apply the filters without touching the per-filter statistics
+    fn matches_uncounted(&self, record: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for filter in &self.filters {
+            if !filter.apply(record, headers)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Stream the input and write each matched row to a file per distinct
+    /// value of `column`. The output path is used as the output directory.
+    fn process_partitioned(&self, input: File, column: &str) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        let input = self.decode(self.retrying(input))?;
+        let (encoding, bom) = (input.encoding, input.bom);
+        let mut reader = self.stream_reader(input.reader);
+
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        let header_span = self.header_span(&reader);
+        let output_columns = self.output_columns(&header_record)?;
+        let column_idx = *output_columns
+            .get(column)
+            .ok_or_else(|| ExtractorError::column_not_in_headers(column, &output_columns))?;
+        match self.config.on_existing_output {
+            ExistingOutput::Overwrite => {}
+            ExistingOutput::Error if self.output_path.join(PARTITION_MANIFEST).exists() => {
+                return Err(ExtractorError::Config(format!(
+                    "Partitioned output {} already exists; enable overwrite to replace it",
+                    self.output_path.display()
+                )));
+            }
+            ExistingOutput::Error => {}
+            ExistingOutput::Append => {
+                return Err(ExtractorError::Config(
+                    "Partitioned output cannot be appended to".to_string()
+                ));
+            }
+        }
+
+        let mut observed = self.observer_set(&output_columns)?;
+        let output_header = self.output_header(&header_record);
+        let header_span = kept_span(&output_header, &header_span).to_vec();
+        let mut writer = PartitionedWriter::new(
+            &self.output_path,
+            column,
+            column_idx,
+            self.config.delimiter,
+            self.writes_header().then(|| output_header.into_owned()),
+        )?
+        .with_output_mode(self.config.output_mode, &header_span)
+        .with_quoting(self.config.quoting)
+        .with_write_mode(self.config.write_mode);
+
+        let mut stats = ProcessingStats {
+            input_size: self.input_path.metadata()
+                .map_err(|e| ExtractorError::io_error(e, &self.input_path))?
+                .len(),
+            encoding,
+            bom,
+            ..ProcessingStats::default()
+        };
+        let mut guard = MatchGuard::new(&self.config, Some(stats.input_size))?;
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            guard.check(reader.position().byte(), stats.rows_processed, stats.rows_matched, &mut stats.warnings)?;
+            stats.rows_processed += 1;
+            let row = self.select_row(&mut stats, &record, &headers, &excluded)?;
+            if let Some(row) = row {
+                writer.write_row(&row, kept_span(&row, record_span(&reader, &record)))?;
+                observed.observe(&row);
+                stats.rows_matched += 1;
+            }
+            release_span(&mut reader);
+        }
+
+        let (_, output_size) = writer.finish()?;
+        stats.output_size = output_size;
+        (stats.column_stats, stats.gene_summary) = observed.finish();
+        stats.processing_time_ms = start.elapsed().as_millis() as u64;
+        Ok(stats)
+    }
+
+    /// Whether shards may be written for the output path
+    fn check_shard_output(&self) -> Result<()> {
+        match self.config.on_existing_output {
+            ExistingOutput::Overwrite => Ok(()),
+            ExistingOutput::Error if ShardManifest::path(&self.output_path).exists() => {
+                Err(ExtractorError::Config(format!(
+                    "Sharded output {} already exists; enable overwrite to replace it",
+                    self.output_path.display()
+                )))
+            }
+            ExistingOutput::Error => Ok(()),
+            ExistingOutput::Append => Err(ExtractorError::Config(
+                "Sharded output cannot be appended to".to_string()
+            )),
+        }
+    }
+
+    /// Stream matched rows into size-limited shards of the output path
+    fn process_sharded(&self, input: File) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        let input = self.decode(self.retrying(input))?;
+        let (encoding, bom) = (input.encoding, input.bom);
+        let mut reader = self.stream_reader(input.reader);
+
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        let header_span = self.header_span(&reader);
+        self.check_shard_output()?;
+
+        let mut observed = self.observer_set(&self.output_columns(&header_record)?)?;
+        let output_header = self.output_header(&header_record);
+        let header_span = kept_span(&output_header, &header_span);
+        let mut writer = ShardedWriter::new(
+            &self.output_path,
+            self.config.max_output_rows,
+            self.config.max_output_bytes,
+            self.config.output_mode,
+            self.config.delimiter,
+            self.config.quoting,
+            self.writes_header().then_some((output_header.as_ref(), header_span)),
+        )?
+        .with_write_mode(self.config.write_mode);
+
+        let mut stats = ProcessingStats {
+            input_size: self.input_path.metadata()
+                .map_err(|e| ExtractorError::io_error(e, &self.input_path))?
+                .len(),
+            encoding,
+            bom,
+            ..ProcessingStats::default()
+        };
+        let mut guard = MatchGuard::new(&self.config, Some(stats.input_size))?;
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            guard.check(reader.position().byte(), stats.rows_processed, stats.rows_matched, &mut stats.warnings)?;
+            stats.rows_processed += 1;
+            let row = self.select_row(&mut stats, &record, &headers, &excluded)?;
+            if let Some(row) = row {
+                writer.write_row(&row, kept_span(&row, record_span(&reader, &record)))?;
+                observed.observe(&row);
+                stats.rows_matched += 1;
+            }
+            release_span(&mut reader);
+        }
+
+        let (_, output_size) = writer.finish()?;
+        stats.output_size = output_size;
+        (stats.column_stats, stats.gene_summary) = observed.finish();
+        stats.processing_time_ms = start.elapsed().as_millis() as u64;
+        Ok(stats)
+    }
+
+    /// Evaluate every pipeline against each row in one streaming pass
+    fn process_pipelines(&self, input: File) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        let input = self.decode(self.retrying(input))?;
+        let (encoding, bom) = (input.encoding, input.bom);
+        let mut reader = self.stream_reader(input.reader);
+
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        let output_header = self.output_header(&header_record);
+        let header_span = self.header_span(&reader);
+        let header_span = kept_span(&output_header, &header_span);
+
+        let mut writers = Vec::with_capacity(self.pipelines.len());
+        let mut outputs = Vec::with_capacity(self.pipelines.len());
+        for pipeline in &self.pipelines {
+            let (output, file) =
+                OutputFile::create(&pipeline.output_path, self.config.write_mode, self.config.on_existing_output)?;
+            let appended_to = output.existing_header(self.config.delimiter)?;
+            outputs.push(output);
+            let separate = appended_to.is_some() && ends_mid_line(&file)?;
+            let mut writer = RowWriter::new(
+                BufWriter::new(AppendWriter::new(file, separate)),
+                self.config.output_mode,
+                self.config.delimiter,
+                self.config.quoting,
+            );
+            if self.writes_header() {
+                match &appended_to {
+                    Some(existing) => check_appended_header(existing, &output_header)?,
+                    None => writer.write(&output_header, header_span)?,
+                }
+            }
+            writers.push(writer);
+        }
+
+        let mut stats = ProcessingStats {
+            input_size: self.input_path.metadata()
+                .map_err(|e| ExtractorError::io_error(e, &self.input_path))?
+                .len(),
+            encoding,
+            bom,
+            ..ProcessingStats::default()
+        };
+        let mut guard = MatchGuard::new(&self.config, Some(stats.input_size))?;
+        let mut pipeline_matches = vec![0u64; self.pipelines.len()];
+        let mut observed = self.observer_set(&self.output_columns(&header_record)?)?;
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            guard.check(reader.position().byte(), stats.rows_processed, stats.rows_matched, &mut stats.warnings)?;
+            stats.rows_processed += 1;
+            let row = self.select_row(&mut stats, &record, &headers, &excluded)?;
+            let Some(row) = row else {
+                release_span(&mut reader);
+                continue;
+            };
+
+            let span = kept_span(&row, record_span(&reader, &record));
+            let mut matched_any = false;
+            for (i, pipeline) in self.pipelines.iter().enumerate() {
+                let matches = match pipeline.matches(&record, &headers) {
+                    Ok(matches) => matches,
+                    Err(e) => {
+                        let e = e.at_row(stats.rows_processed, &record, self.config.delimiter);
+                        self.skip_row(e, &mut stats.row_errors)?;
+                        false
+                    }
+                };
+                if matches {
+                    writers[i].write(&row, span)?;
+                    pipeline_matches[i] += 1;
+                    matched_any = true;
+                }
+            }
+            if matched_any {
+                observed.observe(&row);
+                stats.rows_matched += 1;
+            }
+            release_span(&mut reader);
+        }
+
+        for (i, (pipeline, mut writer)) in self.pipelines.iter().zip(writers).enumerate() {
+            writer.flush().map_err(|e| ExtractorError::io_error(e, &pipeline.output_path))?;
+            let output_size = writer.into_inner()?.get_ref().get_ref().metadata()
+                .map_err(|e| ExtractorError::io_error(e, &pipeline.output_path))?
+                .len();
+            stats.output_size += output_size;
+            stats.pipelines.push(PipelineStats {
+                name: pipeline.name.clone(),
+                output_path: pipeline.output_path.clone(),
+                rows_matched: pipeline_matches[i],
+                output_size,
+            });
+        }
+        // Only publish the outputs once every pipeline has been written
+        for output in outputs {
+            output.commit()?;
+        }
+
+        (stats.column_stats, stats.gene_summary) = observed.finish();
+        stats.processing_time_ms = start.elapsed().as_millis() as u64;
+        Ok(stats)
+    }
+
+    /// Streaming reader that keeps each row's input bytes for passthrough output
+    fn stream_reader<R: Read>(&self, input: R) -> csv::Reader<RawCapture<R>> {
+        ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(RawCapture::new(input))
+    }
+
+    /// Column names of the input: its header row, or `column_names` when it
+    /// has none. `first` is what the reader returned from `byte_headers()`.
+    fn column_header(&self, first: &ByteRecord) -> ByteRecord {
+        match &self.config.column_names {
+            Some(names) if !self.config.has_headers => names.iter().map(|name| name.trim()).collect(),
+            _ => first.clone(),
+        }
+    }
+
+    /// Whether outputs start with a header row
+    fn writes_header(&self) -> bool {
+        self.config.has_headers || self.config.column_names.is_some()
+    }
+
+    /// Wrap `input` to read as UTF-8 delimited text, split into fields first
+    /// if it is fixed-width
+    fn decode<R: Read + Send + 'static>(&self, input: R) -> Result<Decoded> {
+        let mut decoded = decode_reader(input, self.config.encoding)?;
+        if let Some(layout) = &self.config.fixed_width {
+            let lines = BufReader::new(decoded.reader);
+            decoded.reader = Box::new(FixedWidthReader::new(lines, layout.clone(), self.config.delimiter));
+        }
+        Ok(decoded)
+    }
+
+    /// Decode the input, digesting its raw bytes on the way if `digest_input` is set
+    fn decode_input<R: Read + Send + 'static>(&self, input: R) -> Result<(Decoded, Option<InputDigest>)> {
+        let (input, digest) = DigestReader::new(input, self.input_digest_algorithm());
+        Ok((self.decode(input)?, digest))
+    }
+
+    fn input_digest_algorithm(&self) -> Option<DigestAlgorithm> {
+        self.config.digest.filter(|_| self.config.digest_input)
+    }
+
+    /// Wrap the output file in the configured digest and compression. When
+    /// `appending`, the digest also covers what the file already holds.
+    fn open_output(&self, file: File, appending: bool) -> Result<OutputWriter> {
+        let separate = appending && ends_mid_line(&file)?;
+        let existing = match self.config.digest {
+            Some(_) if appending => {
+                // Shares the cursor, which appended writes don't use
+                let mut existing = file.try_clone()?;
+                existing.rewind()?;
+                Some(existing)
+            }
+            _ => None,
+        };
+        let mut output = DigestWriter::new(BufWriter::new(file), self.config.digest);
+        if let Some(existing) = existing {
+            output.include(existing)?;
+        }
+        CompressedWriter::new(
+            AppendWriter::new(output, separate),
+            &self.config.output_compression,
+            self.config.delimiter,
+            self.writes_header(),
+        )
+    }
+
+    /// Finish compressing the output file and write its tabix index, if any,
+    /// returning the size and digest of the file
+    fn finish_output(&self, output: OutputWriter) -> Result<(u64, Option<String>)> {
+        let (output, index) = output.finish()?;
+        if let Some(index) = index {
+            write_tabix(&self.output_path, &index)?;
+        }
+        let (file, digest) = output.into_inner().finish();
+        Ok((file.get_ref().metadata()?.len(), digest))
+    }
+
+    /// Header row of the output: the input columns as changed by the
+    /// transforms, without `exclude_columns`
+    fn output_header<'h>(&self, header: &'h ByteRecord) -> Cow<'h, ByteRecord> {
+        let output = self.transformed_header(header);
+        if self.config.exclude_columns.is_empty() {
+            return output;
+        }
+        Cow::Owned(without_fields(&output, &self.exclusion_mask(&output)))
+    }
+
+    /// The input columns as changed by the transforms
+    fn transformed_header<'h>(&self, header: &'h ByteRecord) -> Cow<'h, ByteRecord> {
+        self.transforms.iter().fold(Cow::Borrowed(header), |header, transform| {
+            match transform.header(&header) {
+                Some(changed) => Cow::Owned(changed),
+                None => header,
+            }
+        })
+    }
+
+    /// Column indices in output rows, for settings that read them such as
+    /// observers and `partition_by`
+    fn output_columns(&self, header: &ByteRecord) -> Result<HashMap<String, usize>> {
+        resolve_columns(&self.output_header(header), &self.config.column_aliases)
+    }
+
+    /// Whether matched rows can differ from the input rows
+    fn rewrites_rows(&self) -> bool {
+        !self.transforms.is_empty() || !self.config.exclude_columns.is_empty() || self.config.output_na_token.is_some()
+    }
+
+    /// Which fields of a transformed row `exclude_columns` drops, for the
+    /// input `header`; empty when nothing is excluded
+    fn excluded_fields(&self, header: &ByteRecord) -> Vec<bool> {
+        if self.config.exclude_columns.is_empty() {
+            return Vec::new();
+        }
+        self.exclusion_mask(&self.transformed_header(header))
+    }
+
+    /// Which columns of the transformed header are in `exclude_columns`
+    fn exclusion_mask(&self, transformed: &ByteRecord) -> Vec<bool> {
+        let columns = resolve_columns(transformed, &self.config.column_aliases).unwrap_or_default();
+        let mut mask = vec![false; transformed.len()];
+        for column in &self.config.exclude_columns {
+            if let Some(&i) = columns.get(column) {
+                mask[i] = true;
+            }
+        }
+        mask
+    }
+
+    /// Run the transforms over a matched row, drop the `excluded` fields and
+    /// write `output_na_token` for fields matching the default empty tokens,
+    /// or `None` if a transform drops the row. Rows left unchanged are
+    /// borrowed, not copied.
+    fn transform_row<'r>(
+        &self,
+        record: &'r ByteRecord,
+        headers: &HashMap<String, usize>,
+        excluded: &[bool],
+    ) -> Result<Option<Cow<'r, ByteRecord>>> {
+        if !self.rewrites_rows() {
+            return Ok(Some(Cow::Borrowed(record)));
+        }
+        let mut row = record.clone();
+        for transform in &self.transforms {
+            if !transform.apply(&mut row, headers)? {
+                return Ok(None);
+            }
+        }
+        if !excluded.is_empty() {
+            row = without_fields(&row, excluded);
+        }
+        if let Some(token) = &self.config.output_na_token {
+            row = row.iter().map(|field| if is_default_empty(field) { token.as_bytes() } else { field }).collect();
+        }
+        Ok(Some(if row == *record { Cow::Borrowed(record) } else { Cow::Owned(row) }))
+    }
+
+    /// Input bytes of the header line, once `byte_headers()` has been read
+    fn header_span<R: Read>(&self, reader: &csv::Reader<RawCapture<R>>) -> Vec<u8> {
+        if self.config.has_headers {
+            reader.get_ref().span(0, reader.position().byte()).to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Without the `parallel` feature, parallel runs use the sequential path
+    #[cfg(not(feature = "parallel"))]
+    fn process_parallel(
+        &self,
+        input: File,
+        output: Option<File>,
+        appended_to: Option<&ByteRecord>,
+        index: Option<&mut Vec<ChunkIndex>>,
+    ) -> Result<ProcessingStats> {
+        match output {
+            Some(output) => self.process_sequential(input, output, appended_to, index),
+            None => self.process_sharded(input),
+        }
+    }
+
+    /// Process file in parallel using multiple threads. With `index`, each
+    /// chunk also indexes its rows on `index_column`. Without `output`, each
+    /// worker writes its own shard of the output path (`ThreadOutput::Partitioned`).
+    #[cfg(feature = "parallel")]
+    fn process_parallel(
+        &self,
+        input: File,
+        output: Option<File>,
+        appended_to: Option<&ByteRecord>,
+        mut index: Option<&mut Vec<ChunkIndex>>,
+    ) -> Result<ProcessingStats> {
+        let file_size = input.metadata()?.len();
+
+        let mmap = utils::create_mmap(&input, &SafeMmapOptions::default())?;
+        // Chunk boundaries are found in the raw bytes, so other encodings and
+        // fixed-width input are streamed
+        let encoding = self.config.encoding.resolve(&mmap);
+        if encoding != InputEncoding::Utf8 || self.config.fixed_width.is_some() {
+            drop(mmap);
+            return match output {
+                Some(output) => self.process_sequential(input, output, appended_to, index),
+                None => self.process_sharded(input),
+            };
+        }
+        let bom = encoding.has_bom(&mmap);
+        let data: &[u8] = if bom { &mmap[UTF8_BOM.len()..] } else { &mmap };
+
+        // Parse the header once; workers share the resulting column map
+        let (header_record, body_start) = if self.config.has_headers && !data.is_empty() {
+            let mut reader = ReaderBuilder::new()
+                .delimiter(self.config.delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(data);
+            let mut header = ByteRecord::new();
+            reader.read_byte_record(&mut header)?;
+            (header, reader.position().byte() as usize)
+        } else {
+            (self.column_header(&ByteRecord::new()), 0)
+        };
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        let output_columns = self.output_columns(&header_record)?;
+        let index_columns = if index.is_some() { self.index_columns(&headers)? } else { None };
+        // Chunk offsets are counted from after the byte order mark
+        let bom_len = if bom { UTF8_BOM.len() as u64 } else { 0 };
+        let index_columns = index_columns.as_ref().map(|columns| (columns, bom_len));
+
+        let progress = Progress::start(&self.config.progress, file_size, "Processing file");
+
+        let pool = &self.config.thread_pool;
+        let threads = pool.num_threads(self.config.num_threads);
+        let header = self.writes_header().then(|| self.output_header(&header_record));
+        let (mut output, thread_files) = match output {
+            Some(output) => {
+                let mut output = RowWriter::new(
+                    self.open_output(output, appended_to.is_some())?,
+                    self.config.output_mode,
+                    self.config.delimiter,
+                    self.config.quoting,
+                );
+                if let Some(header) = &header {
+                    match appended_to {
+                        Some(existing) => check_appended_header(existing, header)?,
+                        None => output.write(header, kept_span(header, &data[..body_start]))?,
+                    }
+                }
+                let scratch = (self.config.thread_output == ThreadOutput::Concatenated)
+                    .then(|| ThreadFiles::scratch(&self.output_path, threads));
+                (Some(output.into_inner()?), scratch)
+            }
+            None => {
+                let mut header_line =
+                    RowWriter::new(Vec::new(), self.config.output_mode, self.config.delimiter, self.config.quoting);
+                if let Some(header) = &header {
+                    header_line.write(header, kept_span(header, &data[..body_start]))?;
+                }
+                let shards =
+                    ThreadFiles::shards(&self.output_path, threads, header_line.into_inner()?, self.config.write_mode);
+                (None, Some(shards))
+            }
+        };
+
+        let max_in_flight = self
+            .config
+            .chunking
+            .max_in_flight
+            .unwrap_or(2 * threads)
+            .max(1);
+        let mut sizer = ChunkSizer::new(&self.config);
+        let mut observed = ObserverSet::default();
+        let mut rows_processed = 0;
+        let mut rows_matched = 0;
+        let mut row_errors = Vec::new();
+        let mut warnings = Vec::new();
+        let mut guard = MatchGuard::new(&self.config, Some(file_size))?;
+        let mut memory = MemoryStats::default();
+
+        let (tx, rx) = unbounded();
+        let input_digest = std::sync::OnceLock::new();
+
+        // Chunks are dispatched from this thread and written in order as they
+        // finish. At most `max_in_flight` chunks are unwritten at any time, so a
+        // slow chunk holds back new work instead of letting finished output pile up.
+        // Without `preserve_order`, rows are written as soon as their chunk
+        // finishes and only unfinished chunks count against the limit; counts,
+        // errors and index entries are still merged in input order. Workers
+        // with their own files write rows themselves and only send the counts.
+        let preserve_order = self.config.preserve_order && thread_files.is_none();
+        pool.in_place_scope(self.config.num_threads, |s| -> Result<()> {
+            // The input digest is computed alongside the chunks
+            if let Some(algorithm) = self.input_digest_algorithm() {
+                let (input_digest, whole) = (&input_digest, &mmap[..]);
+                s.spawn(move |_| {
+                    let _ = input_digest.set(digest_bytes(algorithm, whole));
+                });
+            }
+            let mut next_start = body_start;
+            let mut next_index = 0;
+            let mut next_to_write = 0;
+            let mut received = 0;
+            let mut finished = BTreeMap::new();
+            let mut bytes_merged = (bom_len as usize + body_start) as u64;
+            // Input bytes of unmerged chunks and output bytes of unwritten ones
+            let (mut in_flight_bytes, mut buffered_output) = (0u64, 0u64);
+
+            while next_start < data.len() || next_to_write < next_index {
+                let unwritten = if preserve_order { next_to_write } else { received };
+                while next_start < data.len() && next_index - unwritten < max_in_flight {
+                    // Chunks end on row boundaries so no worker starts inside a quoted field
+                    let start = next_start;
+                    let end = utils::next_row_boundary(data, start, start + sizer.next_size());
+                    let chunk_index = next_index;
+                    next_start = end;
+                    next_index += 1;
+                    in_flight_bytes += (end - start) as u64;
+
+                    let tx = tx.clone();
+                    let filters = &self.filters;
+                    let headers = &headers;
+                    let excluded = &excluded;
+                    let output_columns = &output_columns;
+                    let thread_files = thread_files.as_ref();
+                    let progress = &progress;
+
+                    s.spawn(move |_| {
+                        let started = Instant::now();
+                        let chunk = Chunk { data, start, end };
+                        let results = panic::catch_unwind(AssertUnwindSafe(|| {
+                            self.process_chunk(&chunk, filters, headers, excluded, output_columns, index_columns)
+                        }))
+                        .unwrap_or_else(|_| {
+                            Err(ExtractorError::Parallel(format!("worker panicked on chunk {chunk_index}")))
+                        });
+                        let results = match (thread_files, results) {
+                            (Some(files), Ok(mut result)) => {
+                                let thread = rayon::current_thread_index().unwrap_or(0);
+                                let rows = std::mem::take(&mut result.output_data);
+                                files.write(thread, &rows, result.rows_matched).map(|_| result)
+                            }
+                            (_, results) => results,
+                        };
+                        progress.inc((end - start) as u64);
+
+                        let _ = tx.send((chunk_index, end - start, started.elapsed(), results));
+                    });
+                }
+
+                memory.peak_chunks_in_flight = memory.peak_chunks_in_flight.max(next_index - next_to_write);
+                memory.peak_in_flight_bytes = memory.peak_in_flight_bytes.max(in_flight_bytes);
+
+                let (chunk_index, bytes, elapsed, mut results) = recv_chunk(&rx)?;
+                sizer.record(bytes, elapsed);
+                received += 1;
+                if let (false, Some(output), Ok(chunk_result)) = (preserve_order, output.as_mut(), &mut results) {
+                    output.write_all(&std::mem::take(&mut chunk_result.output_data))?;
+                }
+                if let Ok(chunk_result) = &results {
+                    buffered_output += chunk_result.output_data.len() as u64;
+                    memory.peak_buffered_output_bytes = memory.peak_buffered_output_bytes.max(buffered_output);
+                }
+                finished.insert(chunk_index, (bytes, results));
+
+                // Errors surface in input order, so the first bad row is the one reported
+                while let Some((bytes, chunk_result)) = finished.remove(&next_to_write) {
+                    let chunk_result = chunk_result.map_err(|e| e.after_rows(rows_processed))?;
+                    if let Some(output) = output.as_mut() {
+                        output.write_all(&chunk_result.output_data)?;
+                    }
+                    buffered_output -= chunk_result.output_data.len() as u64;
+                    in_flight_bytes -= bytes as u64;
+                    RowErrorSummary::merge(&mut row_errors, chunk_result.row_errors, rows_processed);
+                    rows_processed += chunk_result.rows_processed;
+                    rows_matched += chunk_result.rows_matched;
+                    observed.merge(chunk_result.observed);
+                    if let (Some(index), Some(rows)) = (index.as_mut(), chunk_result.index) {
+                        index.push(rows);
+                    }
+                    next_to_write += 1;
+                    bytes_merged += bytes as u64;
+                    guard.check(bytes_merged, rows_processed, rows_matched, &mut warnings)?;
+                }
+            }
+            Ok(())
+        })??;
+        let (output_size, output_digest) = match output {
+            Some(mut output) => {
+                if let Some(files) = thread_files {
+                    files.concatenate(&mut output)?;
+                }
+                self.finish_output(output)?
+            }
+            None => (thread_files.expect("shards are written without an output file").finish()?.1, None),
+        };
+        progress.finish();
+
+        let (column_stats, gene_summary) = observed.finish();
+        Ok(ProcessingStats {
+            rows_processed,
+            rows_matched,
+            processing_time_ms: 0, // TODO: Add timing
+            input_size: file_size,
+            output_size,
+            column_stats,
+            gene_summary,
+            encoding,
+            bom,
+            input_digest: input_digest.into_inner(),
+            output_digest,
+            row_errors,
+            warnings,
+            memory,
+            ..ProcessingStats::default()
+        })
+    }
+
+    /// Stream a remote input through the sequential path into the output file
+    #[cfg(feature = "object-store")]
+    fn process_remote(&self) -> Result<ProcessingStats> {
+        if !self.pipelines.is_empty()
+            || self.config.partition_by.is_some()
+            || self.config.max_output_rows.is_some()
+            || self.config.max_output_bytes.is_some()
+            || self.config.thread_output == ThreadOutput::Partitioned
+        {
+            return Err(ExtractorError::Config(
+                "Pipelines, partitioned and sharded output need a local input file".to_string()
+            ));
+        }
+        let object = remote::RemoteObject::open(&self.input_path, &self.config.remote)?;
+        let input_size = object.size();
+        let (input, input_digest) = self.decode_input(object.stream())?;
+        let (output, output_file) =
+            OutputFile::create(&self.output_path, self.config.write_mode, self.config.on_existing_output)?;
+        let appended_to = output.existing_header(self.config.delimiter)?;
+        let (encoding, bom) = (input.encoding, input.bom);
+        let output_file = self.open_output(output_file, appended_to.is_some())?;
+        let (stats, output_file) =
+            self.filter_stream(input.reader, output_file, appended_to.as_ref(), None, Some(input_size))?;
+        let (output_size, output_digest) = self.finish_output(output_file)?;
+        output.commit()?;
+        Ok(ProcessingStats {
+            input_size,
+            output_size,
+            encoding,
+            bom,
+            input_digest: input_digest.map(|digest| digest.finish()),
+            output_digest,
+            ..stats
+        })
+    }
+
+    /// Stream a Parquet input through the sequential path into the output
+    /// file, reading only the row groups whose statistics leave a chance
+    /// that a row passes every filter
+    #[cfg(feature = "parquet")]
+    fn process_parquet(&self) -> Result<ProcessingStats> {
+        if !self.pipelines.is_empty()
+            || self.config.partition_by.is_some()
+            || self.config.max_output_rows.is_some()
+            || self.config.max_output_bytes.is_some()
+            || self.config.thread_output == ThreadOutput::Partitioned
+        {
+            return Err(ExtractorError::Config(
+                "Pipelines, partitioned and sharded output need a delimited input file".to_string()
+            ));
+        }
+        let parquet = ParquetInput::open(&self.input_path)?;
+        let row_groups = if self.config.has_headers {
+            let header: ByteRecord = parquet.column_names().iter().collect();
+            let headers = resolve_columns(&header, &self.config.column_aliases)?;
+            let checks: Vec<(usize, &dyn Filter)> = self
+                .filters
+                .iter()
+                .filter_map(|filter| Some((*headers.get(filter.column_name())?, filter.as_ref())))
+                .collect();
+            parquet.candidate_row_groups(&checks)
+        } else {
+            (0..parquet.num_row_groups()).collect()
+        };
+        let input_size = self.input_path.metadata().map_err(|e| ExtractorError::io_error(e, &self.input_path))?.len();
+        let rows = parquet.rows(row_groups, self.config.delimiter, self.config.has_headers)?;
+
+        let (output, output_file) =
+            OutputFile::create(&self.output_path, self.config.write_mode, self.config.on_existing_output)?;
+        let appended_to = output.existing_header(self.config.delimiter)?;
+        let output_file = self.open_output(output_file, appended_to.is_some())?;
+        let (stats, output_file) = self.filter_stream(rows, output_file, appended_to.as_ref(), None, None)?;
+        let (output_size, output_digest) = self.finish_output(output_file)?;
+        output.commit()?;
+        Ok(ProcessingStats { input_size, output_size, output_digest, ..stats })
+    }
+
+    /// Open the local input file, retrying transient failures
+    fn open_input(&self) -> Result<File> {
+        retry::retry(&self.config.io_retry, || {
+            File::open(&self.input_path).map_err(|e| ExtractorError::io_error(e, &self.input_path))
+        })
+    }
+
+    /// Retry transient failures of reads from `input`
+    fn retrying<R: Read>(&self, input: R) -> RetryingReader<R> {
+        RetryingReader::new(input, self.config.io_retry.clone())
+    }
+
+    /// Open the input for one sequential pass, decoded to UTF-8, returning it
+    /// with its size in bytes. Object store URLs are fetched with ranged GETs.
+    fn open_stream(&self) -> Result<(Decoded, u64)> {
+        #[cfg(feature = "object-store")]
+        if is_remote(&self.input_path) {
+            let object = remote::RemoteObject::open(&self.input_path, &self.config.remote)?;
+            return Ok((self.decode(object.stream())?, object.size()));
+        }
+        #[cfg(feature = "parquet")]
+        if is_parquet(&self.input_path) {
+            let parquet = ParquetInput::open(&self.input_path)?;
+            let row_groups = (0..parquet.num_row_groups()).collect();
+            let rows = parquet.rows(row_groups, self.config.delimiter, self.config.has_headers)?;
+            let size = self.input_path.metadata().map_err(|e| ExtractorError::io_error(e, &self.input_path))?.len();
+            return Ok((decode_reader(rows, InputEncoding::Utf8)?, size));
+        }
+        let file = self.open_input()?;
+        let size = file.metadata()
+            .map_err(|e| ExtractorError::io_error(e, &self.input_path))?
+            .len();
+        Ok((self.decode(self.retrying(file))?, size))
+    }
+
+    /// Process file sequentially in a single thread. With `index`, the rows
+    /// are also indexed on `index_column`.
+    fn process_sequential<R: Read + Send + 'static>(
+        &self,
+        input: R,
+        output: File,
+        appended_to: Option<&ByteRecord>,
+        index: Option<&mut Vec<ChunkIndex>>,
+    ) -> Result<ProcessingStats> {
+        let (input, input_digest) = self.decode_input(self.retrying(input))?;
+        if index.is_some() && self.config.fixed_width.is_some() {
+            return Err(ExtractorError::Config(
+                "An index can't be built while filtering fixed-width input, as row offsets would not match the file"
+                    .to_string(),
+            ));
+        }
+        if index.is_some() && input.encoding != InputEncoding::Utf8 {
+            return Err(ExtractorError::Config(format!(
+                "An index can't be built while filtering {:?} input, as row offsets would not match the file",
+                input.encoding
+            )));
+        }
+        // The decoded stream starts after the byte order mark
+        let bom_len = if input.bom { UTF8_BOM.len() as u64 } else { 0 };
+        let mut rows = ChunkIndex::default();
+        let input_size = self.input_path.metadata()?.len();
+        let output = self.open_output(output, appended_to.is_some())?;
+        let (stats, output) = self.filter_stream(
+            input.reader,
+            output,
+            appended_to,
+            index.is_some().then_some((&mut rows, bom_len)),
+            Some(input_size),
+        )?;
+        if let Some(index) = index {
+            index.push(rows);
+        }
+        let (output_size, output_digest) = self.finish_output(output)?;
+        Ok(ProcessingStats {
+            input_size,
+            output_size,
+            encoding: input.encoding,
+            bom: input.bom,
+            input_digest: input_digest.map(|digest| digest.finish()),
+            output_digest,
+            ..stats
+        })
+    }
+
+    /// Stream the header and the blocks of `blocks` the filters can't rule
+    /// out, read straight from their offsets, through the sequential path
+    fn process_blocks(
+        &self,
+        blocks: &BlockIndex,
+        input: File,
+        output: File,
+        appended_to: Option<&ByteRecord>,
+    ) -> Result<ProcessingStats> {
+        if !blocks.verify(&input)? {
+            return Err(ExtractorError::index_error(IndexErrorKind::Outdated, Some(&self.input_path)));
+        }
+        if blocks.delimiter != self.config.delimiter {
+            return Err(ExtractorError::Config(format!(
+                "The block index was built with delimiter {:?}, not {:?}",
+                blocks.delimiter as char, self.config.delimiter as char
+            )));
+        }
+        let raw_header = self.local_header(&input, "A block index")?;
+        let headers = resolve_columns(&raw_header, &self.config.column_aliases)?;
+        let positions = header_map(&raw_header);
+
+        // Each filter with the block index's summary of its column
+        let checks: Vec<(usize, &dyn Filter)> = self
+            .filters
+            .iter()
+            .filter_map(|filter| {
+                let idx = headers.get(filter.column_name())?;
+                let zone = blocks.columns.iter().position(|column| positions.get(column) == Some(idx))?;
+                Some((zone, filter.as_ref()))
+            })
+            .collect();
+        let ranges = std::iter::once((0, blocks.header_length))
+            .chain(blocks.candidate_blocks(&checks).map(|block| (block.offset, block.length)))
+            .collect();
+        self.process_sequential(RangeReader::new(input, ranges), output, appended_to, None)
+    }
+
+    /// Test the numeric filters against the column cache at `path`, building
+    /// it first if it is missing, stale or lacks a filtered column, then
+    /// stream the header and the rows that pass through the sequential path
+    fn process_cached(
+        &self,
+        path: &std::path::Path,
+        input: File,
+        output: File,
+        appended_to: Option<&ByteRecord>,
+    ) -> Result<ProcessingStats> {
+        let raw_header = self.local_header(&input, "A column cache")?;
+        let headers = resolve_columns(&raw_header, &self.config.column_aliases)?;
+        let names: Vec<String> = header_names(&raw_header).collect();
+        // Each numeric filter with its column's name in the input header
+        let numeric: Vec<(&str, &dyn Filter)> = self
+            .filters
+            .iter()
+            .filter(|filter| filter.test_number(0.0).is_some())
+            .filter_map(|filter| Some((names.get(*headers.get(filter.column_name())?)?.as_str(), filter.as_ref())))
+            .collect();
+
+        let cached = ColumnCache::load(path).ok().filter(|cache| {
+            cache.metadata.delimiter == self.config.delimiter && cache.verify(&input).unwrap_or(false)
+        });
+        let cache = match cached {
+            Some(cache) if numeric.iter().all(|(column, _)| cache.column(column).is_some()) => cache,
+            cached => {
+                // Keep the columns cached so far, so sweeps over different columns share one cache
+                let mut columns = cached.map_or_else(Vec::new, |cache| cache.metadata.columns);
+                for (column, _) in &numeric {
+                    if !columns.iter().any(|c| c == column) {
+                        columns.push(column.to_string());
+                    }
+                }
+                let cache = ColumnCache::build(self.input_path.clone(), columns, self.config.delimiter)?;
+                cache.save(path)?;
+                cache
+            }
+        };
+
+        let checks: Vec<(usize, &dyn Filter)> = numeric
+            .iter()
+            .filter_map(|(column, filter)| {
+                Some((cache.metadata.columns.iter().position(|c| c == column)?, *filter))
+            })
+            .collect();
+        let ranges = std::iter::once((0, cache.metadata.header_length))
+            .chain(cache.passing_ranges(&checks))
+            .collect();
+        self.process_sequential(RangeReader::new(input, ranges), output, appended_to, None)
+    }
+
+    /// Header row of the local input, checking that it is UTF-8 so byte
+    /// offsets recorded by `what` match the file
+    fn local_header(&self, input: &File, what: &str) -> Result<ByteRecord> {
+        let mut file = input.try_clone().map_err(|e| ExtractorError::io_error(e, &self.input_path))?;
+        file.rewind().map_err(|e| ExtractorError::io_error(e, &self.input_path))?;
+        if self.config.fixed_width.is_some() {
+            return Err(ExtractorError::Config(format!(
+                "{what} can't be used with fixed-width input, as its byte offsets would not match the file"
+            )));
+        }
+        let decoded = decode_reader(self.retrying(file), self.config.encoding)?;
+        if decoded.encoding != InputEncoding::Utf8 {
+            return Err(ExtractorError::Config(format!(
+                "{what} can't be used with {:?} input, as its byte offsets would not match the file",
+                decoded.encoding
+            )));
+        }
+        Ok(self.stream_reader(decoded.reader).byte_headers()?.clone())
+    }
+
+    /// Stream rows from `input` to `output`, returning the flushed writer.
+    /// `input_size` is the number of bytes the reader consumed. When
+    /// `appended_to` holds the header of an output being appended to, the
+    /// input header is checked against it instead of being written. `index`
+    /// collects each row's position in the input file on `index_column`,
+    /// given the file offset of the first byte of `input`.
+    fn filter_stream<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+        appended_to: Option<&ByteRecord>,
+        mut index: Option<(&mut ChunkIndex, u64)>,
+        input_size: Option<u64>,
+    ) -> Result<(ProcessingStats, W)> {
+        let mut reader = self.stream_reader(input);
+        let mut writer = RowWriter::new(output, self.config.output_mode, self.config.delimiter, self.config.quoting);
+
+        let header_record = self.column_header(reader.byte_headers()?);
+        let headers = resolve_columns(&header_record, &self.config.column_aliases)?;
+        let excluded = self.excluded_fields(&header_record);
+        if self.writes_header() {
+            let header = self.output_header(&header_record);
+            match appended_to {
+                Some(existing) => check_appended_header(existing, &header)?,
+                None => writer.write(&header, kept_span(&header, &self.header_span(&reader)))?,
+            }
+        }
+        let mut observed = self.observer_set(&self.output_columns(&header_record)?)?;
+        let index_columns = if index.is_some() { self.index_columns(&headers)? } else { None };
+
+        let mut stats = ProcessingStats::default();
+        let mut guard = MatchGuard::new(&self.config, input_size)?;
+
+        // One record buffer reused for every row
+        let mut record = ByteRecord::new();
+        let read_error = |e: csv::Error, rows: u64, record: &ByteRecord| {
+            ExtractorError::from(e).at_row(rows + 1, record, self.config.delimiter)
+        };
+        while reader.read_byte_record(&mut record).map_err(|e| read_error(e, stats.rows_processed, &record))? {
+            guard.check(reader.position().byte(), stats.rows_processed, stats.rows_matched, &mut stats.warnings)?;
+            stats.rows_processed += 1;
+            if let (Some((rows, offset)), Some(columns)) = (index.as_mut(), &index_columns) {
+                let start = record.position().map_or(0, |p| p.byte());
+                let length = (reader.position().byte() - start) as u32;
+                rows.add_row(&record, *offset + start, length, columns)?;
+            }
+
+            let row = self.select_row(&mut stats, &record, &headers, &excluded)?;
+            if let Some(row) = row {
+                writer.write(&row, kept_span(&row, record_span(&reader, &record)))?;
+                observed.observe(&row);
+                stats.rows_matched += 1;
+            }
+            release_span(&mut reader);
+        }
+
+        writer.flush()?;
+        stats.input_size = reader.position().byte();
+        (stats.column_stats, stats.gene_summary) = observed.finish();
+        Ok((stats, writer.into_inner()?))
+    }
+
+    /// Process a single chunk of data.
+    ///
+    /// Rows are parsed into one reused `ByteRecord` and matched rows are copied
+    /// to the output verbatim, so nothing is allocated per row. Rows are split
+    /// at newlines, so a quoted field containing a newline must not straddle a
+    /// chunk boundary.
+    #[cfg(feature = "parallel")]
+    fn process_chunk(
+        &self,
+        chunk: &Chunk,
+        filters: &[Box<dyn Filter>],
+        headers: &HashMap<String, usize>,
+        excluded: &[bool],
+        output_columns: &HashMap<String, usize>,
+        index: Option<(&IndexColumns, u64)>,
+    ) -> Result<ChunkResult> {
+        let data = chunk.data;
+        let mut result = ChunkResult {
+            rows_processed: 0,
+            rows_matched: 0,
+            row_errors: Vec::new(),
+            output_data: Vec::new(),
+            observed: self.observer_set(output_columns)?,
+            index: index.map(|_| ChunkIndex::default()),
+        };
+        let mut output = RowWriter::new(
+            Vec::with_capacity(chunk.end - chunk.start),
+            self.config.output_mode,
+            self.config.delimiter,
+            self.config.quoting,
+        );
+
+        // The chunk owns every row that starts inside start..end; a row that
+        // began in the previous chunk is finished by that chunk instead.
+        let first = if chunk.start == 0 || data[chunk.start - 1] == b'\n' {
+            chunk.start
+        } else {
+            utils::find_line_end(data, chunk.start) + 1
+        };
+        if first >= chunk.end {
+            result.output_data = output.into_inner()?;
+            return Ok(result);
+        }
+
+        // Start the reader on the preceding newline: csv strips a UTF-8 BOM at
+        // the start of its input, which is only right at the start of the file
+        let origin = first.saturating_sub(1);
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(&data[origin..]);
+
+        // Rows are numbered within the chunk; the chunks before it are added
+        // when its result is written
+        let mut record = ByteRecord::new();
+        while reader
+            .read_byte_record(&mut record)
+            .map_err(|e| ExtractorError::from(e).at_row(result.rows_processed + 1, &record, self.config.delimiter))?
+        {
+            let span_start = origin + record.position().map_or(0, |p| p.byte()) as usize;
+            let span = &data[span_start..origin + reader.position().byte() as usize];
+            // Ownership is decided by where the row's content starts, not by
+            // the blank lines or CRLF remainder the reader counts in front of it
+            if span_start + row_content(span).0 >= chunk.end {
+                break;
+            }
+            result.rows_processed += 1;
+            if let (Some(rows), Some((columns, offset))) = (result.index.as_mut(), index) {
+                rows.add_row(&record, offset + span_start as u64, span.len() as u32, columns)?;
+            }
+
+            let row = match self.process_row(&record, filters, headers) {
+                Ok(true) => self.transform_row(&record, headers, excluded),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            };
+            let row = row.or_else(|e| {
+                let e = e.at_row(result.rows_processed, &record, self.config.delimiter);
+                self.skip_row(e, &mut result.row_errors).map(|_| None)
+            })?;
+            if let Some(row) = row {
+                output.write(&row, kept_span(&row, span))?;
+                result.observed.observe(&row);
+                result.rows_matched += 1;
+            }
+        }
+
+        result.output_data = output.into_inner()?;
+        Ok(result)
+    }
+
+    /// Process a single row of data
+    #[cfg(feature = "parallel")]
+    #[inline]
+    fn process_row(
+        &self,
+        record: &ByteRecord,
+        filters: &[Box<dyn Filter>],
+        headers: &HashMap<String, usize>,
+    ) -> Result<bool> {
+        for (filter, counters) in filters.iter().zip(&self.filter_counters) {
+            if !counters.apply(filter.as_ref(), record, headers)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Helper method to parse a row into fields
+    fn parse_row<'a>(&self, row: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut fields = Vec::new();
+        let mut start = 0;
+        let mut in_quotes = false;
+        
+        for (i, &byte) in row.iter().enumerate() {
+            match byte {
+                b'"' => in_quotes = !in_quotes,
+                b',' if !in_quotes => {
+                    fields.push(&row[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        
+        // Add the last field
+        if start < row.len() {
+            fields.push(&row[start..]);
+        }
+        
+        fields
+    }
+
+    /// The row to write for the record just counted in `stats`, if it passes
+    /// the filters. Errors say which row they came from.
+    fn select_row<'r>(
+        &self,
+        stats: &mut ProcessingStats,
+        record: &'r ByteRecord,
+        headers: &HashMap<String, usize>,
+        excluded: &[bool],
+    ) -> Result<Option<Cow<'r, ByteRecord>>> {
+        let row = match self.record_matches(record, headers) {
+            Ok(true) => self.transform_row(record, headers, excluded),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e),
+        };
+        row.or_else(|e| {
+            self.skip_row(e.at_row(stats.rows_processed, record, self.config.delimiter), &mut stats.row_errors)?;
+            Ok(None)
+        })
+    }
+
+    /// Count a data error in `row_errors` under `RowErrorPolicy::Skip`, or
+    /// return it to stop the run
+    fn skip_row(&self, error: ExtractorError, row_errors: &mut Vec<RowErrorSummary>) -> Result<()> {
+        if self.config.on_row_error == RowErrorPolicy::Skip && error.is_data_error() {
+            RowErrorSummary::record(row_errors, &error);
+            return Ok(());
+        }
+        Err(error)
+    }
+
+    /// `result`, or its default (no match) when it is a data error that is
+    /// skipped. Used by the verification passes, which don't count errors.
+    fn or_skipped<T: Default>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(e) if self.config.on_row_error == RowErrorPolicy::Skip && e.is_data_error() => Ok(T::default()),
+            result => result,
+        }
+    }
+
+    /// Check a parsed record against every filter in the pipeline
+    fn record_matches(&self, record: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for (filter, counters) in self.filters.iter().zip(&self.filter_counters) {
+            if !counters.apply(filter.as_ref(), record, headers)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Picks chunk sizes so each chunk takes about the target time, based on the
+/// throughput of chunks that have already finished
+#[cfg(feature = "parallel")]
+struct ChunkSizer {
+    size: usize,
+    min: usize,
+    max: usize,
+    target_secs: f64,
+    adaptive: bool,
+}
+
+#[cfg(feature = "parallel")]
+impl ChunkSizer {
+    fn new(config: &Config) -> Self {
+        let chunking = &config.chunking;
+        // The starting chunk_size is always within range, even outside the defaults
+        let size = config.chunk_size.max(1);
+        let min = chunking.min_chunk_size.min(size).max(1);
+        let max = chunking.max_chunk_size.max(size);
+        Self {
+            size,
+            min,
+            max,
+            target_secs: chunking.target_chunk_ms.max(1) as f64 / 1000.0,
+            adaptive: chunking.adaptive,
+        }
+    }
+
+    fn next_size(&self) -> usize {
+        self.size
+    }
+
+    /// Account for a finished chunk of `bytes` that took `elapsed`
+    fn record(&mut self, bytes: usize, elapsed: Duration) {
+        if !self.adaptive || bytes == 0 {
+            return;
+        }
+        let ideal = bytes as f64 * self.target_secs / elapsed.as_secs_f64().max(1e-6);
+        // Move halfway toward the ideal so one noisy chunk can't swing the size
+        let next = (self.size as f64 + ideal) / 2.0;
+        self.size = (next as usize).clamp(self.min, self.max);
+    }
+}
+
+/// Checks `Config::zero_match_guard` once a run has read enough of its input
+struct MatchGuard {
+    guard: Option<ZeroMatchGuard>,
+    /// Bytes to read before checking
+    after_bytes: u64,
+}
+
+impl MatchGuard {
+    /// Guard for an input of `input_size` bytes; without a size nothing is checked
+    fn new(config: &Config, input_size: Option<u64>) -> Result<Self> {
+        let guard = config.zero_match_guard;
+        if let Some(guard) = guard.filter(|guard| !(guard.after_percent > 0.0 && guard.after_percent <= 100.0)) {
+            return Err(ExtractorError::Config(format!(
+                "zero_match_guard.after_percent must be in (0, 100], not {}",
+                guard.after_percent
+            )));
+        }
+        Ok(match (guard, input_size) {
+            (Some(guard), Some(size)) => Self {
+                guard: Some(guard),
+                after_bytes: (size as f64 * guard.after_percent / 100.0).ceil() as u64,
+            },
+            _ => Self { guard: None, after_bytes: 0 },
+        })
+    }
+
+    /// Account for `bytes_read` bytes of input that gave `rows_processed`
+    /// rows and `rows_matched` matches. Checks once, when the threshold is passed.
+    fn check(
+        &mut self,
+        bytes_read: u64,
+        rows_processed: u64,
+        rows_matched: u64,
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        let Some(guard) = self.guard.filter(|_| bytes_read >= self.after_bytes) else {
+            return Ok(());
+        };
+        self.guard = None;
+        if rows_matched > 0 {
+            return Ok(());
+        }
+        let message = format!(
+            "none of the first {rows_processed} rows ({}% of the input) matched; check the filter columns and values",
+            guard.after_percent
+        );
+        if guard.abort {
+            return Err(ExtractorError::NoMatches(message));
+        }
+        tracing::warn!("{message}");
+        warnings.push(message);
+        Ok(())
+    }
+}
+
+/// Write adapter that counts the bytes passed through it
+struct ByteCounter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for ByteCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write sink that only hashes the bytes it is given
+struct HashWriter(Xxh64);
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Order-independent digest of a set of rows
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RowSetDigest {
+    rows: u64,
+    sum: u64,
+}
+
+impl RowSetDigest {
+    fn add(&mut self, hash: u64) {
+        self.rows += 1;
+        self.sum = self.sum.wrapping_add(hash);
+    }
+}
+
+/// Hash of a record's field values, independent of quoting and delimiter
+fn row_hash(record: &ByteRecord) -> u64 {
+    let mut hasher = Xxh64::new(0);
+    for field in record {
+        hasher.update(&(field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    hasher.digest()
+}
+
+/// Wait for the next finished chunk. A caller that is itself a pool worker
+/// keeps running queued tasks meanwhile, so a one-thread pool can't stall.
+#[cfg(feature = "parallel")]
+fn recv_chunk<T>(rx: &Receiver<T>) -> Result<T> {
+    let closed = || ExtractorError::Parallel("chunk results channel closed".to_string());
+    loop {
+        match rx.try_recv() {
+            Ok(message) => return Ok(message),
+            Err(TryRecvError::Disconnected) => return Err(closed()),
+            Err(TryRecvError::Empty) => {}
+        }
+        match rayon::yield_now() {
+            Some(rayon::Yield::Executed) => {}
+            Some(rayon::Yield::Idle) => {
+                if let Ok(message) = rx.recv_timeout(Duration::from_millis(1)) {
+                    return Ok(message);
+                }
+            }
+            None => return rx.recv().map_err(|_| closed()),
+        }
+    }
+}
+
+/// Input bytes of the record just read by a capturing reader
+#[inline]
+fn record_span<'r, R: Read>(reader: &'r csv::Reader<RawCapture<R>>, record: &ByteRecord) -> &'r [u8] {
+    let start = record.position().map_or(0, |p| p.byte());
+    reader.get_ref().span(start, reader.position().byte())
+}
+
+/// Input bytes to copy for `row` in passthrough mode: none once a transform
+/// has rewritten it, so it is serialized instead
+#[inline]
+#[allow(clippy::ptr_arg)] // Whether the row is borrowed is the point
+fn kept_span<'s>(row: &Cow<'_, ByteRecord>, span: &'s [u8]) -> &'s [u8] {
+    match row {
+        Cow::Borrowed(_) => span,
+        Cow::Owned(_) => &[],
+    }
+}
+
+/// Let the capturing reader drop bytes of rows that have been handled
+#[inline]
+fn release_span<R: Read>(reader: &mut csv::Reader<RawCapture<R>>) {
+    let pos = reader.position().byte();
+    reader.get_mut().release(pos);
+}
+
+/// Check that `column_names` can name the columns of headerless input
+fn check_column_names(config: &Config) -> Result<()> {
+    if let Some(names) = &config.column_names {
+        if config.has_headers {
+            return Err(ExtractorError::Config(
+                "column_names only applies to input without a header row".to_string()
+            ));
+        }
+        let mut seen = std::collections::HashSet::new();
+        if let Some(name) = names.iter().find(|name| name.trim().is_empty() || !seen.insert(name.trim())) {
+            return Err(ExtractorError::Config(format!(
+                "column_names must be non-empty and unique, got {name:?}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The message of a config error, without the "Configuration error" prefix
+fn config_message(error: ExtractorError) -> String {
+    match error {
+        ExtractorError::Config(message) => message,
+        error => error.to_string(),
+    }
+}
+
+/// Whether `path` is a Parquet file, read as rows of delimited text
+#[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+fn is_parquet(path: &std::path::Path) -> bool {
+    #[cfg(feature = "parquet")]
+    {
+        crate::parquet::is_parquet(path)
+    }
+    #[cfg(not(feature = "parquet"))]
+    {
+        false
+    }
+}
+
+/// Whether `path` is an object store URL that is read remotely
+#[cfg_attr(not(feature = "object-store"), allow(unused_variables))]
+fn is_remote(path: &std::path::Path) -> bool {
+    #[cfg(feature = "object-store")]
+    {
+        remote::is_remote(path)
+    }
+    #[cfg(not(feature = "object-store"))]
+    {
+        false
+    }
+}
+
+/// Column names of a header row, trimmed and without a leading byte-order mark
+pub(crate) fn header_names(headers: &ByteRecord) -> impl Iterator<Item = String> + '_ {
+    headers.iter().enumerate().map(|(i, name)| {
+        let name = if i == 0 { name.strip_prefix(UTF8_BOM).unwrap_or(name) } else { name };
+        String::from_utf8_lossy(name).trim().to_string()
+    })
+}
+
+/// Copy `record` without the fields set in `mask`
+fn without_fields(record: &ByteRecord, mask: &[bool]) -> ByteRecord {
+    record
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| !mask.get(i).copied().unwrap_or(false))
+        .map(|(_, field)| field)
+        .collect()
+}
+
+/// Map column names to their indices
+pub(crate) fn header_map(headers: &ByteRecord) -> HashMap<String, usize> {
+    header_names(headers).enumerate().map(|(i, name)| (name, i)).collect()
+}
+
+/// Map column names to their indices, adding each canonical name in
+/// `aliases` for the one column whose header matches it or one of its
+/// variants, ignoring case
+pub(crate) fn resolve_columns(
+    headers: &ByteRecord,
+    aliases: &HashMap<String, Vec<String>>,
+) -> Result<HashMap<String, usize>> {
+    let mut map = header_map(headers);
+    for (canonical, variants) in aliases {
+        if map.contains_key(canonical) {
+            continue;
+        }
+        let mut matches = header_names(headers).enumerate().filter(|(_, header)| {
+            std::iter::once(canonical)
+                .chain(variants)
+                .any(|name| header.eq_ignore_ascii_case(name.trim()))
+        });
+        if let Some((idx, _)) = matches.next() {
+            if let Some((other, _)) = matches.next() {
+                return Err(ExtractorError::Config(format!(
+                    "Column alias '{canonical}' matches both column {} and column {}",
+                    idx + 1,
+                    other + 1
+                )));
+            }
+            map.insert(canonical.clone(), idx);
+        }
+    }
+    Ok(map)
+}
+
+#[derive(Debug)]
+pub(crate) struct ChunkProcessingStats {
+    pub rows_processed: u64,
+    pub rows_matched: u64,
+    pub bytes_processed: u64,
+}
+
+/// Helper for managing chunk boundaries
+struct ChunkBoundary {
+    start: usize,
+    end: usize,
+    is_complete: bool,
+}
+
+impl ChunkBoundary {
+    /// Find the actual boundaries of complete rows within a chunk
+    fn find_boundaries(data: &[u8], chunk_size: usize) -> Self {
+        let mut end = chunk_size;
+        if end > data.len() {
+            end = data.len();
+        }
+
+        // Adjust end to nearest newline
+        while end < data.len() && data[end] != b'\n' {
+            end += 1;
+        }
+
+        // Check if we have a complete chunk
+        let is_complete = end < data.len() || data[data.len() - 1] == b'\n';
+
+        Self {
+            start: 0,
+            end,
+            is_complete,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{ColumnFilter, FilterCondition};
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_chunk_processing() -> Result<()> {
+        let chunk_data = b"name,value\ntest1,100\ntest2,200\n";
+        let chunk = Chunk {
+            data: chunk_data,
+            start: "name,value\n".len(),
+            end: chunk_data.len(),
+        };
+        let headers = header_map(&ByteRecord::from(vec!["name", "value"]));
+        let input = tempfile::NamedTempFile::new()?;
+
+        let mut filter = BioFilter::new(
+            input.path().to_owned(),
+            PathBuf::from("output.csv"),
+            Config::default(),
+            None,
+        )?;
+
+        // Add a test filter
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Numeric(NumericCondition::GreaterThan(150.0)),
+        )?));
+
+        let result = filter.process_chunk(&chunk, &filter.filters, &headers, &[], &headers, None)?;
+        assert_eq!(result.rows_processed, 2);
+        assert_eq!(result.rows_matched, 1); // Only test2,200 should match
+        assert_eq!(result.output_data, b"test2,200\n");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_quoted_fields() -> Result<()> {
+        let chunk_data = b"name,value\n\"test,1\",100\n\"test,2\",200\n";
+        let chunk = Chunk {
+            data: chunk_data,
+            start: "name,value\n".len(),
+            end: chunk_data.len(),
+        };
+        let headers = header_map(&ByteRecord::from(vec!["name", "value"]));
+        let input = tempfile::NamedTempFile::new()?;
+
+        let filter = BioFilter::new(
+            input.path().to_owned(),
+            PathBuf::from("output.csv"),
+            Config::default(),
+            None,
+        )?;
+
+        let result = filter.process_chunk(&chunk, &[], &headers, &[], &headers, None)?;
+        assert_eq!(result.rows_processed, 2);
+        assert_eq!(result.rows_matched, 2); // All rows should match with no filters
+        // Quoting is preserved because rows are copied, not re-serialized
+        assert_eq!(&result.output_data[..], &chunk_data[chunk.start..]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_rows_split_across_chunks() -> Result<()> {
+        let data = b"aaaa,1\nbbbb,2\ncccc,3\n";
+        let headers = header_map(&ByteRecord::from(vec!["name", "value"]));
+        let input = tempfile::NamedTempFile::new()?;
+        let filter = BioFilter::new(input.path().to_owned(), PathBuf::from("out.csv"), Config::default(), None)?;
+
+        // Boundaries fall mid-row; every row must be handled exactly once
+        let mut output = Vec::new();
+        for start in (0..data.len()).step_by(5) {
+            let chunk = Chunk { data, start, end: (start + 5).min(data.len()) };
+            output.extend(filter.process_chunk(&chunk, &[], &headers, &[], &headers, None)?.output_data);
+        }
+        assert_eq!(output, data);
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_chunk_sizer_tracks_throughput() {
+        let config = Config { chunk_size: 1000, ..Config::default() };
+        let mut sizer = ChunkSizer::new(&config);
+        assert_eq!(sizer.next_size(), 1000);
+
+        // Fast chunks grow toward the target time, capped at the maximum
+        for _ in 0..64 {
+            sizer.record(sizer.next_size(), Duration::from_micros(10));
+        }
+        assert_eq!(sizer.next_size(), config.chunking.max_chunk_size);
+
+        // Slow chunks (e.g. very long INFO fields) shrink back down
+        for _ in 0..64 {
+            sizer.record(sizer.next_size(), Duration::from_secs(5));
+        }
+        assert_eq!(sizer.next_size(), 1000);
+
+        let fixed = Config {
+            chunk_size: 1000,
+            chunking: crate::ChunkingConfig { adaptive: false, ..Default::default() },
+            ..Config::default()
+        };
+        let mut sizer = ChunkSizer::new(&fixed);
+        sizer.record(1000, Duration::from_micros(10));
+        assert_eq!(sizer.next_size(), 1000);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let data = b"header\nrow1\nrow2\nrow3";
+        let boundary = ChunkBoundary::find_boundaries(data, 10);
+        assert!(boundary.end > boundary.start);
+        assert!(data[boundary.end] == b'\n' || boundary.end == data.len());
+    }
+}
+
+/// Results from processing a chunk of data
+#[cfg(feature = "parallel")]
+#[derive(Debug)]
+struct ChunkResult {
+    rows_processed: u64,
+    rows_matched: u64,
+    /// Skipped rows, numbered within the chunk
+    row_errors: Vec<RowErrorSummary>,
+    output_data: Vec<u8>,
+    observed: ObserverSet,
+    /// Rows indexed on `index_column`, numbered within the chunk
+    index: Option<ChunkIndex>,
+}
+
+impl Default for ProcessingStats {
+    fn default() -> Self {
+        Self {
+            rows_processed: 0,
+            rows_matched: 0,
+            processing_time_ms: 0,
+            input_size: 0,
+            output_size: 0,
+            pipelines: Vec::new(),
+            pairs: None,
+            column_stats: HashMap::new(),
+            gene_summary: BTreeMap::new(),
+            encoding: InputEncoding::default(),
+            bom: false,
+            input_digest: None,
+            output_digest: None,
+            config: None,
+            row_errors: Vec::new(),
+            warnings: Vec::new(),
+            header: None,
+            memory: MemoryStats::default(),
+        }
+    }
 }
 
-impl ChunkBoundary {
-    /// Find the actual boundaries of complete rows within a chunk
-    fn find_boundaries(data: &[u8], chunk_size: usize) -> Self {
-        let mut end = chunk_size;
-        if end > data.len() {
-            end = data.len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// Keep chunks at the configured size so small inputs still span many chunks
+    fn fixed_chunks() -> crate::ChunkingConfig {
+        crate::ChunkingConfig { adaptive: false, ..Default::default() }
+    }
+
+    #[test]
+    fn test_basic_filtering() -> Result<()> {
+        // Create test data
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "name,value")?;
+        writeln!(input, "test1,100")?;
+        writeln!(input, "test2,200")?;
+
+        let output = NamedTempFile::new()?;
+        
+        let mut filter = BioFilter::new(
+            input.path().to_owned(),
+            output.path().to_owned(),
+            Config { on_existing_output: ExistingOutput::Overwrite, ..Config::default() },
+            None,
+        )?;
+
+        // Add test filter
+        filter.add_filter(Box::new(TestFilter));
+        
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_processed, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_partitioned_output() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "rsid,chromosome,p")?;
+        writeln!(input, "rs1,chr1,0.01")?;
+        writeln!(input, "rs2,chr2,0.5")?;
+        writeln!(input, "rs3,chr1,0.02")?;
+
+        let output_dir = tempfile::tempdir()?;
+        let mut filter = crate::builder(input.path(), output_dir.path())
+            .partition_by("chromosome")
+            .build()?;
+        filter.add_filter(Box::new(TestFilter));
+
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_matched, 3);
+
+        let chr1 = std::fs::read_to_string(output_dir.path().join("chr1.csv"))?;
+        assert_eq!(chr1, "rsid,chromosome,p\nrs1,chr1,0.01\nrs3,chr1,0.02\n");
+
+        let manifest = crate::output::PartitionManifest::load(output_dir.path())?;
+        assert_eq!(manifest.partitions.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gene_panel() -> Result<()> {
+        use crate::gene_ids::{GeneFilter, GeneIdMap};
+
+        let map = Arc::new(GeneIdMap::from_reader(
+            "symbol\tensembl\nTP53\tENSG00000141510\nAPOE\tENSG00000130203\n".as_bytes(),
+            b'\t',
+            "symbol",
+            "ensembl",
+        )?);
+        let add_panel = |filter: &mut BioFilter| {
+            let genes = GeneFilter::new("gene".to_string(), ["TP53", "ENSG00000130203"], map.clone());
+            filter.add_transform(Box::new(genes.query_gene()));
+            filter.add_filter(Box::new(genes));
+        };
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,p\nENSG00000141510.17,0.01\nAPOE,0.02\nLDLR,0.03\nTP53,0.04\n")?;
+
+        let output = NamedTempFile::new()?;
+        let mut filter = crate::builder(input.path(), output.path()).overwrite(true).build()?;
+        add_panel(&mut filter);
+        assert_eq!(filter.process()?.rows_matched, 3);
+        assert_eq!(
+            std::fs::read_to_string(output.path())?,
+            "gene,p,query_gene\nENSG00000141510.17,0.01,TP53\nAPOE,0.02,ENSG00000130203\nTP53,0.04,TP53\n"
+        );
+
+        // One output per gene, partitioned on the appended column
+        let output_dir = tempfile::tempdir()?;
+        let mut filter = crate::builder(input.path(), output_dir.path()).partition_by("query_gene").build()?;
+        add_panel(&mut filter);
+        filter.process()?;
+        let tp53 = std::fs::read_to_string(output_dir.path().join("TP53.csv"))?;
+        assert_eq!(tp53, "gene,p,query_gene\nENSG00000141510.17,0.01,TP53\nTP53,0.04,TP53\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_digests() -> Result<()> {
+        use crate::digest::{digest_bytes, DigestAlgorithm};
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"rsid,p\nrs1,0.01\nrs2,0.5\n")?;
+        let output = NamedTempFile::new()?;
+        let input_digest = digest_bytes(DigestAlgorithm::Sha256, &std::fs::read(input.path())?);
+
+        for parallel in [false, true] {
+            let filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .digest(DigestAlgorithm::Sha256, true)
+                .overwrite(true)
+                .build()?;
+            let stats = filter.process()?;
+            let written = std::fs::read(output.path())?;
+            assert_eq!(stats.output_digest, Some(digest_bytes(DigestAlgorithm::Sha256, &written)));
+            assert_eq!(stats.input_digest.as_ref(), Some(&input_digest));
         }
 
-        // Adjust end to nearest newline
-        while end < data.len() && data[end] != b'\n' {
-            end += 1;
+        // Appending digests the whole file, not just the new rows
+        let filter = crate::builder(input.path(), output.path())
+            .append(true)
+            .digest(DigestAlgorithm::Md5, false)
+            .build()?;
+        let stats = filter.process()?;
+        let written = std::fs::read(output.path())?;
+        assert_eq!(written, b"rsid,p\nrs1,0.01\nrs2,0.5\nrs1,0.01\nrs2,0.5\n");
+        assert_eq!(stats.output_digest, Some(digest_bytes(DigestAlgorithm::Md5, &written)));
+        assert_eq!(stats.input_digest, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharded_output() -> Result<()> {
+        use crate::output::{shard_path, ShardManifest};
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"rsid,p\nrs1,0.1\nrs2,0.2\nrs3,0.3\nrs40,0.4\nrs5,0.5\n")?;
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.csv");
+
+        let filter = crate::builder(input.path(), &output).max_output_rows(2).build()?;
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_matched, 5);
+        assert_eq!(shard_path(&output, 1), dir.path().join("hits.part-0001.csv"));
+        assert_eq!(std::fs::read_to_string(shard_path(&output, 3))?, "rsid,p\nrs5,0.5\n");
+        let manifest = ShardManifest::load(&output)?;
+        let rows: Vec<u64> = manifest.shards.iter().map(|shard| shard.rows).collect();
+        assert_eq!(rows, [2, 2, 1]);
+        assert_eq!(stats.output_size, manifest.shards.iter().map(|shard| shard.bytes).sum::<u64>());
+
+        // 7 header bytes, then rows of 8 and 9 bytes
+        let filter = crate::builder(input.path(), &output).max_output_bytes(23).overwrite(true).build()?;
+        filter.process()?;
+        let manifest = ShardManifest::load(&output)?;
+        let rows: Vec<u64> = manifest.shards.iter().map(|shard| shard.rows).collect();
+        assert_eq!(rows, [2, 1, 1, 1]);
+        for shard in &manifest.shards {
+            assert_eq!(std::fs::metadata(dir.path().join(&shard.file))?.len(), shard.bytes);
+            assert!(shard.bytes <= 23);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_observers() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene,p")?;
+        writeln!(input, "TP53,0.01")?;
+        writeln!(input, "TP53,0.04")?;
+        writeln!(input, "APOE,NA")?;
+        let output = NamedTempFile::new()?;
+
+        let mut filter = BioFilter::new(
+            input.path().to_owned(),
+            output.path().to_owned(),
+            Config { parallel: false, on_existing_output: ExistingOutput::Overwrite, ..Config::default() },
+            None,
+        )?;
+        filter.add_filter(Box::new(TestFilter));
+        filter.add_observer(ColumnObserver::new("gene"));
+        filter.add_observer(ColumnObserver::new("p"));
+
+        let stats = filter.process()?;
+        assert_eq!(stats.column_stats["gene"].unique_count, 2);
+        let p = &stats.column_stats["p"];
+        assert_eq!(p.numeric_count, 2);
+        assert_eq!(p.min, Some(0.01));
+        assert_eq!(p.max, Some(0.04));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gene_summary() -> Result<()> {
+        use crate::hgvs::Consequence;
+        use crate::observers::GeneSummaryObserver;
+
+        let mut input = NamedTempFile::new()?;
+        write!(
+            input,
+            "gene,trait,p,hgvs\nTP53,LDL,1e-9,p.Arg175His\nAPOE,LDL,NA,\nTP53,HDL,0.01,p.Arg213Ter\nTP53,LDL,0.2,p.Pro72=\n"
+        )?;
+        let dir = tempfile::tempdir()?;
+        let summary_path = dir.path().join("genes.json");
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), &dir.path().join("hits.csv"))
+                .with_config(Config { parallel, chunk_size: 16, chunking: fixed_chunks(), ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.set_gene_summary(
+                GeneSummaryObserver::new("gene")
+                    .phenotype_column("trait")
+                    .p_value_column("p")
+                    .consequence_column("hgvs")
+                    .write_to(&summary_path),
+            );
+            let stats = filter.process()?;
+            let tp53 = &stats.gene_summary["TP53"];
+            assert_eq!(tp53.variants, 3);
+            assert_eq!(tp53.phenotypes, [("HDL".to_string(), 1), ("LDL".to_string(), 2)].into_iter().collect());
+            assert_eq!(tp53.min_p_value, Some(1e-9));
+            assert_eq!(tp53.most_severe_consequence, Some(Consequence::StopGained));
+            assert_eq!(
+                std::fs::read_to_string(&summary_path)?,
+                r#"{"APOE":{"variants":1,"phenotypes":{"LDL":1}},"TP53":{"variants":3,"phenotypes":{"HDL":1,"LDL":2},"min_p_value":1e-9,"most_severe_consequence":"StopGained"}}"#
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_pipelines_single_pass() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene,p")?;
+        writeln!(input, "TP53,0.01")?;
+        writeln!(input, "BRCA1,0.02")?;
+        writeln!(input, "APOE,0.03")?;
+
+        let panel_a = NamedTempFile::new()?;
+        let panel_b = NamedTempFile::new()?;
+
+        let mut filter = BioFilter::new(
+            input.path().to_owned(),
+            PathBuf::from("unused.csv"),
+            Config { on_existing_output: ExistingOutput::Overwrite, ..Config::default() },
+            None,
+        )?;
+        filter.add_pipeline(Pipeline::new("panel_a", panel_a.path()).with_filter(Box::new(
+            ColumnFilter::new("gene".to_string(), FilterCondition::OneOf(vec!["TP53".into(), "APOE".into()]))?,
+        )));
+        filter.add_pipeline(Pipeline::new("panel_b", panel_b.path()).with_filter(Box::new(
+            ColumnFilter::new("gene".to_string(), FilterCondition::Equals("BRCA1".into()))?,
+        )));
+
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_processed, 3);
+        assert_eq!(stats.rows_matched, 3);
+        assert_eq!(stats.pipelines[0].rows_matched, 2);
+        assert_eq!(stats.pipelines[1].rows_matched, 1);
+
+        assert_eq!(std::fs::read_to_string(panel_a.path())?, "gene,p\nTP53,0.01\nAPOE,0.03\n");
+        assert_eq!(std::fs::read_to_string(panel_b.path())?, "gene,p\nBRCA1,0.02\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_errors() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "variant,p")?;
+        for i in 1..=200 {
+            match i {
+                137 | 180 => writeln!(input, "rs{i},oops")?,
+                _ => writeln!(input, "rs{i},0.{i}")?,
+            }
+        }
+
+        for parallel in [false, true] {
+            let output = NamedTempFile::new()?;
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 64, chunking: fixed_chunks(), ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(crate::filters::ColumnFilter::new(
+                "p".to_string(),
+                crate::filters::FilterCondition::Numeric(crate::filters::NumericCondition::LessThan(0.5)),
+            )?));
+            let err = filter.process().expect_err("row 137 is not numeric");
+            assert!(
+                matches!(
+                    &err,
+                    ExtractorError::InvalidDataFormat { row: Some(137), snippet: Some(snippet), .. }
+                        if snippet == "rs137,oops"
+                ),
+                "parallel = {parallel}: {err}"
+            );
+
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 64, chunking: fixed_chunks(), ..Config::default() })
+                .skip_bad_rows()
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(crate::filters::ColumnFilter::new(
+                "p".to_string(),
+                crate::filters::FilterCondition::Numeric(crate::filters::NumericCondition::LessThan(0.5)),
+            )?));
+            let stats = filter.process()?;
+            assert_eq!(stats.rows_processed, 200);
+            assert_eq!(
+                stats.row_errors,
+                vec![RowErrorSummary {
+                    category: "data".to_string(),
+                    column: Some("p".to_string()),
+                    count: 2,
+                    first_row: Some(137),
+                    last_row: Some(180),
+                    example: "Invalid numeric value: 'oops'".to_string(),
+                }],
+                "parallel = {parallel}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview() -> Result<()> {
+        use crate::filters::{FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "variant,p")?;
+        for i in 1..=100 {
+            match i {
+                3 => writeln!(input, "rs{i},NA?")?,
+                _ => writeln!(input, "rs{i},0.{}", i % 10)?,
+            }
+        }
+        let output = NamedTempFile::new()?;
+        let filter = crate::builder(input.path(), output.path())
+            .skip_bad_rows()
+            .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(0.25)))
+            .build()?;
+
+        let preview = filter.preview(4)?;
+        assert_eq!(preview.header, Some(StringRecord::from(vec!["variant", "p"])));
+        let variants: Vec<_> = preview.rows.iter().map(|row| &row[0]).collect();
+        assert_eq!(variants, ["rs1", "rs2", "rs10", "rs11"]);
+        assert_eq!(preview.rows_scanned, 11);
+        assert_eq!(preview.row_errors.len(), 1);
+        assert_eq!(filter.preview(1000)?.rows.len(), 30);
+        assert_eq!(output.path().metadata()?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_values() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"rsid,chrom,phenotype\nrs1,1,LDL\nrs2, 2 ,HDL\nrs3,1,\nrs4,X,LDL\nrs5,1,BMI\n")?;
+        let output = NamedTempFile::new()?;
+        let filter = crate::builder(input.path(), output.path()).build()?;
+
+        let chroms = filter.distinct_values("chrom", 10)?;
+        let expected = [("1", 3), ("2", 1), ("X", 1)].map(|(value, count)| (value.to_string(), count));
+        assert_eq!(chroms.values, expected);
+        assert!(!chroms.capped);
+        let phenotypes = filter.distinct_values("phenotype", 2)?;
+        assert_eq!(phenotypes.values, [("LDL".to_string(), 2), ("HDL".to_string(), 1)]);
+        assert!(phenotypes.capped);
+
+        let index = FileIndex::builder(input.path().to_path_buf(), "rsid".to_string())
+            .add_secondary_index("chrom".to_string())
+            .build()?;
+        let indexed = BioFilter::unchecked(
+            input.path().to_path_buf(),
+            output.path().to_path_buf(),
+            Config::default(),
+            Some(Arc::new(index)),
+        );
+        assert_eq!(indexed.distinct_values("chrom", 10)?, chroms);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_index_while_filtering() -> Result<()> {
+        use crate::filters::{FilterCondition, NumericCondition};
+        use crate::index::IndexedReader;
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"\xEF\xBB\xBFgene_id,p\n")?;
+        for i in 0..300 {
+            writeln!(input, "ENSG{},0.{}", i % 250, i % 10)?;
+        }
+
+        for parallel in [false, true] {
+            let output = NamedTempFile::new()?;
+            let filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 512, chunking: fixed_chunks(), ..Config::default() })
+                .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(0.2)))
+                .build_index_while_filtering("gene_id")
+                .overwrite(true)
+                .build()?;
+            assert!(filter.built_index().is_none());
+            assert_eq!(filter.process()?.rows_matched, 60);
+
+            let index = filter.built_index().expect("index built by the run");
+            assert_eq!(index.metadata.row_count, 300);
+            assert!(index.verify(&File::open(input.path())?)?);
+            let mut rows = IndexedReader::new(&index)?;
+            let row = rows.get("ENSG249")?.expect("ENSG249 is indexed");
+            assert_eq!(row, ByteRecord::from(vec!["ENSG249", "0.9"]), "parallel = {parallel}");
+            let repeats = index.get_all_positions("ENSG3");
+            assert_eq!(repeats.iter().map(|p| p.row_number).collect::<Vec<_>>(), [4, 254]);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_memory_stats() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene_id,p")?;
+        for i in 0..2000 {
+            writeln!(input, "ENSG{i},0.{}", i % 10)?;
+        }
+        let output = NamedTempFile::new()?;
+        let config = Config {
+            chunk_size: 1024,
+            chunking: crate::ChunkingConfig { max_in_flight: Some(3), ..fixed_chunks() },
+            ..Config::default()
+        };
+        let stats = crate::builder(input.path(), output.path())
+            .with_config(config)
+            .build_index_while_filtering("gene_id")
+            .overwrite(true)
+            .build()?
+            .process()?;
+
+        let memory = stats.memory;
+        assert!((1..=3).contains(&memory.peak_chunks_in_flight), "{memory:?}");
+        assert!(memory.peak_in_flight_bytes > 0 && memory.peak_in_flight_bytes <= 3 * 1100);
+        assert!(memory.peak_buffered_output_bytes > 0);
+        assert!(memory.index_bytes > 2000 * "ENSG0".len() as u64);
+        if cfg!(target_os = "linux") {
+            assert!(memory.peak_rss_bytes.is_some_and(|rss| rss > 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_index_skips_blocks() -> Result<()> {
+        use crate::blocks::BlockIndex;
+        use crate::filters::{FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"\xEF\xBB\xBFrsid,pos,p\n")?;
+        for i in 0..1000 {
+            writeln!(input, "rs{i},{},{}", 1000 + i, if i % 97 == 0 { "NA".to_string() } else { format!("{}", i as f64 / 1000.0) })?;
+        }
+        let blocks_path = input.path().with_extension("blocks");
+        BlockIndex::builder(input.path().to_owned())
+            .add_column("pos")
+            .add_column("p")
+            .with_rows_per_block(100)
+            .build()?
+            .save(&blocks_path)?;
+
+        let run = |blocks: bool| -> Result<(ProcessingStats, Vec<u8>)> {
+            let output = NamedTempFile::new()?;
+            let mut builder = crate::builder(input.path(), output.path())
+                .with_config(Config { on_row_error: RowErrorPolicy::Skip, ..Config::default() })
+                .filter("pos", FilterCondition::Numeric(NumericCondition::Between(1250.0, 1420.0)))
+                .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(0.4)));
+            if blocks {
+                builder = builder.with_block_index(&blocks_path);
+            }
+            let stats = builder.overwrite(true).build()?.process()?;
+            Ok((stats, std::fs::read(output.path())?))
+        };
+        let (full, expected) = run(false)?;
+        let (skipping, output) = run(true)?;
+        std::fs::remove_file(&blocks_path)?;
+        assert_eq!(output, expected);
+        assert_eq!(skipping.rows_matched, full.rows_matched);
+        // Rows 201-500 have pos in range. Rows 401-500 all have p >= 0.4,
+        // but their "NA" keeps the block from being ruled out on p.
+        assert_eq!((full.rows_processed, skipping.rows_processed), (1000, 300));
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_cache_sweep() -> Result<()> {
+        use crate::column_cache::ColumnCache;
+        use crate::filters::{FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "rsid,beta,p")?;
+        for i in 1..=1000 {
+            let p = if i == 500 { "NA".to_string() } else { format!("{}e-{}", i % 9 + 1, i % 10) };
+            writeln!(input, "rs{i},{},{p}", i % 7)?;
+        }
+        let cache_path = input.path().with_extension("cols");
+
+        let run = |threshold: f64, cached: bool| -> Result<(ProcessingStats, Vec<u8>)> {
+            let output = NamedTempFile::new()?;
+            let mut builder = crate::builder(input.path(), output.path())
+                .with_config(Config { on_row_error: RowErrorPolicy::Skip, ..Config::default() })
+                .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(threshold)));
+            if cached {
+                builder = builder.with_column_cache(&cache_path);
+            }
+            let stats = builder.overwrite(true).build()?.process()?;
+            Ok((stats, std::fs::read(output.path())?))
+        };
+        for threshold in [1e-5, 1e-6, 5e-8] {
+            let (full, expected) = run(threshold, false)?;
+            let (cached, output) = run(threshold, true)?;
+            assert_eq!(output, expected, "p < {threshold}");
+            // Only the passing rows and the "NA" row are read
+            assert_eq!(cached.rows_processed, full.rows_matched + 1);
+        }
+        let cache = ColumnCache::load(&cache_path)?;
+        std::fs::remove_file(&cache_path)?;
+        assert_eq!(cache.metadata.rows, 1000);
+        assert_eq!(cache.column("p").expect("p is cached")[499], None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain() -> Result<()> {
+        use crate::explain::{ExplainTarget, FilterOutcome};
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"rsid,p,clinvar\nrs1,0.01,Benign\nrs12345,0.2,Benign;Pathogenic\nrs7,oops,\n")?;
+        let output = NamedTempFile::new()?;
+        let mut filter = crate::builder(input.path(), output.path()).build()?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
+        )?));
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "clinvar".to_string(),
+            FilterCondition::AnyElementMatches {
+                separator: ";".to_string(),
+                condition: Box::new(FilterCondition::Equals("Pathogenic".to_string())),
+            },
+        )?));
+
+        let explained = filter.explain(&ExplainTarget::key("rsid", "rs12345"))?;
+        assert_eq!(explained.len(), 1);
+        let row = &explained[0];
+        assert_eq!((row.row, row.selected), (2, false));
+        let outcomes: Vec<_> = row.filters.iter().map(|trace| &trace.outcome).collect();
+        assert_eq!(outcomes, [&FilterOutcome::Failed, &FilterOutcome::Passed]);
+        assert_eq!(row.filters[0].detail, "p = \"0.2\", parsed as 0.2");
+        assert_eq!(
+            row.filters[1].detail,
+            "clinvar = \"Benign;Pathogenic\"; elements: \"Benign\" fails, \"Pathogenic\" passes"
+        );
+
+        let explained = filter.explain(&ExplainTarget::Row(3))?;
+        assert!(matches!(explained[0].filters[0].outcome, FilterOutcome::Error(_)), "{}", explained[0]);
+        assert!(filter.explain(&ExplainTarget::Row(4))?.is_empty());
+        assert!(matches!(
+            filter.explain(&ExplainTarget::key("rs_id", "rs1")),
+            Err(ExtractorError::ColumnNotFound { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_passthrough_output_is_byte_exact() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"gene,note,p\r\n\"TP53\",\"a, b\",1.0E-8\r\nAPOE,x,0.5\r\nBRCA1,\"y\",2.50e-9\r\n")?;
+
+        for parallel in [false, true] {
+            let output = NamedTempFile::new()?;
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 16, chunking: fixed_chunks(), ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(crate::filters::ColumnFilter::new(
+                "p".to_string(),
+                crate::filters::FilterCondition::Numeric(crate::filters::NumericCondition::LessThan(0.01)),
+            )?));
+            filter.process()?;
+            assert_eq!(
+                std::fs::read(output.path())?,
+                b"gene,note,p\r\n\"TP53\",\"a, b\",1.0E-8\r\nBRCA1,\"y\",2.50e-9\r\n",
+                "parallel = {parallel}"
+            );
+
+            let normalized = NamedTempFile::new()?;
+            let mut filter = crate::builder(input.path(), normalized.path())
+                .with_config(Config { parallel, chunk_size: 16, chunking: fixed_chunks(), ..Config::default() })
+                .output_mode(crate::OutputMode::Normalized)
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(TestFilter));
+            filter.process()?;
+            assert_eq!(
+                std::fs::read_to_string(normalized.path())?,
+                "gene,note,p\nTP53,\"a, b\",1.0E-8\nAPOE,x,0.5\nBRCA1,y,2.50e-9\n",
+                "parallel = {parallel}"
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_shared_and_global_thread_pools() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene,p")?;
+        for i in 0..200 {
+            writeln!(input, "GENE{i},0.{i}")?;
+        }
+        let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+
+        let mut outputs = Vec::new();
+        for use_global in [false, true] {
+            let output = NamedTempFile::new()?;
+            let builder = crate::builder(input.path(), output.path())
+                .with_config(Config { chunk_size: 64, chunking: fixed_chunks(), ..Config::default() });
+            let builder = if use_global {
+                builder.use_global_thread_pool()
+            } else {
+                builder.with_thread_pool(pool.clone())
+            };
+            let mut filter = builder.overwrite(true).build()?;
+            filter.add_filter(Box::new(TestFilter));
+
+            // The same pool serves repeated runs
+            assert_eq!(filter.process()?.rows_matched, 200);
+            assert_eq!(filter.process()?.rows_matched, 200);
+            outputs.push(std::fs::read(output.path())?);
+        }
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[0], std::fs::read(input.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_in_flight_with_skewed_rows() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "variant,info")?;
+        for i in 0..300 {
+            let info = if i % 37 == 0 { "X".repeat(20_000) } else { format!("AF=0.{i}") };
+            writeln!(input, "rs{i},{info}")?;
+        }
+
+        let mut outputs = Vec::new();
+        for (threads, max_in_flight) in [(1, 1), (4, 2), (4, 64)] {
+            let output = NamedTempFile::new()?;
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config {
+                    chunk_size: 512,
+                    num_threads: Some(threads),
+                    ..Config::default()
+                })
+                .with_chunking(crate::ChunkingConfig {
+                    min_chunk_size: 256,
+                    max_chunk_size: 4096,
+                    target_chunk_ms: 1,
+                    max_in_flight: Some(max_in_flight),
+                    ..Default::default()
+                })
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(TestFilter));
+
+            assert_eq!(filter.process()?.rows_matched, 300);
+            outputs.push(std::fs::read(output.path())?);
+        }
+        for output in &outputs {
+            assert_eq!(output, &std::fs::read(input.path())?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_order() -> Result<()> {
+        use crate::VerifyMode;
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "variant,info")?;
+        for i in 0..300 {
+            let info = if i % 29 == 0 { "X".repeat(10_000) } else { format!("AF=0.{i}") };
+            writeln!(input, "rs{i},{info}")?;
+        }
+        let run = |parallel: bool, preserve_order: bool| -> Result<Vec<u8>> {
+            let output = NamedTempFile::new()?;
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { chunk_size: 256, parallel, num_threads: Some(4), ..Config::default() })
+                .with_chunking(fixed_chunks())
+                .preserve_order(preserve_order)
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(TestFilter));
+            assert_eq!(filter.process()?.rows_matched, 300);
+            Ok(std::fs::read(output.path())?)
+        };
+        let sorted_lines = |bytes: &[u8]| {
+            let mut lines: Vec<Vec<u8>> = bytes.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect();
+            lines.sort();
+            lines
+        };
+
+        let sequential = run(false, true)?;
+        for _ in 0..3 {
+            assert_eq!(run(true, true)?, sequential);
+        }
+        let unordered = run(true, false)?;
+        assert!(unordered.starts_with(b"variant,info\n"));
+        assert_eq!(sorted_lines(&unordered), sorted_lines(&sequential));
+
+        let output = NamedTempFile::new()?;
+        let mut filter = crate::builder(input.path(), output.path())
+            .preserve_order(false)
+            .verify_mode(VerifyMode::Full)
+            .overwrite(true)
+            .build()?;
+        filter.add_filter(Box::new(TestFilter));
+        assert!(matches!(filter.process(), Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_output() -> Result<()> {
+        use crate::output::{shard_path, ShardManifest};
+        use crate::ThreadOutput;
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "variant,info")?;
+        for i in 0..500 {
+            writeln!(input, "rs{i},AF=0.{i}")?;
+        }
+        let expected = std::fs::read_to_string(input.path())?;
+        let sorted_rows = |text: &str| {
+            let mut rows: Vec<String> = text.lines().skip(1).map(str::to_string).collect();
+            rows.sort();
+            rows
+        };
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.csv");
+        let build = |thread_output: ThreadOutput| {
+            crate::builder(input.path(), &output)
+                .with_config(Config { chunk_size: 256, num_threads: Some(4), ..Config::default() })
+                .with_chunking(fixed_chunks())
+                .thread_output(thread_output)
+                .build()
+        };
+
+        let mut filter = build(ThreadOutput::Concatenated)?;
+        filter.add_filter(Box::new(TestFilter));
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_matched, 500);
+        let concatenated = std::fs::read_to_string(&output)?;
+        assert!(concatenated.starts_with("variant,info\n"));
+        assert_eq!(sorted_rows(&concatenated), sorted_rows(&expected));
+        assert_eq!(stats.output_size, concatenated.len() as u64);
+        assert!(!shard_path(&output, 1).exists());
+        std::fs::remove_file(&output)?;
+
+        let mut filter = build(ThreadOutput::Partitioned)?;
+        filter.add_filter(Box::new(TestFilter));
+        assert_eq!(filter.process()?.rows_matched, 500);
+        assert!(!output.exists());
+        let manifest = ShardManifest::load(&output)?;
+        assert!((1..=4).contains(&manifest.shards.len()));
+        assert_eq!(manifest.shards.iter().map(|shard| shard.rows).sum::<u64>(), 500);
+        let mut rows = Vec::new();
+        for shard in &manifest.shards {
+            let text = std::fs::read_to_string(dir.path().join(&shard.file))?;
+            assert!(text.starts_with("variant,info\n"));
+            rows.extend(text.lines().skip(1).map(str::to_string));
         }
+        rows.sort();
+        assert_eq!(rows, sorted_rows(&expected));
 
-        // Check if we have a complete chunk
-        let is_complete = end < data.len() || data[data.len() - 1] == b'\n';
+        let filter = crate::builder(input.path(), &output)
+            .with_config(Config { partition_by: Some("variant".to_string()), ..Config::default() })
+            .thread_output(ThreadOutput::Partitioned)
+            .build()?;
+        assert!(matches!(filter.process(), Err(ExtractorError::Config(_))));
+        Ok(())
+    }
 
-        Self {
-            start: 0,
-            end,
-            is_complete,
+    #[test]
+    fn test_zero_match_guard() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "chrom,pos")?;
+        for i in 0..400 {
+            writeln!(input, "chr{},{i}", 1 + i % 22)?;
+        }
+        let output = NamedTempFile::new()?;
+        let run = |chrom: &str, parallel: bool, abort: bool| -> Result<ProcessingStats> {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { chunk_size: 256, parallel, ..Config::default() })
+                .with_chunking(fixed_chunks())
+                .zero_match_guard(10.0, abort)
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new("chrom".to_string(), FilterCondition::Equals(chrom.into()))?));
+            filter.process()
+        };
+
+        for parallel in [false, true] {
+            let stats = run("Chr1", parallel, false)?;
+            assert_eq!(stats.rows_matched, 0);
+            assert_eq!(stats.warnings.len(), 1);
+            assert!(matches!(run("Chr1", parallel, true), Err(ExtractorError::NoMatches(_))));
+            let stats = run("chr1", parallel, true)?;
+            assert_eq!(stats.rows_matched, 19);
+            assert!(stats.warnings.is_empty());
         }
+
+        let filter = crate::builder(input.path(), output.path()).zero_match_guard(0.0, true).overwrite(true).build()?;
+        assert!(matches!(filter.process(), Err(ExtractorError::Config(_))));
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::filters::{ColumnFilter, FilterCondition};
+    #[test]
+    fn test_header_drift() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (input, output) = (dir.path().join("release.csv"), dir.path().join("hits.csv"));
+        let baseline = dir.path().join("release.header.json");
+        let run = || crate::builder(&input, &output).header_baseline(&baseline).overwrite(true).build()?.process();
+
+        std::fs::write(&input, "chrom,pos,ref\nchr1,100,A\n")?;
+        let stats = run()?;
+        let header = stats.header.expect("input has a header row");
+        assert_eq!(header.columns, ["chrom", "pos", "ref"]);
+        assert!(stats.warnings.is_empty());
+        assert_eq!(HeaderFingerprint::load(&baseline)?, Some(header));
+        assert!(run()?.warnings.is_empty());
+
+        std::fs::write(&input, "pos,chrom,ref,alt\n100,chr1,A,G\n")?;
+        let stats = run()?;
+        assert_eq!(stats.rows_matched, 1);
+        assert_eq!(stats.warnings.len(), 1);
+        assert!(stats.warnings[0].contains(r#"added ["alt"]"#));
+        assert!(stats.warnings[0].contains("reordered"));
+        // The baseline now holds the new columns
+        assert!(run()?.warnings.is_empty());
+        Ok(())
+    }
 
     #[test]
-    fn test_chunk_processing() -> Result<()> {
-        let chunk_data = b"name,value\ntest1,100\ntest2,200\n";
-        let chunk = Chunk {
-            data: chunk_data.to_vec(),
-            start_offset: 0,
-            chunk_index: 0,
+    fn test_filename_columns() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out.tsv");
+        let run = |name: &str, data: &str| -> Result<String> {
+            let input = dir.path().join(name);
+            std::fs::write(&input, data)?;
+            crate::builder(&input, &output)
+                .with_config(Config { delimiter: b'\t', ..Config::default() })
+                .filename_columns("*_*.tsv", [("Phenotype", "{1}"), ("Study", "{2}")])
+                .filename_columns("*.tsv", [("Phenotype", "unknown")])
+                .overwrite(true)
+                .build()?
+                .process()?;
+            Ok(std::fs::read_to_string(&output)?)
         };
 
-        let mut filter = BioFilter::new(
-            PathBuf::from("test.csv"),
-            PathBuf::from("output.csv"),
-            Config::default(),
-            None,
-        )?;
+        assert_eq!(run("LDL_GLGC_2013.tsv", "gene\tp\nAPOE\t1e-8\n")?, "gene\tp\tPhenotype\tStudy\nAPOE\t1e-8\tLDL\tGLGC_2013\n");
+        // Columns the input already has are kept as they are
+        assert_eq!(run("HDL_GLGC.tsv", "gene\tStudy\nAPOE\tMVP\n")?, "gene\tStudy\tPhenotype\nAPOE\tMVP\tHDL\n");
+        assert_eq!(run("hits.tsv", "gene\nAPOE\n")?, "gene\tPhenotype\nAPOE\tunknown\n");
+        assert_eq!(run("hits.csv", "gene\nAPOE\n")?, "gene\nAPOE\n");
+        Ok(())
+    }
 
-        // Add a test filter
+    #[test]
+    fn test_exclude_columns() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,Beta,SE,p\nTP53,0.5,0.1,1e-9\nAPOE,0.2,0.3,0.4\n")?;
+        let output = NamedTempFile::new()?;
+        let mut filter = crate::builder(input.path(), output.path())
+            .with_column_aliases([("beta", ["BETA"])])
+            .exclude_columns(["beta", "p", "n"])
+            .overwrite(true)
+            .build()?;
+        // Excluded columns can still be filtered on
         filter.add_filter(Box::new(ColumnFilter::new(
-            "value".to_string(),
-            FilterCondition::Numeric(NumericCondition::GreaterThan(150.0)),
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
         )?));
+        assert_eq!(filter.process()?.rows_matched, 1);
+        assert_eq!(std::fs::read_to_string(output.path())?, "gene,SE\nTP53,0.1\n");
 
-        let result = filter.process_chunk(&chunk, &filter.filters)?;
-        assert_eq!(result.rows_processed, 2);
-        assert_eq!(result.rows_matched, 1); // Only test2,200 should match
-        
+        // Exclusions apply to the transformed columns, including added ones
+        struct Prepend;
+        impl RowTransform for Prepend {
+            fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+                Some(std::iter::once(&b"study"[..]).chain(header).collect())
+            }
+            fn apply(&self, row: &mut ByteRecord, _headers: &HashMap<String, usize>) -> Result<bool> {
+                *row = std::iter::once(&b"GLGC"[..]).chain(&*row).collect();
+                Ok(true)
+            }
+            fn description(&self) -> String {
+                "prepend study".to_string()
+            }
+        }
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 16, ..Config::default() })
+                .exclude_columns(["SE", "row_hash"])
+                .overwrite(true)
+                .build()?;
+            filter.add_transform(Box::new(Prepend));
+            filter.add_transform(Box::new(crate::RowHash::new(["gene"])));
+            filter.process()?;
+            assert_eq!(
+                std::fs::read_to_string(output.path())?,
+                "study,gene,Beta,p\nGLGC,TP53,0.5,1e-9\nGLGC,APOE,0.2,0.4\n"
+            );
+        }
         Ok(())
     }
 
     #[test]
-    fn test_quoted_fields() -> Result<()> {
-        let chunk_data = b"name,value\n\"test,1\",100\n\"test,2\",200\n";
-        let chunk = Chunk {
-            data: chunk_data.to_vec(),
-            start_offset: 0,
-            chunk_index: 0,
+    fn test_output_na_token() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,beta,se,p\nTP53,N/A,.,1e-9\nAPOE,0.2, null ,\nBRCA1,NA,NaN,0.3\nNAT2,0.1,0.2,0.4\n")?;
+        let output = NamedTempFile::new()?;
+        for parallel in [false, true] {
+            crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, chunk_size: 32, ..Config::default() })
+                .output_na_token("NA")
+                .overwrite(true)
+                .build()?
+                .process()?;
+            assert_eq!(
+                std::fs::read_to_string(output.path())?,
+                "gene,beta,se,p\nTP53,NA,NA,1e-9\nAPOE,0.2,NA,NA\nBRCA1,NA,NA,0.3\nNAT2,0.1,0.2,0.4\n"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_hash() -> Result<()> {
+        use crate::transform::RowHash;
+
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out.csv");
+        let hashes = |data: &str| -> Result<Vec<String>> {
+            let input = dir.path().join("in.csv");
+            std::fs::write(&input, data)?;
+            let mut filter = crate::builder(&input, &output).overwrite(true).build()?;
+            filter.add_transform(Box::new(RowHash::new(["chrom", "pos", "ref", "alt"]).named("variant_hash")));
+            filter.process()?;
+            let mut reader = csv::Reader::from_path(&output)?;
+            assert_eq!(reader.headers()?.iter().last(), Some("variant_hash"));
+            reader.records().map(|row| Ok(row?.iter().last().unwrap_or_default().to_string())).collect()
         };
 
-        let filter = BioFilter::new(
-            PathBuf::from("test.csv"),
-            PathBuf::from("output.csv"),
-            Config::default(),
-            None,
+        let week1 = hashes("chrom,pos,ref,alt,p\n1,100,A,G,0.5\n2,200,C,T,0.1\n")?;
+        // A later release reorders columns, adds one and changes p-values
+        let week2 = hashes("p,beta,alt,ref,pos,chrom\n0.01,1.2,G,A,100,1\n0.2,0.3,T,C,200,2\n0.3,0.1,T,C,201,2\n")?;
+        assert_eq!(week1[0].len(), 16);
+        assert_eq!(week1, week2[..2]);
+        assert_ne!(week2[1], week2[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_width_input() -> Result<()> {
+        use crate::filters::{FilterCondition, NumericCondition};
+        use crate::FixedWidth;
+
+        let dir = tempfile::tempdir()?;
+        let (input, output) = (dir.path().join("legacy.txt"), dir.path().join("hits.tsv"));
+        std::fs::write(
+            &input,
+            "gene      disease              p\nTP53      Li-Fraumeni       0.5\nAPOE      heart failure  1e-9\n",
         )?;
+        let stats = crate::builder(&input, &output)
+            .with_config(Config { delimiter: b'\t', chunk_size: 16, ..Config::default() })
+            .fixed_width(FixedWidth::FromHeader)
+            .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(1e-6)))
+            .build()?
+            .process()?;
+        assert_eq!(stats.rows_matched, 1);
+        assert_eq!(std::fs::read_to_string(&output)?, "gene\tdisease\tp\nAPOE\theart failure\t1e-9\n");
+        Ok(())
+    }
 
-        let result = filter.process_chunk(&chunk, &[])?;
-        assert_eq!(result.rows_processed, 2);
-        assert_eq!(result.rows_matched, 2); // All rows should match with no filters
-        
+    #[test]
+    fn test_verified_processing() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+        use crate::{OutputMode, VerifyMode};
+
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,note\r\nTP53,\"a\r\nb\"\r\nAPOE,x\r\n\"BRCA1\",\"y,z\"\r\n")?;
+        let output = NamedTempFile::new()?;
+
+        for output_mode in [OutputMode::Passthrough, OutputMode::Normalized] {
+            for verify_mode in [VerifyMode::Full, VerifyMode::Sampled { every: 1 }] {
+                let mut filter = crate::builder(input.path(), output.path())
+                    .with_config(Config { chunk_size: 8, chunking: fixed_chunks(), ..Config::default() })
+                    .output_mode(output_mode)
+                    .verify_mode(verify_mode)
+                    .overwrite(true)
+                    .build()?;
+                filter.add_filter(Box::new(ColumnFilter::new(
+                    "gene".to_string(),
+                    FilterCondition::OneOf(vec!["TP53".into(), "BRCA1".into()]),
+                )?));
+                assert_eq!(filter.process()?.rows_matched, 2);
+                assert_eq!(filter.process_verified()?.rows_matched, 2);
+                // Verification does not count towards the filter statistics
+                assert_eq!(filter.filter_stats()[0].evaluated, 3);
+
+                // A tampered output no longer matches the reference path
+                let written = std::fs::read_to_string(output.path())?;
+                std::fs::write(output.path(), written.replace("TP53", "TP54"))?;
+                assert!(matches!(
+                    filter.verify_output(verify_mode, output.path()),
+                    Err(ExtractorError::VerificationFailed(_))
+                ));
+            }
+        }
+
+        let output_dir = tempfile::tempdir()?;
+        let partitioned = crate::builder(input.path(), output_dir.path())
+            .partition_by("gene")
+            .build()?;
+        assert!(matches!(partitioned.process_verified(), Err(ExtractorError::Config(_))));
         Ok(())
     }
 
     #[test]
-    fn test_chunk_boundaries() {
-        let data = b"header\nrow1\nrow2\nrow3";
-        let boundary = ChunkBoundary::find_boundaries(data, 10);
-        assert!(boundary.end > boundary.start);
-        assert!(data[boundary.end] == b'\n' || boundary.end == data.len());
+    fn test_atomic_output() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,p\nTP53,0.01\nAPOE,oops\n")?;
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.csv");
+        std::fs::write(&output, "previous run\n")?;
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), &output)
+                .with_config(Config { parallel, ..Config::default() })
+                .atomic_output(true)
+                .fsync(true)
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new(
+                "p".to_string(),
+                FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
+            )?));
+
+            // The failed run leaves neither a truncated output nor its temporary file
+            assert!(filter.process().is_err());
+            assert_eq!(std::fs::read_to_string(&output)?, "previous run\n");
+            assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        }
+
+        let synced = crate::builder(input.path(), &output).atomic_output(true).fsync(true).build()?;
+        assert_eq!(synced.config.write_mode, crate::WriteMode::Atomic { fsync: true });
+        let direct = crate::builder(input.path(), &output).fsync(true).build()?;
+        assert_eq!(direct.config.write_mode, crate::WriteMode::Direct);
+        let undone = crate::builder(input.path(), &output).atomic_output(true).fsync(true).atomic_output(false);
+        assert_eq!(undone.build()?.config.write_mode, crate::WriteMode::Direct);
+
+        let mut filter = crate::builder(input.path(), &output).atomic_output(true).overwrite(true).build()?;
+        filter.add_filter(Box::new(ColumnFilter::new("gene".to_string(), FilterCondition::Equals("TP53".into()))?));
+        filter.process()?;
+        assert_eq!(std::fs::read_to_string(&output)?, "gene,p\nTP53,0.01\n");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
     }
-}
 
-/// Results from processing a chunk of data
-#[derive(Debug)]
-struct ChunkResult {
-    rows_processed: u64,
-    rows_matched: u64,
-    output_data: Vec<u8>,
-}
+    #[test]
+    fn test_existing_output() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
 
-impl Default for ProcessingStats {
-    fn default() -> Self {
-        Self {
-            rows_processed: 0,
-            rows_matched: 0,
-            processing_time_ms: 0,
-            input_size: 0,
-            output_size: 0,
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,p\nTP53,0.01\nAPOE,0.2\n")?;
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.csv");
+        std::fs::write(&output, "gene,p\nBRCA1,0.03")?;
+
+        let refused = crate::builder(input.path(), &output).overwrite(false).build()?;
+        assert!(matches!(refused.process(), Err(ExtractorError::Config(_))));
+        assert_eq!(std::fs::read_to_string(&output)?, "gene,p\nBRCA1,0.03");
+
+        for (parallel, atomic) in [(false, false), (true, true)] {
+            let mut builder = crate::builder(input.path(), &output)
+                .with_config(Config { parallel, ..Config::default() })
+                .overwrite(false)
+                .append(true);
+            if atomic {
+                builder = builder.atomic_output(true);
+            }
+            let mut filter = builder.build()?;
+            filter.add_filter(Box::new(ColumnFilter::new("gene".to_string(), FilterCondition::Equals("TP53".into()))?));
+            filter.process()?;
+        }
+        assert_eq!(
+            std::fs::read_to_string(&output)?,
+            "gene,p\nBRCA1,0.03\nTP53,0.01\nTP53,0.01\n"
+        );
+
+        // Rows are only appended under the same columns, and a rejected
+        // append leaves the file untouched
+        std::fs::write(&output, "gene,beta\nBRCA1,0.3")?;
+        for parallel in [false, true] {
+            let mismatched = crate::builder(input.path(), &output)
+                .with_config(Config { parallel, ..Config::default() })
+                .append(true)
+                .build()?;
+            assert!(matches!(mismatched.process(), Err(ExtractorError::Config(_))));
+            assert_eq!(std::fs::read_to_string(&output)?, "gene,beta\nBRCA1,0.3");
         }
+
+        // A missing output is created with its header as usual
+        std::fs::remove_file(&output)?;
+        crate::builder(input.path(), &output).overwrite(false).append(true).build()?.process()?;
+        assert_eq!(std::fs::read_to_string(&output)?, "gene,p\nTP53,0.01\nAPOE,0.2\n");
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    #[test]
+    fn test_column_names_for_headerless_input() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        write!(input, "TP53,0.01\nAPOE,0.2\nBRCA1,0.003\n")?;
+        let output = NamedTempFile::new()?;
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .column_names(["gene", "p_value"])
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new(
+                "p_value".to_string(),
+                FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
+            )?));
+            assert_eq!(filter.process()?.rows_processed, 3);
+            assert_eq!(std::fs::read_to_string(output.path())?, "gene,p_value\nTP53,0.01\nBRCA1,0.003\n");
+        }
+
+        let duplicated = crate::builder(input.path(), output.path()).column_names(["gene", "gene"]).build();
+        assert!(matches!(duplicated, Err(ExtractorError::Config(_))));
+        let with_header = crate::builder(input.path(), output.path())
+            .with_config(Config { column_names: Some(vec!["gene".into()]), ..Config::default() })
+            .build();
+        assert!(matches!(with_header, Err(ExtractorError::Config(_))));
+        Ok(())
+    }
 
     #[test]
-    fn test_basic_filtering() -> Result<()> {
-        // Create test data
+    fn test_column_aliases() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let output = NamedTempFile::new()?;
+        for header in ["SNP,P", "snp,pval", "rsid,P-VALUE"] {
+            let mut input = NamedTempFile::new()?;
+            write!(input, "{header}\nrs1,0.01\nrs2,0.2\n")?;
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_column_aliases([("p_value", ["pval", "p-value"]), ("variant", ["snp", "rsid"])])
+                .with_column_aliases([("p_value", ["p"])])
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new(
+                "p_value".to_string(),
+                FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
+            )?));
+            filter.add_filter(Box::new(ColumnFilter::new("variant".to_string(), FilterCondition::NotEmpty)?));
+            assert_eq!(filter.process()?.rows_matched, 1);
+        }
+
+        let headers = ByteRecord::from(vec!["P", "pval"]);
+        let aliases = HashMap::from([("p_value".to_string(), vec!["p".to_string(), "PVAL".to_string()])]);
+        assert!(matches!(resolve_columns(&headers, &aliases), Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_latin1_input() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+
         let mut input = NamedTempFile::new()?;
-        writeln!(input, "name,value")?;
-        writeln!(input, "test1,100")?;
-        writeln!(input, "test2,200")?;
+        input.write_all(b"patient,city\n1,Montr\xe9al\n2,Qu\xe9bec\n")?;
+        let output = NamedTempFile::new()?;
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new(
+                "city".to_string(),
+                FilterCondition::Equals("Montr\u{e9}al".into()),
+            )?));
+            assert_eq!(filter.process()?.rows_matched, 1);
+            assert_eq!(std::fs::read_to_string(output.path())?, "patient,city\n1,Montr\u{e9}al\n");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_utf8_bom() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
 
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"\xEF\xBB\xBFMarkerID,p\nrs1,0.01\nrs2,0.2\n")?;
         let output = NamedTempFile::new()?;
-        
-        let mut filter = BioFilter::new(
-            input.path().to_owned(),
-            output.path().to_owned(),
-            Config::default(),
-            None,
-        )?;
 
-        // Add test filter
-        filter.add_filter(Box::new(TestFilter));
-        
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.add_filter(Box::new(ColumnFilter::new(
+                "MarkerID".to_string(),
+                FilterCondition::Equals("rs1".into()),
+            )?));
+            let stats = filter.process()?;
+            assert!(stats.bom);
+            assert_eq!(stats.encoding, crate::InputEncoding::Utf8);
+            assert_eq!(std::fs::read_to_string(output.path())?, "MarkerID,p\nrs1,0.01\n");
+        }
+
+        let index = FileIndex::builder(input.path().to_path_buf(), "MarkerID".to_string()).build()?;
+        assert_eq!(index.columns[0], "MarkerID");
+        assert!(index.get_position("rs2").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_by_key() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"rsid,p\nrs1,0.01\nrs2,0.02\nrs3,0.03\n")?;
+        let mut withdrawn = NamedTempFile::new()?;
+        withdrawn.write_all(b"# withdrawn\nrs2\n\n rs3 \n")?;
+        let output = NamedTempFile::new()?;
+
+        let mut filter = crate::builder(input.path(), output.path()).overwrite(true).build()?;
+        filter.exclude_by_key(withdrawn.path(), "rsid")?;
         let stats = filter.process()?;
-        assert_eq!(stats.rows_processed, 2);
+        assert_eq!(stats.rows_matched, 1);
+        assert_eq!(std::fs::read_to_string(output.path())?, "rsid,p\nrs1,0.01\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_filter() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+        use crate::group::GroupFilter;
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"gene,p\nAPOE,1e-9\nTP53,1e-9\nAPOE,0.5\nAPOE,1e-10\nTP53,0.3\n")?;
+        let output = NamedTempFile::new()?;
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            let significant = ColumnFilter::new(
+                "p".to_string(),
+                FilterCondition::Numeric(NumericCondition::LessThan(5e-8)),
+            )?;
+            filter.add_group_filter(GroupFilter::new("gene".to_string(), 2).counting(Box::new(significant)));
+            let stats = filter.process()?;
+            assert_eq!(stats.rows_matched, 3);
+            assert_eq!(std::fs::read_to_string(output.path())?, "gene,p\nAPOE,1e-9\nAPOE,0.5\nAPOE,1e-10\n");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_output() -> Result<()> {
+        use crate::compression::{OutputCompression, TabixColumns};
+        use flate2::read::MultiGzDecoder;
+
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"chrom\tpos\tp\n1\t100\t0.01\n1\t200\t0.5\n2\t50\t0.02\n")?;
+        let expected = "chrom\tpos\tp\n1\t100\t0.01\n2\t50\t0.02\n";
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.tsv.gz");
+
+        let tabix = OutputCompression::Bgzip { tabix: Some(TabixColumns::new("chrom", "pos")) };
+        for compression in [OutputCompression::Gzip, OutputCompression::Zstd, tabix] {
+            for parallel in [false, true] {
+                let mut filter = crate::builder(input.path(), &output)
+                    .with_config(Config { parallel, delimiter: b'\t', ..Config::default() })
+                    .output_compression(compression.clone())
+                    .build()?;
+                filter.add_filter(Box::new(crate::filters::ColumnFilter::new(
+                    "p".to_string(),
+                    crate::filters::FilterCondition::Numeric(crate::filters::NumericCondition::LessThan(0.05)),
+                )?));
+                let stats = filter.process()?;
+                assert_eq!(stats.output_size, std::fs::metadata(&output)?.len());
+
+                let compressed = std::fs::read(&output)?;
+                let text = match compression {
+                    OutputCompression::Zstd => zstd::decode_all(&compressed[..])?,
+                    _ => {
+                        let mut text = Vec::new();
+                        MultiGzDecoder::new(&compressed[..]).read_to_end(&mut text)?;
+                        text
+                    }
+                };
+                assert_eq!(text, expected.as_bytes());
+            }
+        }
+        let mut index = Vec::new();
+        MultiGzDecoder::new(File::open(dir.path().join("hits.tsv.gz.tbi"))?).read_to_end(&mut index)?;
+        assert!(index.starts_with(b"TBI\x01"));
+
+        let filter = crate::builder(input.path(), &output)
+            .output_compression(OutputCompression::Bgzip { tabix: Some(TabixColumns::new("chrom", "pos")) })
+            .build()?;
+        assert!(matches!(filter.process(), Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "liftover")]
+    #[test]
+    fn test_liftover() -> Result<()> {
+        use crate::liftover::{ChainFile, Liftover, Unmapped};
+
+        let chain = ChainFile::parse(&b"chain 100 chr1 5000 + 1000 1100 chr1 6000 + 2000 2100 1\n100\n"[..])?;
+        let chain = Arc::new(chain);
+        let mut input = NamedTempFile::new()?;
+        input.write_all(b"chrom,pos,rsid\nchr1,1001,rs1\nchr1,4000,rs2\nchr1,1100,\"rs3\"\n")?;
+        let output = NamedTempFile::new()?;
+
+        for parallel in [false, true] {
+            let mut filter = crate::builder(input.path(), output.path())
+                .with_config(Config { parallel, ..Config::default() })
+                .overwrite(true)
+                .build()?;
+            filter.add_transform(Box::new(Liftover::new(chain.clone(), "chrom", "pos")));
+            let stats = filter.process()?;
+            assert_eq!(stats.rows_matched, 2);
+            assert_eq!(
+                std::fs::read_to_string(output.path())?,
+                "chrom,pos,rsid\nchr1,2001,rs1\nchr1,2100,rs3\n"
+            );
+        }
+
+        let mut filter = crate::builder(input.path(), output.path()).overwrite(true).build()?;
+        filter.add_transform(Box::new(
+            Liftover::new(chain, "chrom", "pos").unmapped(Unmapped::Flag("lifted".into())),
+        ));
+        filter.process_verified()?;
+        assert_eq!(
+            std::fs::read_to_string(output.path())?,
+            "chrom,pos,rsid,lifted\nchr1,2001,rs1,mapped\nchr1,4000,rs2,unmapped\nchr1,2100,rs3,mapped\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_stream() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+
+        let input = b"gene,p\r\nTP53,0.01\r\n\"APOE, e4\",NA\r\nBRCA1,0.2\r\n";
+        let mut filter = BioFilter::streaming(Config::default());
+        filter.add_filter(Box::new(ColumnFilter::new("p".to_string(), FilterCondition::NotEmpty)?));
+
+        let mut output = Vec::new();
+        let stats = filter.process_stream(&input[..], &mut output)?;
+        assert_eq!(stats.rows_processed, 3);
+        assert_eq!(stats.rows_matched, 2);
+        assert_eq!(stats.input_size, input.len() as u64);
+        assert_eq!(stats.output_size, output.len() as u64);
+        assert_eq!(output, b"gene,p\r\nTP53,0.01\r\nBRCA1,0.2\r\n");
+        assert_eq!(filter.filter_stats()[0].rejected, 1);
+
+        // No files are involved, so file-based processing fails cleanly
+        assert!(filter.process().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_stats() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene,chromosome,p")?;
+        writeln!(input, "TP53,chr17,0.01")?;
+        writeln!(input, "BRCA1,chr17,0.2")?;
+        writeln!(input, "APOE,chr19,0.03")?;
+        writeln!(input, "LDLR,chr19,0.5")?;
+
+        let output_dir = tempfile::tempdir()?;
+        let mut filter = crate::builder(input.path(), output_dir.path())
+            .partition_by("chromosome")
+            .overwrite(true)
+            .build()?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(0.05)),
+        )?));
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "chromosome".to_string(),
+            FilterCondition::Equals("chr17".into()),
+        )?));
+
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_matched, 1);
+
+        let filter_stats = filter.filter_stats();
+        assert_eq!(filter_stats[0].evaluated, 4);
+        assert_eq!(filter_stats[0].rejected, 2);
+        assert_eq!(filter_stats[1].evaluated, 2);
+        assert_eq!(filter_stats[1].rejected, 1);
+        assert!(filter.filter_report().contains("p < 0.05"));
+
+        // Counters restart with each run
+        filter.process()?;
+        assert_eq!(filter.filter_stats()[0].evaluated, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimize_filter_order() -> Result<()> {
+        use crate::filters::{ColumnFilter, FilterCondition};
+
+        let mut input = NamedTempFile::new()?;
+        writeln!(input, "gene,consequence")?;
+        for i in 0..50 {
+            writeln!(input, "GENE{i},intron_variant")?;
+        }
+        writeln!(input, "TP53,missense_variant")?;
+        let output_dir = tempfile::tempdir()?;
+
+        let mut filter = crate::builder(input.path(), output_dir.path())
+            .partition_by("consequence")
+            .build()?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "consequence".to_string(),
+            FilterCondition::Regex("^[a-z_]+variant$".into()),
+        )?));
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "gene".to_string(),
+            FilterCondition::Equals("TP53".into()),
+        )?));
+
+        // The regex rejects nothing in the sample, so the selective Equals moves first
+        let order = filter.optimize_filter_order(100)?;
+        assert_eq!(order, vec![1, 0]);
+        assert_eq!(filter.filter_stats()[0].description, "gene equals 'TP53'");
+
+        let stats = filter.process()?;
+        assert_eq!(stats.rows_matched, 1);
+        assert_eq!(filter.filter_stats()[1].evaluated, 1);
         Ok(())
     }
 }
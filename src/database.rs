@@ -0,0 +1,377 @@
+//! Database output sinks, enabled with the `sqlite` or `duckdb` feature.
+//! Matched rows are inserted into a table whose schema comes from the CSV
+//! headers (all `TEXT`) or is inferred from a sample of the input.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::filters::DEFAULT_EMPTY_TOKENS;
+use crate::output::RowSink;
+use crate::{Config, Result};
+
+/// SQL type of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// 64-bit integer (`BIGINT`)
+    Integer,
+    /// Double precision float (`DOUBLE`)
+    Real,
+    /// Text (`TEXT`)
+    Text,
+}
+
+impl ColumnType {
+    /// Type name understood by both SQLite and DuckDB
+    fn sql_name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "BIGINT",
+            ColumnType::Real => "DOUBLE",
+            ColumnType::Text => "TEXT",
+        }
+    }
+}
+
+/// Column names and types of the table rows are inserted into
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    columns: Vec<(String, ColumnType)>,
+}
+
+impl TableSchema {
+    /// Every column as `TEXT`, named after the header
+    pub fn from_headers(headers: &ByteRecord) -> Self {
+        Self {
+            columns: header_names(headers).map(|name| (name, ColumnType::Text)).collect(),
+        }
+    }
+
+    /// Infer column types from the first `sample_rows` rows of `path` (0 reads
+    /// the whole file). A column is `Integer` if every non-empty value parses
+    /// as an `i64`, `Real` if every one parses as a float, and `Text`
+    /// otherwise. Empty and NA values are ignored and inserted as NULL.
+    pub fn infer(path: &Path, config: &Config, sample_rows: usize) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(config.delimiter)
+            .has_headers(config.has_headers)
+            .flexible(true)
+            .from_path(path)?;
+        let mut schema = if config.has_headers {
+            Self::from_headers(reader.byte_headers()?)
+        } else if let Some(names) = &config.column_names {
+            Self::from_headers(&names.iter().collect())
+        } else {
+            Self::numbered(reader.byte_headers()?.len())
+        };
+
+        // Per column: (seen a value, all integers, all numbers)
+        let mut seen = vec![(false, true, true); schema.columns.len()];
+        let mut record = ByteRecord::new();
+        let mut rows = 0;
+        while (sample_rows == 0 || rows < sample_rows) && reader.read_byte_record(&mut record)? {
+            rows += 1;
+            for (field, state) in record.iter().zip(seen.iter_mut()) {
+                let Some(value) = non_empty(field) else { continue };
+                state.0 = true;
+                state.1 &= value.parse::<i64>().is_ok();
+                state.2 &= state.1 || value.parse::<f64>().is_ok();
+            }
+        }
+
+        for ((_, ty), (any, integer, real)) in schema.columns.iter_mut().zip(seen) {
+            *ty = match (any, integer, real) {
+                (true, true, _) => ColumnType::Integer,
+                (true, false, true) => ColumnType::Real,
+                _ => ColumnType::Text,
+            };
+        }
+        Ok(schema)
+    }
+
+    /// Columns named `column_1`, `column_2`, ..., as used for input without headers
+    fn numbered(len: usize) -> Self {
+        Self {
+            columns: (1..=len).map(|i| (format!("column_{i}"), ColumnType::Text)).collect(),
+        }
+    }
+
+    /// Override the type of one column
+    pub fn with_type(mut self, column: &str, ty: ColumnType) -> Result<Self> {
+        let Some(idx) = self.columns.iter().position(|(name, _)| name == column) else {
+            let names: Vec<&String> = self.columns.iter().map(|(name, _)| name).collect();
+            return Err(ExtractorError::column_not_found(column, &names));
+        };
+        self.columns[idx].1 = ty;
+        Ok(self)
+    }
+
+    /// Column names and types, in input order
+    pub fn columns(&self) -> &[(String, ColumnType)] {
+        &self.columns
+    }
+
+    /// Check the schema lines up with the header of the run
+    fn check_header(&self, header: &ByteRecord) -> Result<()> {
+        if self.columns.len() != header.len() {
+            return Err(ExtractorError::Config(format!(
+                "Table schema has {} columns but the input has {}",
+                self.columns.len(),
+                header.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn create_sql(&self, table: &str) -> String {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(name, ty)| format!("{} {}", quote_ident(name), ty.sql_name()))
+            .collect();
+        format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_ident(table), columns.join(", "))
+    }
+
+    fn insert_sql(&self, table: &str) -> String {
+        let names: Vec<String> = self.columns.iter().map(|(name, _)| quote_ident(name)).collect();
+        let params = vec!["?"; self.columns.len()].join(", ");
+        format!("INSERT INTO {} ({}) VALUES ({})", quote_ident(table), names.join(", "), params)
+    }
+}
+
+/// A field converted to the type of its column
+#[derive(Debug, PartialEq)]
+enum Field {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+/// Convert each field of `record`; missing trailing fields are NULL
+fn fields(schema: &TableSchema, record: &ByteRecord) -> Result<Vec<Field>> {
+    schema
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, ty))| {
+            let Some(value) = record.get(i).and_then(non_empty) else {
+                return Ok(Field::Null);
+            };
+            let invalid = || ExtractorError::InvalidDataFormat {
+                column: name.clone(),
+                message: format!("'{value}' is not a valid {}", ty.sql_name()),
+                row: None,
+                snippet: None,
+            };
+            Ok(match ty {
+                ColumnType::Integer => Field::Integer(value.parse().map_err(|_| invalid())?),
+                ColumnType::Real => Field::Real(value.parse().map_err(|_| invalid())?),
+                ColumnType::Text => Field::Text(value.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Trimmed field text, or `None` for empty and NA values
+fn non_empty(field: &[u8]) -> Option<&str> {
+    let value = std::str::from_utf8(field).ok()?.trim();
+    (!DEFAULT_EMPTY_TOKENS.contains(&value)).then_some(value)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn db_error(err: impl Display) -> ExtractorError {
+    ExtractorError::Database(err.to_string())
+}
+
+/// Inserts matched rows into a SQLite table inside a single transaction.
+/// The table is created if it doesn't exist; existing rows are kept.
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    conn: rusqlite::Connection,
+    table: String,
+    schema: Option<TableSchema>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSink {
+    /// Open (or create) the database at `path` and write into `table`
+    pub fn open(path: &Path, table: &str) -> Result<Self> {
+        let conn = rusqlite::Connection::open(path).map_err(db_error)?;
+        Ok(Self::new(conn, table))
+    }
+
+    /// Write into `table` of an existing connection
+    pub fn new(conn: rusqlite::Connection, table: &str) -> Self {
+        Self { conn, table: table.to_string(), schema: None }
+    }
+
+    /// Create the table with this schema instead of all-`TEXT` columns
+    pub fn with_schema(mut self, schema: TableSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Hand back the connection, e.g. to query the table
+    pub fn into_connection(self) -> rusqlite::Connection {
+        self.conn
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl RowSink for SqliteSink {
+    fn begin(&mut self, header: &ByteRecord) -> Result<()> {
+        let schema = self.schema.get_or_insert_with(|| TableSchema::from_headers(header));
+        schema.check_header(header)?;
+        self.conn
+            .execute_batch(&format!("BEGIN; {};", schema.create_sql(&self.table)))
+            .map_err(db_error)
+    }
+
+    fn write_row(&mut self, record: &ByteRecord) -> Result<()> {
+        use rusqlite::types::Value;
+
+        let schema = self.schema.as_ref().expect("begin() sets the schema");
+        let values = fields(schema, record)?.into_iter().map(|field| match field {
+            Field::Null => Value::Null,
+            Field::Integer(v) => Value::Integer(v),
+            Field::Real(v) => Value::Real(v),
+            Field::Text(v) => Value::Text(v),
+        });
+        self.conn
+            .prepare_cached(&schema.insert_sql(&self.table))
+            .and_then(|mut stmt| stmt.execute(rusqlite::params_from_iter(values)))
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.conn.execute_batch("COMMIT").map_err(db_error)
+    }
+}
+
+/// Inserts matched rows into a DuckDB table inside a single transaction.
+/// The table is created if it doesn't exist; existing rows are kept.
+#[cfg(feature = "duckdb")]
+pub struct DuckDbSink {
+    conn: duckdb::Connection,
+    table: String,
+    schema: Option<TableSchema>,
+}
+
+#[cfg(feature = "duckdb")]
+impl DuckDbSink {
+    /// Open (or create) the database at `path` and write into `table`
+    pub fn open(path: &Path, table: &str) -> Result<Self> {
+        let conn = duckdb::Connection::open(path).map_err(db_error)?;
+        Ok(Self::new(conn, table))
+    }
+
+    /// Write into `table` of an existing connection
+    pub fn new(conn: duckdb::Connection, table: &str) -> Self {
+        Self { conn, table: table.to_string(), schema: None }
+    }
+
+    /// Create the table with this schema instead of all-`TEXT` columns
+    pub fn with_schema(mut self, schema: TableSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Hand back the connection, e.g. to query the table
+    pub fn into_connection(self) -> duckdb::Connection {
+        self.conn
+    }
+}
+
+#[cfg(feature = "duckdb")]
+impl RowSink for DuckDbSink {
+    fn begin(&mut self, header: &ByteRecord) -> Result<()> {
+        let schema = self.schema.get_or_insert_with(|| TableSchema::from_headers(header));
+        schema.check_header(header)?;
+        self.conn
+            .execute_batch(&format!("BEGIN TRANSACTION; {};", schema.create_sql(&self.table)))
+            .map_err(db_error)
+    }
+
+    fn write_row(&mut self, record: &ByteRecord) -> Result<()> {
+        use duckdb::types::Value;
+
+        let schema = self.schema.as_ref().expect("begin() sets the schema");
+        let values = fields(schema, record)?.into_iter().map(|field| match field {
+            Field::Null => Value::Null,
+            Field::Integer(v) => Value::BigInt(v),
+            Field::Real(v) => Value::Double(v),
+            Field::Text(v) => Value::Text(v),
+        });
+        self.conn
+            .prepare_cached(&schema.insert_sql(&self.table))
+            .and_then(|mut stmt| stmt.execute(duckdb::params_from_iter(values)))
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.conn.execute_batch("COMMIT").map_err(db_error)
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::core::BioFilter;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_sqlite_sink_with_inferred_schema() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,pos,p\nTP53,7668402,1e-9\nAPOE,44908684,NA\nBRCA1,43044295,0.5\n")?;
+        let config = Config::default();
+
+        let schema = TableSchema::infer(input.path(), &config, 100)?;
+        assert_eq!(
+            schema.columns().iter().map(|(_, ty)| *ty).collect::<Vec<_>>(),
+            [ColumnType::Text, ColumnType::Integer, ColumnType::Real]
+        );
+
+        let mut filter = BioFilter::new(input.path().into(), "unused.csv".into(), config, None)?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "pos".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(44_000_000.0)),
+        )?));
+
+        let mut sink = SqliteSink::new(rusqlite::Connection::open_in_memory().map_err(db_error)?, "hits")
+            .with_schema(schema);
+        let stats = filter.process_into(&mut sink)?;
+        assert_eq!((stats.rows_processed, stats.rows_matched), (3, 2));
+
+        let conn = sink.into_connection();
+        let rows: Vec<(String, i64, Option<f64>)> = conn
+            .prepare("SELECT gene, pos, p FROM hits ORDER BY pos")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .collect()
+            })
+            .map_err(db_error)?;
+        assert_eq!(rows, [("TP53".to_string(), 7668402, Some(1e-9)), ("BRCA1".to_string(), 43044295, Some(0.5))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_conversion() {
+        let schema = TableSchema::from_headers(&ByteRecord::from(vec!["a", "b", "c"]))
+            .with_type("b", ColumnType::Integer)
+            .unwrap();
+        let row = fields(&schema, &ByteRecord::from(vec!["x", "NA"])).unwrap();
+        assert_eq!(row, [Field::Text("x".to_string()), Field::Null, Field::Null]);
+        assert!(fields(&schema, &ByteRecord::from(vec!["x", "1.5"])).is_err());
+        assert_eq!(schema.insert_sql("t"), r#"INSERT INTO "t" ("a", "b", "c") VALUES (?, ?, ?)"#);
+    }
+}
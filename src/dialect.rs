@@ -0,0 +1,203 @@
+//! Presets for common statistical-genetics tables: GWAS summary statistics,
+//! PLINK association output and PLINK `.bim`/`.fam` files. A preset maps the
+//! format's column names onto canonical ones (`variant_id`, `chromosome`,
+//! `base_pair_location`, `effect_allele`, `other_allele`, `beta`,
+//! `odds_ratio`, `standard_error`, `p_value`, ...), so the same filters work
+//! whichever tool wrote the file.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use crate::error::ExtractorError;
+use crate::{Config, Result};
+
+/// A tabular format with known column names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Tab-separated GWAS summary statistics with `SNP CHR BP A1 A2 BETA SE P`
+    /// style headers, as written by METAL, BOLT-LMM, SAIGE and most consortia
+    GwasSumstats,
+    /// PLINK 1.9 `--assoc`/`--linear`/`--logistic` or PLINK 2 `--glm`
+    /// output. PLINK 1.9 aligns its columns with spaces; run such files
+    /// through `to_tsv` first.
+    PlinkAssoc,
+    /// PLINK `.bim` variant table: headerless, tab-separated
+    PlinkBim,
+    /// PLINK `.fam` sample table: headerless, space-separated
+    PlinkFam,
+}
+
+/// Canonical name and the header variants it stands for
+type Alias = (&'static str, &'static [&'static str]);
+
+const SUMSTATS_ALIASES: &[Alias] = &[
+    ("variant_id", &["SNP", "rsid", "MarkerName", "SNPID", "ID"]),
+    ("chromosome", &["CHR", "CHROM", "#CHROM"]),
+    ("base_pair_location", &["BP", "POS", "position"]),
+    ("effect_allele", &["A1", "EA", "Allele1"]),
+    ("other_allele", &["A2", "NEA", "Allele2"]),
+    ("beta", &["BETA", "b", "Effect"]),
+    ("odds_ratio", &["OR"]),
+    ("standard_error", &["SE", "StdErr"]),
+    ("p_value", &["P", "PVAL", "P-value", "P_BOLT_LMM"]),
+    ("effect_allele_frequency", &["EAF", "FRQ", "Freq1", "AF_Allele2"]),
+    ("n", &["N", "NMISS"]),
+];
+
+const PLINK_ASSOC_ALIASES: &[Alias] = &[
+    ("variant_id", &["SNP", "ID"]),
+    ("chromosome", &["CHR", "#CHROM"]),
+    ("base_pair_location", &["BP", "POS"]),
+    ("effect_allele", &["A1"]),
+    ("other_allele", &["A2", "AX", "OMITTED"]),
+    ("beta", &["BETA"]),
+    ("odds_ratio", &["OR"]),
+    ("standard_error", &["SE", "LOG(OR)_SE"]),
+    ("p_value", &["P"]),
+    ("effect_allele_frequency", &["A1_FREQ", "MAF"]),
+    ("n", &["NMISS", "OBS_CT"]),
+];
+
+const BIM_COLUMNS: &[&str] =
+    &["chromosome", "variant_id", "genetic_distance", "base_pair_location", "effect_allele", "other_allele"];
+
+const FAM_COLUMNS: &[&str] = &["family_id", "individual_id", "paternal_id", "maternal_id", "sex", "phenotype"];
+
+impl Dialect {
+    /// Guess the dialect from the file name (`.bim`, `.fam`, `.assoc*`,
+    /// `.qassoc`, `.glm.*`), or from a header naming SNP, CHR, BP and P
+    /// columns. `None` if neither matches.
+    pub fn detect(path: &Path) -> Result<Option<Dialect>> {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let name = name.strip_suffix(".gz").unwrap_or(&name);
+        if name.ends_with(".bim") {
+            return Ok(Some(Dialect::PlinkBim));
+        }
+        if name.ends_with(".fam") {
+            return Ok(Some(Dialect::PlinkFam));
+        }
+        if name.contains(".assoc") || name.ends_with(".qassoc") || name.contains(".glm.") {
+            return Ok(Some(Dialect::PlinkAssoc));
+        }
+
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let mut header = String::new();
+        BufReader::new(file).read_line(&mut header).map_err(|e| ExtractorError::io_error(e, path))?;
+        let columns: Vec<String> =
+            header.split(|c: char| c == ',' || c.is_whitespace()).map(str::to_ascii_uppercase).collect();
+        let has = |name: &str| columns.iter().any(|column| column == name);
+        Ok((has("SNP") && has("CHR") && has("BP") && has("P")).then_some(Dialect::GwasSumstats))
+    }
+
+    /// Canonical column names mapped to the header variants they stand for
+    pub fn aliases(self) -> Vec<(&'static str, &'static [&'static str])> {
+        match self {
+            Dialect::GwasSumstats => SUMSTATS_ALIASES.to_vec(),
+            Dialect::PlinkAssoc => PLINK_ASSOC_ALIASES.to_vec(),
+            Dialect::PlinkBim | Dialect::PlinkFam => Vec::new(),
+        }
+    }
+
+    /// Column names of headerless formats
+    pub fn column_names(self) -> Option<&'static [&'static str]> {
+        match self {
+            Dialect::PlinkBim => Some(BIM_COLUMNS),
+            Dialect::PlinkFam => Some(FAM_COLUMNS),
+            Dialect::GwasSumstats | Dialect::PlinkAssoc => None,
+        }
+    }
+
+    /// Field delimiter
+    pub fn delimiter(self) -> u8 {
+        match self {
+            Dialect::PlinkFam => b' ',
+            _ => b'\t',
+        }
+    }
+
+    /// Set `config` up to read this dialect: delimiter, column aliases and,
+    /// for headerless formats, column names
+    pub fn apply(self, config: &mut Config) {
+        config.delimiter = self.delimiter();
+        for (canonical, variants) in self.aliases() {
+            config
+                .column_aliases
+                .entry(canonical.to_string())
+                .or_default()
+                .extend(variants.iter().map(|variant| variant.to_string()));
+        }
+        if let Some(names) = self.column_names() {
+            config.has_headers = false;
+            config.column_names = Some(names.iter().map(|name| name.to_string()).collect());
+        }
+    }
+}
+
+/// Rewrite a file whose columns are aligned with runs of spaces, like PLINK
+/// 1.9 output, as tab-separated. Returns the number of lines written.
+pub fn to_tsv(input: &Path, output: &Path) -> Result<u64> {
+    let file = File::open(input).map_err(|e| ExtractorError::io_error(e, input))?;
+    let out = File::create(output).map_err(|e| ExtractorError::io_error(e, output))?;
+    let mut writer = BufWriter::new(out);
+    let mut lines = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| ExtractorError::io_error(e, input))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        writeln!(writer, "{}", fields.join("\t")).map_err(|e| ExtractorError::io_error(e, output))?;
+        lines += 1;
+    }
+    writer.flush().map_err(|e| ExtractorError::io_error(e, output))?;
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{FilterCondition, NumericCondition};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dialects() -> Result<()> {
+        let dir = TempDir::new()?;
+        let significant = || FilterCondition::Numeric(NumericCondition::LessThan(1e-6));
+
+        let assoc = dir.path().join("plink.assoc.logistic");
+        std::fs::write(
+            &assoc,
+            " CHR        SNP         BP   A1       TEST    NMISS         OR         STAT            P\n   \
+             1  rs3094315     752566    G        ADD     3000     0.9876      -0.2140        0.8305\n  \
+             19   rs429358   44908684    C        ADD     3000      3.410        9.832     8.12e-23\n",
+        )?;
+        let tsv = dir.path().join("plink.tsv");
+        assert_eq!(Dialect::detect(&assoc)?, Some(Dialect::PlinkAssoc));
+        assert_eq!(to_tsv(&assoc, &tsv)?, 3);
+        let output = dir.path().join("hits.tsv");
+        crate::builder(&tsv, &output)
+            .dialect(Dialect::PlinkAssoc)
+            .filter("p_value", significant())
+            .filter("odds_ratio", FilterCondition::Numeric(NumericCondition::GreaterThan(1.0)))
+            .build()?
+            .process()?;
+        assert!(std::fs::read_to_string(&output)?.ends_with("19\trs429358\t44908684\tC\tADD\t3000\t3.410\t9.832\t8.12e-23\n"));
+
+        let sumstats = dir.path().join("sumstats.txt");
+        std::fs::write(&sumstats, "SNP\tCHR\tBP\tA1\tA2\tBETA\tSE\tP\nrs1\t1\t100\tA\tG\t0.2\t0.01\t1e-9\n")?;
+        assert_eq!(Dialect::detect(&sumstats)?, Some(Dialect::GwasSumstats));
+
+        let bim = dir.path().join("cohort.bim");
+        std::fs::write(&bim, "1\trs3094315\t0\t752566\tG\tA\n19\trs429358\t0\t44908684\tC\tT\n")?;
+        let stats = crate::builder(&bim, &output)
+            .dialect(Dialect::detect(&bim)?.unwrap())
+            .filter("chromosome", FilterCondition::Equals("19".to_string()))
+            .overwrite(true)
+            .build()?
+            .process()?;
+        assert_eq!(stats.rows_matched, 1);
+        let written = std::fs::read_to_string(&output)?;
+        assert!(written.starts_with("chromosome\tvariant_id\tgenetic_distance\tbase_pair_location"));
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! MD5 and SHA-256 digests of input and output files, computed while they are
+//! read and written so provenance records don't need a second pass.
+
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Digest algorithm for `Config::digest`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DigestAlgorithm {
+    /// MD5, as used by most data repositories' file listings
+    Md5,
+    /// SHA-256 (default)
+    #[default]
+    Sha256,
+}
+
+/// Running digest of one file
+#[derive(Clone)]
+pub(crate) enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: DigestAlgorithm) -> Self {
+        match algorithm {
+            DigestAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            DigestAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Lowercase hex digest
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// Digest of `data` in one go
+pub(crate) fn digest_bytes(algorithm: DigestAlgorithm, data: &[u8]) -> String {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Writer that digests everything written through it
+pub(crate) struct DigestWriter<W> {
+    inner: W,
+    hasher: Option<Hasher>,
+}
+
+impl<W: Write> DigestWriter<W> {
+    /// Digest with `algorithm`, or pass writes straight through when `None`
+    pub(crate) fn new(inner: W, algorithm: Option<DigestAlgorithm>) -> Self {
+        Self { inner, hasher: algorithm.map(Hasher::new) }
+    }
+
+    /// Digest bytes already in the file, e.g. when appending to it
+    pub(crate) fn include<R: Read>(&mut self, mut existing: R) -> io::Result<()> {
+        let Some(hasher) = &mut self.hasher else {
+            return Ok(());
+        };
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            match existing.read(&mut buffer)? {
+                0 => return Ok(()),
+                n => hasher.update(&buffer[..n]),
+            }
+        }
+    }
+
+    /// The inner writer and the hex digest of everything written
+    pub(crate) fn finish(self) -> (W, Option<String>) {
+        (self.inner, self.hasher.map(Hasher::finish))
+    }
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that digests everything read through it. The digest is shared, so
+/// it can be collected after the reader has been handed to a decoder.
+pub(crate) struct DigestReader<R> {
+    inner: R,
+    hasher: Option<Arc<Mutex<Hasher>>>,
+}
+
+impl<R: Read> DigestReader<R> {
+    /// Digest with `algorithm`, returning a handle to collect it from; reads
+    /// pass straight through when `None`
+    pub(crate) fn new(inner: R, algorithm: Option<DigestAlgorithm>) -> (Self, Option<InputDigest>) {
+        let hasher = algorithm.map(|algorithm| Arc::new(Mutex::new(Hasher::new(algorithm))));
+        let handle = hasher.clone().map(InputDigest);
+        (Self { inner, hasher }, handle)
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = &self.hasher {
+            hasher.lock().unwrap_or_else(|e| e.into_inner()).update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// Handle to the digest of a `DigestReader`
+pub(crate) struct InputDigest(Arc<Mutex<Hasher>>);
+
+impl InputDigest {
+    /// Hex digest of everything read so far
+    pub(crate) fn finish(&self) -> String {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone().finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digests() -> io::Result<()> {
+        assert_eq!(digest_bytes(DigestAlgorithm::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+
+        let sha256_abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let (mut reader, digest) = DigestReader::new(&b"abc"[..], Some(DigestAlgorithm::Sha256));
+        io::copy(&mut reader, &mut io::sink())?;
+        assert_eq!(digest.map(|digest| digest.finish()).as_deref(), Some(sha256_abc));
+
+        let mut writer = DigestWriter::new(Vec::new(), Some(DigestAlgorithm::Sha256));
+        writer.include(&b"a"[..])?;
+        writer.write_all(b"bc")?;
+        assert_eq!(writer.finish(), (b"bc".to_vec(), Some(sha256_abc.to_string())));
+        Ok(())
+    }
+}
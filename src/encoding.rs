@@ -0,0 +1,165 @@
+//! Input character encodings. With the `encoding` feature, Latin-1 and UTF-16
+//! input is transcoded to UTF-8 as it is read, so filters and outputs only
+//! ever see UTF-8.
+
+use std::io::{BufRead, BufReader, Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Bytes inspected when detecting the encoding
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// UTF-8 byte-order mark
+pub(crate) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Character encoding of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InputEncoding {
+    /// Detect from a byte-order mark or the first 64KB (default). Without the
+    /// `encoding` feature input is always read as UTF-8.
+    #[default]
+    Auto,
+    /// UTF-8, read without transcoding
+    Utf8,
+    /// ISO-8859-1, decoded as its Windows-1252 superset
+    Latin1,
+    /// UTF-16, little-endian
+    Utf16Le,
+    /// UTF-16, big-endian
+    Utf16Be,
+}
+
+impl InputEncoding {
+    /// Guess the encoding of data starting with `sample`: a byte-order mark
+    /// wins, then UTF-16 if every other byte is NUL, then valid UTF-8, and
+    /// Latin-1 otherwise
+    pub fn detect(sample: &[u8]) -> Self {
+        match sample {
+            [0xEF, 0xBB, 0xBF, ..] => return InputEncoding::Utf8,
+            [0xFF, 0xFE, ..] => return InputEncoding::Utf16Le,
+            [0xFE, 0xFF, ..] => return InputEncoding::Utf16Be,
+            _ => {}
+        }
+
+        let pairs = &sample[..sample.len().min(1024) & !1];
+        let half = pairs.len() / 2;
+        let mostly_nul = |offset: usize| {
+            half > 0 && pairs.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count() * 10 >= half * 9
+        };
+        if mostly_nul(1) {
+            return InputEncoding::Utf16Le;
+        }
+        if mostly_nul(0) {
+            return InputEncoding::Utf16Be;
+        }
+
+        match std::str::from_utf8(sample) {
+            Ok(_) => InputEncoding::Utf8,
+            // A multi-byte character cut off by the end of the sample is fine
+            Err(e) if e.error_len().is_none() => InputEncoding::Utf8,
+            Err(_) => InputEncoding::Latin1,
+        }
+    }
+
+    /// Whether `data` starts with this encoding's byte-order mark
+    pub(crate) fn has_bom(self, data: &[u8]) -> bool {
+        match self {
+            InputEncoding::Utf8 => data.starts_with(UTF8_BOM),
+            InputEncoding::Utf16Le => data.starts_with(b"\xFF\xFE"),
+            InputEncoding::Utf16Be => data.starts_with(b"\xFE\xFF"),
+            InputEncoding::Auto | InputEncoding::Latin1 => false,
+        }
+    }
+
+    /// The concrete encoding for input starting with `sample`
+    pub(crate) fn resolve(self, sample: &[u8]) -> Self {
+        match self {
+            InputEncoding::Auto if cfg!(feature = "encoding") => {
+                Self::detect(&sample[..sample.len().min(SNIFF_LEN)])
+            }
+            InputEncoding::Auto => InputEncoding::Utf8,
+            other => other,
+        }
+    }
+}
+
+/// Input wrapped to read as UTF-8 without a byte-order mark
+pub(crate) struct Decoded {
+    pub(crate) reader: Box<dyn Read + Send>,
+    /// Encoding the input was read as
+    pub(crate) encoding: InputEncoding,
+    /// Whether the input started with a byte-order mark
+    pub(crate) bom: bool,
+}
+
+/// Wrap `input` so that it reads as UTF-8, dropping any byte-order mark
+pub(crate) fn decode_reader<R: Read + Send + 'static>(input: R, encoding: InputEncoding) -> Result<Decoded> {
+    let mut input = BufReader::with_capacity(SNIFF_LEN, input);
+    let sample = input.fill_buf()?;
+    let encoding = encoding.resolve(sample);
+    let bom = encoding.has_bom(sample);
+    if encoding == InputEncoding::Utf8 {
+        if bom {
+            input.consume(UTF8_BOM.len());
+        }
+        return Ok(Decoded { reader: Box::new(input), encoding, bom });
+    }
+    Ok(Decoded { reader: transcode(input, encoding)?, encoding, bom })
+}
+
+#[cfg(feature = "encoding")]
+fn transcode<R: Read + Send + 'static>(input: R, encoding: InputEncoding) -> Result<Box<dyn Read + Send>> {
+    let encoding = match encoding {
+        InputEncoding::Latin1 => encoding_rs::WINDOWS_1252,
+        InputEncoding::Utf16Le => encoding_rs::UTF_16LE,
+        InputEncoding::Utf16Be => encoding_rs::UTF_16BE,
+        InputEncoding::Auto | InputEncoding::Utf8 => encoding_rs::UTF_8,
+    };
+    Ok(Box::new(
+        encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(input),
+    ))
+}
+
+#[cfg(not(feature = "encoding"))]
+fn transcode<R: Read + Send + 'static>(_input: R, encoding: InputEncoding) -> Result<Box<dyn Read + Send>> {
+    Err(crate::error::ExtractorError::Config(format!(
+        "Reading {encoding:?} input requires the `encoding` feature"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(InputEncoding::detect(b"gene,p\nTP53,0.01\n"), InputEncoding::Utf8);
+        assert_eq!(InputEncoding::detect("g\u{e8}ne\n".as_bytes()), InputEncoding::Utf8);
+        // Sample ends halfway through a two-byte character
+        assert_eq!(InputEncoding::detect(&"g\u{e8}".as_bytes()[..2]), InputEncoding::Utf8);
+        assert_eq!(InputEncoding::detect(b"patient,city\n1,Montr\xe9al\n"), InputEncoding::Latin1);
+        assert_eq!(InputEncoding::detect(b"\xff\xfeg\0e\0"), InputEncoding::Utf16Le);
+        assert_eq!(InputEncoding::detect(b"g\0e\0n\0e\0\n\0"), InputEncoding::Utf16Le);
+        assert_eq!(InputEncoding::detect(b"\0g\0e\0n\0e\0,\0\xe9"), InputEncoding::Utf16Be);
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_decode_reader() -> Result<()> {
+        let read = |data: &'static [u8], encoding| -> Result<String> {
+            let mut out = String::new();
+            decode_reader(data, encoding)?.reader.read_to_string(&mut out)?;
+            Ok(out)
+        };
+        assert_eq!(read(b"city\nMontr\xe9al\n", InputEncoding::Auto)?, "city\nMontr\u{e9}al\n");
+        assert_eq!(read(b"\xff\xfeg\0,\0\xe9\0\n\0", InputEncoding::Auto)?, "g,\u{e9}\n");
+        assert_eq!(read(b"\xef\xbb\xbfgene\n", InputEncoding::Auto)?, "gene\n");
+        // An explicit encoding overrides detection
+        assert_eq!(read(b"caf\xc3\xa9", InputEncoding::Latin1)?, "caf\u{c3}\u{a9}");
+        Ok(())
+    }
+}
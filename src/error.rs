@@ -1,8 +1,11 @@
 //! Error types for the Extractor library.
 //! This module defines all possible errors that can occur during CSV processing.
 
+use std::collections::HashMap;
 use std::io;
 use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Main error type for the Extractor library
@@ -21,6 +24,18 @@ pub enum ExtractorError {
     #[error("CSV error: {0}")]
     Csv(#[from] csv::Error),
 
+    /// CSV parsing error while filtering, at a known input row
+    #[error("CSV error at row {row}: {source}{}", snippet_hint(.snippet))]
+    CsvRow {
+        /// Data row, counted from 1 after the header
+        row: u64,
+        /// Start of the row as read so far
+        snippet: Option<String>,
+        /// The parser's error
+        #[source]
+        source: csv::Error,
+    },
+
     /// JSON serialization/deserialization errors (for index files)
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
@@ -52,21 +67,52 @@ pub enum ExtractorError {
     Parallel(String),
 
     /// Column not found in CSV
-    #[error("Column '{0}' not found in CSV headers")]
-    ColumnNotFound(String),
+    #[error("Column '{column}' not found in CSV headers{}", column_hint(.suggestions, .headers))]
+    ColumnNotFound {
+        /// The column that was asked for
+        column: String,
+        /// Headers with similar names, closest first
+        suggestions: Vec<String>,
+        /// All headers in file order, when known
+        headers: Vec<String>,
+    },
 
     /// Invalid data format
-    #[error("Invalid data format in column '{column}': {message}")]
+    #[error("Invalid data format in column '{column}'{}: {message}{}", row_hint(.row), snippet_hint(.snippet))]
     InvalidDataFormat {
         column: String,
         message: String,
+        /// Data row, counted from 1 after the header, when known
         row: Option<u64>,
+        /// Start of the offending row, when known
+        snippet: Option<String>,
     },
 
+    /// Parallel output differed from the sequential reference path
+    #[error("Output verification failed: {0}")]
+    VerificationFailed(String),
+
+    /// A SQL query that can't be parsed or isn't supported
+    #[error("Invalid query: {0}")]
+    Query(String),
+
+    /// A database sink failed to create its table or insert rows
+    #[error("Database error: {0}")]
+    Database(String),
+
+    /// Nothing matched after the share of the input set by
+    /// `Config::zero_match_guard`, which was set to abort
+    #[error("No matches: {0}")]
+    NoMatches(String),
+
     /// Resource exhaustion (memory, file handles, etc.)
     #[error("Resource exhaustion: {0}")]
     ResourceExhaustion(String),
 
+    /// The run was stopped through its `CancellationToken`
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     /// Generic error for unexpected situations
     #[error("{0}")]
     Other(String),
@@ -87,6 +133,19 @@ pub enum IndexErrorKind {
     #[error("Index is outdated")]
     Outdated,
 
+    /// Index file was cut short or altered after it was written
+    #[error("Index file is incomplete or corrupted")]
+    Corrupted,
+
+    /// Index was written by a newer version of the library
+    #[error("Index format version {found} is newer than the supported version {supported}; upgrade the library to read it")]
+    UnsupportedVersion {
+        /// Version in the file
+        found: u32,
+        /// Newest version this build reads
+        supported: u32,
+    },
+
     /// Error building index
     #[error("Failed to build index: {0}")]
     BuildError(String),
@@ -130,6 +189,54 @@ impl ExtractorError {
         ExtractorError::Filter { kind, column }
     }
 
+    /// Create a column-not-found error, suggesting the `headers` that look
+    /// like a misspelling of `column`
+    pub fn column_not_found<S: AsRef<str>>(column: &str, headers: &[S]) -> Self {
+        let headers: Vec<String> = headers.iter().map(|header| header.as_ref().to_string()).collect();
+        ExtractorError::ColumnNotFound {
+            column: column.to_string(),
+            suggestions: similar_columns(column, &headers),
+            headers,
+        }
+    }
+
+    /// `column_not_found` for a header map, as built from the header row
+    pub fn column_not_in_headers(column: &str, headers: &HashMap<String, usize>) -> Self {
+        let mut names: Vec<(&usize, &String)> = headers.iter().map(|(name, idx)| (idx, name)).collect();
+        names.sort();
+        let names: Vec<&String> = names.into_iter().map(|(_, name)| name).collect();
+        Self::column_not_found(column, &names)
+    }
+
+    /// Add the data row `row` and the start of `record` to a data or CSV
+    /// error that doesn't say where it happened yet
+    pub(crate) fn at_row(self, row: u64, record: &csv::ByteRecord, delimiter: u8) -> Self {
+        match self {
+            ExtractorError::InvalidDataFormat { column, message, row: None, .. } => ExtractorError::InvalidDataFormat {
+                column,
+                message,
+                row: Some(row),
+                snippet: Some(snippet(record, delimiter)),
+            },
+            ExtractorError::Csv(source) => {
+                ExtractorError::CsvRow { row, snippet: Some(snippet(record, delimiter)), source }
+            }
+            error => error,
+        }
+    }
+
+    /// Shift the row of an error raised in one chunk of the input by the
+    /// `rows` in the chunks before it
+    pub(crate) fn after_rows(self, rows: u64) -> Self {
+        match self {
+            ExtractorError::InvalidDataFormat { column, message, row, snippet } => {
+                ExtractorError::InvalidDataFormat { column, message, row: row.map(|row| row + rows), snippet }
+            }
+            ExtractorError::CsvRow { row, snippet, source } => ExtractorError::CsvRow { row: row + rows, snippet, source },
+            error => error,
+        }
+    }
+
     /// Create a new configuration error
     pub fn config<S: Into<String>>(message: S) -> Self {
         ExtractorError::Config(message.into())
@@ -140,6 +247,17 @@ impl ExtractorError {
         matches!(self, ExtractorError::Io { .. })
     }
 
+    /// Check if the error is a transient I/O failure that may succeed when
+    /// retried, such as a timeout or a stale handle on a network filesystem.
+    /// Other errors are fatal.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ExtractorError::Io { source, .. } => crate::retry::is_transient(source),
+            ExtractorError::Csv(error) => matches!(error.kind(), csv::ErrorKind::Io(source) if crate::retry::is_transient(source)),
+            _ => false,
+        }
+    }
+
     /// Check if the error is related to invalid data
     pub fn is_data_error(&self) -> bool {
         matches!(self, ExtractorError::InvalidDataFormat { .. })
@@ -149,21 +267,173 @@ impl ExtractorError {
     pub fn category(&self) -> &'static str {
         match self {
             ExtractorError::Io { .. } => "io",
-            ExtractorError::Csv(_) => "csv",
+            ExtractorError::Csv(_) | ExtractorError::CsvRow { .. } => "csv",
             ExtractorError::Json(_) => "json",
             ExtractorError::Config(_) => "config",
             ExtractorError::Index { .. } => "index",
             ExtractorError::Filter { .. } => "filter",
             ExtractorError::Mmap(_) => "mmap",
             ExtractorError::Parallel(_) => "parallel",
-            ExtractorError::ColumnNotFound(_) => "column",
+            ExtractorError::ColumnNotFound { .. } => "column",
             ExtractorError::InvalidDataFormat { .. } => "data",
+            ExtractorError::VerificationFailed(_) => "verification",
+            ExtractorError::Query(_) => "query",
+            ExtractorError::Database(_) => "database",
+            ExtractorError::NoMatches(_) => "no_matches",
             ExtractorError::ResourceExhaustion(_) => "resource",
+            ExtractorError::Cancelled(_) => "cancelled",
             ExtractorError::Other(_) => "other",
         }
     }
 }
 
+/// What to do with a row whose filters or transforms fail on bad data, e.g.
+/// a non-numeric value in a numeric column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RowErrorPolicy {
+    /// Stop the run with the error (default)
+    #[default]
+    Fail,
+    /// Leave the row out of the output and count it in `ProcessingStats::row_errors`
+    Skip,
+}
+
+/// Rows skipped under `RowErrorPolicy::Skip` that failed the same way
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowErrorSummary {
+    /// `ExtractorError::category` of the errors
+    pub category: String,
+    /// Column the errors were in, when known
+    pub column: Option<String>,
+    /// Number of rows skipped
+    pub count: u64,
+    /// First data row with the error
+    pub first_row: Option<u64>,
+    /// Last data row with the error
+    pub last_row: Option<u64>,
+    /// Message of the first error, without its row
+    pub example: String,
+}
+
+impl RowErrorSummary {
+    /// Count `error` in the summary for its category and column
+    pub(crate) fn record(summaries: &mut Vec<Self>, error: &ExtractorError) {
+        let (column, row, example) = match error {
+            ExtractorError::InvalidDataFormat { column, message, row, .. } => {
+                (Some(column.clone()), *row, message.clone())
+            }
+            ExtractorError::CsvRow { row, source, .. } => (None, Some(*row), source.to_string()),
+            error => (None, None, error.to_string()),
+        };
+        let category = error.category();
+        match summaries.iter_mut().find(|summary| summary.category == category && summary.column == column) {
+            Some(summary) => {
+                summary.count += 1;
+                summary.first_row = summary.first_row.or(row);
+                summary.last_row = row.or(summary.last_row);
+            }
+            None => summaries.push(Self {
+                category: category.to_string(),
+                column,
+                count: 1,
+                first_row: row,
+                last_row: row,
+                example,
+            }),
+        }
+    }
+
+    /// Add the summaries of a later chunk of the input, whose rows are
+    /// numbered from 1 after the `rows` before it
+    pub(crate) fn merge(summaries: &mut Vec<Self>, later: Vec<Self>, rows: u64) {
+        for mut next in later {
+            next.first_row = next.first_row.map(|row| row + rows);
+            next.last_row = next.last_row.map(|row| row + rows);
+            match summaries
+                .iter_mut()
+                .find(|summary| summary.category == next.category && summary.column == next.column)
+            {
+                Some(summary) => {
+                    summary.count += next.count;
+                    summary.first_row = summary.first_row.or(next.first_row);
+                    summary.last_row = next.last_row.or(summary.last_row);
+                }
+                None => summaries.push(next),
+            }
+        }
+    }
+}
+
+/// Longest row snippet kept in an error, in characters
+const MAX_SNIPPET_CHARS: usize = 120;
+
+/// The row's fields joined by `delimiter`, cut to `MAX_SNIPPET_CHARS`
+fn snippet(record: &csv::ByteRecord, delimiter: u8) -> String {
+    let text = itertools::join(record.iter().map(String::from_utf8_lossy), &char::from(delimiter).to_string());
+    match text.char_indices().nth(MAX_SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// " at row N" when the row is known
+fn row_hint(row: &Option<u64>) -> String {
+    row.map(|row| format!(" at row {row}")).unwrap_or_default()
+}
+
+/// " (row: ...)" when a snippet is known
+fn snippet_hint(snippet: &Option<String>) -> String {
+    snippet.as_ref().map(|snippet| format!(" (row: {snippet:?})")).unwrap_or_default()
+}
+
+/// Most suggestions given for an unknown column
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Headers that could be a misspelling of `column`, closest first. Names are
+/// compared ignoring case and `_`, `-`, `.` and spaces, so `P-value` finds
+/// `p_value`.
+fn similar_columns(column: &str, headers: &[String]) -> Vec<String> {
+    let normalize = |name: &str| -> String {
+        name.chars()
+            .filter(|c| !matches!(c, '_' | '-' | '.' | ' '))
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let wanted = normalize(column);
+    let max_edits = (wanted.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, f64, &String)> = headers
+        .iter()
+        .filter(|header| header.as_str() != column)
+        .filter_map(|header| {
+            let name = normalize(header);
+            let edits = strsim::levenshtein(&wanted, &name);
+            let similarity = strsim::jaro_winkler(&wanted, &name);
+            (edits <= max_edits || similarity >= 0.9).then_some((edits, similarity, header))
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+    let mut suggestions: Vec<String> = Vec::new();
+    for (_, _, header) in scored {
+        if suggestions.len() < MAX_SUGGESTIONS && !suggestions.contains(header) {
+            suggestions.push(header.clone());
+        }
+    }
+    suggestions
+}
+
+/// "; did you mean ...? Headers: ..." suffix of a column-not-found message
+fn column_hint(suggestions: &[String], headers: &[String]) -> String {
+    let mut hint = String::new();
+    if !suggestions.is_empty() {
+        let quoted: Vec<String> = suggestions.iter().map(|name| format!("'{name}'")).collect();
+        hint.push_str(&format!("; did you mean {}?", quoted.join(" or ")));
+    }
+    if !headers.is_empty() {
+        hint.push_str(&format!(" Headers: {}", headers.join(", ")));
+    }
+    hint
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,13 +491,14 @@ mod tests {
         let parallel_err = ExtractorError::Parallel("parallel error".to_string());
         assert_eq!(parallel_err.category(), "parallel");
 
-        let column_err = ExtractorError::ColumnNotFound("gene_name".to_string());
+        let column_err = ExtractorError::column_not_found("gene_name", &["gene"]);
         assert_eq!(column_err.category(), "column");
 
         let data_err = ExtractorError::InvalidDataFormat {
             column: "gene_name".to_string(),
             message: "invalid format".to_string(),
             row: Some(1),
+            snippet: None,
         };
         assert_eq!(data_err.category(), "data");
 
@@ -251,4 +522,25 @@ mod tests {
             panic!("Expected ExtractorError::Filter");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_column_suggestions() {
+        let headers = ["chrom", "pos", "gwas.p_value", "gwas.beta", "gnomad.af.afr", "gnomad.af.amr"];
+        let err = ExtractorError::column_not_found("gwas.pvalue", &headers);
+        let ExtractorError::ColumnNotFound { suggestions, .. } = &err else {
+            panic!("Expected ExtractorError::ColumnNotFound");
+        };
+        assert_eq!(suggestions, &["gwas.p_value"]);
+        assert!(err.to_string().contains("did you mean 'gwas.p_value'? Headers: chrom, pos, gwas.p_value"));
+
+        let err = ExtractorError::column_not_found("gnomad.af.asj", &headers);
+        let ExtractorError::ColumnNotFound { suggestions, .. } = &err else {
+            panic!("Expected ExtractorError::ColumnNotFound");
+        };
+        assert_eq!(suggestions, &["gnomad.af.afr", "gnomad.af.amr"]);
+
+        let map = HashMap::from([("pos".to_string(), 1), ("chrom".to_string(), 0)]);
+        let err = ExtractorError::column_not_in_headers("CHROM", &map);
+        assert_eq!(err.to_string(), "Column 'CHROM' not found in CSV headers; did you mean 'chrom'? Headers: chrom, pos");
+    }
+}
@@ -0,0 +1,85 @@
+//! Explain mode: for chosen rows, the result of every filter and the values it
+//! looked at, to answer "why is rs12345 not in the output" without bisecting
+//! the filters by hand.
+
+use std::fmt;
+
+/// Rows for `BioFilter::explain` to report on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplainTarget {
+    /// Data row number, counting from 1 after the header
+    Row(u64),
+    /// Every row whose `column` holds `value`, ignoring surrounding whitespace
+    Key {
+        /// Column to look in, e.g. `rsid`
+        column: String,
+        /// Value to look for, e.g. `rs12345`
+        value: String,
+    },
+}
+
+impl ExplainTarget {
+    /// Every row whose `column` holds `value`
+    pub fn key<C: Into<String>, V: Into<String>>(column: C, value: V) -> Self {
+        ExplainTarget::Key { column: column.into(), value: value.into() }
+    }
+}
+
+/// What one filter decided for a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterOutcome {
+    /// The row passes the filter
+    Passed,
+    /// The filter rejects the row
+    Failed,
+    /// The filter couldn't be evaluated, e.g. a non-numeric value for a numeric condition
+    Error(String),
+}
+
+/// How one filter handled a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterTrace {
+    /// Pipeline the filter belongs to, or `None` for the top-level filters
+    pub pipeline: Option<String>,
+    /// Filter description
+    pub filter: String,
+    /// Pass, fail or error
+    pub outcome: FilterOutcome,
+    /// Values the filter looked at, e.g. `p = "0.2", parsed as 0.2`
+    pub detail: String,
+}
+
+/// Every filter's result for one row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowExplanation {
+    /// Data row number, counting from 1 after the header
+    pub row: u64,
+    /// Whether all top-level filters pass, so that the row is written (before
+    /// any transforms). Pipelines are decided by their own traces.
+    pub selected: bool,
+    /// Top-level filters in order, then the filters of each pipeline
+    pub filters: Vec<FilterTrace>,
+}
+
+impl fmt::Display for RowExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.selected { "selected" } else { "not selected" };
+        write!(f, "row {}: {verdict}", self.row)?;
+        for trace in &self.filters {
+            let outcome = match &trace.outcome {
+                FilterOutcome::Passed => "PASS",
+                FilterOutcome::Failed => "FAIL",
+                FilterOutcome::Error(_) => "ERROR",
+            };
+            write!(f, "\n  {outcome} ")?;
+            if let Some(pipeline) = &trace.pipeline {
+                write!(f, "[{pipeline}] ")?;
+            }
+            write!(f, "{} ({})", trace.filter, trace.detail)?;
+            if let FilterOutcome::Error(message) = &trace.outcome {
+                write!(f, ": {message}")?;
+            }
+        }
+        Ok(())
+    }
+}
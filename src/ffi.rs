@@ -0,0 +1,281 @@
+//! C interface, enabled with the `ffi` feature and exported from the cdylib.
+//! `include/extractor.h` is generated from this module by cbindgen at build time.
+//!
+//! Every function returns an `ExtractorStatus`; on failure the message is
+//! available from `extractor_last_error` on the same thread.
+
+// Raw pointers from C and the unmangled exports are unsafe code
+#![allow(unsafe_code)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::core::BioFilter;
+use crate::error::ExtractorError;
+use crate::filters::{ColumnFilter, FilterCondition};
+use crate::{Config, ProcessingStats, Result};
+
+/// Result code of every `extractor_*` call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractorStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A pointer was null or a string was not valid UTF-8 / JSON
+    InvalidArgument = 1,
+    /// Reading the input or writing the output failed
+    Io = 2,
+    /// A filter refers to a column the input does not have
+    ColumnNotFound = 3,
+    /// Any other library error
+    Failed = 4,
+    /// The library panicked; the handle should be freed
+    Panic = 5,
+}
+
+/// Counters from the most recent `extractor_process` call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtractorStats {
+    /// Number of rows processed
+    pub rows_processed: u64,
+    /// Number of rows matched
+    pub rows_matched: u64,
+    /// Processing time in milliseconds
+    pub processing_time_ms: u64,
+    /// Input file size in bytes
+    pub input_size: u64,
+    /// Output file size in bytes
+    pub output_size: u64,
+}
+
+impl From<&ProcessingStats> for ExtractorStats {
+    fn from(stats: &ProcessingStats) -> Self {
+        Self {
+            rows_processed: stats.rows_processed,
+            rows_matched: stats.rows_matched,
+            processing_time_ms: stats.processing_time_ms,
+            input_size: stats.input_size,
+            output_size: stats.output_size,
+        }
+    }
+}
+
+/// Opaque extraction handle, created by `extractor_open`
+pub struct ExtractorHandle {
+    filter: BioFilter,
+    stats: Option<ExtractorStats>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+fn status_of(err: &ExtractorError) -> ExtractorStatus {
+    match err {
+        ExtractorError::Io { .. } => ExtractorStatus::Io,
+        ExtractorError::ColumnNotFound { .. } => ExtractorStatus::ColumnNotFound,
+        ExtractorError::Config(_) | ExtractorError::Json(_) => ExtractorStatus::InvalidArgument,
+        _ => ExtractorStatus::Failed,
+    }
+}
+
+/// Run `op`, recording any error or panic for `extractor_last_error`
+fn guard<F: FnOnce() -> Result<()>>(op: F) -> ExtractorStatus {
+    match panic::catch_unwind(AssertUnwindSafe(op)) {
+        Ok(Ok(())) => ExtractorStatus::Ok,
+        Ok(Err(err)) => {
+            set_last_error(err.to_string());
+            status_of(&err)
+        }
+        Err(_) => {
+            set_last_error("extractor panicked".to_string());
+            ExtractorStatus::Panic
+        }
+    }
+}
+
+/// Borrow a C string argument as UTF-8
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    if value.is_null() {
+        return Err(ExtractorError::Config(format!("{name} is null")));
+    }
+    // SAFETY: the caller passes a NUL-terminated string that outlives the call
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|_| ExtractorError::Config(format!("{name} is not valid UTF-8")))
+}
+
+/// Borrow a handle argument
+unsafe fn handle_arg<'a>(handle: *mut ExtractorHandle) -> Result<&'a mut ExtractorHandle> {
+    // SAFETY: the caller passes null or a live handle from `extractor_open`
+    unsafe { handle.as_mut() }.ok_or_else(|| ExtractorError::Config("handle is null".to_string()))
+}
+
+/// Open `input_path` for filtering into `output_path` with the default
+/// configuration and store the new handle in `*out`.
+///
+/// # Safety
+///
+/// `input_path` and `output_path` must be NUL-terminated strings and `out`
+/// must point to writable storage for a handle pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn extractor_open(
+    input_path: *const c_char,
+    output_path: *const c_char,
+    out: *mut *mut ExtractorHandle,
+) -> ExtractorStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(ExtractorError::Config("out is null".to_string()));
+        }
+        // SAFETY: forwarded from the caller's contract
+        let input = PathBuf::from(unsafe { str_arg(input_path, "input_path") }?);
+        let output = PathBuf::from(unsafe { str_arg(output_path, "output_path") }?);
+        let filter = BioFilter::new(input, output, Config::default(), None)?;
+        let handle = Box::new(ExtractorHandle { filter, stats: None });
+        // SAFETY: `out` was checked for null above
+        unsafe { *out = Box::into_raw(handle) };
+        Ok(())
+    })
+}
+
+/// Add a filter on `column`. `condition_json` is a `FilterCondition` in its
+/// serde form, e.g. `{"Numeric":{"LessThan":5e-8}}` or `"NotEmpty"`.
+///
+/// # Safety
+///
+/// `handle` must come from `extractor_open` and not have been freed; the
+/// strings must be NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn extractor_add_filter(
+    handle: *mut ExtractorHandle,
+    column: *const c_char,
+    condition_json: *const c_char,
+) -> ExtractorStatus {
+    guard(|| {
+        // SAFETY: forwarded from the caller's contract
+        let handle = unsafe { handle_arg(handle) }?;
+        let column = unsafe { str_arg(column, "column") }?;
+        let condition: FilterCondition =
+            serde_json::from_str(unsafe { str_arg(condition_json, "condition_json") }?)?;
+        let filter = ColumnFilter::new(column.to_string(), condition)?;
+        handle.filter.add_filter(Box::new(filter));
+        Ok(())
+    })
+}
+
+/// Run the extraction, writing matched rows to the output path
+///
+/// # Safety
+///
+/// `handle` must come from `extractor_open` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn extractor_process(handle: *mut ExtractorHandle) -> ExtractorStatus {
+    guard(|| {
+        // SAFETY: forwarded from the caller's contract
+        let handle = unsafe { handle_arg(handle) }?;
+        let stats = handle.filter.process()?;
+        handle.stats = Some(ExtractorStats::from(&stats));
+        Ok(())
+    })
+}
+
+/// Copy the statistics of the last `extractor_process` call into `*out`
+///
+/// # Safety
+///
+/// `handle` must come from `extractor_open` and not have been freed, and
+/// `out` must point to a writable `ExtractorStats`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn extractor_get_stats(
+    handle: *mut ExtractorHandle,
+    out: *mut ExtractorStats,
+) -> ExtractorStatus {
+    guard(|| {
+        // SAFETY: forwarded from the caller's contract
+        let handle = unsafe { handle_arg(handle) }?;
+        let stats = handle
+            .stats
+            .ok_or_else(|| ExtractorError::Config("extractor_process has not run".to_string()))?;
+        // SAFETY: the caller passes null or a writable `ExtractorStats`
+        let out = unsafe { out.as_mut() }.ok_or_else(|| ExtractorError::Config("out is null".to_string()))?;
+        *out = stats;
+        Ok(())
+    })
+}
+
+/// Release a handle. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `handle` must be null or come from `extractor_open`, and must not be used
+/// again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn extractor_free(handle: *mut ExtractorHandle) {
+    if !handle.is_null() {
+        // SAFETY: the handle was created by `Box::into_raw` in `extractor_open`
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Message of the last failed call on this thread, or null if none has
+/// failed. The string stays valid until the next failing call on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn extractor_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn c(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    #[test]
+    fn test_ffi_round_trip() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        write!(input, "gene,p\nTP53,0.01\nAPOE,0.5\n")?;
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("hits.csv");
+        let input_path = c(input.path().to_str().unwrap());
+        let output_path = c(output.to_str().unwrap());
+
+        unsafe {
+            let mut handle = ptr::null_mut();
+            assert_eq!(extractor_open(input_path.as_ptr(), output_path.as_ptr(), &mut handle), ExtractorStatus::Ok);
+
+            let mut stats = ExtractorStats::default();
+            assert_eq!(extractor_get_stats(handle, &mut stats), ExtractorStatus::InvalidArgument);
+
+            let condition = c(r#"{"Numeric":{"LessThan":0.05}}"#);
+            assert_eq!(extractor_add_filter(handle, c("p").as_ptr(), condition.as_ptr()), ExtractorStatus::Ok);
+            assert_eq!(
+                extractor_add_filter(handle, c("p").as_ptr(), c("{").as_ptr()),
+                ExtractorStatus::InvalidArgument
+            );
+            assert!(!extractor_last_error().is_null());
+
+            assert_eq!(extractor_process(handle), ExtractorStatus::Ok);
+            assert_eq!(extractor_get_stats(handle, &mut stats), ExtractorStatus::Ok);
+            assert_eq!((stats.rows_processed, stats.rows_matched), (2, 1));
+
+            extractor_free(handle);
+            assert_eq!(extractor_process(ptr::null_mut()), ExtractorStatus::InvalidArgument);
+        }
+        assert_eq!(std::fs::read_to_string(&output)?, "gene,p\nTP53,0.01\n");
+        Ok(())
+    }
+}
@@ -12,12 +12,20 @@
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::str;
-use std::sync::OnceLock;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
+use chrono::{NaiveDate, NaiveDateTime};
 use csv::ByteRecord;
 use serde::{Deserialize, Serialize};
 
+use crate::blocks::ColumnZone;
+use crate::chrom::chrom_eq;
 use crate::error::{ExtractorError, FilterErrorKind};
+use crate::hgvs::{Consequence, Impact};
 use crate::Result;
 
 /// Trait for implementing filters
@@ -30,6 +38,164 @@ pub trait Filter: Send + Sync {
 
     /// Get a description of the filter
     fn description(&self) -> String;
+
+    /// Show the values `apply` looks at in `row`, e.g. `p = "0.2"`, so that
+    /// `BioFilter::explain` can say why the filter passed or failed
+    fn explain(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> String {
+        explain_field(self.column_name(), row, headers)
+    }
+
+    /// Whether a row of a block summarized by `zone`, the block's summary of
+    /// this filter's column, could pass. Returning `false` lets a scan with a
+    /// block index skip the block, so only do so when no row could pass.
+    fn may_match(&self, _zone: &ColumnZone) -> bool {
+        true
+    }
+
+    /// For a filter that only tests its column's value as a number, whether
+    /// `x` passes; `None` for any other filter. A column cache evaluates such
+    /// filters on its stored numbers instead of reading rows.
+    fn test_number(&self, _x: f64) -> Option<bool> {
+        None
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for std::sync::Arc<F> {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        (**self).apply(row, headers)
+    }
+
+    fn column_name(&self) -> &str {
+        (**self).column_name()
+    }
+
+    fn description(&self) -> String {
+        (**self).description()
+    }
+
+    fn explain(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> String {
+        (**self).explain(row, headers)
+    }
+
+    fn may_match(&self, zone: &ColumnZone) -> bool {
+        (**self).may_match(zone)
+    }
+
+    fn test_number(&self, x: f64) -> Option<bool> {
+        (**self).test_number(x)
+    }
+}
+
+/// `column = "value"` for the field of `column` in `row`
+fn explain_field(column: &str, row: &ByteRecord, headers: &HashMap<String, usize>) -> String {
+    match headers.get(column).and_then(|&idx| row.get(idx)) {
+        Some(value) => format!("{column} = {:?}", String::from_utf8_lossy(value)),
+        None => format!("{column} is missing"),
+    }
+}
+
+/// Parse a field the way numeric conditions do, or `None` if it isn't a number
+pub(crate) fn parse_number(value: &[u8]) -> Option<f64> {
+    str::from_utf8(value).ok()?.trim().parse().ok()
+}
+
+/// Values treated as missing unless a filter is given its own tokens
+pub const DEFAULT_EMPTY_TOKENS: &[&str] = &["", "NA", "N/A", "NULL", ".", "NaN", "None", "null", "nan"];
+
+/// Whether a field counts as empty under the default tokens, the way the
+/// `Empty` condition sees it
+pub(crate) fn is_default_empty(value: &[u8]) -> bool {
+    let trimmed = trim_ascii(value);
+    trimmed.is_empty() || DEFAULT_EMPTY_TOKENS.iter().any(|token| token.as_bytes().eq_ignore_ascii_case(trimmed))
+}
+
+/// Match statistics for one filter of a pipeline
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterStats {
+    /// Position of the filter in the pipeline (0 = evaluated first)
+    pub position: usize,
+    /// Filter description, e.g. `p < 5e-8`
+    pub description: String,
+    /// Rows the filter was evaluated on. Rows rejected by an earlier filter never reach it.
+    pub evaluated: u64,
+    /// Rows the filter rejected
+    pub rejected: u64,
+    /// Rows where evaluation failed (e.g. a non-numeric value in a numeric filter)
+    pub errors: u64,
+    /// Average evaluation time per row in nanoseconds
+    pub avg_eval_ns: f64,
+}
+
+impl FilterStats {
+    /// Fraction of evaluated rows this filter rejected (0.0 when never evaluated)
+    pub fn rejection_rate(&self) -> f64 {
+        if self.evaluated == 0 {
+            0.0
+        } else {
+            self.rejected as f64 / self.evaluated as f64
+        }
+    }
+}
+
+/// Thread-safe counters behind a `FilterStats`
+#[derive(Debug, Default)]
+pub(crate) struct FilterCounters {
+    evaluated: AtomicU64,
+    rejected: AtomicU64,
+    errors: AtomicU64,
+    eval_nanos: AtomicU64,
+}
+
+impl FilterCounters {
+    /// Apply `filter`, recording the outcome and evaluation time
+    #[inline]
+    pub(crate) fn apply(
+        &self,
+        filter: &dyn Filter,
+        row: &ByteRecord,
+        headers: &HashMap<String, usize>,
+    ) -> Result<bool> {
+        // There is no monotonic clock on wasm32-unknown-unknown; Instant::now() panics there
+        #[cfg(not(target_arch = "wasm32"))]
+        let start = Instant::now();
+        let result = filter.apply(row, headers);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.eval_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        match &result {
+            Ok(false) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(true) => {}
+        }
+        result
+    }
+
+    /// Zero all counters before a new run
+    pub(crate) fn reset(&self) {
+        self.evaluated.store(0, Ordering::Relaxed);
+        self.rejected.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.eval_nanos.store(0, Ordering::Relaxed);
+    }
+
+    /// Snapshot the counters for the filter at `position`
+    pub(crate) fn snapshot(&self, position: usize, filter: &dyn Filter) -> FilterStats {
+        let evaluated = self.evaluated.load(Ordering::Relaxed);
+        let nanos = self.eval_nanos.load(Ordering::Relaxed);
+        FilterStats {
+            position,
+            description: filter.description(),
+            evaluated,
+            rejected: self.rejected.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            avg_eval_ns: if evaluated == 0 { 0.0 } else { nanos as f64 / evaluated as f64 },
+        }
+    }
 }
 
 /// Numeric comparison conditions
@@ -39,6 +205,20 @@ pub enum NumericCondition {
     LessThan(f64),
     Equal(f64),
     NotEqual(f64),
+    /// x >= threshold (exact, e.g. MAF >= 0.01)
+    GreaterOrEqual(f64),
+    /// x <= threshold
+    LessOrEqual(f64),
+    /// min <= x <= max
+    Between(f64, f64),
+    /// |x| > threshold
+    AbsGreaterThan(f64),
+    /// |x| >= threshold (e.g. |log2FC| >= 1)
+    AbsGreaterOrEqual(f64),
+    /// |x| < threshold
+    AbsLessThan(f64),
+    /// x is neither infinite nor NaN
+    IsFinite,
 }
 
 /// Range condition for numeric values
@@ -50,6 +230,130 @@ pub struct RangeCondition {
     pub inclusive: bool,
 }
 
+/// Comparison applied by a date condition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateOp {
+    /// Strictly before the value
+    Before,
+    /// Strictly after the value
+    After,
+    /// Before or equal to the value
+    OnOrBefore,
+    /// After or equal to the value
+    OnOrAfter,
+    /// Equal to the value
+    On,
+    /// Between the value and `end`, inclusive
+    Between {
+        /// Inclusive upper bound, in the same format as the value
+        end: String,
+    },
+}
+
+/// How the length of a value is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LengthUnit {
+    /// Raw byte length
+    Bytes,
+    /// Number of UTF-8 characters
+    Chars,
+}
+
+/// Length comparison conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LengthCondition {
+    Equal(usize),
+    NotEqual(usize),
+    GreaterThan(usize),
+    LessThan(usize),
+    /// min <= len <= max
+    Between(usize, usize),
+    /// Same length as another column of the row
+    EqualToColumn(String),
+    /// Length differs from another column of the row (e.g. `ref` vs `alt` for indels)
+    NotEqualToColumn(String),
+}
+
+/// Textual notation of a numeric value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberNotation {
+    /// Exponent form such as `5e-8` or `1.2E+03`
+    Scientific,
+    /// Plain decimal or integer such as `0.00000005`
+    Plain,
+}
+
+/// How numbers are written in a column, for numeric and range conditions on
+/// exports like `1,234.56`, `1.234,56` or `12%`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberFormat {
+    /// Decimal separator (default: `.`)
+    pub decimal: char,
+    /// Thousands separator, checked to group digits by three (default: none)
+    pub thousands: Option<char>,
+    /// Read a trailing `%` as hundredths, so `12%` is 0.12 (default: false)
+    pub percent: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self { decimal: '.', thousands: None, percent: false }
+    }
+}
+
+impl NumberFormat {
+    /// `1,234.56`
+    pub fn english() -> Self {
+        Self { decimal: '.', thousands: Some(','), percent: false }
+    }
+
+    /// `1.234,56`
+    pub fn european() -> Self {
+        Self { decimal: ',', thousands: Some('.'), percent: false }
+    }
+
+    /// Also read a trailing `%` as hundredths
+    pub fn with_percent(mut self) -> Self {
+        self.percent = true;
+        self
+    }
+
+    /// Parse `s` written in this format, or `None` if it isn't
+    pub fn parse(&self, s: &str) -> Option<f64> {
+        let s = s.trim();
+        let (s, scale) = match s.strip_suffix('%') {
+            Some(rest) if self.percent => (rest.trim_end(), 0.01),
+            _ => (s, 1.0),
+        };
+        let (integer, fraction) = match s.split_once(self.decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (s, None),
+        };
+        let mut plain = String::with_capacity(s.len());
+        match self.thousands.filter(|sep| integer.contains(*sep)) {
+            Some(sep) => {
+                let unsigned = integer.trim_start_matches(['-', '+']);
+                plain.push_str(&integer[..integer.len() - unsigned.len()]);
+                // The first group has one to three digits, the rest exactly three
+                for (i, group) in unsigned.split(sep).enumerate() {
+                    let grouped = if i == 0 { (1..=3).contains(&group.len()) } else { group.len() == 3 };
+                    if !grouped || !group.bytes().all(|b| b.is_ascii_digit()) {
+                        return None;
+                    }
+                    plain.push_str(group);
+                }
+            }
+            None => plain.push_str(integer),
+        }
+        if let Some(fraction) = fraction {
+            plain.push('.');
+            plain.push_str(fraction);
+        }
+        plain.parse::<f64>().ok().map(|x| x * scale)
+    }
+}
+
 /// Filter condition types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FilterCondition {
@@ -61,6 +365,85 @@ pub enum FilterCondition {
     Range(RangeCondition),
     Empty,
     NotEmpty,
+    /// Compare a date/time column. `format` is a chrono format string such as
+    /// `%Y-%m-%d` or `%Y-%m-%dT%H:%M:%S`; date-only values compare as midnight.
+    Date {
+        /// Comparison to apply
+        op: DateOp,
+        /// Date to compare against, in `format`
+        value: String,
+        /// chrono format string used for both `value` and the column
+        format: String,
+    },
+    /// Compare the length of the value, measured in `unit`
+    Length {
+        /// Bytes or characters
+        unit: LengthUnit,
+        /// Comparison to apply
+        cond: LengthCondition,
+    },
+    /// Value is a number written in the given notation. Non-numeric values never match.
+    Notation(NumberNotation),
+    /// Value names the same chromosome, ignoring a `chr` prefix and case
+    /// (`chrX`, `X` and `x` are equal; so are `chrM` and `MT`)
+    ChromEquals(String),
+    /// Value implies one of these consequences, read from a protein HGVS
+    /// change (`p.Arg117His`) or an effect term (`missense_variant`)
+    Consequence(Vec<Consequence>),
+    /// Split a multi-value cell such as `Pathogenic;Benign` on `separator`
+    /// and match if `condition` holds for any element. Elements are trimmed.
+    AnyElementMatches {
+        /// Separator between values, e.g. `;` or `|`
+        separator: String,
+        /// Condition applied to each element
+        condition: Box<FilterCondition>,
+    },
+    /// Like `AnyElementMatches`, but `condition` must hold for every element
+    AllElementsMatch {
+        /// Separator between values, e.g. `;` or `|`
+        separator: String,
+        /// Condition applied to each element
+        condition: Box<FilterCondition>,
+    },
+    /// Value passes the predicate registered under this name with
+    /// `register_predicate`. The predicate sees the raw, untrimmed field.
+    Custom(String),
+}
+
+/// A user-supplied test of a field's bytes
+type PredicateFn = dyn Fn(&[u8]) -> bool + Send + Sync;
+
+/// Shared handle to a `PredicateFn`
+#[derive(Clone)]
+struct Predicate(Arc<PredicateFn>);
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Predicate")
+    }
+}
+
+/// Predicates that `FilterCondition::Custom` refers to by name
+fn predicates() -> &'static RwLock<HashMap<String, Predicate>> {
+    static PREDICATES: OnceLock<RwLock<HashMap<String, Predicate>>> = OnceLock::new();
+    PREDICATES.get_or_init(Default::default)
+}
+
+/// Register `predicate` under `name`, so `FilterCondition::Custom(name)`
+/// can use it in builders, profiles and element conditions. Replaces any
+/// predicate registered under the same name; filters built before keep the
+/// one they were built with.
+pub fn register_predicate<F>(name: impl Into<String>, predicate: F)
+where
+    F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+{
+    let mut registry = predicates().write().unwrap_or_else(|e| e.into_inner());
+    registry.insert(name.into(), Predicate(Arc::new(predicate)));
+}
+
+/// Remove the predicate registered under `name`, returning whether there was one
+pub fn unregister_predicate(name: &str) -> bool {
+    predicates().write().unwrap_or_else(|e| e.into_inner()).remove(name).is_some()
 }
 
 impl FilterCondition {
@@ -75,6 +458,13 @@ impl FilterCondition {
                 NumericCondition::LessThan(v) => format!("{column} < {v}"),
                 NumericCondition::Equal(v) => format!("{column} = {v}"),
                 NumericCondition::NotEqual(v) => format!("{column} != {v}"),
+                NumericCondition::GreaterOrEqual(v) => format!("{column} >= {v}"),
+                NumericCondition::LessOrEqual(v) => format!("{column} <= {v}"),
+                NumericCondition::Between(min, max) => format!("{min} <= {column} <= {max}"),
+                NumericCondition::AbsGreaterThan(v) => format!("|{column}| > {v}"),
+                NumericCondition::AbsGreaterOrEqual(v) => format!("|{column}| >= {v}"),
+                NumericCondition::AbsLessThan(v) => format!("|{column}| < {v}"),
+                NumericCondition::IsFinite => format!("{column} is finite"),
             },
             FilterCondition::OneOf(values) => format!("{column} in {:?}", values),
             FilterCondition::Range(range) => format!(
@@ -86,6 +476,44 @@ impl FilterCondition {
             ),
             FilterCondition::Empty => format!("{column} is empty"),
             FilterCondition::NotEmpty => format!("{column} is not empty"),
+            FilterCondition::Date { op, value, .. } => match op {
+                DateOp::Before => format!("{column} before {value}"),
+                DateOp::After => format!("{column} after {value}"),
+                DateOp::OnOrBefore => format!("{column} on or before {value}"),
+                DateOp::OnOrAfter => format!("{column} on or after {value}"),
+                DateOp::On => format!("{column} on {value}"),
+                DateOp::Between { end } => format!("{column} between {value} and {end}"),
+            },
+            FilterCondition::Length { unit, cond } => {
+                let len = match unit {
+                    LengthUnit::Bytes => format!("len({column})"),
+                    LengthUnit::Chars => format!("chars({column})"),
+                };
+                match cond {
+                    LengthCondition::Equal(n) => format!("{len} = {n}"),
+                    LengthCondition::NotEqual(n) => format!("{len} != {n}"),
+                    LengthCondition::GreaterThan(n) => format!("{len} > {n}"),
+                    LengthCondition::LessThan(n) => format!("{len} < {n}"),
+                    LengthCondition::Between(min, max) => format!("{min} <= {len} <= {max}"),
+                    LengthCondition::EqualToColumn(other) => format!("{len} = len({other})"),
+                    LengthCondition::NotEqualToColumn(other) => format!("{len} != len({other})"),
+                }
+            }
+            FilterCondition::Notation(NumberNotation::Scientific) => {
+                format!("{column} is in scientific notation")
+            }
+            FilterCondition::Notation(NumberNotation::Plain) => {
+                format!("{column} is a plain decimal")
+            }
+            FilterCondition::ChromEquals(target) => format!("{column} is chromosome {target}"),
+            FilterCondition::Consequence(consequences) => format!("{column} consequence in {consequences:?}"),
+            FilterCondition::AnyElementMatches { separator, condition } => {
+                format!("any '{separator}'-separated element of {}", condition.description(column))
+            }
+            FilterCondition::AllElementsMatch { separator, condition } => {
+                format!("every '{separator}'-separated element of {}", condition.description(column))
+            }
+            FilterCondition::Custom(name) => format!("{name}({column})"),
         }
     }
 }
@@ -100,10 +528,18 @@ pub struct ColumnFilter {
     col_idx: OnceLock<usize>,
     cached_regex: Option<Regex>,
     one_of_set: Option<HashSet<Vec<u8>>>,
+    /// Parsed bounds for date conditions (value, optional range end)
+    date_bounds: Option<(NaiveDateTime, Option<NaiveDateTime>)>,
+    /// Filter applied to each element of a multi-value cell
+    element_filter: Option<Box<ColumnFilter>>,
+    /// Predicate of a custom condition
+    predicate: Option<Predicate>,
 
-    /// Tokens that should be treated as "empty" (case-insensitive).
+    /// Tokens that should be treated as "empty" (ASCII case-insensitive).
     /// Defaults include "", "NA", "N/A", "NULL", ".", "NaN".
-    empty_tokens: HashSet<String>,
+    empty_tokens: Vec<Vec<u8>>,
+    /// How numbers are written, when not plain `1234.5`
+    number_format: Option<NumberFormat>,
 }
 
 impl ColumnFilter {
@@ -131,13 +567,53 @@ impl ColumnFilter {
             None
         };
 
-        // Default empty/NA tokens
-        let empty_tokens = [
-            "", "NA", "N/A", "NULL", ".", "NaN", "None", "null", "nan",
-        ]
-        .iter()
-        .map(|s| s.to_ascii_lowercase())
-        .collect::<HashSet<_>>();
+        // Pre-parse date bounds so bad formats fail at construction
+        let date_bounds = if let FilterCondition::Date { op, value, format } = &condition {
+            let invalid = || {
+                ExtractorError::filter_error(FilterErrorKind::InvalidCondition, Some(column.clone()))
+            };
+            let start = parse_datetime(value, format).ok_or_else(invalid)?;
+            let end = match op {
+                DateOp::Between { end } => Some(parse_datetime(end, format).ok_or_else(invalid)?),
+                _ => None,
+            };
+            Some((start, end))
+        } else {
+            None
+        };
+
+        // Per-element conditions get their own prepared filter
+        let element_filter = match &condition {
+            FilterCondition::AnyElementMatches { separator, condition }
+            | FilterCondition::AllElementsMatch { separator, condition } => {
+                if separator.is_empty() {
+                    return Err(ExtractorError::filter_error(
+                        FilterErrorKind::InvalidCondition,
+                        Some(column),
+                    ));
+                }
+                Some(Box::new(ColumnFilter::new(column.clone(), (**condition).clone())?))
+            }
+            _ => None,
+        };
+
+        // Custom conditions take their predicate from the registry now, so
+        // an unknown name fails at construction
+        let predicate = match &condition {
+            FilterCondition::Custom(name) => {
+                let registry = predicates().read().unwrap_or_else(|e| e.into_inner());
+                let predicate = registry.get(name).cloned().ok_or_else(|| {
+                    ExtractorError::config(format!("No predicate is registered as '{name}' (filtering '{column}')"))
+                })?;
+                Some(predicate)
+            }
+            _ => None,
+        };
+
+        let empty_tokens = DEFAULT_EMPTY_TOKENS
+            .iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
 
         Ok(Self {
             column,
@@ -145,16 +621,52 @@ impl ColumnFilter {
             col_idx: OnceLock::new(),
             cached_regex,
             one_of_set,
+            date_bounds,
+            element_filter,
+            predicate,
             empty_tokens,
+            number_format: None,
         })
     }
 
+    /// Create a filter that keeps rows whose field passes `predicate`, for
+    /// tests that no `FilterCondition` covers, such as a call to an external
+    /// scoring function. The predicate sees the raw, untrimmed field.
+    pub fn from_fn<F>(column: String, predicate: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            column,
+            condition: FilterCondition::Custom("fn".to_string()),
+            col_idx: OnceLock::new(),
+            cached_regex: None,
+            one_of_set: None,
+            date_bounds: None,
+            element_filter: None,
+            predicate: Some(Predicate(Arc::new(predicate))),
+            empty_tokens: DEFAULT_EMPTY_TOKENS.iter().map(|s| s.as_bytes().to_vec()).collect(),
+            number_format: None,
+        }
+    }
+
     /// Optionally customize which tokens count as "empty"
     pub fn with_empty_tokens(mut self, tokens: impl IntoIterator<Item = String>) -> Self {
-        self.empty_tokens = tokens
-            .into_iter()
-            .map(|s| s.to_ascii_lowercase())
-            .collect::<HashSet<_>>();
+        self.empty_tokens = tokens.into_iter().map(String::into_bytes).collect();
+        if let Some(element_filter) = self.element_filter.take() {
+            let tokens = self.empty_tokens.iter().map(|t| String::from_utf8_lossy(t).into_owned());
+            self.element_filter = Some(Box::new(element_filter.with_empty_tokens(tokens)));
+        }
+        self
+    }
+
+    /// Parse numbers for numeric and range conditions in `format`, e.g.
+    /// `NumberFormat::european()` for `1.234,56`
+    pub fn with_number_format(mut self, format: NumberFormat) -> Self {
+        self.number_format = Some(format);
+        if let Some(element_filter) = self.element_filter.take() {
+            self.element_filter = Some(Box::new(element_filter.with_number_format(format)));
+        }
         self
     }
 
@@ -165,7 +677,7 @@ impl ColumnFilter {
         }
         let idx = *headers
             .get(&self.column)
-            .ok_or_else(|| ExtractorError::ColumnNotFound(self.column.clone()))?;
+            .ok_or_else(|| ExtractorError::column_not_in_headers(&self.column, headers))?;
         // Set once; subsequent calls are fast
         let _ = self.col_idx.set(idx);
         Ok(idx)
@@ -179,38 +691,117 @@ impl ColumnFilter {
                 column: self.column.clone(),
                 message: format!("Row has no field at index {idx}"),
                 row: None,
+                snippet: None,
             }
         })
     }
 
+    /// Look up another column of the same row (used by cross-column conditions)
+    #[inline]
+    fn other_value<'a>(
+        &self,
+        other: &str,
+        row: &'a ByteRecord,
+        headers: &HashMap<String, usize>,
+    ) -> Result<&'a [u8]> {
+        let idx = *headers
+            .get(other)
+            .ok_or_else(|| ExtractorError::column_not_in_headers(other, headers))?;
+        row.get(idx).ok_or_else(|| ExtractorError::InvalidDataFormat {
+            column: other.to_string(),
+            message: format!("Row has no field at index {idx}"),
+            row: None,
+            snippet: None,
+        })
+    }
+
     #[inline]
     fn parse_numeric(&self, value: &[u8]) -> Result<f64> {
         let s = str::from_utf8(value).map_err(|_| ExtractorError::InvalidDataFormat {
             column: self.column.clone(),
             message: "Invalid UTF-8".to_string(),
             row: None,
+            snippet: None,
         })?;
         let s = s.trim();
-        s.parse::<f64>().map_err(|_| ExtractorError::InvalidDataFormat {
+        let parsed = match &self.number_format {
+            Some(format) => format.parse(s),
+            None => s.parse::<f64>().ok(),
+        };
+        parsed.ok_or_else(|| ExtractorError::InvalidDataFormat {
             column: self.column.clone(),
             message: format!("Invalid numeric value: '{s}'"),
             row: None,
+            snippet: None,
         })
     }
 
     #[inline]
     fn is_empty_token(&self, value: &[u8]) -> bool {
-        // Trim ASCII whitespace, then case-insensitive token check
+        // Trim ASCII whitespace, then case-insensitive token check without allocating
         let trimmed = trim_ascii(value);
-        if trimmed.is_empty() {
-            return true;
-        }
-        // Lowercase ASCII only (common CSVs); avoid alloc if possible
-        let lower = ascii_to_lower_lossy(trimmed);
-        self.empty_tokens.contains(&lower)
+        trimmed.is_empty()
+            || self
+                .empty_tokens
+                .iter()
+                .any(|token| token.eq_ignore_ascii_case(trimmed))
     }
 
     #[inline]
+    /// Whether a number between `min` and `max` could pass a numeric or
+    /// range condition
+    fn range_may_match(&self, min: f64, max: f64) -> bool {
+        // Smallest and largest absolute value in min..=max
+        let abs_min = if min <= 0.0 && max >= 0.0 { 0.0 } else { min.abs().min(max.abs()) };
+        let abs_max = min.abs().max(max.abs());
+        match &self.condition {
+            FilterCondition::Numeric(cond) => match cond {
+                NumericCondition::GreaterThan(t) => max > *t,
+                NumericCondition::LessThan(t) => min < *t,
+                NumericCondition::Equal(t) => {
+                    (min..=max).contains(t) || Self::approx_eq(min, *t) || Self::approx_eq(max, *t)
+                }
+                NumericCondition::NotEqual(t) => min != max || !Self::approx_eq(min, *t),
+                NumericCondition::GreaterOrEqual(t) => max >= *t,
+                NumericCondition::LessOrEqual(t) => min <= *t,
+                NumericCondition::Between(lo, hi) => max >= *lo && min <= *hi,
+                NumericCondition::AbsGreaterThan(t) => abs_max > *t,
+                NumericCondition::AbsGreaterOrEqual(t) => abs_max >= *t,
+                NumericCondition::AbsLessThan(t) => abs_min < *t,
+                NumericCondition::IsFinite => true,
+            },
+            FilterCondition::Range(r) if r.inclusive => max >= r.min && min <= r.max,
+            FilterCondition::Range(r) => max > r.min && min < r.max,
+            _ => true,
+        }
+    }
+
+    /// Whether `x` passes a numeric or range condition; `None` for any other
+    fn number_passes(&self, x: f64) -> Option<bool> {
+        let pass = match &self.condition {
+            FilterCondition::Numeric(cond) => match cond {
+                NumericCondition::GreaterThan(t) => x > *t,
+                NumericCondition::LessThan(t) => x < *t,
+                NumericCondition::Equal(t) => Self::approx_eq(x, *t),
+                NumericCondition::NotEqual(t) => !Self::approx_eq(x, *t),
+                NumericCondition::GreaterOrEqual(t) => x >= *t,
+                NumericCondition::LessOrEqual(t) => x <= *t,
+                NumericCondition::Between(min, max) => x >= *min && x <= *max,
+                NumericCondition::AbsGreaterThan(t) => x.abs() > *t,
+                NumericCondition::AbsGreaterOrEqual(t) => x.abs() >= *t,
+                NumericCondition::AbsLessThan(t) => x.abs() < *t,
+                NumericCondition::IsFinite => x.is_finite(),
+            },
+            FilterCondition::Range(r) => {
+                let lower_ok = if r.inclusive { x >= r.min } else { x > r.min };
+                let upper_ok = if r.inclusive { x <= r.max } else { x < r.max };
+                lower_ok && upper_ok
+            }
+            _ => return None,
+        };
+        Some(pass)
+    }
+
     fn approx_eq(a: f64, b: f64) -> bool {
         // Relative tolerance to avoid strict bitwise equality woes.
         let tol = 1e-12_f64.max(1e-12 * a.abs().max(b.abs()));
@@ -220,9 +811,87 @@ impl ColumnFilter {
 
 impl Filter for ColumnFilter {
     fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
-        use memchr::memmem;
-
         let value = self.get_value(row, headers)?;
+        self.evaluate(value, row, headers)
+    }
+
+    fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    fn description(&self) -> String {
+        self.condition.description(&self.column)
+    }
+
+    /// Adds the parsed number for numeric conditions and the result for each
+    /// element of a multi-value cell
+    fn explain(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> String {
+        let shown = explain_field(&self.column, row, headers);
+        let Ok(value) = self.get_value(row, headers) else {
+            return shown;
+        };
+        match &self.condition {
+            FilterCondition::Numeric(_) | FilterCondition::Range(_) => match self.parse_numeric(value) {
+                Ok(x) => format!("{shown}, parsed as {x}"),
+                Err(_) => shown,
+            },
+            FilterCondition::AnyElementMatches { separator, .. }
+            | FilterCondition::AllElementsMatch { separator, .. } => {
+                let element_filter = self.element_filter.as_ref().expect("element filter prebuilt");
+                let elements: Vec<String> = split_elements(value, separator.as_bytes())
+                    .map(|element| {
+                        let outcome = match element_filter.evaluate(element, row, headers) {
+                            Ok(true) => "passes",
+                            Ok(false) => "fails",
+                            Err(_) => "is invalid",
+                        };
+                        format!("{:?} {outcome}", String::from_utf8_lossy(element))
+                    })
+                    .collect();
+                format!("{shown}; elements: {}", elements.join(", "))
+            }
+            _ => shown,
+        }
+    }
+
+    fn test_number(&self, x: f64) -> Option<bool> {
+        // A column cache stores plain numbers, which a formatted column may misread
+        if self.number_format.is_some() {
+            return None;
+        }
+        self.number_passes(x)
+    }
+
+    /// Rules blocks out on their numeric range for numeric and range
+    /// conditions, and on their distinct values for equality conditions
+    fn may_match(&self, zone: &ColumnZone) -> bool {
+        // A row without the field fails the filter rather than being rejected
+        if zone.missing {
+            return true;
+        }
+        let has = |target: &String| zone.values.as_ref().is_none_or(|values| values.binary_search(target).is_ok());
+        match &self.condition {
+            FilterCondition::Equals(target) => has(target),
+            FilterCondition::OneOf(targets) => targets.iter().any(has),
+            // Zone ranges are of plain numbers, which a formatted column may misread
+            FilterCondition::Numeric(_) | FilterCondition::Range(_)
+                if !zone.non_numeric && self.number_format.is_none() =>
+            {
+                match (zone.min, zone.max) {
+                    (Some(min), Some(max)) => self.range_may_match(min, max),
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+}
+
+impl ColumnFilter {
+    /// Check the condition against `value`, the column's field (or one
+    /// element of it) in `row`
+    fn evaluate(&self, value: &[u8], row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        use memchr::memmem;
 
         match &self.condition {
             FilterCondition::Equals(target) => Ok(value == target.as_bytes()),
@@ -234,40 +903,174 @@ impl Filter for ColumnFilter {
                     column: self.column.clone(),
                     message: "Invalid UTF-8".to_string(),
                     row: None,
+                    snippet: None,
                 })?;
                 Ok(self.cached_regex.as_ref().expect("regex precompiled").is_match(s))
             }
-            FilterCondition::Numeric(cond) => {
+            FilterCondition::Numeric(_) | FilterCondition::Range(_) => {
                 let x = self.parse_numeric(value)?;
-                let pass = match cond {
-                    NumericCondition::GreaterThan(t) => x > *t,
-                    NumericCondition::LessThan(t) => x < *t,
-                    NumericCondition::Equal(t) => Self::approx_eq(x, *t),
-                    NumericCondition::NotEqual(t) => !Self::approx_eq(x, *t),
-                };
-                Ok(pass)
+                Ok(self.number_passes(x).expect("numeric condition"))
             }
             FilterCondition::OneOf(_) => {
                 let set = self.one_of_set.as_ref().expect("one_of_set prebuilt");
                 Ok(set.contains(value))
             }
-            FilterCondition::Range(r) => {
-                let x = self.parse_numeric(value)?;
-                let lower_ok = if r.inclusive { x >= r.min } else { x > r.min };
-                let upper_ok = if r.inclusive { x <= r.max } else { x < r.max };
-                Ok(lower_ok && upper_ok)
-            }
             FilterCondition::Empty => Ok(self.is_empty_token(value)),
             FilterCondition::NotEmpty => Ok(!self.is_empty_token(value)),
+            FilterCondition::Date { op, format, .. } => {
+                let s = str::from_utf8(value).map_err(|_| ExtractorError::InvalidDataFormat {
+                    column: self.column.clone(),
+                    message: "Invalid UTF-8".to_string(),
+                    row: None,
+                    snippet: None,
+                })?;
+                let s = s.trim();
+                let x = parse_datetime(s, format).ok_or_else(|| ExtractorError::InvalidDataFormat {
+                    column: self.column.clone(),
+                    message: format!("Invalid date '{s}' for format '{format}'"),
+                    row: None,
+                    snippet: None,
+                })?;
+                let (t, end) = self.date_bounds.expect("date bounds preparsed");
+                let pass = match op {
+                    DateOp::Before => x < t,
+                    DateOp::After => x > t,
+                    DateOp::OnOrBefore => x <= t,
+                    DateOp::OnOrAfter => x >= t,
+                    DateOp::On => x == t,
+                    DateOp::Between { .. } => x >= t && x <= end.expect("range end preparsed"),
+                };
+                Ok(pass)
+            }
+            FilterCondition::Length { unit, cond } => {
+                let len = measure_len(value, *unit);
+                let pass = match cond {
+                    LengthCondition::Equal(n) => len == *n,
+                    LengthCondition::NotEqual(n) => len != *n,
+                    LengthCondition::GreaterThan(n) => len > *n,
+                    LengthCondition::LessThan(n) => len < *n,
+                    LengthCondition::Between(min, max) => len >= *min && len <= *max,
+                    LengthCondition::EqualToColumn(other) => {
+                        len == measure_len(self.other_value(other, row, headers)?, *unit)
+                    }
+                    LengthCondition::NotEqualToColumn(other) => {
+                        len != measure_len(self.other_value(other, row, headers)?, *unit)
+                    }
+                };
+                Ok(pass)
+            }
+            FilterCondition::Notation(notation) => {
+                let s = match str::from_utf8(trim_ascii(value)) {
+                    Ok(s) => s,
+                    Err(_) => return Ok(false),
+                };
+                if !s.parse::<f64>().is_ok_and(f64::is_finite) {
+                    return Ok(false);
+                }
+                let has_exponent = s.bytes().any(|b| b == b'e' || b == b'E');
+                Ok(match notation {
+                    NumberNotation::Scientific => has_exponent,
+                    NumberNotation::Plain => !has_exponent,
+                })
+            }
+            FilterCondition::ChromEquals(target) => {
+                Ok(str::from_utf8(value).is_ok_and(|s| chrom_eq(s, target)))
+            }
+            FilterCondition::Consequence(consequences) => Ok(str::from_utf8(value)
+                .ok()
+                .and_then(Consequence::infer)
+                .is_some_and(|consequence| consequences.contains(&consequence))),
+            FilterCondition::AnyElementMatches { separator, .. } => {
+                let element_filter = self.element_filter.as_ref().expect("element filter prebuilt");
+                for element in split_elements(value, separator.as_bytes()) {
+                    if element_filter.evaluate(element, row, headers)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            FilterCondition::AllElementsMatch { separator, .. } => {
+                let element_filter = self.element_filter.as_ref().expect("element filter prebuilt");
+                for element in split_elements(value, separator.as_bytes()) {
+                    if !element_filter.evaluate(element, row, headers)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            FilterCondition::Custom(_) => {
+                let Predicate(predicate) = self.predicate.as_ref().expect("predicate resolved");
+                Ok(predicate(value))
+            }
         }
     }
+}
+
+/// Keeps variants whose SnpEff putative impact is at least a given level,
+/// e.g. `HIGH` and `MODERATE` for "at least MODERATE"
+#[derive(Debug)]
+pub struct ImpactFilter {
+    column: String,
+    at_least: Impact,
+    /// Whether `column` holds effect terms or HGVS changes rather than impacts
+    from_effect: bool,
+    col_idx: OnceLock<usize>,
+}
+
+impl ImpactFilter {
+    /// Filter on a column of impact levels such as `snpeff.ann.putative_impact`
+    pub fn new(column: String, at_least: Impact) -> Self {
+        Self { column, at_least, from_effect: false, col_idx: OnceLock::new() }
+    }
+
+    /// Filter on a column of effect terms (`missense_variant`) or protein
+    /// HGVS changes, using the impact SnpEff gives their consequence.
+    /// Unrecognised effects count as `MODIFIER`.
+    pub fn from_effect(column: String, at_least: Impact) -> Self {
+        Self { from_effect: true, ..Self::new(column, at_least) }
+    }
+
+    fn impact(&self, value: &str) -> Option<Impact> {
+        if !self.from_effect {
+            return Impact::parse(value);
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(Consequence::infer(value).map_or(Impact::Modifier, Impact::from))
+    }
+}
+
+impl Filter for ImpactFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let idx = match self.col_idx.get() {
+            Some(idx) => *idx,
+            None => {
+                let idx = *headers
+                    .get(&self.column)
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(&self.column, headers))?;
+                *self.col_idx.get_or_init(|| idx)
+            }
+        };
+        let value = row.get(idx).ok_or_else(|| ExtractorError::InvalidDataFormat {
+            column: self.column.clone(),
+            message: format!("Row has no field at index {idx}"),
+            row: None,
+            snippet: None,
+        })?;
+        Ok(str::from_utf8(value)
+            .ok()
+            .and_then(|value| self.impact(value))
+            .is_some_and(|impact| impact >= self.at_least))
+    }
 
     fn column_name(&self) -> &str {
         &self.column
     }
 
     fn description(&self) -> String {
-        self.condition.description(&self.column)
+        format!("{} impact at least {:?}", self.column, self.at_least)
     }
 }
 
@@ -286,14 +1089,35 @@ fn trim_ascii(bytes: &[u8]) -> &[u8] {
     &bytes[start..end]
 }
 
+/// Trimmed elements of a multi-value field
+fn split_elements<'a>(value: &'a [u8], separator: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let mut rest = Some(value);
+    std::iter::from_fn(move || {
+        let current = rest?;
+        let (element, next) = match memchr::memmem::find(current, separator) {
+            Some(at) => (&current[..at], Some(&current[at + separator.len()..])),
+            None => (current, None),
+        };
+        rest = next;
+        Some(trim_ascii(element))
+    })
+}
+
+/// Length of a field in bytes or UTF-8 characters, without decoding or allocating
 #[inline]
-fn ascii_to_lower_lossy(bytes: &[u8]) -> String {
-    // Avoid allocation per row by limiting to ASCII fold; OK for typical CSVs.
-    let mut s = String::with_capacity(bytes.len());
-    for &b in bytes {
-        s.push((b as char).to_ascii_lowercase());
+fn measure_len(bytes: &[u8], unit: LengthUnit) -> usize {
+    match unit {
+        LengthUnit::Bytes => bytes.len(),
+        // Count every byte that is not a UTF-8 continuation byte (0b10xx_xxxx)
+        LengthUnit::Chars => bytes.iter().filter(|&&b| (b & 0xC0) != 0x80).count(),
     }
-    s
+}
+
+/// Parse a date or date-time with a chrono format; date-only values become midnight
+fn parse_datetime(s: &str, format: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, format)
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(s, format).ok()?.and_hms_opt(0, 0, 0))
 }
 
 #[cfg(test)]
@@ -364,6 +1188,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_inclusive_and_absolute_numeric_filters() -> Result<()> {
+        let h = headers();
+        let check = |cond: NumericCondition, value: &str| -> Result<bool> {
+            ColumnFilter::new("value".to_string(), FilterCondition::Numeric(cond))?
+                .apply(&row("x", value), &h)
+        };
+
+        assert!(check(NumericCondition::GreaterOrEqual(0.01), "0.01")?);
+        assert!(!check(NumericCondition::GreaterOrEqual(0.01), "0.0099")?);
+        assert!(check(NumericCondition::LessOrEqual(5e-8), "5e-8")?);
+        assert!(!check(NumericCondition::LessOrEqual(5e-8), "5.1e-8")?);
+        assert!(check(NumericCondition::Between(1.0, 2.0), "2")?);
+        assert!(!check(NumericCondition::Between(1.0, 2.0), "2.5")?);
+        assert!(check(NumericCondition::AbsGreaterOrEqual(1.0), "-1.0")?);
+        assert!(!check(NumericCondition::AbsGreaterThan(1.0), "-1.0")?);
+        assert!(check(NumericCondition::AbsLessThan(1.0), "-0.5")?);
+        assert!(check(NumericCondition::IsFinite, "3.2")?);
+        assert!(!check(NumericCondition::IsFinite, "inf")?);
+        assert!(!check(NumericCondition::IsFinite, "NaN")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_formats() -> Result<()> {
+        let (english, european) = (NumberFormat::english(), NumberFormat::european().with_percent());
+        assert_eq!(english.parse("1,234.56"), Some(1234.56));
+        assert_eq!(english.parse("-12,345"), Some(-12345.0));
+        assert_eq!(european.parse("1.234,56"), Some(1234.56));
+        assert_eq!(european.parse("12,5 %"), Some(0.125));
+        assert_eq!(english.parse("12%"), None);
+        assert_eq!(english.parse("1,23.4"), None);
+        assert_eq!(european.parse("1.2345"), None);
+
+        let f = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Numeric(NumericCondition::GreaterThan(1000.0)),
+        )?
+        .with_number_format(NumberFormat::european());
+        let h = headers();
+        assert!(f.apply(&row("x", "1.234,5"), &h)?);
+        assert!(!f.apply(&row("x", "999,9"), &h)?);
+        assert!(f.apply(&row("x", "1,234.5"), &h).is_err());
+        assert_eq!(f.test_number(1234.5), None);
+        Ok(())
+    }
+
     #[test]
     fn test_range_filter_inclusive() -> Result<()> {
         let f = ColumnFilter::new(
@@ -413,6 +1284,229 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_date_filters() -> Result<()> {
+        let h = headers();
+        let date = |op: DateOp, value: &str| {
+            ColumnFilter::new(
+                "value".to_string(),
+                FilterCondition::Date {
+                    op,
+                    value: value.to_string(),
+                    format: "%Y-%m-%d".to_string(),
+                },
+            )
+        };
+
+        let before = date(DateOp::Before, "2020-01-01")?;
+        assert!(before.apply(&row("x", "2019-12-31"), &h)?);
+        assert!(!before.apply(&row("x", "2020-01-01"), &h)?);
+
+        let on_or_after = date(DateOp::OnOrAfter, "2020-01-01")?;
+        assert!(on_or_after.apply(&row("x", "2020-01-01"), &h)?);
+
+        let between = date(DateOp::Between { end: "2021-06-30".to_string() }, "2021-01-01")?;
+        assert!(between.apply(&row("x", "2021-03-15"), &h)?);
+        assert!(!between.apply(&row("x", "2021-07-01"), &h)?);
+        assert!(between.apply(&row("x", "not a date"), &h).is_err());
+
+        let timestamps = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Date {
+                op: DateOp::After,
+                value: "2022-05-01 12:00:00".to_string(),
+                format: "%Y-%m-%d %H:%M:%S".to_string(),
+            },
+        )?;
+        assert!(timestamps.apply(&row("x", "2022-05-01 12:00:01"), &h)?);
+        assert!(!timestamps.apply(&row("x", "2022-05-01 11:59:59"), &h)?);
+
+        assert!(date(DateOp::On, "01/02/2020").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_filters() -> Result<()> {
+        let h = headers();
+        let length = |unit: LengthUnit, cond: LengthCondition| {
+            ColumnFilter::new("name".to_string(), FilterCondition::Length { unit, cond })
+        };
+
+        let long_id = length(LengthUnit::Bytes, LengthCondition::GreaterThan(3))?;
+        assert!(long_id.apply(&row("rs123", "A"), &h)?);
+        assert!(!long_id.apply(&row("rs1", "A"), &h)?);
+
+        let chars = length(LengthUnit::Chars, LengthCondition::Equal(2))?;
+        assert!(chars.apply(&row("αβ", "A"), &h)?);
+        assert!(!length(LengthUnit::Bytes, LengthCondition::Equal(2))?.apply(&row("αβ", "A"), &h)?);
+
+        let indel = length(
+            LengthUnit::Bytes,
+            LengthCondition::NotEqualToColumn("value".to_string()),
+        )?;
+        assert!(indel.apply(&row("A", "AT"), &h)?);
+        assert!(!indel.apply(&row("A", "G"), &h)?);
+
+        let missing = length(
+            LengthUnit::Bytes,
+            LengthCondition::EqualToColumn("alt".to_string()),
+        )?;
+        assert!(missing.apply(&row("A", "G"), &h).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notation_filters() -> Result<()> {
+        let h = headers();
+        let sci = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Notation(NumberNotation::Scientific),
+        )?;
+        let plain = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Notation(NumberNotation::Plain),
+        )?;
+
+        assert!(sci.apply(&row("x", "5e-8"), &h)?);
+        assert!(sci.apply(&row("x", " 1.2E+03 "), &h)?);
+        assert!(!sci.apply(&row("x", "0.00000005"), &h)?);
+        assert!(plain.apply(&row("x", "0.00000005"), &h)?);
+        assert!(plain.apply(&row("x", "-12"), &h)?);
+        assert!(!plain.apply(&row("x", "5e-8"), &h)?);
+        assert!(!plain.apply(&row("x", "NA"), &h)?);
+        assert!(!sci.apply(&row("x", "inf"), &h)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chrom_equals_filter() -> Result<()> {
+        let h = headers();
+        let chr_x = ColumnFilter::new("name".to_string(), FilterCondition::ChromEquals("chrX".into()))?;
+        assert!(chr_x.apply(&row("X", "1"), &h)?);
+        assert!(chr_x.apply(&row("chrx", "1"), &h)?);
+        assert!(!chr_x.apply(&row("chrX_random", "1"), &h)?);
+        assert!(!chr_x.apply(&row("Y", "1"), &h)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_impact_filter() -> Result<()> {
+        let h = headers();
+        let moderate = ImpactFilter::new("name".to_string(), Impact::Moderate);
+        assert!(moderate.apply(&row("HIGH", "1"), &h)?);
+        assert!(moderate.apply(&row("moderate", "1"), &h)?);
+        assert!(moderate.apply(&row("MODIFIER&HIGH", "1"), &h)?);
+        assert!(!moderate.apply(&row("LOW", "1"), &h)?);
+        assert!(!moderate.apply(&row("", "1"), &h)?);
+
+        let high = ImpactFilter::from_effect("name".to_string(), Impact::High);
+        assert!(high.apply(&row("stop_gained", "1"), &h)?);
+        assert!(high.apply(&row("p.Arg97ProfsTer23", "1"), &h)?);
+        assert!(!high.apply(&row("missense_variant", "1"), &h)?);
+        let any = ImpactFilter::from_effect("name".to_string(), Impact::Modifier);
+        assert!(any.apply(&row("intron_variant", "1"), &h)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_element_conditions() -> Result<()> {
+        let h = headers();
+        let any = ColumnFilter::new(
+            "name".to_string(),
+            FilterCondition::AnyElementMatches {
+                separator: ";".to_string(),
+                condition: Box::new(FilterCondition::Equals("Pathogenic".to_string())),
+            },
+        )?;
+        assert!(any.apply(&row("Benign; Pathogenic", "1"), &h)?);
+        assert!(!any.apply(&row("Likely_pathogenic;Benign", "1"), &h)?);
+
+        let all = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::AllElementsMatch {
+                separator: "|".to_string(),
+                condition: Box::new(FilterCondition::Numeric(NumericCondition::LessThan(0.05))),
+            },
+        )?;
+        assert!(all.apply(&row("x", "0.01|0.02"), &h)?);
+        assert!(!all.apply(&row("x", "0.01|0.2"), &h)?);
+        assert!(all.apply(&row("x", "0.01|abc"), &h).is_err());
+
+        let bad = FilterCondition::AnyElementMatches {
+            separator: String::new(),
+            condition: Box::new(FilterCondition::Empty),
+        };
+        assert!(ColumnFilter::new("name".to_string(), bad).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_consequence_filter() -> Result<()> {
+        let h = headers();
+        let altering = ColumnFilter::new(
+            "name".to_string(),
+            FilterCondition::Consequence(Consequence::PROTEIN_ALTERING.to_vec()),
+        )?;
+        assert!(altering.apply(&row("p.Arg117His", "1"), &h)?);
+        assert!(altering.apply(&row("stop_gained", "1"), &h)?);
+        assert!(!altering.apply(&row("p.Arg117=", "1"), &h)?);
+        assert!(!altering.apply(&row("NA", "1"), &h)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_predicates() -> Result<()> {
+        let h = headers();
+        let even = ColumnFilter::from_fn("value".to_string(), |field| field.last().is_some_and(|b| b % 2 == 0));
+        assert!(even.apply(&row("x", "12"), &h)?);
+        assert!(!even.apply(&row("x", "7"), &h)?);
+
+        register_predicate("is_rsid", |field: &[u8]| field.starts_with(b"rs"));
+        let rsid = ColumnFilter::new("name".to_string(), FilterCondition::Custom("is_rsid".to_string()))?;
+        assert!(rsid.apply(&row("rs429358", "1"), &h)?);
+        assert_eq!(rsid.description(), "is_rsid(name)");
+        let any = ColumnFilter::new(
+            "name".to_string(),
+            FilterCondition::AnyElementMatches {
+                separator: ";".to_string(),
+                condition: Box::new(FilterCondition::Custom("is_rsid".to_string())),
+            },
+        )?;
+        assert!(any.apply(&row("chr1:100;rs1", "1"), &h)?);
+
+        assert!(unregister_predicate("is_rsid"));
+        // Filters keep the predicate they were built with
+        assert!(rsid.apply(&row("rs1", "1"), &h)?);
+        assert!(ColumnFilter::new("name".to_string(), FilterCondition::Custom("is_rsid".to_string())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_counters() -> Result<()> {
+        let f = ColumnFilter::new(
+            "value".to_string(),
+            FilterCondition::Numeric(NumericCondition::GreaterThan(100.0)),
+        )?;
+        let counters = FilterCounters::default();
+        let h = headers();
+
+        assert!(counters.apply(&f, &row("x", "150"), &h)?);
+        assert!(!counters.apply(&f, &row("x", "50"), &h)?);
+        assert!(!counters.apply(&f, &row("x", "10"), &h)?);
+        assert!(counters.apply(&f, &row("x", "NA"), &h).is_err());
+
+        let stats = counters.snapshot(0, &f);
+        assert_eq!(stats.description, "value > 100");
+        assert_eq!(stats.evaluated, 4);
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.rejection_rate(), 0.5);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(0, &f).evaluated, 0);
+        Ok(())
+    }
+
     #[test]
     fn test_descriptions() -> Result<()> {
         let f = ColumnFilter::new(
@@ -420,6 +1514,12 @@ mod tests {
             FilterCondition::Numeric(NumericCondition::LessThan(3.14)),
         )?;
         assert_eq!(f.description(), "value < 3.14");
+
+        let f = ColumnFilter::new(
+            "log2FC".to_string(),
+            FilterCondition::Numeric(NumericCondition::AbsGreaterOrEqual(1.0)),
+        )?;
+        assert_eq!(f.description(), "|log2FC| >= 1");
         Ok(())
     }
 }
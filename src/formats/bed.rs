@@ -0,0 +1,150 @@
+//! BED records and reader
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use crate::error::ExtractorError;
+use crate::Result;
+
+/// One BED interval: 0-based start, exclusive end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedRecord {
+    /// Chromosome name
+    pub chrom: String,
+    /// First base, 0-based
+    pub start: u64,
+    /// One past the last base
+    pub end: u64,
+    /// Name column, if present
+    pub name: Option<String>,
+    /// Columns after the name (score, strand, ...)
+    pub fields: Vec<String>,
+}
+
+impl BedRecord {
+    /// Create a three-column record
+    pub fn new(chrom: String, start: u64, end: u64) -> Self {
+        Self { chrom, start, end, name: None, fields: Vec::new() }
+    }
+
+    /// Number of bases covered
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Whether the interval covers no bases, as for an insertion point
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Display for BedRecord {
+    /// The record as a tab-separated BED line, without a line ending
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.chrom, self.start, self.end)?;
+        if let Some(name) = &self.name {
+            write!(f, "\t{name}")?;
+        }
+        for field in &self.fields {
+            write!(f, "\t{field}")?;
+        }
+        Ok(())
+    }
+}
+
+/// BED file parser. Blank lines, `#` comments and `track`/`browser` lines
+/// are skipped. Columns are tab-separated, or whitespace-separated in files
+/// without tabs.
+pub struct BedReader<R: BufRead> {
+    reader: R,
+    line: String,
+    /// Lines read so far, for error messages
+    line_number: u64,
+}
+
+impl<R: BufRead> BedReader<R> {
+    /// Create a new BED reader
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: String::new(), line_number: 0 }
+    }
+
+    fn invalid(&self, column: &str, message: String) -> ExtractorError {
+        ExtractorError::InvalidDataFormat {
+            column: column.to_string(),
+            message,
+            row: Some(self.line_number),
+            snippet: Some(self.line.trim_end().chars().take(80).collect()),
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<BedRecord>> {
+        let line = loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            let line = self.line.trim_end_matches(['\r', '\n']);
+            let skip = line.trim().is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser");
+            if !skip {
+                break line;
+            }
+        };
+        let fields: Vec<&str> = if line.contains('\t') { line.split('\t').collect() } else { line.split_whitespace().collect() };
+        let [chrom, start, end, rest @ ..] = &fields[..] else {
+            return Err(self.invalid("chrom", format!("expected at least 3 columns, found {}", fields.len())));
+        };
+        let coordinate = |column: &str, value: &str| {
+            value.trim().parse::<u64>().map_err(|_| self.invalid(column, format!("{value:?} is not a coordinate")))
+        };
+        let (start, end) = (coordinate("start", start)?, coordinate("end", end)?);
+        if end < start {
+            return Err(self.invalid("end", format!("end {end} is before start {start}")));
+        }
+        let mut record = BedRecord::new(chrom.to_string(), start, end);
+        if let [name, rest @ ..] = rest {
+            record.name = Some(name.to_string());
+            record.fields = rest.iter().map(|field| field.to_string()).collect();
+        }
+        Ok(Some(record))
+    }
+}
+
+impl BedReader<BufReader<File>> {
+    /// Create from file path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Ok(BedReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> Iterator for BedReader<R> {
+    type Item = Result<BedRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_bed_parsing() {
+        let data = "track name=peaks\n# comment\nchr1\t10\t20\tpeak1\t500\t+\n\nchr2 5 8\n";
+        let records: Vec<BedRecord> = BedReader::new(Cursor::new(data)).collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].to_string(), "chr1\t10\t20\tpeak1\t500\t+");
+        assert_eq!((records[1].chrom.as_str(), records[1].len()), ("chr2", 3));
+
+        let bad = BedReader::new(Cursor::new("chr1\t10\t20\nchr1\t30\t25\n")).nth(1).unwrap();
+        assert!(matches!(bad, Err(ExtractorError::InvalidDataFormat { row: Some(2), .. })));
+    }
+}
@@ -0,0 +1,203 @@
+//! Writes the reference sequence around each matched row to FASTA, turning
+//! a filtered table of variants or regions into sequences for primer
+//! design, motif scans or alignment.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use csv::ByteRecord;
+use super::fasta::{FastaReader, FastaRecord};
+use super::seq_filter::finish;
+use super::BioRecord;
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::output::{ExistingOutput, OutputFile, RowSink, WriteMode};
+use crate::Result;
+
+/// Row sink that writes one FASTA record per matched row, holding the
+/// reference sequence of the row's region widened by `flank` bases on each
+/// side. Use it with `BioFilter::process_into`.
+///
+/// Rows whose chromosome isn't in the reference or whose coordinates don't
+/// parse are skipped and counted.
+pub struct FastaSink {
+    reference: FastaReader<BufReader<File>>,
+    output_path: PathBuf,
+    write_mode: WriteMode,
+    chrom_column: String,
+    start_column: String,
+    end_column: Option<String>,
+    id_column: Option<String>,
+    flank: u64,
+    one_based: bool,
+    /// Column indices of chrom, start, end and ID, set by `begin`
+    columns: Option<[Option<usize>; 4]>,
+    output: Option<(OutputFile, BufWriter<File>)>,
+    written: u64,
+    skipped: u64,
+}
+
+impl FastaSink {
+    /// Write sequences from the indexed `reference` to `output_path`, one
+    /// base per row at `start_column` unless an end column is set.
+    /// Coordinates are 0-based and end-exclusive (as in BED).
+    pub fn new(reference: &Path, output_path: PathBuf, chrom_column: String, start_column: String) -> Result<Self> {
+        Ok(Self {
+            reference: FastaReader::indexed(reference)?,
+            output_path,
+            write_mode: WriteMode::default(),
+            chrom_column,
+            start_column,
+            end_column: None,
+            id_column: None,
+            flank: 0,
+            one_based: false,
+            columns: None,
+            output: None,
+            written: 0,
+            skipped: 0,
+        })
+    }
+
+    /// Read the region's end from this column
+    pub fn end_column(mut self, column: String) -> Self {
+        self.end_column = Some(column);
+        self
+    }
+
+    /// Name records by this column instead of by their region
+    pub fn id_column(mut self, column: String) -> Self {
+        self.id_column = Some(column);
+        self
+    }
+
+    /// Bases to add on each side of the region, clamped to the sequence (default: 0)
+    pub fn flank(mut self, bases: u64) -> Self {
+        self.flank = bases;
+        self
+    }
+
+    /// Read coordinates as 1-based with an inclusive end, as in VCF and
+    /// GWAS summary statistics
+    pub fn one_based(mut self, one_based: bool) -> Self {
+        self.one_based = one_based;
+        self
+    }
+
+    /// How the output file is written (default: `WriteMode::Direct`)
+    pub fn with_write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Number of sequences written
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Number of rows skipped for an unknown chromosome or bad coordinates
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Region of `record` as 0-based, end-exclusive bounds before flanking
+    fn region<'r>(&self, record: &'r ByteRecord) -> Option<(&'r str, u64, u64)> {
+        let [chrom, start, end, _] = self.columns.expect("begin() resolves the columns");
+        let field = |idx: Option<usize>| {
+            idx.and_then(|idx| record.get(idx)).and_then(|field| std::str::from_utf8(field).ok()).map(str::trim)
+        };
+        let chrom = field(chrom)?;
+        let start: u64 = field(start)?.parse().ok()?;
+        let start = if self.one_based { start.saturating_sub(1) } else { start };
+        let end: u64 = match end {
+            // A 1-based inclusive end is the same number as a 0-based exclusive one
+            Some(_) => field(end)?.parse().ok()?,
+            None => start + 1,
+        };
+        Some((chrom, start, end))
+    }
+}
+
+impl RowSink for FastaSink {
+    fn begin(&mut self, header: &ByteRecord) -> Result<()> {
+        let names: Vec<String> = header_names(header).collect();
+        let find = |column: &String| {
+            names.iter().position(|name| name == column).ok_or_else(|| ExtractorError::column_not_found(column, &names))
+        };
+        self.columns = Some([
+            Some(find(&self.chrom_column)?),
+            Some(find(&self.start_column)?),
+            self.end_column.as_ref().map(find).transpose()?,
+            self.id_column.as_ref().map(find).transpose()?,
+        ]);
+        let (output, file) = OutputFile::create(&self.output_path, self.write_mode, ExistingOutput::Overwrite)?;
+        self.output = Some((output, BufWriter::new(file)));
+        Ok(())
+    }
+
+    fn write_row(&mut self, record: &ByteRecord) -> Result<()> {
+        let Some((chrom, start, end)) = self.region(record) else {
+            self.skipped += 1;
+            return Ok(());
+        };
+        let Some(entry) = self.reference.index().and_then(|index| index.get(chrom)) else {
+            self.skipped += 1;
+            return Ok(());
+        };
+        let (name, length) = (entry.name.clone(), entry.length);
+        let (from, to) = (start.saturating_sub(self.flank), end.saturating_add(self.flank).min(length));
+        let sequence = self.reference.fetch(&name, from, to)?;
+
+        let region = format!("{chrom}:{}-{to}", from + 1);
+        let id = self.columns.and_then(|[.., id]| id).and_then(|idx| record.get(idx));
+        let fasta = match id {
+            Some(id) => FastaRecord::new(String::from_utf8_lossy(id).trim().to_string(), sequence).with_description(region),
+            None => FastaRecord::new(region, sequence),
+        };
+        let (output, writer) = self.output.as_mut().expect("begin() opens the output");
+        writer.write_all(fasta.to_string().as_bytes()).map_err(|e| ExtractorError::io_error(e, output.path()))?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some((output, writer)) = self.output.take() else {
+            return Ok(());
+        };
+        finish(&output, writer)?;
+        output.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::BioFilter;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+    use crate::Config;
+    use tempfile::{NamedTempFile, TempDir};
+
+    #[test]
+    fn test_fasta_sink() -> Result<()> {
+        let dir = TempDir::new()?;
+        let reference = dir.path().join("ref.fa");
+        std::fs::write(&reference, ">chr1\nAACCGGTTAC\nGTACGT\n>chr2\nTTTT\n")?;
+        let input = NamedTempFile::new()?;
+        std::fs::write(input.path(), "rsid,chrom,pos,p\nrs1,1,5,0.001\nrs2,chr2,2,0.001\nrs3,chr9,1,0.001\nrs4,1,1,0.5\n")?;
+
+        let output = dir.path().join("flanks.fa");
+        let mut sink = FastaSink::new(&reference, output.clone(), "chrom".into(), "pos".into())?
+            .one_based(true)
+            .flank(2)
+            .id_column("rsid".into());
+        let mut filter = BioFilter::new(input.path().into(), "unused.csv".into(), Config::default(), None)?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(0.01)),
+        )?));
+        let stats = filter.process_into(&mut sink)?;
+        assert_eq!((stats.rows_matched, sink.written(), sink.skipped()), (3, 2, 1));
+        assert_eq!(std::fs::read_to_string(&output)?, ">rs1 1:3-7\nCCGGT\n>rs2 chr2:1-4\nTTTT\n");
+        Ok(())
+    }
+}
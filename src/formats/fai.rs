@@ -0,0 +1,298 @@
+//! FASTA index in the samtools faidx `.fai` format, for random access to
+//! sequence slices, and a row transform that appends the reference
+//! sequence of each row's region.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use csv::ByteRecord;
+use super::fasta::FastaReader;
+use crate::chrom::chrom_eq;
+use crate::error::{ExtractorError, IndexErrorKind};
+use crate::transform::RowTransform;
+use crate::Result;
+
+/// One sequence of a `.fai` index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiEntry {
+    /// Sequence name, the header up to the first whitespace
+    pub name: String,
+    /// Number of bases
+    pub length: u64,
+    /// Byte offset of the first base
+    pub offset: u64,
+    /// Bases per line
+    pub line_bases: u64,
+    /// Bytes per line, including the line ending
+    pub line_width: u64,
+}
+
+impl FaiEntry {
+    /// Byte offset of 0-based base `pos`
+    fn position(&self, pos: u64) -> u64 {
+        self.offset + pos / self.line_bases * self.line_width + pos % self.line_bases
+    }
+
+    /// Byte range holding bases `start..end` (0-based, end exclusive),
+    /// clamped to the sequence; `None` when that leaves no bases
+    pub(crate) fn byte_range(&self, start: u64, end: u64) -> Option<(u64, u64)> {
+        let end = end.min(self.length);
+        (start < end).then(|| (self.position(start), self.position(end - 1) + 1))
+    }
+}
+
+/// A samtools-compatible FASTA index
+#[derive(Debug, Clone, Default)]
+pub struct FaiIndex {
+    entries: Vec<FaiEntry>,
+    by_name: HashMap<String, usize>,
+}
+
+/// Where samtools looks for the index of `fasta`: `<fasta>.fai`
+pub fn fai_path(fasta: &Path) -> PathBuf {
+    let mut path = fasta.as_os_str().to_owned();
+    path.push(".fai");
+    PathBuf::from(path)
+}
+
+impl FaiIndex {
+    /// Index a FASTA file. Every sequence line but the last of a record
+    /// must have the same length, as samtools requires.
+    pub fn build(fasta: &Path) -> Result<Self> {
+        let file = File::open(fasta).map_err(|e| ExtractorError::io_error(e, fasta))?;
+        let invalid = |line: u64, message: &str| {
+            ExtractorError::index_error(
+                IndexErrorKind::BuildError(format!("line {line}: {message}")),
+                Some(fasta),
+            )
+        };
+
+        let mut reader = BufReader::new(file);
+        let mut index = FaiIndex::default();
+        let mut current: Option<FaiEntry> = None;
+        // Set after a line shorter than `line_bases`, which must end the record
+        let mut ended = false;
+        let (mut offset, mut line_number) = (0u64, 0u64);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let width = reader.read_until(b'\n', &mut line).map_err(|e| ExtractorError::io_error(e, fasta))? as u64;
+            if width == 0 {
+                break;
+            }
+            offset += width;
+            line_number += 1;
+            if let Some(header) = line.strip_prefix(b">") {
+                if let Some(entry) = current.take() {
+                    index.push(entry).map_err(|message| invalid(line_number, &message))?;
+                }
+                let header = String::from_utf8_lossy(header);
+                let name = header.split_whitespace().next().unwrap_or_default().to_string();
+                current = Some(FaiEntry { name, length: 0, offset, line_bases: 0, line_width: 0 });
+                ended = false;
+                continue;
+            }
+
+            let bases = line.iter().filter(|&&b| b != b'\n' && b != b'\r').count() as u64;
+            let Some(entry) = current.as_mut() else {
+                if bases == 0 {
+                    continue;
+                }
+                return Err(invalid(line_number, "sequence before the first header"));
+            };
+            if bases == 0 {
+                ended = true;
+                continue;
+            }
+            if ended {
+                return Err(invalid(line_number, &format!("uneven line lengths in {}", entry.name)));
+            }
+            if entry.line_bases == 0 {
+                entry.line_bases = bases;
+                entry.line_width = width;
+            } else if bases > entry.line_bases || (bases == entry.line_bases && width != entry.line_width) {
+                return Err(invalid(line_number, &format!("uneven line lengths in {}", entry.name)));
+            }
+            ended = bases < entry.line_bases;
+            entry.length += bases;
+        }
+        if let Some(entry) = current {
+            index.push(entry).map_err(|message| invalid(line_number, &message))?;
+        }
+        Ok(index)
+    }
+
+    fn push(&mut self, entry: FaiEntry) -> std::result::Result<(), String> {
+        if self.by_name.contains_key(&entry.name) {
+            return Err(format!("duplicate sequence name {}", entry.name));
+        }
+        self.by_name.insert(entry.name.clone(), self.entries.len());
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Read a `.fai` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let invalid = || ExtractorError::index_error(IndexErrorKind::InvalidFormat, Some(path));
+        let mut index = FaiIndex::default();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [name, length, offset, line_bases, line_width, ..] = fields[..] else {
+                return Err(invalid());
+            };
+            let number = |field: &str| field.parse::<u64>().map_err(|_| invalid());
+            let entry = FaiEntry {
+                name: name.to_string(),
+                length: number(length)?,
+                offset: number(offset)?,
+                line_bases: number(line_bases)?,
+                line_width: number(line_width)?,
+            };
+            index.push(entry).map_err(|_| invalid())?;
+        }
+        Ok(index)
+    }
+
+    /// Write the index as a `.fai` file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text: String = self
+            .entries
+            .iter()
+            .map(|e| format!("{}\t{}\t{}\t{}\t{}\n", e.name, e.length, e.offset, e.line_bases, e.line_width))
+            .collect();
+        std::fs::write(path, text).map_err(|e| ExtractorError::io_error(e, path))
+    }
+
+    /// Indexed sequences in file order
+    pub fn entries(&self) -> &[FaiEntry] {
+        &self.entries
+    }
+
+    /// Look up a sequence by name. A name that isn't in the index matches
+    /// the same chromosome written another way (`chr7` and `7`).
+    pub fn get(&self, name: &str) -> Option<&FaiEntry> {
+        match self.by_name.get(name) {
+            Some(&i) => Some(&self.entries[i]),
+            None => self.entries.iter().find(|entry| chrom_eq(&entry.name, name)),
+        }
+    }
+}
+
+/// Row transform that appends the reference sequence of each row's region,
+/// fetched from an indexed FASTA. Rows whose chromosome isn't in the FASTA
+/// or whose coordinates don't parse get an empty value.
+pub struct FastaSlice {
+    fasta: Mutex<FastaReader<BufReader<File>>>,
+    chrom_column: String,
+    start_column: String,
+    end_column: String,
+    one_based: bool,
+    column: String,
+}
+
+impl FastaSlice {
+    /// Fetch regions from `fasta`, read from the given columns as 0-based,
+    /// end-exclusive coordinates (as in BED)
+    pub fn new(fasta: &Path, chrom_column: String, start_column: String, end_column: String) -> Result<Self> {
+        Ok(Self {
+            fasta: Mutex::new(FastaReader::indexed(fasta)?),
+            chrom_column,
+            start_column,
+            end_column,
+            one_based: false,
+            column: "sequence".to_string(),
+        })
+    }
+
+    /// Read coordinates as 1-based with an inclusive end, as in VCF and
+    /// GWAS summary statistics
+    pub fn one_based(mut self, one_based: bool) -> Self {
+        self.one_based = one_based;
+        self
+    }
+
+    /// Name of the appended column (default: `sequence`)
+    pub fn column_name(mut self, name: String) -> Self {
+        self.column = name;
+        self
+    }
+
+    fn field<'r>(&self, row: &'r ByteRecord, column: &str, headers: &HashMap<String, usize>) -> Result<&'r str> {
+        let idx = headers.get(column).ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))?;
+        Ok(std::str::from_utf8(row.get(*idx).unwrap_or_default()).unwrap_or_default().trim())
+    }
+}
+
+impl RowTransform for FastaSlice {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let chrom = self.field(row, &self.chrom_column, headers)?;
+        let start = self.field(row, &self.start_column, headers)?.parse::<u64>().ok();
+        let end = self.field(row, &self.end_column, headers)?.parse::<u64>().ok();
+        let sequence = match (start, end) {
+            (Some(start), Some(end)) => {
+                let start = if self.one_based { start.saturating_sub(1) } else { start };
+                let mut fasta = self.fasta.lock().unwrap_or_else(|e| e.into_inner());
+                match fasta.index().and_then(|index| index.get(chrom)) {
+                    Some(entry) => {
+                        let name = entry.name.clone();
+                        fasta.fetch(&name, start, end)?
+                    }
+                    None => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        };
+        row.push_field(&sequence);
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("append {} from {}:{}-{}", self.column, self.chrom_column, self.start_column, self.end_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_fai_index_and_fetch() -> Result<()> {
+        let fasta = NamedTempFile::new()?;
+        std::fs::write(fasta.path(), ">chr1 test\nACGTA\nCCGGT\nTT\n>chr2\nGGGG\nAA\n")?;
+        let index = FaiIndex::build(fasta.path())?;
+        let fai = fai_path(fasta.path());
+        index.save(&fai)?;
+        assert_eq!(std::fs::read_to_string(&fai)?, "chr1\t12\t11\t5\t6\nchr2\t6\t32\t4\t5\n");
+        assert_eq!(FaiIndex::load(&fai)?.entries(), index.entries());
+
+        let mut reader = FastaReader::indexed(fasta.path())?;
+        assert_eq!(reader.fetch("chr1", 3, 8)?, b"TACCG");
+        assert_eq!(reader.fetch("1", 10, 100)?, b"TT");
+        assert_eq!(reader.fetch("chr2", 2, 6)?, b"GGAA");
+        assert!(reader.fetch("chr3", 0, 1).is_err());
+        std::fs::remove_file(&fai)?;
+
+        let slice = FastaSlice::new(fasta.path(), "chrom".into(), "start".into(), "end".into())?.one_based(true);
+        let headers: HashMap<String, usize> = [("chrom", 0), ("start", 1), ("end", 2)]
+            .into_iter()
+            .map(|(name, i)| (name.to_string(), i))
+            .collect();
+        let mut row = ByteRecord::from(vec!["2", "4", "5"]);
+        assert!(slice.apply(&mut row, &headers)?);
+        assert_eq!(row.get(3), Some(&b"GA"[..]));
+
+        std::fs::write(fasta.path(), ">chr1\nACG\nACGT\n")?;
+        assert!(FaiIndex::build(fasta.path()).is_err());
+        Ok(())
+    }
+}
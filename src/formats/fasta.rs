@@ -1,7 +1,11 @@
-use std::io::{BufRead, BufReader};
+//! FASTA records and reader
+
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::fs::File;
 use std::path::Path;
-use super::{BioRecord, BioFilter};
+use super::fai::{fai_path, FaiIndex};
+use super::{BioRecord, SequenceStats};
+use crate::error::ExtractorError;
 use crate::Result;
 
 /// Represents a FASTA record
@@ -34,6 +38,11 @@ impl FastaRecord {
         self
     }
 
+    /// Description following the ID on the header line
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
     /// Add metadata
     pub fn add_metadata(&mut self, key: String, value: String) {
         self.metadata.push((key, value));
@@ -69,7 +78,7 @@ impl BioRecord for FastaRecord {
         
         // Format sequence in lines of 60 characters
         for chunk in self.sequence.chunks(60) {
-            output.push_str(&String::from_utf8_losix(chunk).unwrap_or_default());
+            output.push_str(&String::from_utf8_lossy(chunk));
             output.push('\n');
         }
         
@@ -77,43 +86,16 @@ impl BioRecord for FastaRecord {
     }
 }
 
-impl BioFilter for FastaRecord {
-    fn gc_content(&self) -> f64 {
-        let mut gc_count = 0;
-        let total = self.sequence.len();
-        
-        for &base in &self.sequence {
-            match base.to_ascii_uppercase() {
-                b'G' | b'C' => gc_count += 1,
-                _ => {}
-            }
-        }
-        
-        if total > 0 {
-            gc_count as f64 / total as f64
-        } else {
-            0.0
-        }
-    }
-
-    fn sequence_length(&self) -> usize {
-        self.sequence.len()
-    }
-
-    fn min_quality_score(&self) -> Option<u8> {
-        None
-    }
-
-    fn contains_pattern(&self, pattern: &[u8]) -> bool {
-        self.sequence.windows(pattern.len())
-            .any(|window| window.eq_ignore_ascii_case(pattern))
-    }
-}
+impl SequenceStats for FastaRecord {}
 
 /// FASTA file parser
 pub struct FastaReader<R: BufRead> {
     reader: R,
     current_line: String,
+    /// Header line of the next record, read while finishing the previous one
+    next_header: Option<String>,
+    /// Index for `fetch`
+    index: Option<FaiIndex>,
 }
 
 impl<R: BufRead> FastaReader<R> {
@@ -122,46 +104,99 @@ impl<R: BufRead> FastaReader<R> {
         Self {
             reader,
             current_line: String::new(),
+            next_header: None,
+            index: None,
         }
     }
 
+    /// Use `index` for `fetch`
+    pub fn with_index(mut self, index: FaiIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// The index used by `fetch`, if any
+    pub fn index(&self) -> Option<&FaiIndex> {
+        self.index.as_ref()
+    }
+}
+
+impl<R: BufRead + Seek> FastaReader<R> {
+    /// Bases `start..end` (0-based, end exclusive) of sequence `id`, clamped
+    /// to its length. Needs an index; `id` may name the chromosome another
+    /// way (`chr7` for `7`). Moves the read position, so don't mix with
+    /// iterating over records.
+    pub fn fetch(&mut self, id: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let index = self
+            .index
+            .as_ref()
+            .ok_or_else(|| ExtractorError::Config("fetch needs a FASTA index".to_string()))?;
+        let entry = index.get(id).ok_or_else(|| ExtractorError::InvalidDataFormat {
+            column: "id".to_string(),
+            message: format!("{id} is not in the FASTA index"),
+            row: None,
+            snippet: None,
+        })?;
+        let Some((from, to)) = entry.byte_range(start, end) else {
+            return Ok(Vec::new());
+        };
+        self.next_header = None;
+        self.reader.seek(SeekFrom::Start(from))?;
+        let mut bases = Vec::with_capacity((to - from) as usize);
+        (&mut self.reader).take(to - from).read_to_end(&mut bases)?;
+        bases.retain(|&b| b != b'\n' && b != b'\r');
+        Ok(bases)
+    }
+}
+
+impl FastaReader<BufReader<File>> {
     /// Create from file path
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<FastaReader<BufReader<File>>> {
-        let file = File::open(path)?;
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
         Ok(FastaReader::new(BufReader::new(file)))
     }
+
+    /// Open `path` for `fetch`, with its `.fai` index if that is at least as
+    /// new as the FASTA, otherwise indexing it in memory
+    pub fn indexed<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let fai = fai_path(path);
+        let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+        let index = match (modified(&fai), modified(path)) {
+            (Some(indexed), Some(changed)) if indexed >= changed => FaiIndex::load(&fai)?,
+            _ => FaiIndex::build(path)?,
+        };
+        Ok(Self::from_path(path)?.with_index(index))
+    }
 }
 
 impl<R: BufRead> Iterator for FastaReader<R> {
     type Item = Result<FastaRecord>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut id = String::new();
-        let mut description = None;
         let mut sequence = Vec::new();
-        
+
         // Find next header line
-        loop {
+        let header = loop {
+            if let Some(header) = self.next_header.take() {
+                break header;
+            }
             self.current_line.clear();
             match self.reader.read_line(&mut self.current_line) {
                 Ok(0) => return None, // EOF
                 Ok(_) => {
-                    let line = self.current_line.trim();
-                    if line.starts_with('>') {
-                        // Parse header
-                        let header = &line[1..];
-                        if let Some(space_idx) = header.find(' ') {
-                            id = header[..space_idx].to_string();
-                            description = Some(header[space_idx + 1..].to_string());
-                        } else {
-                            id = header.to_string();
-                        }
-                        break;
+                    if let Some(header) = self.current_line.trim().strip_prefix('>') {
+                        break header.to_string();
                     }
                 },
                 Err(e) => return Some(Err(e.into())),
             }
-        }
+        };
+        let (id, description) = match header.split_once(' ') {
+            Some((id, description)) => (id.to_string(), Some(description.to_string())),
+            None => (header, None),
+        };
 
         // Read sequence lines until next header or EOF
         loop {
@@ -170,8 +205,9 @@ impl<R: BufRead> Iterator for FastaReader<R> {
                 Ok(0) => break, // EOF
                 Ok(_) => {
                     let line = self.current_line.trim();
-                    if line.starts_with('>') {
+                    if let Some(header) = line.strip_prefix('>') {
                         // Next record found
+                        self.next_header = Some(header.to_string());
                         break;
                     }
                     sequence.extend(line.bytes());
@@ -180,7 +216,11 @@ impl<R: BufRead> Iterator for FastaReader<R> {
             }
         }
 
-        Some(Ok(FastaRecord::new(id, sequence).with_description(description.unwrap_or_default())))
+        let record = FastaRecord::new(id, sequence);
+        Some(Ok(match description {
+            Some(description) => record.with_description(description),
+            None => record,
+        }))
     }
 }
 
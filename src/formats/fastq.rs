@@ -0,0 +1,356 @@
+//! FASTQ records and reader
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::fs::File;
+use std::path::Path;
+use super::{BioRecord, SequenceStats};
+use crate::error::ExtractorError;
+use crate::Result;
+
+/// Records read before settling on a quality encoding
+const DETECT_RECORDS: usize = 1000;
+
+/// Highest quality character of current Phred+33 data (`K`, Q42)
+const MAX_PHRED33: u8 = b'K';
+
+/// How quality scores are written as characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QualityEncoding {
+    /// Score + 33, as in Sanger and Illumina 1.8+ (default)
+    #[default]
+    Phred33,
+    /// Score + 64, as in Illumina 1.3 to 1.7
+    Phred64,
+}
+
+impl QualityEncoding {
+    /// Character value of quality score 0
+    pub fn offset(self) -> u8 {
+        match self {
+            QualityEncoding::Phred33 => 33,
+            QualityEncoding::Phred64 => 64,
+        }
+    }
+
+    /// Guess the encoding from quality strings. Characters below `@` only
+    /// occur in Phred+33 and ones above `K` only in Phred+64; data that
+    /// shows neither is taken as the far more common Phred+33.
+    pub fn detect<'a>(qualities: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let (mut min, mut max) = (u8::MAX, 0);
+        for &q in qualities.into_iter().flatten() {
+            min = min.min(q);
+            max = max.max(q);
+        }
+        if min >= Self::Phred64.offset() && max > MAX_PHRED33 {
+            QualityEncoding::Phred64
+        } else {
+            QualityEncoding::Phred33
+        }
+    }
+}
+
+impl fmt::Display for QualityEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Phred+{}", self.offset())
+    }
+}
+
+/// Represents a FASTQ record
+#[derive(Debug, Clone)]
+pub struct FastqRecord {
+    /// Sequence identifier
+    id: String,
+    /// Optional description
+    description: Option<String>,
+    /// Sequence data
+    sequence: Vec<u8>,
+    /// Quality characters, one per base
+    quality: Vec<u8>,
+    /// How `quality` encodes scores
+    encoding: QualityEncoding,
+    /// Additional metadata
+    metadata: Vec<(String, String)>,
+}
+
+impl FastqRecord {
+    /// Create a new FASTQ record with Phred+33 qualities
+    pub fn new(id: String, sequence: Vec<u8>, quality: Vec<u8>) -> Self {
+        Self {
+            id,
+            description: None,
+            sequence,
+            quality,
+            encoding: QualityEncoding::Phred33,
+            metadata: vec![("quality_encoding".to_string(), QualityEncoding::Phred33.to_string())],
+        }
+    }
+
+    /// Mark the qualities as written in `encoding`, without changing them
+    pub fn with_encoding(mut self, encoding: QualityEncoding) -> Self {
+        self.encoding = encoding;
+        for (key, value) in &mut self.metadata {
+            if key == "quality_encoding" {
+                *value = encoding.to_string();
+            }
+        }
+        self
+    }
+
+    /// How the quality characters encode scores, also in the
+    /// `quality_encoding` metadata
+    pub fn encoding(&self) -> QualityEncoding {
+        self.encoding
+    }
+
+    /// Rewrite the qualities in `encoding`. Scores too high for the target
+    /// are capped at `~`.
+    pub fn to_encoding(mut self, encoding: QualityEncoding) -> Self {
+        let (from, to) = (self.encoding.offset(), encoding.offset());
+        for q in &mut self.quality {
+            *q = q.saturating_sub(from).saturating_add(to).min(b'~');
+        }
+        self.with_encoding(encoding)
+    }
+
+    /// Add description
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Description following the ID on the header line
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+impl BioRecord for FastqRecord {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sequence(&self) -> Option<&[u8]> {
+        Some(&self.sequence)
+    }
+
+    fn quality(&self) -> Option<&[u8]> {
+        Some(&self.quality)
+    }
+
+    fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    fn to_string(&self) -> String {
+        let mut output = String::with_capacity(2 * self.sequence.len() + 100);
+        output.push('@');
+        output.push_str(&self.id);
+        if let Some(desc) = &self.description {
+            output.push(' ');
+            output.push_str(desc);
+        }
+        output.push('\n');
+        output.push_str(&String::from_utf8_lossy(&self.sequence));
+        output.push_str("\n+\n");
+        output.push_str(&String::from_utf8_lossy(&self.quality));
+        output.push('\n');
+        output
+    }
+}
+
+impl SequenceStats for FastqRecord {
+    fn quality_offset(&self) -> u8 {
+        self.encoding.offset()
+    }
+}
+
+/// FASTQ file parser for four-line records. Unless set, the quality
+/// encoding is detected from the first 1000 records.
+pub struct FastqReader<R: BufRead> {
+    reader: R,
+    /// Records read so far, for error messages
+    records: u64,
+    /// Records handed out so far
+    returned: u64,
+    encoding: Option<QualityEncoding>,
+    /// Records read ahead to detect the encoding
+    pending: VecDeque<FastqRecord>,
+    /// Error hit while reading ahead, returned after `pending`
+    error: Option<ExtractorError>,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    /// Create a new FASTQ reader
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            records: 0,
+            returned: 0,
+            encoding: None,
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    /// Read qualities as `encoding` instead of detecting it
+    pub fn with_encoding(mut self, encoding: QualityEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// The quality encoding, once set or detected
+    pub fn encoding(&self) -> Option<QualityEncoding> {
+        self.encoding
+    }
+
+    fn detect_encoding(&mut self) -> QualityEncoding {
+        while self.pending.len() < DETECT_RECORDS && self.error.is_none() {
+            match self.read_record() {
+                Ok(Some(record)) => self.pending.push_back(record),
+                Ok(None) => break,
+                Err(e) => self.error = Some(e),
+            }
+        }
+        let encoding = QualityEncoding::detect(self.pending.iter().map(|record| record.quality.as_slice()));
+        self.encoding = Some(encoding);
+        encoding
+    }
+
+    fn next_record(&mut self) -> Result<Option<FastqRecord>> {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => self.detect_encoding(),
+        };
+        let record = match self.pending.pop_front() {
+            Some(record) => record,
+            None => match self.error.take() {
+                Some(e) => return Err(e),
+                None => match self.read_record()? {
+                    Some(record) => record,
+                    None => return Ok(None),
+                },
+            },
+        };
+        self.returned += 1;
+        // A character below the offset means the encoding is wrong, which
+        // would skew every score
+        if let Some(&low) = record.quality.iter().find(|&&q| q < encoding.offset()) {
+            return Err(ExtractorError::InvalidDataFormat {
+                column: "quality".to_string(),
+                message: format!("{:?} in {} is outside {encoding}; set the encoding", low as char, record.id),
+                row: Some(self.returned),
+                snippet: None,
+            });
+        }
+        Ok(Some(record.with_encoding(encoding)))
+    }
+
+    /// Read one line without its line ending; `None` at EOF
+    fn line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    fn invalid(&self, field: &str, message: String) -> ExtractorError {
+        ExtractorError::InvalidDataFormat {
+            column: field.to_string(),
+            message,
+            row: Some(self.records),
+            snippet: None,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<FastqRecord>> {
+        // Skip blank lines between records
+        let header = loop {
+            match self.line()? {
+                None => return Ok(None),
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => break line,
+            }
+        };
+        self.records += 1;
+        let Some(header) = header.strip_prefix('@') else {
+            return Err(self.invalid("header", format!("expected '@', found {header:?}")));
+        };
+        let (id, description) = match header.split_once(' ') {
+            Some((id, description)) => (id.to_string(), Some(description.to_string())),
+            None => (header.to_string(), None),
+        };
+        let sequence = self.line()?.ok_or_else(|| self.invalid("sequence", "missing sequence line".to_string()))?;
+        let separator = self.line()?.unwrap_or_default();
+        if !separator.starts_with('+') {
+            return Err(self.invalid("separator", format!("expected '+', found {separator:?}")));
+        }
+        let quality = self.line()?.unwrap_or_default();
+        if quality.len() != sequence.len() {
+            return Err(self.invalid(
+                "quality",
+                format!("{} quality scores for {} bases in {id}", quality.len(), sequence.len()),
+            ));
+        }
+        let record = FastqRecord::new(id, sequence.into_bytes(), quality.into_bytes());
+        Ok(Some(match description {
+            Some(description) => record.with_description(description),
+            None => record,
+        }))
+    }
+}
+
+impl FastqReader<BufReader<File>> {
+    /// Create from file path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Ok(FastqReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<FastqRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fastq_parsing() {
+        let data = "@read1 lane=1\nACGT\n+\nII#I\n\n@read2\nGG\n+read2\nII\n";
+        let records: Vec<FastqRecord> = FastqReader::new(Cursor::new(data)).collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].id(), records[0].description()), ("read1", Some("lane=1")));
+        assert_eq!(records[0].min_quality_score(), Some(2));
+        assert_eq!(records[1].to_string(), "@read2\nGG\n+\nII\n");
+
+        let truncated = FastqReader::new(Cursor::new("@read1\nACGT\n+\nII\n")).next().unwrap();
+        assert!(matches!(truncated, Err(ExtractorError::InvalidDataFormat { row: Some(1), .. })));
+    }
+
+    #[test]
+    fn test_quality_encoding() {
+        let data = "@r1\nACGT\n+\nhhhB\n@r2\nAC\n+\nh@\n";
+        let mut reader = FastqReader::new(Cursor::new(data));
+        let records: Vec<FastqRecord> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(reader.encoding(), Some(QualityEncoding::Phred64));
+        assert_eq!(records[0].min_quality_score(), Some(2));
+        assert!(records[0].metadata().contains(&("quality_encoding".to_string(), "Phred+64".to_string())));
+        let converted = records[0].clone().to_encoding(QualityEncoding::Phred33);
+        assert_eq!((converted.quality(), converted.min_quality_score()), (Some(&b"III#"[..]), Some(2)));
+
+        // High Phred+33 scores alone don't look like Phred+64
+        assert_eq!(QualityEncoding::detect([&b"IIJJ"[..]]), QualityEncoding::Phred33);
+        let forced = FastqReader::new(Cursor::new("@r1\nACGT\n+\nII#I\n")).with_encoding(QualityEncoding::Phred64).next();
+        assert!(matches!(forced, Some(Err(ExtractorError::InvalidDataFormat { row: Some(1), .. }))));
+    }
+}
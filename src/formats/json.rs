@@ -0,0 +1,154 @@
+//! Writes matched rows as JSON objects keyed by column name, either as one
+//! JSON array or as newline-delimited JSON, so a web service can stream an
+//! extraction straight into its response.
+
+use std::io::Write;
+
+use csv::ByteRecord;
+use crate::core::header_names;
+use crate::output::RowSink;
+use crate::Result;
+
+/// Row sink that writes each matched row as a JSON object whose keys are the
+/// output's column names. Use it with `BioFilter::process_into`.
+///
+/// Values are JSON strings unless `typed` is set. Bytes that aren't UTF-8
+/// are replaced with U+FFFD.
+pub struct JsonSink<W: Write> {
+    writer: W,
+    ndjson: bool,
+    typed: bool,
+    /// Column names as JSON strings, set by `begin`
+    keys: Vec<String>,
+    written: u64,
+}
+
+impl<W: Write> JsonSink<W> {
+    /// Write a JSON array of row objects to `writer`
+    pub fn new(writer: W) -> Self {
+        Self { writer, ndjson: false, typed: false, keys: Vec::new(), written: 0 }
+    }
+
+    /// Write one object per line (NDJSON) instead of an array
+    pub fn ndjson(mut self) -> Self {
+        self.ndjson = true;
+        self
+    }
+
+    /// Write values that are valid JSON numbers as numbers and empty
+    /// values as `null` (default: every value is a string)
+    pub fn typed(mut self, typed: bool) -> Self {
+        self.typed = typed;
+        self
+    }
+
+    /// Number of rows written
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// The writer, once the run has finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write `field` as a JSON value
+    fn write_value(&mut self, field: &[u8]) -> Result<()> {
+        if self.typed {
+            if field.is_empty() {
+                self.writer.write_all(b"null")?;
+                return Ok(());
+            }
+            if serde_json::from_slice::<serde_json::Number>(field).is_ok() {
+                self.writer.write_all(field)?;
+                return Ok(());
+            }
+        }
+        serde_json::to_writer(&mut self.writer, &String::from_utf8_lossy(field))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowSink for JsonSink<W> {
+    fn begin(&mut self, header: &ByteRecord) -> Result<()> {
+        self.keys = header_names(header).map(|name| serde_json::to_string(&name)).collect::<std::result::Result<_, _>>()?;
+        if !self.ndjson {
+            self.writer.write_all(b"[")?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, record: &ByteRecord) -> Result<()> {
+        match (self.ndjson, self.written) {
+            (true, _) => {}
+            (false, 0) => self.writer.write_all(b"\n")?,
+            (false, _) => self.writer.write_all(b",\n")?,
+        }
+        self.writer.write_all(b"{")?;
+        for (i, field) in record.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            // Fields past the header, in flexible input, are keyed by position
+            let key = self.keys.get(i).cloned().unwrap_or_else(|| format!("\"column_{}\"", i + 1));
+            self.writer.write_all(key.as_bytes())?;
+            self.writer.write_all(b":")?;
+            self.write_value(field)?;
+        }
+        self.writer.write_all(b"}")?;
+        if self.ndjson {
+            self.writer.write_all(b"\n")?;
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.ndjson {
+            self.writer.write_all(if self.written == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::BioFilter;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+    use crate::Config;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_json_sink() -> Result<()> {
+        let input = NamedTempFile::new()?;
+        std::fs::write(input.path(), "rsid,chrom,p,note\nrs1,1,1e-8,\"say \"\"hi\"\"\"\nrs2,2,0.5,x\nrs3,X,0.001,\n")?;
+        let mut filter = BioFilter::new(input.path().into(), "unused.csv".into(), Config::default(), None)?;
+        filter.add_filter(Box::new(ColumnFilter::new(
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(0.01)),
+        )?));
+
+        let mut sink = JsonSink::new(Vec::new());
+        filter.process_into(&mut sink)?;
+        assert_eq!(sink.written(), 2);
+        let json: serde_json::Value = serde_json::from_slice(&sink.into_inner())?;
+        assert_eq!(json[0]["note"], r#"say "hi""#);
+        assert_eq!(json[1]["p"], "0.001");
+
+        let mut sink = JsonSink::new(Vec::new()).ndjson().typed(true);
+        filter.process_into(&mut sink)?;
+        assert_eq!(
+            String::from_utf8_lossy(&sink.into_inner()),
+            "{\"rsid\":\"rs1\",\"chrom\":1,\"p\":1e-8,\"note\":\"say \\\"hi\\\"\"}\n\
+             {\"rsid\":\"rs3\",\"chrom\":\"X\",\"p\":0.001,\"note\":null}\n"
+        );
+
+        let mut sink = JsonSink::new(Vec::new());
+        sink.begin(&ByteRecord::from(vec!["a"]))?;
+        sink.finish()?;
+        assert_eq!(sink.into_inner(), b"[]\n");
+        Ok(())
+    }
+}
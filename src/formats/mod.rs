@@ -1,22 +1,34 @@
 //! Bioinformatics file format support module
 //! Handles common file formats used in bioinformatics
 
+pub mod bed;
+pub mod extract;
+pub mod fai;
 pub mod fasta;
 pub mod fastq;
-pub mod bed;
-pub mod format_detector;
+pub mod json;
+pub mod motif;
+pub mod seq_filter;
 
+use std::fs::File;
+use std::io::Read;
 use crate::error::ExtractorError;
 use crate::Result;
+use motif::{iupac_bases, iupac_matches, Motif};
 use std::path::Path;
 
 /// Supported file formats
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FileFormat {
-    CSV,
-    FASTA,
-    FASTQ,
-    BED,
+    /// Delimited text with a header row
+    Csv,
+    /// Sequences with `>` header lines
+    Fasta,
+    /// Sequences with qualities, four lines per record
+    Fastq,
+    /// BED intervals
+    Bed,
+    /// Anything else
     Unknown,
 }
 
@@ -24,24 +36,111 @@ pub enum FileFormat {
 pub trait BioRecord: Send + Sync {
     /// Get the record identifier
     fn id(&self) -> &str;
-    
+
     /// Get the sequence if available
     fn sequence(&self) -> Option<&[u8]>;
-    
+
     /// Get the quality scores if available
     fn quality(&self) -> Option<&[u8]>;
-    
+
     /// Get additional metadata
     fn metadata(&self) -> &[(String, String)];
-    
+
     /// Convert record to string
     fn to_string(&self) -> String;
 }
 
+/// Sequence-level measures that sequence filters test
+pub trait SequenceStats: BioRecord {
+    /// Fraction of bases that are G or C (0 for an empty sequence)
+    fn gc_content(&self) -> f64 {
+        let sequence = self.sequence().unwrap_or_default();
+        let gc_count = sequence
+            .iter()
+            .filter(|base| matches!(base.to_ascii_uppercase(), b'G' | b'C'))
+            .count();
+        if sequence.is_empty() {
+            0.0
+        } else {
+            gc_count as f64 / sequence.len() as f64
+        }
+    }
+
+    /// Number of bases
+    fn sequence_length(&self) -> usize {
+        self.sequence().map_or(0, <[u8]>::len)
+    }
+
+    /// Character value of quality score 0 (default: 33, Phred+33)
+    fn quality_offset(&self) -> u8 {
+        33
+    }
+
+    /// Lowest Phred quality score, if the record has qualities
+    fn min_quality_score(&self) -> Option<u8> {
+        let offset = self.quality_offset();
+        self.quality()?.iter().min().map(|q| q.saturating_sub(offset))
+    }
+
+    /// Mean Phred quality score, if the record has qualities
+    fn mean_quality_score(&self) -> Option<f64> {
+        let quality = self.quality().filter(|quality| !quality.is_empty())?;
+        let offset = self.quality_offset();
+        let total: u64 = quality.iter().map(|q| u64::from(q.saturating_sub(offset))).sum();
+        Some(total as f64 / quality.len() as f64)
+    }
+
+    /// Whether the sequence contains `pattern`, ignoring case. IUPAC codes
+    /// in the pattern match any of the bases they stand for; other bytes,
+    /// such as protein residues, `*` or `-`, match themselves.
+    fn contains_pattern(&self, pattern: &[u8]) -> bool {
+        let fits = |base: u8, code: u8| match iupac_bases(code) {
+            0 => base.eq_ignore_ascii_case(&code),
+            _ => iupac_matches(base, code),
+        };
+        pattern.is_empty()
+            || self
+                .sequence()
+                .unwrap_or_default()
+                .windows(pattern.len())
+                .any(|window| window.iter().zip(pattern).all(|(&base, &code)| fits(base, code)))
+    }
+
+    /// Whether the sequence contains `motif`
+    fn contains_motif(&self, motif: &Motif) -> bool {
+        motif.is_match(self.sequence().unwrap_or_default())
+    }
+}
+
 /// File format detection and validation
 pub trait FormatDetector {
     /// Detect file format from content
     fn detect_format(path: &Path) -> Result<FileFormat>;
     /// Validate file format
     fn validate(path: &Path) -> Result<bool>;
-}
\ No newline at end of file
+}
+
+impl FormatDetector for FileFormat {
+    /// Sequence files are told apart by their first character (`>` or `@`),
+    /// anything else by its extension
+    fn detect_format(path: &Path) -> Result<FileFormat> {
+        let mut start = Vec::with_capacity(256);
+        File::open(path)
+            .and_then(|file| file.take(256).read_to_end(&mut start))
+            .map_err(|e| ExtractorError::io_error(e, path))?;
+        let format = match start.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'>') => FileFormat::Fasta,
+            Some(b'@') => FileFormat::Fastq,
+            _ => match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+                Some("csv" | "tsv" | "txt") => FileFormat::Csv,
+                Some("bed") => FileFormat::Bed,
+                _ => FileFormat::Unknown,
+            },
+        };
+        Ok(format)
+    }
+
+    fn validate(path: &Path) -> Result<bool> {
+        Ok(Self::detect_format(path)? != FileFormat::Unknown)
+    }
+}
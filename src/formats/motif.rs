@@ -0,0 +1,184 @@
+//! Motif search over nucleotide sequences. Patterns may use IUPAC
+//! ambiguity codes and are matched with a bit-parallel (shift-and) scan
+//! that also tolerates a number of mismatches and can search the reverse
+//! complement.
+
+use std::fmt;
+use crate::error::{ExtractorError, FilterErrorKind};
+use crate::Result;
+
+/// Longest motif the bit-parallel scan supports, one bit per base
+pub const MAX_MOTIF_LEN: usize = 64;
+
+/// Bases an IUPAC code stands for, as a bit set (A=1, C=2, G=4, T/U=8);
+/// 0 for anything else
+pub(crate) fn iupac_bases(code: u8) -> u8 {
+    match code.to_ascii_uppercase() {
+        b'A' => 0b0001,
+        b'C' => 0b0010,
+        b'G' => 0b0100,
+        b'T' | b'U' => 0b1000,
+        b'M' => 0b0011,
+        b'R' => 0b0101,
+        b'W' => 0b1001,
+        b'S' => 0b0110,
+        b'Y' => 0b1010,
+        b'K' => 0b1100,
+        b'V' => 0b0111,
+        b'H' => 0b1011,
+        b'D' => 0b1101,
+        b'B' => 0b1110,
+        b'N' => 0b1111,
+        _ => 0,
+    }
+}
+
+/// Whether sequence byte `base` is covered by IUPAC `code`. An ambiguous
+/// base matches only codes covering all of its bases.
+pub(crate) fn iupac_matches(base: u8, code: u8) -> bool {
+    let base = iupac_bases(base);
+    base != 0 && base & !iupac_bases(code) == 0
+}
+
+/// Complement of an IUPAC base set: A and T swap, as do C and G
+fn complement(bases: u8) -> u8 {
+    (bases & 0b0001) << 3 | (bases & 0b0010) << 1 | (bases & 0b0100) >> 1 | (bases & 0b1000) >> 3
+}
+
+/// Per-byte match masks: bit `i` of `masks[b]` is set when byte `b` fits
+/// position `i` of the pattern
+type Masks = Box<[u64; 256]>;
+
+fn masks(positions: impl Iterator<Item = u8>) -> Masks {
+    let mut masks = Box::new([0u64; 256]);
+    for (i, code) in positions.enumerate() {
+        for (byte, mask) in masks.iter_mut().enumerate() {
+            let base = iupac_bases(byte as u8);
+            if base != 0 && base & !code == 0 {
+                *mask |= 1 << i;
+            }
+        }
+    }
+    masks
+}
+
+/// A compiled nucleotide motif
+#[derive(Clone)]
+pub struct Motif {
+    pattern: String,
+    forward: Masks,
+    /// Masks of the reverse complement, when searching both strands
+    reverse: Option<Masks>,
+    max_mismatches: usize,
+}
+
+impl Motif {
+    /// Compile `pattern`, which may use IUPAC codes and is case-insensitive
+    pub fn new(pattern: &str) -> Result<Self> {
+        let invalid = || ExtractorError::filter_error(FilterErrorKind::InvalidCondition, Some(pattern.to_string()));
+        if pattern.is_empty() || pattern.len() > MAX_MOTIF_LEN {
+            return Err(invalid());
+        }
+        if pattern.bytes().any(|code| iupac_bases(code) == 0) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            forward: masks(pattern.bytes().map(iupac_bases)),
+            reverse: None,
+            max_mismatches: 0,
+        })
+    }
+
+    /// Allow up to `n` mismatched bases (default: 0)
+    pub fn with_max_mismatches(mut self, n: usize) -> Self {
+        self.max_mismatches = n;
+        self
+    }
+
+    /// Also match the reverse complement of the motif
+    pub fn with_reverse_complement(mut self) -> Self {
+        self.reverse = Some(masks(self.pattern.bytes().rev().map(|code| complement(iupac_bases(code)))));
+        self
+    }
+
+    /// The motif as given
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `sequence` contains the motif on either searched strand
+    pub fn is_match(&self, sequence: &[u8]) -> bool {
+        self.scan(&self.forward, sequence) || self.reverse.as_ref().is_some_and(|masks| self.scan(masks, sequence))
+    }
+
+    /// Shift-and scan with mismatches: bit `i` of `states[d]` is set when
+    /// the first `i + 1` pattern positions match the text ending here with
+    /// at most `d` mismatches
+    fn scan(&self, masks: &Masks, sequence: &[u8]) -> bool {
+        let len = self.pattern.len();
+        if sequence.len() < len {
+            return false;
+        }
+        let accept = 1u64 << (len - 1);
+        let mut states = vec![0u64; self.max_mismatches + 1];
+        for &byte in sequence {
+            let mask = masks[byte as usize];
+            let mut previous = states[0];
+            states[0] = (states[0] << 1 | 1) & mask;
+            for state in &mut states[1..] {
+                let current = *state;
+                *state = (current << 1 | 1) & mask | (previous << 1 | 1);
+                previous = current;
+            }
+            if states[self.max_mismatches] & accept != 0 {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl fmt::Debug for Motif {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Motif")
+            .field("pattern", &self.pattern)
+            .field("max_mismatches", &self.max_mismatches)
+            .field("reverse_complement", &self.reverse.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::fasta::FastaRecord;
+    use crate::formats::SequenceStats;
+
+    #[test]
+    fn test_motif_search() -> Result<()> {
+        let motif = Motif::new("GAATTYN")?;
+        assert!(motif.is_match(b"ccgaattcag"));
+        assert!(!motif.is_match(b"ccgaattaag"));
+        // A read N is only covered by a pattern N
+        assert!(!Motif::new("GAN")?.is_match(b"NAC"));
+
+        let motif = Motif::new("AACCGT")?;
+        assert!(!motif.is_match(b"TTACGGTTT"));
+        assert!(motif.clone().with_reverse_complement().is_match(b"TTACGGTTT"));
+        assert!(motif.clone().with_max_mismatches(1).is_match(b"GGAACTGTT"));
+        assert!(!motif.clone().with_max_mismatches(1).is_match(b"GGATCTGTT"));
+        assert!(motif.with_max_mismatches(2).is_match(b"GGATCTGTT"));
+
+        assert!(Motif::new(&"A".repeat(MAX_MOTIF_LEN)).is_ok());
+        assert!(Motif::new(&"A".repeat(MAX_MOTIF_LEN + 1)).is_err());
+        assert!(Motif::new("ACXT").is_err());
+
+        let record = FastaRecord::new("r".to_string(), b"ttgaattcaa".to_vec());
+        assert!(record.contains_pattern(b"GAATTY") && !record.contains_pattern(b"GAATTR"));
+        let protein = FastaRecord::new("p".to_string(), b"MEFILPQW*".to_vec());
+        assert!(protein.contains_pattern(b"efilp") && protein.contains_pattern(b"QW*"));
+        assert!(!protein.contains_pattern(b"EFIM"));
+        Ok(())
+    }
+}
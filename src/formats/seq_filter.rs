@@ -0,0 +1,471 @@
+//! Filtering engine for FASTA and FASTQ files, the sequence counterpart of
+//! `BioFilter`. Records are read in batches of about `Config::chunk_size`
+//! bases, tested in parallel on the configured thread pool and written in
+//! input order.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use super::fasta::FastaReader;
+use super::fastq::{FastqReader, FastqRecord, QualityEncoding};
+use super::motif::Motif;
+use super::{BioRecord, FileFormat, FormatDetector, SequenceStats};
+use crate::error::ExtractorError;
+use crate::output::{ends_mid_line, AppendWriter, OutputFile};
+use crate::{Config, PairStats, ProcessingStats, Result};
+
+/// Condition on one sequence record
+#[derive(Debug, Clone)]
+pub enum SeqCondition {
+    /// At least this many bases
+    MinLength(usize),
+    /// At most this many bases
+    MaxLength(usize),
+    /// GC fraction between `min` and `max`, inclusive
+    GcRange {
+        /// Lowest GC fraction (0-1)
+        min: f64,
+        /// Highest GC fraction (0-1)
+        max: f64,
+    },
+    /// Every base has at least this Phred quality (FASTQ only)
+    MinQuality(u8),
+    /// Mean Phred quality of at least this (FASTQ only)
+    MinMeanQuality(f64),
+    /// Sequence contains this motif
+    Motif(Motif),
+    /// Record ID is one of these
+    IdIn(HashSet<String>),
+}
+
+impl SeqCondition {
+    /// Read an ID list, one ID per line; blank lines and `#` comments are skipped
+    pub fn id_list(path: &std::path::Path) -> Result<Self> {
+        let ids = std::fs::read_to_string(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Ok(SeqCondition::IdIn(
+            ids.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+
+    /// Whether the condition needs quality scores
+    fn needs_quality(&self) -> bool {
+        matches!(self, SeqCondition::MinQuality(_) | SeqCondition::MinMeanQuality(_))
+    }
+
+    fn matches<T: SequenceStats>(&self, record: &T) -> bool {
+        match self {
+            SeqCondition::MinLength(n) => record.sequence_length() >= *n,
+            SeqCondition::MaxLength(n) => record.sequence_length() <= *n,
+            SeqCondition::GcRange { min, max } => (*min..=*max).contains(&record.gc_content()),
+            SeqCondition::MinQuality(q) => record.min_quality_score().is_none_or(|min| min >= *q),
+            SeqCondition::MinMeanQuality(q) => record.mean_quality_score().is_none_or(|mean| mean >= *q),
+            SeqCondition::Motif(motif) => record.contains_motif(motif),
+            SeqCondition::IdIn(ids) => ids.contains(record.id()),
+        }
+    }
+}
+
+/// Name shared by both mates of a pair, without a `/1` or `/2` suffix
+fn mate_name(id: &str) -> &str {
+    id.strip_suffix("/1").or_else(|| id.strip_suffix("/2")).unwrap_or(id)
+}
+
+/// Zip two mate files into pairs, failing when their IDs or lengths disagree
+fn pairs<T, A, B>(mut first: A, mut second: B) -> impl Iterator<Item = Result<(T, T)>>
+where
+    T: BioRecord,
+    A: Iterator<Item = Result<T>>,
+    B: Iterator<Item = Result<T>>,
+{
+    let mut pair = 0;
+    std::iter::from_fn(move || {
+        pair += 1;
+        let invalid = |message: String| ExtractorError::InvalidDataFormat {
+            column: "id".to_string(),
+            message,
+            row: Some(pair),
+            snippet: None,
+        };
+        let Some(a) = first.next() else {
+            return second.next().map(|_| Err(invalid("first mate file ended before the second".to_string())));
+        };
+        Some(match (a, second.next()) {
+            (Ok(_), None) => Err(invalid("second mate file ended before the first".to_string())),
+            (Err(e), _) | (_, Some(Err(e))) => Err(e),
+            (Ok(a), Some(Ok(b))) if mate_name(a.id()) != mate_name(b.id()) => {
+                Err(invalid(format!("mates {} and {} are out of step", a.id(), b.id())))
+            }
+            (Ok(a), Some(Ok(b))) => Ok((a, b)),
+        })
+    })
+}
+
+type RecordWriter = BufWriter<AppendWriter<File>>;
+
+/// Filters FASTA or FASTQ records into a file of the same format
+pub struct SeqFilter {
+    config: Config,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    /// Second mate file and its output, for paired-end reads
+    mate: Option<(PathBuf, PathBuf)>,
+    /// Input format, detected from the input when not set
+    format: Option<FileFormat>,
+    /// FASTQ quality encoding of the input, detected when not set
+    quality_encoding: Option<QualityEncoding>,
+    /// FASTQ quality encoding to write, when converting
+    output_encoding: Option<QualityEncoding>,
+    conditions: Vec<SeqCondition>,
+    /// Whether some condition needs quality scores
+    needs_quality: bool,
+}
+
+impl SeqFilter {
+    /// Create a new SeqFilter. Of `config`, the parallelism, chunk size and
+    /// output settings apply.
+    pub fn new(input_path: PathBuf, output_path: PathBuf, config: Config) -> Result<Self> {
+        if !input_path.exists() {
+            return Err(ExtractorError::io_error(
+                std::io::Error::new(std::io::ErrorKind::NotFound, "Input file does not exist"),
+                &input_path,
+            ));
+        }
+        Ok(Self {
+            config,
+            input_path,
+            output_path,
+            mate: None,
+            format: None,
+            quality_encoding: None,
+            output_encoding: None,
+            conditions: Vec::new(),
+            needs_quality: false,
+        })
+    }
+
+    /// Read the input as `format` instead of detecting it
+    pub fn with_format(mut self, format: FileFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Read FASTQ qualities as `encoding` instead of detecting it
+    pub fn with_quality_encoding(mut self, encoding: QualityEncoding) -> Self {
+        self.quality_encoding = Some(encoding);
+        self
+    }
+
+    /// Write FASTQ qualities in `encoding`, converting them if the input
+    /// uses another
+    pub fn with_output_encoding(mut self, encoding: QualityEncoding) -> Self {
+        self.output_encoding = Some(encoding);
+        self
+    }
+
+    /// Filter paired-end reads: the input holds the first mates (R1) and
+    /// `input_path` the second (R2), in the same order. A pair is written
+    /// to both outputs only if both mates pass, so the outputs stay in step.
+    pub fn with_mate(mut self, input_path: PathBuf, output_path: PathBuf) -> Self {
+        self.mate = Some((input_path, output_path));
+        self
+    }
+
+    /// Add a condition every written record must meet
+    pub fn add_condition(&mut self, condition: SeqCondition) -> Result<()> {
+        self.needs_quality |= condition.needs_quality();
+        self.conditions.push(condition);
+        Ok(())
+    }
+
+    /// Filter the input into the output file. `rows_processed` and
+    /// `rows_matched` count records, or pairs when filtering mates, whose
+    /// counts are in `pairs`.
+    pub fn process(&self) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        let format = match self.format {
+            Some(format) => format,
+            None => FileFormat::detect_format(&self.input_path)?,
+        };
+        if format == FileFormat::Fasta && self.needs_quality {
+            return Err(ExtractorError::Config("Quality conditions need FASTQ input".to_string()));
+        }
+        if !matches!(format, FileFormat::Fasta | FileFormat::Fastq) {
+            return Err(ExtractorError::Config(format!("SeqFilter reads FASTA or FASTQ, not {format:?}")));
+        }
+        let input_size = |path: &PathBuf| path.metadata().map(|m| m.len()).map_err(|e| ExtractorError::io_error(e, path));
+
+        let (output, file) =
+            OutputFile::create(&self.output_path, self.config.write_mode, self.config.on_existing_output)?;
+        let mut writer = record_writer(file, output.path())?;
+        let Some((mate_input, mate_output)) = &self.mate else {
+            let (rows_processed, rows_matched) = if format == FileFormat::Fasta {
+                self.run(|| self.filter_records(FastaReader::from_path(&self.input_path)?, &mut writer))?
+            } else {
+                self.run(|| self.filter_records(self.fastq(&self.input_path)?, &mut writer))?
+            };
+            let output_size = finish(&output, writer)?;
+            output.commit()?;
+            return Ok(ProcessingStats {
+                rows_processed,
+                rows_matched,
+                processing_time_ms: start.elapsed().as_millis() as u64,
+                input_size: input_size(&self.input_path)?,
+                output_size,
+                ..ProcessingStats::default()
+            });
+        };
+
+        let (mate, mate_file) =
+            OutputFile::create(mate_output, self.config.write_mode, self.config.on_existing_output)?;
+        let mut mate_writer = record_writer(mate_file, mate.path())?;
+        let writers = (&mut writer, &mut mate_writer);
+        let pair_stats = if format == FileFormat::Fasta {
+            self.run(|| {
+                let reads = pairs(FastaReader::from_path(&self.input_path)?, FastaReader::from_path(mate_input)?);
+                self.filter_pairs(reads, writers)
+            })?
+        } else {
+            self.run(|| {
+                let reads = pairs(self.fastq(&self.input_path)?, self.fastq(mate_input)?);
+                self.filter_pairs(reads, writers)
+            })?
+        };
+        let output_size = finish(&output, writer)? + finish(&mate, mate_writer)?;
+        output.commit()?;
+        mate.commit()?;
+        Ok(ProcessingStats {
+            rows_processed: pair_stats.pairs_processed,
+            rows_matched: pair_stats.pairs_kept,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+            input_size: input_size(&self.input_path)? + input_size(mate_input)?,
+            output_size,
+            pairs: Some(pair_stats),
+            ..ProcessingStats::default()
+        })
+    }
+
+    /// FASTQ records of `path` in the configured encodings
+    fn fastq(&self, path: &Path) -> Result<impl Iterator<Item = Result<FastqRecord>> + Send + use<>> {
+        let mut reader = FastqReader::from_path(path)?;
+        if let Some(encoding) = self.quality_encoding {
+            reader = reader.with_encoding(encoding);
+        }
+        let output = self.output_encoding;
+        Ok(reader.map(move |record| record.map(|record| match output {
+            Some(encoding) => record.to_encoding(encoding),
+            None => record,
+        })))
+    }
+
+    /// Run `work` on the configured thread pool
+    fn run<R: Send>(&self, work: impl FnOnce() -> Result<R> + Send) -> Result<R> {
+        #[cfg(feature = "parallel")]
+        if self.config.parallel {
+            return self.config.thread_pool.install(self.config.num_threads, work)?;
+        }
+        work()
+    }
+
+    /// Filter `records` into `writer`, returning the records read and written
+    fn filter_records<T, I>(&self, records: I, writer: &mut RecordWriter) -> Result<(u64, u64)>
+    where
+        T: SequenceStats + Send + Sync,
+        I: Iterator<Item = Result<T>>,
+    {
+        let (mut processed, mut matched) = (0, 0);
+        self.for_each_batch(records, T::sequence_length, |batch| {
+            for (record, keep) in batch.iter().zip(self.evaluate(batch, |record| self.keep(record))) {
+                if keep {
+                    write_record(writer, record, &self.output_path)?;
+                    matched += 1;
+                }
+            }
+            processed += batch.len() as u64;
+            Ok(())
+        })?;
+        Ok((processed, matched))
+    }
+
+    /// Filter mate pairs into the two writers
+    fn filter_pairs<T, I>(&self, pairs: I, writers: (&mut RecordWriter, &mut RecordWriter)) -> Result<PairStats>
+    where
+        T: SequenceStats + Send + Sync,
+        I: Iterator<Item = Result<(T, T)>>,
+    {
+        let (writer, mate_writer) = writers;
+        let mate_output = self.mate.as_ref().map_or(&self.output_path, |(_, output)| output);
+        let mut stats = PairStats::default();
+        let bases = |(first, second): &(T, T)| first.sequence_length() + second.sequence_length();
+        self.for_each_batch(pairs, bases, |batch| {
+            let kept = self.evaluate(batch, |(first, second)| (self.keep(first), self.keep(second)));
+            for ((first, second), kept) in batch.iter().zip(kept) {
+                match kept {
+                    (true, true) => {
+                        write_record(writer, first, &self.output_path)?;
+                        write_record(mate_writer, second, mate_output)?;
+                        stats.pairs_kept += 1;
+                    }
+                    (true, false) => stats.orphaned_first += 1,
+                    (false, true) => stats.orphaned_second += 1,
+                    (false, false) => {}
+                }
+            }
+            stats.pairs_processed += batch.len() as u64;
+            Ok(())
+        })?;
+        Ok(stats)
+    }
+
+    /// Hand `items` to `handle` in batches of about `chunk_size` bases
+    fn for_each_batch<U, I>(
+        &self,
+        items: I,
+        bases: impl Fn(&U) -> usize,
+        mut handle: impl FnMut(&[U]) -> Result<()>,
+    ) -> Result<()>
+    where
+        I: Iterator<Item = Result<U>>,
+    {
+        let mut batch = Vec::new();
+        let mut batch_bases = 0;
+        let mut items = items.peekable();
+        while let Some(item) = items.next() {
+            let item = item?;
+            batch_bases += bases(&item);
+            batch.push(item);
+            if batch_bases < self.config.chunk_size && items.peek().is_some() {
+                continue;
+            }
+            handle(&batch)?;
+            batch.clear();
+            batch_bases = 0;
+        }
+        Ok(())
+    }
+
+    /// Apply `test` to each item of `batch`, in parallel when configured
+    fn evaluate<U: Sync, O: Send>(&self, batch: &[U], test: impl Fn(&U) -> O + Send + Sync) -> Vec<O> {
+        #[cfg(feature = "parallel")]
+        if self.config.parallel {
+            return batch.par_iter().map(&test).collect();
+        }
+        batch.iter().map(test).collect()
+    }
+
+    /// Whether `record` meets every condition
+    fn keep<T: SequenceStats>(&self, record: &T) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(record))
+    }
+}
+
+fn write_record<T: BioRecord>(writer: &mut RecordWriter, record: &T, path: &Path) -> Result<()> {
+    writer.write_all(record.to_string().as_bytes()).map_err(|e| ExtractorError::io_error(e, path))
+}
+
+/// Buffer records for `file`, starting them on a new line if it is being
+/// appended to and doesn't end with one
+fn record_writer(file: File, path: &Path) -> Result<RecordWriter> {
+    let separate = ends_mid_line(&file).map_err(|e| ExtractorError::io_error(e, path))?;
+    Ok(BufWriter::new(AppendWriter::new(file, separate)))
+}
+
+/// Flush and sync a finished output, returning its size
+pub(super) fn finish<W: Write + Into<File>>(output: &OutputFile, writer: BufWriter<W>) -> Result<u64> {
+    let file: File = writer.into_inner().map_err(|e| ExtractorError::io_error(e.into_error(), output.path()))?.into();
+    file.sync_all().map_err(|e| ExtractorError::io_error(e, output.path()))?;
+    Ok(file.metadata().map_err(|e| ExtractorError::io_error(e, output.path()))?.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ExistingOutput;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_seq_filter() -> Result<()> {
+        let mut input = NamedTempFile::new()?;
+        for i in 0..200 {
+            let sequence = if i % 2 == 0 { "ACGTGGCCNA" } else { "ATATATAT" };
+            let quality = if i % 3 == 0 { "#" } else { "I" }.repeat(sequence.len());
+            write!(input, "@read{i}\n{sequence}\n+\n{quality}\n")?;
+        }
+        let fasta = NamedTempFile::new()?;
+        std::fs::write(fasta.path(), ">a x\nACGT\nGC\n>b\nATAT\n")?;
+
+        for parallel in [false, true] {
+            let output = NamedTempFile::new()?;
+            let config = Config {
+                parallel,
+                chunk_size: 64,
+                on_existing_output: ExistingOutput::Overwrite,
+                ..Config::default()
+            };
+            let mut filter = SeqFilter::new(input.path().to_owned(), output.path().to_owned(), config.clone())?;
+            filter.add_condition(SeqCondition::MinLength(9))?;
+            filter.add_condition(SeqCondition::Motif(Motif::new("GGSCN")?))?;
+            filter.add_condition(SeqCondition::MinQuality(30))?;
+            let stats = filter.process()?;
+            assert_eq!((stats.rows_processed, stats.rows_matched), (200, 66));
+            let written: Vec<_> = FastqReader::from_path(output.path())?.collect::<Result<_>>()?;
+            let ids: Vec<&str> = written.iter().take(3).map(|record| record.id()).collect();
+            assert_eq!(ids, ["read2", "read4", "read8"], "parallel = {parallel}");
+
+            let mut filter = SeqFilter::new(fasta.path().to_owned(), output.path().to_owned(), config.clone())?;
+            filter.add_condition(SeqCondition::GcRange { min: 0.5, max: 1.0 })?;
+            assert_eq!(filter.process()?.rows_matched, 1);
+            assert_eq!(std::fs::read_to_string(output.path())?, ">a x\nACGTGC\n");
+
+            std::fs::write(output.path(), ">z\nGG")?;
+            let append = Config { on_existing_output: ExistingOutput::Append, ..config.clone() };
+            let mut filter = SeqFilter::new(fasta.path().to_owned(), output.path().to_owned(), append.clone())?;
+            filter.add_condition(SeqCondition::MinLength(100))?;
+            assert_eq!(filter.process()?.rows_matched, 0);
+            assert_eq!(std::fs::read_to_string(output.path())?, ">z\nGG");
+            let mut filter = SeqFilter::new(fasta.path().to_owned(), output.path().to_owned(), append)?;
+            filter.add_condition(SeqCondition::GcRange { min: 0.5, max: 1.0 })?;
+            filter.process()?;
+            assert_eq!(std::fs::read_to_string(output.path())?, ">z\nGG\n>a x\nACGTGC\n");
+            filter.add_condition(SeqCondition::MinQuality(20))?;
+            assert!(matches!(filter.process(), Err(ExtractorError::Config(_))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_reads() -> Result<()> {
+        let (mut r1, mut r2) = (NamedTempFile::new()?, NamedTempFile::new()?);
+        for i in 0..100 {
+            let (q1, q2) = (if i % 4 == 1 { "#" } else { "I" }, if i % 4 == 2 { "#" } else { "I" });
+            write!(r1, "@pair{i}/1\nACGTACGT\n+\n{}\n", q1.repeat(8))?;
+            write!(r2, "@pair{i}/2\nTTGCAAGC\n+\n{}\n", q2.repeat(8))?;
+        }
+        let (out1, out2) = (NamedTempFile::new()?, NamedTempFile::new()?);
+        let config = Config { chunk_size: 40, on_existing_output: ExistingOutput::Overwrite, ..Config::default() };
+        let mut filter = SeqFilter::new(r1.path().to_owned(), out1.path().to_owned(), config.clone())?
+            .with_mate(r2.path().to_owned(), out2.path().to_owned());
+        filter.add_condition(SeqCondition::MinQuality(30))?;
+        let stats = filter.process()?;
+        let expected = PairStats { pairs_processed: 100, pairs_kept: 50, orphaned_first: 25, orphaned_second: 25 };
+        assert_eq!(stats.pairs, Some(expected));
+        let kept: Vec<Vec<String>> = [out1.path(), out2.path()]
+            .iter()
+            .map(|path| Ok(FastqReader::from_path(path)?.map(|r| r.unwrap().id().to_string()).collect()))
+            .collect::<Result<_>>()?;
+        assert_eq!(&kept[0][..2], ["pair0/1", "pair3/1"]);
+        assert_eq!(&kept[1][..2], ["pair0/2", "pair3/2"]);
+
+        writeln!(r2, "@extra/2\nA\n+\nI")?;
+        let err = filter.process().unwrap_err();
+        assert!(matches!(err, ExtractorError::InvalidDataFormat { row: Some(101), .. }), "{err}");
+        Ok(())
+    }
+}
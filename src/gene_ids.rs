@@ -0,0 +1,338 @@
+//! Gene identifier mapping: load an HGNC or Ensembl table linking gene
+//! symbols to Ensembl gene IDs, then match genes by either form, or append
+//! the canonical symbol to matched rows. A panel of genes can be queried in
+//! one run, with each row tagged by the gene it matched.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::transform::RowTransform;
+use crate::Result;
+
+/// Columns of the HGNC complete set (`hgnc_complete_set.txt`) read by `load_hgnc`
+const HGNC_SYMBOL: &str = "symbol";
+const HGNC_ENSEMBL: &str = "ensembl_gene_id";
+const HGNC_ALIASES: [&str; 2] = ["prev_symbol", "alias_symbol"];
+
+/// Gene symbols and Ensembl gene IDs, held in memory. Ensembl IDs are matched
+/// without their version suffix, so `ENSG00000141510.17` is `ENSG00000141510`.
+#[derive(Debug, Default)]
+pub struct GeneIdMap {
+    /// Canonical symbol by symbol, alias or Ensembl ID
+    canonical: HashMap<String, String>,
+    /// Ensembl ID by canonical symbol
+    ensembl: HashMap<String, String>,
+}
+
+impl GeneIdMap {
+    /// Load the HGNC complete set: tab-separated, with approved symbols in
+    /// `symbol` and Ensembl IDs in `ensembl_gene_id`. Previous and alias
+    /// symbols (`prev_symbol`, `alias_symbol`, separated by `|`) map to the
+    /// approved symbol unless they are ambiguous.
+    pub fn load_hgnc(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Self::read(file, b'\t', HGNC_SYMBOL, HGNC_ENSEMBL, &HGNC_ALIASES)
+    }
+
+    /// Load a mapping table with a header row, such as an Ensembl BioMart
+    /// export, taking symbols and Ensembl IDs from the named columns
+    pub fn load(path: &Path, delimiter: u8, symbol_column: &str, ensembl_column: &str) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Self::from_reader(file, delimiter, symbol_column, ensembl_column)
+    }
+
+    /// Read a mapping table with a header row
+    pub fn from_reader<R: Read>(input: R, delimiter: u8, symbol_column: &str, ensembl_column: &str) -> Result<Self> {
+        Self::read(input, delimiter, symbol_column, ensembl_column, &[])
+    }
+
+    /// Read a table, also mapping the `|`-separated symbols of those
+    /// `alias_columns` that it has
+    fn read<R: Read>(
+        input: R,
+        delimiter: u8,
+        symbol_column: &str,
+        ensembl_column: &str,
+        alias_columns: &[&str],
+    ) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let names: Vec<String> = header_names(reader.byte_headers()?).collect();
+        let position = |column: &str| {
+            names
+                .iter()
+                .position(|name| name == column)
+                .ok_or_else(|| ExtractorError::column_not_found(column, &names))
+        };
+        let (symbol_idx, ensembl_idx) = (position(symbol_column)?, position(ensembl_column)?);
+        let alias_idx: Vec<usize> = alias_columns.iter().filter_map(|column| position(column).ok()).collect();
+
+        let mut map = Self::default();
+        let mut aliases: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let field = |idx: usize| String::from_utf8_lossy(record.get(idx).unwrap_or_default()).trim().to_string();
+            let symbol = field(symbol_idx);
+            if symbol.is_empty() {
+                continue;
+            }
+            let ensembl = unversioned(&field(ensembl_idx)).to_string();
+            if !ensembl.is_empty() {
+                map.canonical.entry(ensembl.clone()).or_insert_with(|| symbol.clone());
+                map.ensembl.entry(symbol.clone()).or_insert(ensembl);
+            }
+            for &idx in &alias_idx {
+                for alias in field(idx).split('|').map(str::trim).filter(|alias| !alias.is_empty()) {
+                    aliases.entry(alias.to_string()).or_default().insert(symbol.clone());
+                }
+            }
+            map.canonical.insert(symbol.clone(), symbol);
+        }
+        // Approved symbols win over aliases, and an alias of several genes maps to none
+        for (alias, symbols) in aliases {
+            if symbols.len() == 1 && !map.canonical.contains_key(&alias) {
+                map.canonical.insert(alias, symbols.into_iter().next().expect("one symbol"));
+            }
+        }
+        Ok(map)
+    }
+
+    /// Number of canonical symbols with an Ensembl ID
+    pub fn len(&self) -> usize {
+        self.ensembl.len()
+    }
+
+    /// Whether the table mapped no genes
+    pub fn is_empty(&self) -> bool {
+        self.ensembl.is_empty()
+    }
+
+    /// Canonical symbol for a symbol, alias or Ensembl ID
+    pub fn symbol(&self, id: &str) -> Option<&str> {
+        self.canonical.get(unversioned(id.trim())).map(String::as_str)
+    }
+
+    /// Ensembl gene ID for a symbol, alias or Ensembl ID
+    pub fn ensembl_id(&self, id: &str) -> Option<&str> {
+        self.ensembl.get(self.symbol(id)?).map(String::as_str)
+    }
+}
+
+/// Read a gene panel: one gene symbol or Ensembl ID per line. Blank lines
+/// and lines starting with `#` are skipped.
+pub fn load_gene_list(path: &Path) -> Result<Vec<String>> {
+    let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+    let mut genes = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| ExtractorError::io_error(e, path))?;
+        let gene = line.trim();
+        if !gene.is_empty() && !gene.starts_with('#') {
+            genes.push(gene.to_string());
+        }
+    }
+    Ok(genes)
+}
+
+/// `id` without an Ensembl version suffix (`ENSG00000141510.17`)
+fn unversioned(id: &str) -> &str {
+    match id.split_once('.') {
+        Some((stable, version)) if id.starts_with("ENS") && version.bytes().all(|b| b.is_ascii_digit()) => stable,
+        _ => id,
+    }
+}
+
+/// Index of `column` in the main input, or `ColumnNotFound`
+fn column_index(column: &str, headers: &HashMap<String, usize>) -> Result<usize> {
+    headers
+        .get(column)
+        .copied()
+        .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))
+}
+
+/// Keeps rows whose `column` names one of the genes, by symbol, alias or
+/// Ensembl ID. Values the map doesn't know only match exactly.
+pub struct GeneFilter {
+    column: String,
+    map: Arc<GeneIdMap>,
+    /// The genes as given, by canonical symbol (or the name as given when unknown)
+    genes: HashMap<String, String>,
+}
+
+impl GeneFilter {
+    /// Keep rows whose `column` is one of `genes`
+    pub fn new<I, S>(column: String, genes: I, map: Arc<GeneIdMap>) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let genes = genes
+            .into_iter()
+            .map(|gene| {
+                let gene = gene.as_ref().trim();
+                (map.symbol(gene).unwrap_or(gene).to_string(), gene.to_string())
+            })
+            .collect();
+        Self { column, map, genes }
+    }
+
+    /// Transform that tags each row with the gene it matched, so a panel
+    /// queried in one run can be split up again, e.g. with `partition_by`
+    pub fn query_gene(&self) -> QueryGene {
+        QueryGene {
+            column: self.column.clone(),
+            map: self.map.clone(),
+            genes: self.genes.clone(),
+            output_column: "query_gene".to_string(),
+        }
+    }
+}
+
+/// The gene of `genes` that `value` names, as it was given
+fn matched<'g>(map: &GeneIdMap, genes: &'g HashMap<String, String>, value: &[u8]) -> Option<&'g str> {
+    let value = String::from_utf8_lossy(value);
+    let value = value.trim();
+    genes.get(map.symbol(value).unwrap_or(value)).map(String::as_str)
+}
+
+impl Filter for GeneFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = row.get(column_index(&self.column, headers)?).unwrap_or_default();
+        Ok(matched(&self.map, &self.genes, value).is_some())
+    }
+
+    fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    fn description(&self) -> String {
+        let mut genes: Vec<&str> = self.genes.keys().map(String::as_str).collect();
+        genes.sort_unstable();
+        format!("{} is {}", self.column, genes.join(" or "))
+    }
+}
+
+/// Row transform that appends the canonical symbol of the gene in `column`,
+/// or an empty value if the map doesn't know it
+pub struct GeneSymbol {
+    column: String,
+    map: Arc<GeneIdMap>,
+    output_column: String,
+}
+
+impl GeneSymbol {
+    /// Append the symbol for `column` as `gene_symbol`
+    pub fn new(column: String, map: Arc<GeneIdMap>) -> Self {
+        Self { column, map, output_column: "gene_symbol".to_string() }
+    }
+
+    /// Name the appended column (default: `gene_symbol`)
+    pub fn named(mut self, output_column: &str) -> Self {
+        self.output_column = output_column.to_string();
+        self
+    }
+}
+
+impl RowTransform for GeneSymbol {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.output_column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = String::from_utf8_lossy(row.get(column_index(&self.column, headers)?).unwrap_or_default());
+        let symbol = self.map.symbol(&value).unwrap_or_default().to_string();
+        row.push_field(symbol.as_bytes());
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("append gene symbol of {} as {}", self.column, self.output_column)
+    }
+}
+
+/// Row transform that appends the gene of a `GeneFilter` panel a row
+/// matched, as the filter was given it, or an empty value. From
+/// `GeneFilter::query_gene`.
+pub struct QueryGene {
+    column: String,
+    map: Arc<GeneIdMap>,
+    genes: HashMap<String, String>,
+    output_column: String,
+}
+
+impl QueryGene {
+    /// Name the appended column (default: `query_gene`)
+    pub fn named(mut self, output_column: &str) -> Self {
+        self.output_column = output_column.to_string();
+        self
+    }
+}
+
+impl RowTransform for QueryGene {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.output_column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = row.get(column_index(&self.column, headers)?).unwrap_or_default();
+        let query = matched(&self.map, &self.genes, value).unwrap_or_default().to_string();
+        row.push_field(query.as_bytes());
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("append queried gene of {} as {}", self.column, self.output_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HGNC: &str = "\
+hgnc_id\tsymbol\tensembl_gene_id\tprev_symbol\talias_symbol
+HGNC:11998\tTP53\tENSG00000141510\t\tp53|LFS1
+HGNC:613\tAPOE\tENSG00000130203\t\tAD2
+HGNC:1100\tBRCA1\tENSG00000012048\tRNF53\tBRCC1|PPP1R53
+HGNC:9999\tFAKE1\tENSG00000000001\t\tAD2
+";
+
+    #[test]
+    fn test_gene_id_mapping() -> Result<()> {
+        let map = Arc::new(GeneIdMap::read(HGNC.as_bytes(), b'\t', HGNC_SYMBOL, HGNC_ENSEMBL, &HGNC_ALIASES)?);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.symbol("ENSG00000141510.17"), Some("TP53"));
+        assert_eq!(map.symbol("RNF53"), Some("BRCA1"));
+        assert_eq!(map.symbol("AD2"), None, "ambiguous alias");
+        assert_eq!(map.ensembl_id("p53"), Some("ENSG00000141510"));
+
+        let headers: HashMap<String, usize> = [("gene".to_string(), 0)].into_iter().collect();
+        let filter = GeneFilter::new("gene".to_string(), ["TP53", "MYC"], map.clone());
+        for (value, kept) in [("TP53", true), ("ENSG00000141510", true), ("p53", true), ("MYC", true), ("APOE", false)] {
+            assert_eq!(filter.apply(&ByteRecord::from(vec![value]), &headers)?, kept, "{value}");
+        }
+        assert_eq!(filter.description(), "gene is MYC or TP53");
+
+        let symbol = GeneSymbol::new("gene".to_string(), map);
+        assert_eq!(symbol.header(&ByteRecord::from(vec!["gene"])), Some(ByteRecord::from(vec!["gene", "gene_symbol"])));
+        let mut row = ByteRecord::from(vec!["ENSG00000012048.23"]);
+        assert!(symbol.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["ENSG00000012048.23", "BRCA1"]));
+        let mut row = ByteRecord::from(vec!["ENSG99999999999"]);
+        symbol.apply(&mut row, &headers)?;
+        assert_eq!(row, ByteRecord::from(vec!["ENSG99999999999", ""]));
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! Cross-row filters evaluated in two passes: the first counts rows per group,
+//! the second keeps the rows of groups that qualified, e.g. every row of genes
+//! with at least 3 variants at p < 5e-8.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock};
+
+use csv::ByteRecord;
+
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::Result;
+
+/// A filter that has to see the whole input before it can decide on a row.
+/// `BioFilter` feeds every row to `observe` in a first pass, then calls
+/// `finish` before filtering.
+pub(crate) trait Prepass: Filter {
+    /// Look at one row of the first pass
+    fn observe(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<()>;
+
+    /// End the first pass, replacing the result of any earlier one
+    fn finish(&self);
+}
+
+/// Field of `column`, trimmed, or `ColumnNotFound`
+pub(crate) fn field<'r>(row: &'r ByteRecord, column: &str, headers: &HashMap<String, usize>) -> Result<&'r [u8]> {
+    let idx = *headers
+        .get(column)
+        .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))?;
+    Ok(row.get(idx).unwrap_or_default().trim_ascii())
+}
+
+/// Keeps the rows of groups (distinct values of `group_column`) that have at
+/// least `min_rows` rows matching a predicate. Add it with
+/// `BioFilter::add_group_filter`, which runs the counting pass first.
+pub struct GroupFilter {
+    group_column: String,
+    min_rows: u64,
+    /// Rows counted towards `min_rows`; every row when `None`
+    predicate: Option<Box<dyn Filter>>,
+    /// Rows per group so far in the counting pass
+    counts: Mutex<HashMap<Vec<u8>, u64>>,
+    /// Groups that qualified in the counting pass
+    qualifying: RwLock<HashSet<Vec<u8>>>,
+}
+
+impl GroupFilter {
+    /// Keep groups with at least `min_rows` rows
+    pub fn new(group_column: String, min_rows: u64) -> Self {
+        Self {
+            group_column,
+            min_rows,
+            predicate: None,
+            counts: Mutex::new(HashMap::new()),
+            qualifying: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Only count rows matching `predicate`; all rows of a qualifying group
+    /// are kept, matching or not
+    pub fn counting(mut self, predicate: Box<dyn Filter>) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+}
+
+impl Prepass for GroupFilter {
+    fn observe(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<()> {
+        let counted = match &self.predicate {
+            Some(predicate) => predicate.apply(row, headers)?,
+            None => true,
+        };
+        if !counted {
+            return Ok(());
+        }
+        let group = field(row, &self.group_column, headers)?;
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        match counts.get_mut(group) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(group.to_vec(), 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self) {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap_or_else(|e| e.into_inner()));
+        let qualifying = counts
+            .into_iter()
+            .filter_map(|(group, count)| (count >= self.min_rows).then_some(group))
+            .collect();
+        *self.qualifying.write().unwrap_or_else(|e| e.into_inner()) = qualifying;
+    }
+}
+
+impl Filter for GroupFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let group = field(row, &self.group_column, headers)?;
+        Ok(self.qualifying.read().unwrap_or_else(|e| e.into_inner()).contains(group))
+    }
+
+    fn column_name(&self) -> &str {
+        &self.group_column
+    }
+
+    fn description(&self) -> String {
+        match &self.predicate {
+            Some(predicate) => format!(
+                "{} with at least {} rows where {}",
+                self.group_column,
+                self.min_rows,
+                predicate.description()
+            ),
+            None => format!("{} with at least {} rows", self.group_column, self.min_rows),
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Effect-allele harmonization: align the alleles of summary statistics with
+//! a reference panel, flipping effect sizes and allele frequencies where the
+//! effect and other alleles are swapped, and flagging strand-ambiguous SNPs.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::transform::{replace_field, RowTransform};
+use crate::variant_ids::chrom_name;
+use crate::Result;
+
+/// Reference and alternate allele of a panel variant
+type Alleles = (String, String);
+
+/// Variants of a reference panel, held in memory. The panel's alternate
+/// allele is the effect allele rows are aligned to.
+#[derive(Debug, Default)]
+pub struct ReferencePanel {
+    /// Alleles by (normalized chromosome, position)
+    loci: HashMap<(String, u64), Vec<Alleles>>,
+}
+
+impl ReferencePanel {
+    /// Load a table with a header row and chromosome, position, reference
+    /// and alternate allele columns, named in that order by `columns`
+    pub fn load(path: &Path, delimiter: u8, columns: [&str; 4]) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Self::from_reader(file, delimiter, columns)
+    }
+
+    /// Read a table with a header row; see `load`. Rows without a valid
+    /// locus are skipped.
+    pub fn from_reader<R: Read>(input: R, delimiter: u8, columns: [&str; 4]) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let names: Vec<String> = header_names(reader.byte_headers()?).collect();
+        let indices = columns
+            .iter()
+            .map(|&column| {
+                names
+                    .iter()
+                    .position(|name| name == column)
+                    .ok_or_else(|| ExtractorError::column_not_found(column, &names))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut panel = Self::default();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let field = |i: usize| String::from_utf8_lossy(record.get(indices[i]).unwrap_or_default()).into_owned();
+            let Some(key) = locus(&field(0), &field(1)) else { continue };
+            panel.loci.entry(key).or_default().push((allele(&field(2)), allele(&field(3))));
+        }
+        Ok(panel)
+    }
+
+    /// Number of loci in the panel
+    pub fn len(&self) -> usize {
+        self.loci.len()
+    }
+
+    /// Whether the panel has no loci
+    pub fn is_empty(&self) -> bool {
+        self.loci.is_empty()
+    }
+
+    /// How the `effect`/`other` alleles at a locus compare with the panel
+    pub fn compare(&self, chrom: &str, pos: &str, effect: &str, other: &str) -> Harmonization {
+        self.matching(chrom, pos, effect, other).0
+    }
+
+    /// Comparison with the panel, and the panel alleles that matched
+    fn matching(&self, chrom: &str, pos: &str, effect: &str, other: &str) -> (Harmonization, Option<&Alleles>) {
+        let Some(alleles) = locus(chrom, pos).and_then(|key| self.loci.get(&key)) else {
+            return (Harmonization::Missing, None);
+        };
+        let (effect, other) = (allele(effect), allele(other));
+        let find = |effect: &str, other: &str| {
+            alleles.iter().find_map(|panel| {
+                let (ref_allele, alt_allele) = (panel.0.as_str(), panel.1.as_str());
+                if (alt_allele, ref_allele) == (effect, other) {
+                    Some((false, panel))
+                } else if (ref_allele, alt_allele) == (effect, other) {
+                    Some((true, panel))
+                } else {
+                    None
+                }
+            })
+        };
+
+        // An A/T or C/G SNP reads the same on both strands, so a swap can't be told from a strand flip
+        if let Some((_, panel)) = find(&effect, &other).filter(|_| reverse_complement(&effect).as_ref() == Some(&other)) {
+            return (Harmonization::Ambiguous, Some(panel));
+        }
+        match find(&effect, &other) {
+            Some((false, panel)) => (Harmonization::Aligned, Some(panel)),
+            Some((true, panel)) => (Harmonization::Flipped, Some(panel)),
+            None => match reverse_complement(&effect).zip(reverse_complement(&other)) {
+                Some((effect, other)) => match find(&effect, &other) {
+                    Some((false, panel)) => (Harmonization::Strand, Some(panel)),
+                    Some((true, panel)) => (Harmonization::StrandFlipped, Some(panel)),
+                    None => (Harmonization::Mismatch, None),
+                },
+                None => (Harmonization::Mismatch, None),
+            },
+        }
+    }
+}
+
+/// Normalized (chromosome, position) key
+fn locus(chrom: &str, pos: &str) -> Option<(String, u64)> {
+    chrom_name(chrom).zip(pos.trim().parse().ok())
+}
+
+/// Allele in upper case, without surrounding whitespace
+fn allele(allele: &str) -> String {
+    allele.trim().to_ascii_uppercase()
+}
+
+/// Reverse complement of an upper-case allele; `None` unless it is all `ACGT`
+fn reverse_complement(allele: &str) -> Option<String> {
+    allele
+        .bytes()
+        .rev()
+        .map(|base| match base {
+            b'A' => Some('T'),
+            b'T' => Some('A'),
+            b'C' => Some('G'),
+            b'G' => Some('C'),
+            _ => None,
+        })
+        .collect::<Option<String>>()
+        .filter(|complement| !complement.is_empty())
+}
+
+/// How a row's alleles compare with the reference panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Harmonization {
+    /// The effect allele is the panel's alternate allele
+    Aligned,
+    /// The effect and other alleles are swapped
+    Flipped,
+    /// The alleles are on the opposite strand
+    Strand,
+    /// The alleles are on the opposite strand and swapped
+    StrandFlipped,
+    /// An A/T or C/G SNP, whose strand can't be told from its alleles
+    Ambiguous,
+    /// The panel has the locus with other alleles
+    Mismatch,
+    /// The panel doesn't have the locus
+    Missing,
+}
+
+impl Harmonization {
+    /// Name written to the flag column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Harmonization::Aligned => "aligned",
+            Harmonization::Flipped => "flipped",
+            Harmonization::Strand => "strand",
+            Harmonization::StrandFlipped => "strand_flipped",
+            Harmonization::Ambiguous => "ambiguous",
+            Harmonization::Mismatch => "mismatch",
+            Harmonization::Missing => "missing",
+        }
+    }
+
+    /// Whether the row was aligned with the panel
+    pub fn is_resolved(&self) -> bool {
+        matches!(
+            self,
+            Harmonization::Aligned | Harmonization::Flipped | Harmonization::Strand | Harmonization::StrandFlipped
+        )
+    }
+
+    /// Whether effects and frequencies were flipped
+    fn swaps(&self) -> bool {
+        matches!(self, Harmonization::Flipped | Harmonization::StrandFlipped)
+    }
+}
+
+/// How a column changes when the alleles are swapped
+#[derive(Debug, Clone, Copy)]
+enum Flip {
+    /// Effect sizes such as beta or z: negated
+    Sign,
+    /// Ratios such as odds ratios: inverted
+    Ratio,
+    /// Effect allele frequencies: one minus the value
+    Frequency,
+}
+
+impl Flip {
+    /// The flipped value, or `None` to keep values that aren't numbers
+    fn apply(self, value: &str) -> Option<String> {
+        let value = value.trim();
+        let x: f64 = value.parse().ok().filter(|x: &f64| x.is_finite())?;
+        match self {
+            Flip::Sign if x == 0.0 => None,
+            Flip::Sign => Some(value.strip_prefix('-').map_or_else(|| format!("-{value}"), str::to_string)),
+            Flip::Ratio => (x != 0.0).then(|| (1.0 / x).to_string()),
+            Flip::Frequency => {
+                // Keep the precision of the input, so 0.3 becomes 0.7 rather than 0.7000000000000001
+                let decimals = value.split_once('.').map(|(_, fraction)| fraction.len());
+                match decimals.filter(|_| !value.contains(['e', 'E'])) {
+                    Some(decimals) => Some(format!("{:.*}", decimals, 1.0 - x)),
+                    None => Some((1.0 - x).to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Row transform that aligns effect and other alleles with a reference
+/// panel. Swapped rows get the panel's alleles and flipped effects and
+/// frequencies; rows on the opposite strand get the panel's alleles. A
+/// column of `Harmonization` flags is appended.
+pub struct Harmonize {
+    panel: Arc<ReferencePanel>,
+    chrom_column: String,
+    pos_column: String,
+    effect_column: String,
+    other_column: String,
+    flips: Vec<(String, Flip)>,
+    output_column: String,
+    drop_unresolved: bool,
+}
+
+impl Harmonize {
+    /// Align the `chromosome`, `base_pair_location`, `effect_allele` and
+    /// `other_allele` columns (as named by `Dialect::Sumstats`) with `panel`
+    pub fn new(panel: Arc<ReferencePanel>) -> Self {
+        Self {
+            panel,
+            chrom_column: "chromosome".to_string(),
+            pos_column: "base_pair_location".to_string(),
+            effect_column: "effect_allele".to_string(),
+            other_column: "other_allele".to_string(),
+            flips: Vec::new(),
+            output_column: "harmonization".to_string(),
+            drop_unresolved: false,
+        }
+    }
+
+    /// Read the locus and alleles from other columns
+    pub fn with_columns(mut self, chrom: &str, pos: &str, effect_allele: &str, other_allele: &str) -> Self {
+        self.chrom_column = chrom.to_string();
+        self.pos_column = pos.to_string();
+        self.effect_column = effect_allele.to_string();
+        self.other_column = other_allele.to_string();
+        self
+    }
+
+    /// Negate `column` (a beta or z-score) when the alleles are swapped
+    pub fn flip_sign(mut self, column: &str) -> Self {
+        self.flips.push((column.to_string(), Flip::Sign));
+        self
+    }
+
+    /// Invert `column` (an odds or hazard ratio) when the alleles are swapped
+    pub fn flip_ratio(mut self, column: &str) -> Self {
+        self.flips.push((column.to_string(), Flip::Ratio));
+        self
+    }
+
+    /// Replace `column` (an effect allele frequency) with one minus its value
+    /// when the alleles are swapped
+    pub fn flip_frequency(mut self, column: &str) -> Self {
+        self.flips.push((column.to_string(), Flip::Frequency));
+        self
+    }
+
+    /// Name the appended flag column (default: `harmonization`)
+    pub fn named(mut self, output_column: &str) -> Self {
+        self.output_column = output_column.to_string();
+        self
+    }
+
+    /// Drop rows that are ambiguous, mismatched or missing from the panel
+    /// instead of only flagging them (default: false)
+    pub fn drop_unresolved(mut self, drop: bool) -> Self {
+        self.drop_unresolved = drop;
+        self
+    }
+}
+
+/// Index of `column` in the main input, or `ColumnNotFound`
+fn column_index(column: &str, headers: &HashMap<String, usize>) -> Result<usize> {
+    headers
+        .get(column)
+        .copied()
+        .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))
+}
+
+impl RowTransform for Harmonize {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.output_column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let [chrom, pos, effect, other] = [&self.chrom_column, &self.pos_column, &self.effect_column, &self.other_column]
+            .map(|column| column_index(column, headers));
+        let (effect, other) = (effect?, other?);
+        let field = |row: &ByteRecord, idx: usize| String::from_utf8_lossy(row.get(idx).unwrap_or_default()).into_owned();
+        let [chrom, pos, effect_value, other_value] = [chrom?, pos?, effect, other].map(|idx| field(row, idx));
+        let (status, panel) = self.panel.matching(&chrom, &pos, &effect_value, &other_value);
+
+        if let Some((ref_allele, alt_allele)) = panel.filter(|_| status.is_resolved()) {
+            *row = replace_field(row, effect, alt_allele.as_bytes());
+            *row = replace_field(row, other, ref_allele.as_bytes());
+        }
+        if status.swaps() {
+            for (column, flip) in &self.flips {
+                let idx = column_index(column, headers)?;
+                if let Some(value) = flip.apply(&field(row, idx)) {
+                    *row = replace_field(row, idx, value.as_bytes());
+                }
+            }
+        }
+        row.push_field(status.as_str().as_bytes());
+        Ok(status.is_resolved() || !self.drop_unresolved)
+    }
+
+    fn description(&self) -> String {
+        format!("harmonize {}/{} with reference panel", self.effect_column, self.other_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PANEL: &str = "\
+CHROM\tPOS\tREF\tALT
+1\t1000\tA\tG
+1\t2000\tC\tT
+1\t3000\tA\tT
+2\t500\tG\tGA
+";
+
+    #[test]
+    fn test_harmonize() -> Result<()> {
+        let panel = Arc::new(ReferencePanel::from_reader(PANEL.as_bytes(), b'\t', ["CHROM", "POS", "REF", "ALT"])?);
+        assert_eq!(panel.len(), 4);
+        for (chrom, pos, effect, other, status) in [
+            ("chr1", "1000", "g", "a", Harmonization::Aligned),
+            ("1", "1000", "A", "G", Harmonization::Flipped),
+            ("1", "2000", "A", "G", Harmonization::Strand),
+            ("1", "2000", "G", "A", Harmonization::StrandFlipped),
+            ("1", "3000", "T", "A", Harmonization::Ambiguous),
+            ("1", "3000", "A", "C", Harmonization::Mismatch),
+            ("2", "500", "TC", "C", Harmonization::Strand),
+            ("3", "1000", "G", "A", Harmonization::Missing),
+        ] {
+            assert_eq!(panel.compare(chrom, pos, effect, other), status, "{chrom}:{pos}:{other}:{effect}");
+        }
+
+        let names = ["chromosome", "base_pair_location", "effect_allele", "other_allele", "beta", "odds_ratio", "effect_allele_frequency"];
+        let headers: HashMap<String, usize> = names.iter().enumerate().map(|(i, name)| (name.to_string(), i)).collect();
+        let harmonize = Harmonize::new(panel)
+            .flip_sign("beta")
+            .flip_ratio("odds_ratio")
+            .flip_frequency("effect_allele_frequency");
+        assert_eq!(harmonize.header(&ByteRecord::from(names.to_vec())).map(|h| h.len()), Some(8));
+
+        let mut row = ByteRecord::from(vec!["1", "2000", "g", "a", "0.25", "1.25", "0.3"]);
+        assert!(harmonize.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["1", "2000", "T", "C", "-0.25", "0.8", "0.7", "strand_flipped"]));
+        let mut row = ByteRecord::from(vec!["1", "1000", "G", "A", "-0.1", "NA", "0.12"]);
+        assert!(harmonize.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["1", "1000", "G", "A", "-0.1", "NA", "0.12", "aligned"]));
+
+        let harmonize = harmonize.drop_unresolved(true);
+        let mut row = ByteRecord::from(vec!["1", "3000", "A", "T", "0.5", "1.6", "0.4"]);
+        assert!(!harmonize.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["1", "3000", "A", "T", "0.5", "1.6", "0.4", "ambiguous"]));
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+//! Protein-level HGVS notation (`p.Arg117His`), the variant consequence it
+//! implies and SnpEff impact levels, so protein-altering variants can be
+//! selected without regexes.
+
+use serde::{Deserialize, Serialize};
+
+/// Amino acids as (three-letter, one-letter) codes; `Ter` is the stop codon
+const AMINO_ACIDS: &[(&str, char)] = &[
+    ("Ala", 'A'), ("Arg", 'R'), ("Asn", 'N'), ("Asp", 'D'), ("Cys", 'C'),
+    ("Gln", 'Q'), ("Glu", 'E'), ("Gly", 'G'), ("His", 'H'), ("Ile", 'I'),
+    ("Leu", 'L'), ("Lys", 'K'), ("Met", 'M'), ("Phe", 'F'), ("Pro", 'P'),
+    ("Ser", 'S'), ("Thr", 'T'), ("Trp", 'W'), ("Tyr", 'Y'), ("Val", 'V'),
+    ("Sec", 'U'), ("Pyl", 'O'), ("Xaa", 'X'), ("Ter", '*'),
+];
+
+/// Effect of a variant on the protein, most severe first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Consequence {
+    /// A premature stop codon (nonsense)
+    StopGained,
+    /// Reading frame shifted by an indel
+    Frameshift,
+    /// The stop codon replaced by an amino acid
+    StopLost,
+    /// The initiator methionine changed
+    StartLost,
+    /// Amino acids removed without a frameshift
+    InframeDeletion,
+    /// Amino acids added without a frameshift
+    InframeInsertion,
+    /// One amino acid replaced by another
+    Missense,
+    /// Protein sequence unchanged
+    Synonymous,
+}
+
+impl Consequence {
+    /// Every consequence that changes the protein sequence
+    pub const PROTEIN_ALTERING: &'static [Consequence] = &[
+        Consequence::StopGained,
+        Consequence::Frameshift,
+        Consequence::StopLost,
+        Consequence::StartLost,
+        Consequence::InframeDeletion,
+        Consequence::InframeInsertion,
+        Consequence::Missense,
+    ];
+
+    /// Consequence of a Sequence Ontology effect term as written by SnpEff or
+    /// VEP, e.g. `missense_variant`. Terms joined with `&` or `,` give the
+    /// most severe one recognised.
+    pub fn from_effect(effect: &str) -> Option<Self> {
+        effect
+            .split(['&', ','])
+            .filter_map(|term| match term.trim() {
+                "stop_gained" => Some(Consequence::StopGained),
+                "frameshift_variant" => Some(Consequence::Frameshift),
+                "stop_lost" => Some(Consequence::StopLost),
+                "start_lost" => Some(Consequence::StartLost),
+                "inframe_deletion" | "conservative_inframe_deletion" | "disruptive_inframe_deletion" => {
+                    Some(Consequence::InframeDeletion)
+                }
+                "inframe_insertion" | "conservative_inframe_insertion" | "disruptive_inframe_insertion" => {
+                    Some(Consequence::InframeInsertion)
+                }
+                "missense_variant" => Some(Consequence::Missense),
+                "synonymous_variant" | "stop_retained_variant" | "start_retained_variant" => {
+                    Some(Consequence::Synonymous)
+                }
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Consequence of a value that is either a protein HGVS change or an
+    /// effect term
+    pub fn infer(value: &str) -> Option<Self> {
+        match ProteinChange::parse(value) {
+            Some(change) => change.consequence(),
+            None => Self::from_effect(value),
+        }
+    }
+}
+
+/// SnpEff putative impact, ordered `Modifier < Low < Moderate < High`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Impact {
+    /// Non-coding or intergenic
+    Modifier,
+    /// Mostly harmless, e.g. synonymous
+    Low,
+    /// Non-disruptive but may change protein effectiveness, e.g. missense
+    Moderate,
+    /// Disruptive, e.g. stop gained or frameshift
+    High,
+}
+
+impl Impact {
+    /// Parse `HIGH`, `MODERATE`, `LOW` or `MODIFIER` (any case). Values joined
+    /// with `&` or `,` give the highest one recognised.
+    pub fn parse(value: &str) -> Option<Self> {
+        value
+            .split(['&', ','])
+            .filter_map(|level| match level.trim().to_ascii_uppercase().as_str() {
+                "HIGH" => Some(Impact::High),
+                "MODERATE" => Some(Impact::Moderate),
+                "LOW" => Some(Impact::Low),
+                "MODIFIER" => Some(Impact::Modifier),
+                _ => None,
+            })
+            .max()
+    }
+}
+
+impl From<Consequence> for Impact {
+    /// The impact SnpEff assigns to the consequence
+    fn from(consequence: Consequence) -> Self {
+        match consequence {
+            Consequence::StopGained
+            | Consequence::Frameshift
+            | Consequence::StopLost
+            | Consequence::StartLost => Impact::High,
+            Consequence::InframeDeletion | Consequence::InframeInsertion | Consequence::Missense => {
+                Impact::Moderate
+            }
+            Consequence::Synonymous => Impact::Low,
+        }
+    }
+}
+
+/// What a protein change does at its position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProteinEdit {
+    /// Replaced by this amino acid (`*` for a stop)
+    Substitution(char),
+    /// No change (`p.Arg117=`)
+    Synonymous,
+    /// Frameshift (`fs`, with or without the new amino acid and stop)
+    Frameshift,
+    /// Deletion of the residue or range
+    Deletion,
+    /// Insertion of these amino acids after the residue
+    Insertion(String),
+    /// Duplication of the residue or range
+    Duplication,
+    /// The residue or range replaced by these amino acids
+    DelIns(String),
+    /// The protein extended past the stop codon (`ext`)
+    Extension,
+    /// Effect unknown (`p.Met1?`)
+    Unknown,
+}
+
+/// A parsed protein HGVS change such as `p.Arg117His`, `p.R117H`,
+/// `p.(Gln5Ter)` or `p.Lys2_Gly5del`. Amino acids are one-letter codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProteinChange {
+    /// Reference amino acid at `position`
+    pub reference: char,
+    /// 1-based residue position
+    pub position: u32,
+    /// Last residue of a range (`_Gly5`), if any
+    pub end: Option<(char, u32)>,
+    /// The change itself
+    pub edit: ProteinEdit,
+}
+
+impl ProteinChange {
+    /// Parse the protein part of an HGVS expression. A transcript prefix
+    /// (`NP_000537.3:`) is ignored; `p.?`, `p.0` and anything unrecognised
+    /// give `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value.rsplit_once(':').map_or(value, |(_, change)| change);
+        let mut rest = value.strip_prefix("p.")?;
+        if let Some(inner) = rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+            rest = inner;
+        }
+
+        let (reference, position) = residue(&mut rest)?;
+        let end = match rest.strip_prefix('_') {
+            Some(range) => {
+                rest = range;
+                Some(residue(&mut rest)?)
+            }
+            None => None,
+        };
+
+        let edit = if rest == "=" {
+            ProteinEdit::Synonymous
+        } else if rest == "?" {
+            ProteinEdit::Unknown
+        } else if rest == "del" {
+            ProteinEdit::Deletion
+        } else if rest == "dup" {
+            ProteinEdit::Duplication
+        } else if let Some(inserted) = rest.strip_prefix("delins") {
+            ProteinEdit::DelIns(amino_acids(inserted)?)
+        } else if let Some(inserted) = rest.strip_prefix("ins") {
+            ProteinEdit::Insertion(amino_acids(inserted)?)
+        } else if rest.contains("fs") {
+            ProteinEdit::Frameshift
+        } else if rest.contains("ext") {
+            ProteinEdit::Extension
+        } else {
+            let alternate = amino_acid(&mut rest)?;
+            if !rest.is_empty() {
+                return None;
+            }
+            ProteinEdit::Substitution(alternate)
+        };
+        Some(Self { reference, position, end, edit })
+    }
+
+    /// Consequence of the change, or `None` when its effect is unknown
+    pub fn consequence(&self) -> Option<Consequence> {
+        let stop = self.reference == '*';
+        let start = self.reference == 'M' && self.position == 1;
+        Some(match &self.edit {
+            ProteinEdit::Unknown => return None,
+            ProteinEdit::Synonymous => Consequence::Synonymous,
+            ProteinEdit::Substitution(alt) if *alt == self.reference => Consequence::Synonymous,
+            ProteinEdit::Substitution(_) | ProteinEdit::Extension if stop => Consequence::StopLost,
+            ProteinEdit::Substitution(_) | ProteinEdit::Deletion if start => Consequence::StartLost,
+            ProteinEdit::Substitution('*') => Consequence::StopGained,
+            ProteinEdit::Substitution(_) => Consequence::Missense,
+            ProteinEdit::Extension => Consequence::StopLost,
+            ProteinEdit::Frameshift => Consequence::Frameshift,
+            ProteinEdit::Deletion => Consequence::InframeDeletion,
+            ProteinEdit::Insertion(inserted) | ProteinEdit::DelIns(inserted) if inserted.contains('*') => {
+                Consequence::StopGained
+            }
+            ProteinEdit::Insertion(_) | ProteinEdit::Duplication => Consequence::InframeInsertion,
+            ProteinEdit::DelIns(inserted) => {
+                let deleted = self.end.map_or(1, |(_, end)| end.saturating_sub(self.position) as usize + 1);
+                match inserted.chars().count().cmp(&deleted) {
+                    std::cmp::Ordering::Less => Consequence::InframeDeletion,
+                    std::cmp::Ordering::Equal => Consequence::Missense,
+                    std::cmp::Ordering::Greater => Consequence::InframeInsertion,
+                }
+            }
+        })
+    }
+}
+
+/// Take one amino acid (three-letter, one-letter, `*` or `X` for a stop) from
+/// the front of `rest`
+fn amino_acid(rest: &mut &str) -> Option<char> {
+    if let Some(&(code, letter)) = AMINO_ACIDS.iter().find(|(code, _)| rest.starts_with(code)) {
+        *rest = &rest[code.len()..];
+        return Some(letter);
+    }
+    let letter = rest.chars().next().filter(|c| c.is_ascii_uppercase() || *c == '*')?;
+    *rest = &rest[1..];
+    Some(if letter == 'X' { '*' } else { letter })
+}
+
+/// Take an amino acid and its position, e.g. `Arg117`
+fn residue(rest: &mut &str) -> Option<(char, u32)> {
+    let reference = amino_acid(rest)?;
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let position = rest[..digits].parse().ok()?;
+    *rest = &rest[digits..];
+    Some((reference, position))
+}
+
+/// A run of amino acids such as `GlyTer` or `GW`, as one-letter codes
+fn amino_acids(mut rest: &str) -> Option<String> {
+    let mut letters = String::new();
+    while !rest.is_empty() {
+        letters.push(amino_acid(&mut rest)?);
+    }
+    (!letters.is_empty()).then_some(letters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protein_consequence() {
+        let change = ProteinChange::parse("p.Arg117His").unwrap();
+        assert_eq!((change.reference, change.position), ('R', 117));
+        assert_eq!(change.edit, ProteinEdit::Substitution('H'));
+
+        let consequence = |value: &str| Consequence::infer(value);
+        assert_eq!(consequence("p.R117H"), Some(Consequence::Missense));
+        assert_eq!(consequence("NP_000483.3:p.(Arg117His)"), Some(Consequence::Missense));
+        assert_eq!(consequence("p.Leu10Leu"), Some(Consequence::Synonymous));
+        assert_eq!(consequence("p.Gly12="), Some(Consequence::Synonymous));
+        assert_eq!(consequence("p.Gln5Ter"), Some(Consequence::StopGained));
+        assert_eq!(consequence("p.Trp24*"), Some(Consequence::StopGained));
+        assert_eq!(consequence("p.Ter110GlnextTer17"), Some(Consequence::StopLost));
+        assert_eq!(consequence("p.Met1Val"), Some(Consequence::StartLost));
+        assert_eq!(consequence("p.Arg97ProfsTer23"), Some(Consequence::Frameshift));
+        assert_eq!(consequence("p.Lys2_Gly5del"), Some(Consequence::InframeDeletion));
+        assert_eq!(consequence("p.Lys2_Leu3insGlnSer"), Some(Consequence::InframeInsertion));
+        assert_eq!(consequence("p.Cys28_Lys29delinsTrp"), Some(Consequence::InframeDeletion));
+        assert_eq!(consequence("p.Met1?"), None);
+        assert_eq!(consequence("p.?"), None);
+
+        assert_eq!(consequence("missense_variant&splice_region_variant"), Some(Consequence::Missense));
+        assert_eq!(consequence("stop_gained,frameshift_variant"), Some(Consequence::StopGained));
+        assert_eq!(consequence("intron_variant"), None);
+    }
+}
@@ -1,13 +1,29 @@
 //! Indexing functionality for fast CSV data access.
 //! Provides file indexing and efficient row lookup capabilities.
 
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{self, File};
 use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use csv::{ByteRecord, ReaderBuilder};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh64::xxh64;
+use crate::core::header_names;
 use crate::error::{ExtractorError, IndexErrorKind};
-use crate::Result;
+#[cfg(feature = "object-store")]
+use crate::remote::RemoteObject;
+#[cfg(feature = "object-store")]
+use crate::RemoteConfig;
+#[cfg(feature = "parallel")]
+use crate::utils::SafeMmapOptions;
+use crate::progress::{CallbackProgress, Progress};
+use crate::utils::{self, CancellationToken, ChecksumAlgorithm, ReadSeek, ThreadPoolSource};
+use crate::{ProgressConfig, Result};
 
 /// Represents a position in the CSV file
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +36,15 @@ pub struct Position {
     pub row_number: u64,
 }
 
+/// Byte offset of a known row, used to seek close to an arbitrary row number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowCheckpoint {
+    /// Row number (same numbering as `Position::row_number`)
+    pub row_number: u64,
+    /// Byte offset of the start of that row
+    pub offset: u64,
+}
+
 /// Index metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexMetadata {
@@ -29,19 +54,165 @@ pub struct IndexMetadata {
     pub file_size: u64,
     /// File modification time at index creation
     pub modified_time: u64,
-    /// Checksum of the first few KB of the file
+    /// Checksum of the file, computed with `checksum_algorithm`
     pub file_checksum: u64,
+    /// Algorithm used for `file_checksum`
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
     /// Number of indexed rows
     pub row_count: u64,
     /// Header row position
     pub header_position: Position,
     /// Index creation timestamp
     pub created_at: u64,
+    /// Key statistics for each secondary index column
+    #[serde(default)]
+    pub key_summaries: HashMap<String, KeySummary>,
+}
+
+/// Cardinality and most frequent keys of a secondary index column, so callers
+/// can judge a column without scanning the file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySummary {
+    /// Number of distinct non-empty keys
+    pub cardinality: u64,
+    /// Number of rows with a non-empty key
+    pub rows: u64,
+    /// Most frequent keys with their row counts, most frequent first
+    pub top_keys: Vec<(String, u64)>,
 }
 
+impl KeySummary {
+    /// Summarize one secondary index, keeping the `top_k` most frequent keys
+    fn of(keys: &HashMap<String, Vec<Position>>, top_k: usize) -> Self {
+        let by_count = |(a, m): &(&String, u64), (b, n): &(&String, u64)| n.cmp(m).then_with(|| a.cmp(b));
+        let mut counts: Vec<(&String, u64)> = keys.iter().map(|(key, rows)| (key, rows.len() as u64)).collect();
+        let rows = counts.iter().map(|(_, count)| count).sum();
+        if counts.len() > top_k && top_k > 0 {
+            counts.select_nth_unstable_by(top_k - 1, by_count);
+        }
+        counts.truncate(top_k);
+        counts.sort_unstable_by(by_count);
+        Self {
+            cardinality: keys.len() as u64,
+            rows,
+            top_keys: counts.into_iter().map(|(key, count)| (key.clone(), count)).collect(),
+        }
+    }
+}
+
+/// Starts the footer ending a saved index, followed by the hex xxh64 of the JSON before it
+const INDEX_FOOTER: &[u8] = b"\n#extractor-index xxh64:";
+
+/// Split a saved index into its JSON and the checksum in its footer, or
+/// `None` if it has no complete footer
+fn split_footer(data: &[u8]) -> Option<(&[u8], u64)> {
+    let data = data.strip_suffix(b"\n")?;
+    let at = data.len().checked_sub(INDEX_FOOTER.len() + 16)?;
+    let (json, footer) = data.split_at(at);
+    let hex = footer.strip_prefix(INDEX_FOOTER)?;
+    let checksum = u64::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    Some((json, checksum))
+}
+
+/// Format version of a serialized index, without deserializing the rest
+fn format_version(json: &[u8]) -> serde_json::Result<u32> {
+    #[derive(Deserialize)]
+    struct Versioned {
+        #[serde(default)]
+        format_version: u32,
+    }
+    serde_json::from_slice::<Versioned>(json).map(|versioned| versioned.format_version)
+}
+
+/// Bring a serialized index from version `from` up to `INDEX_FORMAT_VERSION`,
+/// one version at a time
+fn migrate(index: &mut serde_json::Value, from: u32) {
+    for version in from..INDEX_FORMAT_VERSION {
+        match version {
+            // Fields added since version 0 (duplicate positions, row checkpoints,
+            // checksum algorithm, key summaries) have defaults, and key
+            // summaries are computed on load
+            0 => {}
+            _ => unreachable!("no migration from index format version {version}"),
+        }
+    }
+    if let Some(index) = index.as_object_mut() {
+        index.insert("format_version".to_string(), INDEX_FORMAT_VERSION.into());
+    }
+}
+
+/// Write `contents` to `path` followed by a footer with its checksum. The
+/// file is written beside `path` and renamed over it, so concurrent saves
+/// never interleave and readers only ever see a complete file.
+pub(crate) fn save_checked(path: &Path, mut contents: Vec<u8>) -> Result<()> {
+    let checksum = xxh64(&contents, 0);
+    contents.extend_from_slice(INDEX_FOOTER);
+    contents.extend_from_slice(format!("{checksum:016x}\n").as_bytes());
+
+    let temp = temp_path(path);
+    let written = File::create(&temp)
+        .and_then(|mut file| file.write_all(&contents).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temp, path));
+    written.map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        ExtractorError::io_error(e, path)
+    })
+}
+
+/// Read a file written by `save_checked`, returning the contents before its
+/// footer. A footer that doesn't match means the file was cut short or
+/// altered; files without one are returned whole.
+pub(crate) fn read_checked(path: &Path) -> Result<Vec<u8>> {
+    let mut data = fs::read(path).map_err(|e| ExtractorError::io_error(e, path))?;
+    match split_footer(&data) {
+        Some((json, checksum)) if xxh64(json, 0) != checksum => {
+            Err(ExtractorError::index_error(IndexErrorKind::Corrupted, Some(path)))
+        }
+        Some((json, _)) => {
+            data.truncate(json.len());
+            Ok(data)
+        }
+        None => Ok(data),
+    }
+}
+
+/// Temporary file beside `path` that no other save, in this process or another, uses
+fn temp_path(path: &Path) -> PathBuf {
+    static SAVES: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.{}.tmp", std::process::id(), SAVES.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+/// Rows between row-offset checkpoints by default
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1024;
+
+/// Number of most frequent keys kept per secondary column by default
+pub const DEFAULT_TOP_KEYS: usize = 20;
+
+/// How the index builder treats a primary key that appears on more than one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Keep {
+    /// Keep the first row for the key and ignore later ones
+    First,
+    /// Keep every row; repeats are stored in `FileIndex::duplicate_positions`
+    All,
+    /// Fail the build on the first duplicate (default)
+    #[default]
+    Error,
+}
+
+/// Version of the index file format written by `FileIndex::save`. Version 0
+/// is the layout used before indices were versioned.
+pub const INDEX_FORMAT_VERSION: u32 = 1;
+
 /// Main index structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileIndex {
+    /// Format version the index was read or built as
+    #[serde(default)]
+    pub format_version: u32,
     /// Index metadata
     pub metadata: IndexMetadata,
     /// Column to index mapping
@@ -50,8 +221,18 @@ pub struct FileIndex {
     pub primary_column: String,
     /// Row positions by primary key
     pub positions: HashMap<String, Position>,
+    /// Additional positions for primary keys that repeat (only with `Keep::All`).
+    /// The first occurrence stays in `positions`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub duplicate_positions: HashMap<String, Vec<Position>>,
+    /// Duplicate key policy the index was built with
+    #[serde(default)]
+    pub duplicate_policy: Keep,
     /// Secondary indices
     pub secondary_indices: HashMap<String, HashMap<String, Vec<Position>>>,
+    /// Periodic row-offset checkpoints, sorted by row number
+    #[serde(default)]
+    pub row_checkpoints: Vec<RowCheckpoint>,
 }
 
 impl FileIndex {
@@ -60,29 +241,59 @@ impl FileIndex {
         IndexBuilder::new(source_file, primary_column)
     }
 
-    /// Load an existing index from file
+    /// Load an existing index from file. A file whose footer checksum doesn't
+    /// match was cut short or altered and is rejected; indices saved before
+    /// the footer was added are read as plain JSON. Indices saved without key
+    /// summaries get them computed here.
     pub fn load(path: &Path) -> Result<Self> {
-        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
-        let reader = BufReader::new(file);
-        serde_json::from_reader(reader).map_err(|e| ExtractorError::index_error(
-            IndexErrorKind::InvalidFormat,
-            Some(path.to_owned())
-        ))
+        let json = &read_checked(path)?[..];
+        let invalid = |_| ExtractorError::index_error(IndexErrorKind::InvalidFormat, Some(path));
+        let mut index: Self = match format_version(json).map_err(invalid)? {
+            INDEX_FORMAT_VERSION => serde_json::from_slice(json).map_err(invalid)?,
+            found if found > INDEX_FORMAT_VERSION => {
+                return Err(ExtractorError::index_error(
+                    IndexErrorKind::UnsupportedVersion { found, supported: INDEX_FORMAT_VERSION },
+                    Some(path),
+                ));
+            }
+            found => {
+                let mut value = serde_json::from_slice(json).map_err(invalid)?;
+                migrate(&mut value, found);
+                serde_json::from_value(value).map_err(invalid)?
+            }
+        };
+        for (column, keys) in &index.secondary_indices {
+            if !index.metadata.key_summaries.contains_key(column) {
+                index.metadata.key_summaries.insert(column.clone(), KeySummary::of(keys, DEFAULT_TOP_KEYS));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Rewrite the index at `path` in the current format if it was saved in
+    /// an older one, returning whether it was. `load` reads older versions
+    /// as well; upgrading saves migrating them on every load.
+    pub fn upgrade(path: &Path) -> Result<bool> {
+        let data = fs::read(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let json = split_footer(&data).map_or(&data[..], |(json, _)| json);
+        let current = format_version(json).is_ok_and(|version| version == INDEX_FORMAT_VERSION);
+        if current {
+            return Ok(false);
+        }
+        Self::load(path)?.save(path)?;
+        Ok(true)
     }
 
-    /// Save index to file
+    /// Save index to file, followed by a footer with the checksum of its
+    /// contents. The index is written to a temporary file beside `path` and
+    /// renamed over it, so concurrent saves to one path never interleave and
+    /// readers only ever see a complete index.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)
-            .map_err(|e| ExtractorError::io_error(e, path))?;
-            
-        serde_json::to_writer(file, self).map_err(|e| ExtractorError::index_error(
+        let json = serde_json::to_vec(self).map_err(|e| ExtractorError::index_error(
             IndexErrorKind::BuildError(e.to_string()),
             Some(path.to_owned())
-        ))
+        ))?;
+        save_checked(path, json)
     }
 
     /// Verify index against current file state
@@ -118,6 +329,33 @@ impl FileIndex {
         self.positions.get(key)
     }
 
+    /// Get every position for a primary key value, in file order.
+    /// Only indices built with `Keep::All` can return more than one.
+    pub fn get_all_positions(&self, key: &str) -> Vec<&Position> {
+        let mut all: Vec<&Position> = self.positions.get(key).into_iter().collect();
+        if let Some(rest) = self.duplicate_positions.get(key) {
+            all.extend(rest);
+        }
+        all
+    }
+
+    /// Fetch a single row by row number (the first data row is 1).
+    /// Returns `None` if the row is past the end of the file.
+    pub fn get_row(&self, row_number: u64) -> Result<Option<ByteRecord>> {
+        RowReader::new(self)?.get_row(row_number)
+    }
+
+    /// Closest checkpoint at or before `row_number`
+    pub fn checkpoint_for(&self, row_number: u64) -> Option<&RowCheckpoint> {
+        let idx = self.row_checkpoints.partition_point(|c| c.row_number <= row_number);
+        idx.checked_sub(1).map(|i| &self.row_checkpoints[i])
+    }
+
+    /// Cardinality and most frequent keys of a secondary index column
+    pub fn column_summary(&self, column: &str) -> Option<&KeySummary> {
+        self.metadata.key_summaries.get(column)
+    }
+
     /// Get positions for a secondary index value
     pub fn get_secondary_positions(&self, column: &str, value: &str) -> Option<&Vec<Position>> {
         self.secondary_indices.get(column)?.get(value)
@@ -125,20 +363,7 @@ impl FileIndex {
 
     /// Calculate file checksum
     fn calculate_checksum(&self, file: &File) -> Result<u64> {
-        let mut buffer = [0u8; 8192];
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        let mut handle = file.try_clone()
-            .map_err(|e| ExtractorError::io_error(e, &self.metadata.source_file))?;
-            
-        handle.seek(SeekFrom::Start(0))
-            .map_err(|e| ExtractorError::io_error(e, &self.metadata.source_file))?;
-            
-        let bytes_read = handle.read(&mut buffer)
-            .map_err(|e| ExtractorError::io_error(e, &self.metadata.source_file))?;
-            
-        use std::hash::Hasher;
-        hasher.write(&buffer[..bytes_read]);
-        Ok(hasher.finish())
+        utils::checksum_file(file, &self.metadata.source_file, self.metadata.checksum_algorithm)
     }
 }
 
@@ -148,6 +373,14 @@ pub struct IndexBuilder {
     primary_column: String,
     secondary_columns: Vec<String>,
     chunk_size: usize,
+    num_threads: Option<usize>,
+    thread_pool: ThreadPoolSource,
+    on_duplicate: Keep,
+    checkpoint_interval: u64,
+    checksum_algorithm: ChecksumAlgorithm,
+    top_keys: usize,
+    progress: ProgressConfig,
+    cancellation: Option<CancellationToken>,
 }
 
 impl IndexBuilder {
@@ -158,6 +391,14 @@ impl IndexBuilder {
             primary_column,
             secondary_columns: Vec::new(),
             chunk_size: 1024 * 1024, // 1MB default
+            num_threads: None,
+            thread_pool: ThreadPoolSource::default(),
+            on_duplicate: Keep::Error,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            checksum_algorithm: ChecksumAlgorithm::Sampled { blocks: 16 },
+            top_keys: DEFAULT_TOP_KEYS,
+            progress: ProgressConfig::default(),
+            cancellation: None,
         }
     }
 
@@ -173,36 +414,120 @@ impl IndexBuilder {
         self
     }
 
+    /// Set the number of worker threads used to index chunks (default: num_cpus)
+    pub fn with_num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Index chunks on a shared or global pool instead of a dedicated one
+    pub fn with_thread_pool(mut self, pool: ThreadPoolSource) -> Self {
+        self.thread_pool = pool;
+        self
+    }
+
+    /// Set how repeated primary keys are handled (default: `Keep::Error`)
+    pub fn on_duplicate(mut self, policy: Keep) -> Self {
+        self.on_duplicate = policy;
+        self
+    }
+
+    /// Set the checksum used to detect changes to the source file
+    /// (default: 16 sampled blocks)
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum_algorithm = algorithm;
+        self
+    }
+
+    /// Record a row-offset checkpoint every `interval` rows (default: 1024).
+    /// Smaller intervals make `RowReader` seeks land closer at the cost of index size.
+    pub fn with_checkpoint_interval(mut self, interval: u64) -> Self {
+        self.checkpoint_interval = interval.max(1);
+        self
+    }
+
+    /// Keep the `k` most frequent keys of each secondary column in its key
+    /// summary (default: 20)
+    pub fn with_top_keys(mut self, k: usize) -> Self {
+        self.top_keys = k;
+        self
+    }
+
+    /// Set the progress bar and sinks (default: a bar with the
+    /// `progress-bars` feature)
+    pub fn with_progress(mut self, progress: ProgressConfig) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Call `callback` with the bytes indexed so far and the file size as
+    /// each chunk finishes
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        self.progress.sinks.push(Arc::new(CallbackProgress::new(callback)));
+        self
+    }
+
+    /// Stop building with `ExtractorError::Cancelled` once `token` is cancelled.
+    /// Chunks already being indexed are finished first.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
     /// Build the index
     pub fn build(self) -> Result<FileIndex> {
-        let file = File::open(&self.source_file)
-            .map_err(|e| ExtractorError::io_error(e, &self.source_file))?;
-            
-        let metadata = file.metadata()
-            .map_err(|e| ExtractorError::io_error(e, &self.source_file))?;
+        self.build_with(IndexBuilderState::build_index)
+    }
+
+    /// Build the index from rows another pass over the file has already
+    /// collected, in file order
+    pub(crate) fn build_from_chunks(self, chunks: Vec<ChunkIndex>) -> Result<FileIndex> {
+        self.build_with(|state| state.merge_chunks(chunks.into_iter().map(Ok)))
+    }
+
+    /// Fill in the index positions with `index`, then add the metadata
+    fn build_with<F>(self, index: F) -> Result<FileIndex>
+    where
+        F: FnOnce(&mut IndexBuilderState) -> Result<()>,
+    {
+        let source = IndexSource::open(&self.source_file)?;
+        let (file_size, modified) = source.size_and_modified(&self.source_file)?;
 
         let mut builder = IndexBuilderState {
-            file,
+            source,
+            source_file: self.source_file.clone(),
+            checksum_algorithm: self.checksum_algorithm,
             primary_column: self.primary_column,
             secondary_columns: self.secondary_columns,
             chunk_size: self.chunk_size,
+            num_threads: self.num_threads,
+            thread_pool: self.thread_pool.clone(),
+            on_duplicate: self.on_duplicate,
+            checkpoint_interval: self.checkpoint_interval,
+            progress: self.progress.clone(),
+            cancellation: self.cancellation.clone(),
             positions: HashMap::new(),
+            duplicate_positions: HashMap::new(),
+            row_checkpoints: Vec::new(),
             secondary_indices: HashMap::new(),
         };
 
-        builder.build_index()?;
+        index(&mut builder)?;
 
         Ok(FileIndex {
+            format_version: INDEX_FORMAT_VERSION,
             metadata: IndexMetadata {
                 source_file: self.source_file,
-                file_size: metadata.len(),
-                modified_time: metadata.modified()
-                    .map_err(|e| ExtractorError::io_error(e, &self.source_file))?
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
+                file_size,
+                modified_time: modified.as_secs(),
                 file_checksum: builder.calculate_checksum()?,
-                row_count: builder.positions.len() as u64,
+                checksum_algorithm: self.checksum_algorithm,
+                row_count: (builder.positions.len()
+                    + builder.duplicate_positions.values().map(Vec::len).sum::<usize>())
+                    as u64,
                 header_position: Position {
                     offset: 0,
                     length: 0,
@@ -212,175 +537,570 @@ impl IndexBuilder {
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs(),
+                key_summaries: builder.secondary_indices
+                    .iter()
+                    .map(|(column, keys)| (column.clone(), KeySummary::of(keys, self.top_keys)))
+                    .collect(),
             },
             columns: vec![self.primary_column],
             primary_column: self.primary_column,
             positions: builder.positions,
+            duplicate_positions: builder.duplicate_positions,
+            duplicate_policy: self.on_duplicate,
             secondary_indices: builder.secondary_indices,
+            row_checkpoints: builder.row_checkpoints,
         })
     }
 }
 
+/// File an index is built from
+enum IndexSource {
+    Local(File),
+    #[cfg(feature = "object-store")]
+    Remote(RemoteObject),
+}
+
+impl IndexSource {
+    fn open(path: &Path) -> Result<Self> {
+        #[cfg(feature = "object-store")]
+        if crate::remote::is_remote(path) {
+            return Ok(IndexSource::Remote(RemoteObject::open(path, &RemoteConfig::default())?));
+        }
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Ok(IndexSource::Local(file))
+    }
+
+    /// Size in bytes and modification time since the Unix epoch
+    fn size_and_modified(&self, path: &Path) -> Result<(u64, std::time::Duration)> {
+        match self {
+            IndexSource::Local(file) => {
+                let metadata = file.metadata().map_err(|e| ExtractorError::io_error(e, path))?;
+                let modified = metadata
+                    .modified()
+                    .map_err(|e| ExtractorError::io_error(e, path))?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Ok((metadata.len(), modified))
+            }
+            #[cfg(feature = "object-store")]
+            IndexSource::Remote(object) => Ok((object.size(), object.modified())),
+        }
+    }
+
+    /// The whole content: memory mapped with the `parallel` feature, read into
+    /// memory otherwise, and downloaded for remote objects
+    fn load(&self) -> Result<Box<dyn AsRef<[u8]>>> {
+        match self {
+            #[cfg(feature = "parallel")]
+            IndexSource::Local(file) => Ok(Box::new(utils::create_mmap(file, &SafeMmapOptions {
+                max_size: None,
+                read_only: true,
+            })?)),
+            #[cfg(not(feature = "parallel"))]
+            IndexSource::Local(file) => {
+                let mut buf = Vec::new();
+                (&*file).read_to_end(&mut buf)?;
+                Ok(Box::new(buf))
+            }
+            #[cfg(feature = "object-store")]
+            IndexSource::Remote(object) => Ok(Box::new(object.read_all()?)),
+        }
+    }
+
+    fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<u64> {
+        match self {
+            IndexSource::Local(file) => utils::checksum_file(file, path, algorithm),
+            #[cfg(feature = "object-store")]
+            IndexSource::Remote(object) => {
+                utils::checksum_reader(object.reader(), object.size(), Ok(object.modified()), path, algorithm)
+            }
+        }
+    }
+}
+
 /// Internal state for index building
 struct IndexBuilderState {
-    file: File,
+    source: IndexSource,
+    source_file: PathBuf,
+    checksum_algorithm: ChecksumAlgorithm,
     primary_column: String,
     secondary_columns: Vec<String>,
     chunk_size: usize,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    num_threads: Option<usize>,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    thread_pool: ThreadPoolSource,
+    on_duplicate: Keep,
+    checkpoint_interval: u64,
+    progress: ProgressConfig,
+    cancellation: Option<CancellationToken>,
     positions: HashMap<String, Position>,
+    duplicate_positions: HashMap<String, Vec<Position>>,
     secondary_indices: HashMap<String, HashMap<String, Vec<Position>>>,
+    row_checkpoints: Vec<RowCheckpoint>,
 }
 
 impl IndexBuilderState {
-    /// Implementation of index building for IndexBuilderState
-fn build_index(&mut self) -> Result<()> {
-    let file_size = self.file.metadata()?.len();
-    let mut reader = BufReader::with_capacity(self.chunk_size, &self.file);
-    
-    // Read and parse headers first
-    let mut headers_line = String::new();
-    let header_pos = reader.stream_position()?;
-    reader.read_line(&mut headers_line)?;
-    let headers: Vec<String> = headers_line.trim().split(',').map(String::from).collect();
-
-    // Find column indices
-    let primary_idx = headers.iter()
-        .position(|h| h == &self.primary_column)
-        .ok_or_else(|| ExtractorError::Index {
-            kind: IndexErrorKind::BuildError("Primary column not found".into()),
-            path: None,
-        })?;
-
-    let secondary_indices: Vec<usize> = self.secondary_columns.iter()
-        .map(|col| headers.iter().position(|h| h == col))
-        .collect::<Option<Vec<_>>>()
-        .ok_or_else(|| ExtractorError::Index {
-            kind: IndexErrorKind::BuildError("One or more secondary columns not found".into()),
-            path: None,
-        })?;
-
-    // Store header position
-    let header_position = Position {
-        offset: header_pos,
-        length: headers_line.len() as u32,
-        row_number: 0,
-    };
-
-    // Initialize progress bar if feature is enabled
-    #[cfg(feature = "progress-bars")]
-    let progress = indicatif::ProgressBar::new(file_size);
-    #[cfg(feature = "progress-bars")]
-    progress.set_style(
-        indicatif::ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap()
-            .progress_chars("=>-")
-    );
-
-    let mut row_number: u64 = 1;  // Start after header
-    let mut line = String::new();
-    let mut in_quoted_field = false;
-    
-    while reader.read_line(&mut line)? > 0 {
-        let start_pos = reader.stream_position()? - line.len() as u64;
-        
-        // Skip empty lines
-        if line.trim().is_empty() {
-            line.clear();
-            continue;
-        }
-
-        // Parse the line considering quoted fields
-        let mut fields = Vec::new();
-        let mut current_field = String::new();
-        
-        for c in line.chars() {
-            match c {
-                '"' => in_quoted_field = !in_quoted_field,
-                ',' if !in_quoted_field => {
-                    fields.push(std::mem::take(&mut current_field));
-                },
-                _ => current_field.push(c),
+    /// Build the primary and secondary indices.
+    ///
+    /// The file is memory mapped and split into byte chunks on line boundaries.
+    /// Each chunk is parsed into `ByteRecord`s on the thread pool and the per-chunk
+    /// maps are merged back in file order, so row numbers and secondary position
+    /// lists come out exactly as a sequential scan would produce them. Without
+    /// the `parallel` feature the file is read into memory and the chunks are
+    /// parsed one after another. Remote objects are downloaded into memory.
+    fn build_index(&mut self) -> Result<()> {
+        let content = self.source.load()?;
+        let data: &[u8] = (*content).as_ref();
+
+        // Read and parse headers first; the header ends at the first line
+        // break outside quotes
+        let body_start = utils::next_row_boundary(data, 0, 1);
+        let mut header_reader = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(&data[..body_start]);
+        let mut header_record = ByteRecord::new();
+        header_reader.read_byte_record(&mut header_record)?;
+        let headers: Vec<String> = header_names(&header_record).collect();
+
+        // Find column indices
+        let primary_idx = headers.iter()
+            .position(|h| h == &self.primary_column)
+            .ok_or_else(|| ExtractorError::Index {
+                kind: IndexErrorKind::BuildError("Primary column not found".into()),
+                path: None,
+            })?;
+
+        let secondary = self.secondary_columns.iter()
+            .map(|col| Some((col.clone(), headers.iter().position(|h| h == col)?)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ExtractorError::Index {
+                kind: IndexErrorKind::BuildError("One or more secondary columns not found".into()),
+                path: None,
+            })?;
+        let columns = IndexColumns {
+            primary: primary_idx,
+            secondary,
+            on_duplicate: self.on_duplicate,
+            checkpoint_interval: self.checkpoint_interval,
+        };
+
+        let chunks = chunk_bounds(data, body_start, self.chunk_size);
+
+        let progress = Progress::start(&self.progress, data.len() as u64, "Building index");
+        progress.inc(body_start as u64);
+
+        let this = &*self;
+        let index_chunk = |&(start, end): &(usize, usize)| {
+            if this.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                return Err(ExtractorError::Cancelled("index build".into()));
             }
+            let chunk = this.index_chunk(&data[start..end], start as u64, &columns);
+            progress.inc((end - start) as u64);
+            chunk
+        };
+        #[cfg(feature = "parallel")]
+        let chunk_indices: Vec<Result<ChunkIndex>> = self
+            .thread_pool
+            .install(self.num_threads, || chunks.par_iter().map(index_chunk).collect())?;
+        #[cfg(not(feature = "parallel"))]
+        let chunk_indices: Vec<Result<ChunkIndex>> = chunks.iter().map(index_chunk).collect();
+        self.merge_chunks(chunk_indices)?;
+        progress.finish();
+
+        // Validate index
+        if self.positions.is_empty() {
+            return Err(ExtractorError::Index {
+                kind: IndexErrorKind::BuildError("No valid rows found for indexing".into()),
+                path: None,
+            });
         }
-        fields.push(current_field);  // Add the last field
 
-        // Create position record
-        let position = Position {
-            offset: start_pos,
-            length: line.len() as u32,
-            row_number,
-        };
+        Ok(())
+    }
 
-        // Store primary index
-        if let Some(primary_value) = fields.get(primary_idx) {
-            let primary_key = primary_value.trim().to_string();
-            if !primary_key.is_empty() {
-                // Check for duplicates
-                if self.positions.contains_key(&primary_key) {
-                    return Err(ExtractorError::Index {
-                        kind: IndexErrorKind::BuildError(
-                            format!("Duplicate primary key found: {}", primary_key)
-                        ),
-                        path: None,
-                    });
+    /// Merge per-chunk results in file order, rebasing row numbers as we go.
+    /// Row 0 is the header, so the first data row is row 1.
+    fn merge_chunks<I: IntoIterator<Item = Result<ChunkIndex>>>(&mut self, chunk_indices: I) -> Result<()> {
+        let mut row_base: u64 = 0;
+        for chunk in chunk_indices {
+            let chunk = chunk?;
+
+            for (primary_key, mut position) in chunk.positions {
+                position.row_number += row_base;
+                insert_primary(
+                    &mut self.positions,
+                    &mut self.duplicate_positions,
+                    self.on_duplicate,
+                    primary_key,
+                    position,
+                )?;
+            }
+
+            // Chunk-local repeats come after the chunk's first occurrence
+            for (primary_key, mut positions) in chunk.duplicate_positions {
+                for position in &mut positions {
+                    position.row_number += row_base;
                 }
-                self.positions.insert(primary_key, position.clone());
+                self.duplicate_positions.entry(primary_key).or_default().extend(positions);
             }
+
+            for (column, values) in chunk.secondary_indices {
+                let column_index = self.secondary_indices.entry(column).or_default();
+                for (value, mut positions) in values {
+                    for position in &mut positions {
+                        position.row_number += row_base;
+                    }
+                    column_index.entry(value).or_default().extend(positions);
+                }
+            }
+
+            self.row_checkpoints.extend(chunk.row_checkpoints.into_iter().map(|c| RowCheckpoint {
+                row_number: c.row_number + row_base,
+                offset: c.offset,
+            }));
+
+            row_base += chunk.rows;
         }
+        Ok(())
+    }
+
+    /// Index the rows of a single chunk. `base_offset` is the chunk's byte offset in
+    /// the file; row numbers are local to the chunk (starting at 1) and are rebased
+    /// by the caller when chunks are merged.
+    fn index_chunk(&self, data: &[u8], base_offset: u64, columns: &IndexColumns) -> Result<ChunkIndex> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data);
+        let mut record = ByteRecord::new();
+        let mut chunk = ChunkIndex::default();
+
+        while reader.read_byte_record(&mut record)? {
+            let start = record.position().map(|p| p.byte()).unwrap_or(0);
+            let end = reader.position().byte();
+            chunk.add_row(&record, base_offset + start, (end - start) as u32, columns)?;
+        }
+
+        Ok(chunk)
+    }
+
+    fn calculate_checksum(&self) -> Result<u64> {
+        self.source.checksum(&self.source_file, self.checksum_algorithm)
+    }
+}
+
+/// Where the indexed columns are in each row, and how rows are added
+pub(crate) struct IndexColumns {
+    /// Position of the primary key column
+    pub(crate) primary: usize,
+    /// Name and position of each secondary column
+    pub(crate) secondary: Vec<(String, usize)>,
+    pub(crate) on_duplicate: Keep,
+    pub(crate) checkpoint_interval: u64,
+}
+
+impl IndexColumns {
+    /// Index only the primary key at `primary`, keeping repeated keys
+    pub(crate) fn primary(primary: usize) -> Self {
+        Self {
+            primary,
+            secondary: Vec::new(),
+            on_duplicate: Keep::All,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+}
+
+/// Index entries collected from one chunk of the source file
+#[derive(Debug, Default)]
+pub(crate) struct ChunkIndex {
+    /// Number of data rows in the chunk
+    rows: u64,
+    positions: HashMap<String, Position>,
+    duplicate_positions: HashMap<String, Vec<Position>>,
+    secondary_indices: HashMap<String, HashMap<String, Vec<Position>>>,
+    row_checkpoints: Vec<RowCheckpoint>,
+}
+
+impl ChunkIndex {
+    /// Rough heap size of the chunk's entries in bytes
+    pub(crate) fn approx_bytes(&self) -> u64 {
+        let entry = std::mem::size_of::<(String, Position)>();
+        let keys = |map: &HashMap<String, Vec<Position>>| -> usize {
+            map.iter().map(|(key, positions)| key.len() + entry + positions.len() * std::mem::size_of::<Position>()).sum()
+        };
+        let primary: usize = self.positions.keys().map(|key| key.len() + entry).sum();
+        let secondary: usize = self.secondary_indices.values().map(keys).sum();
+        let checkpoints = self.row_checkpoints.len() * std::mem::size_of::<RowCheckpoint>();
+        (primary + keys(&self.duplicate_positions) + secondary + checkpoints) as u64
+    }
+
+    /// Add the next row of the chunk, `length` bytes at `offset` in the file.
+    /// Row numbers are local to the chunk, starting at 1.
+    pub(crate) fn add_row(&mut self, record: &ByteRecord, offset: u64, length: u32, columns: &IndexColumns) -> Result<()> {
+        self.rows += 1;
+
+        // The first row of every chunk is a checkpoint, then every `checkpoint_interval` rows
+        if (self.rows - 1).is_multiple_of(columns.checkpoint_interval) {
+            self.row_checkpoints.push(RowCheckpoint { row_number: self.rows, offset });
+        }
+
+        let position = Position { offset, length, row_number: self.rows };
 
         // Store secondary indices
-        for (idx, &sec_idx) in secondary_indices.iter().enumerate() {
-            if let Some(sec_value) = fields.get(sec_idx) {
-                let sec_key = sec_value.trim().to_string();
-                if !sec_key.is_empty() {
-                    self.secondary_indices
-                        .entry(self.secondary_columns[idx].clone())
-                        .or_insert_with(HashMap::new())
-                        .entry(sec_key)
-                        .or_insert_with(Vec::new)
-                        .push(position.clone());
+        for (column, idx) in &columns.secondary {
+            if let Some(key) = field_key(record, *idx) {
+                self.secondary_indices
+                    .entry(column.clone())
+                    .or_default()
+                    .entry(key)
+                    .or_default()
+                    .push(position.clone());
+            }
+        }
+
+        // Store primary index
+        if let Some(primary_key) = field_key(record, columns.primary) {
+            insert_primary(
+                &mut self.positions,
+                &mut self.duplicate_positions,
+                columns.on_duplicate,
+                primary_key,
+                position,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads rows by row number, seeking to the nearest checkpoint and
+/// scanning forward from there.
+pub struct RowReader<'a> {
+    index: &'a FileIndex,
+    file: Box<dyn ReadSeek>,
+}
+
+impl<'a> RowReader<'a> {
+    /// Open the index's source file for row access
+    pub fn new(index: &'a FileIndex) -> Result<Self> {
+        let file = utils::open_seekable(&index.metadata.source_file)?;
+        Ok(Self { index, file })
+    }
+
+    /// Fetch a single row by row number
+    pub fn get_row(&mut self, row_number: u64) -> Result<Option<ByteRecord>> {
+        Ok(self.get_rows(row_number..row_number.saturating_add(1))?.pop())
+    }
+
+    /// Fetch a contiguous range of rows, e.g. `1_000_000..1_000_500` for one page.
+    /// Rows past the end of the file are simply not returned.
+    pub fn get_rows(&mut self, rows: Range<u64>) -> Result<Vec<ByteRecord>> {
+        let mut records = Vec::new();
+        let first = rows.start.max(1);
+        if first >= rows.end {
+            return Ok(records);
+        }
+        let Some(checkpoint) = self.index.checkpoint_for(first) else {
+            return Ok(records);
+        };
+
+        let path = &self.index.metadata.source_file;
+        self.file
+            .seek(SeekFrom::Start(checkpoint.offset))
+            .map_err(|e| ExtractorError::io_error(e, path))?;
+
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(BufReader::new(&mut self.file));
+        let mut record = ByteRecord::new();
+        let mut row_number = checkpoint.row_number;
+
+        while row_number < rows.end && reader.read_byte_record(&mut record)? {
+            if row_number >= first {
+                records.push(record.clone());
+            }
+            row_number += 1;
+        }
+
+        Ok(records)
+    }
+}
+
+/// Batched random access by primary key.
+///
+/// Positions for a batch of keys are sorted by offset and rows that sit within
+/// `max_gap` bytes of each other are fetched with a single read, so large
+/// batches turn into a few sequential reads instead of one seek per key.
+pub struct IndexedReader<'a> {
+    index: &'a FileIndex,
+    file: Box<dyn ReadSeek>,
+    max_gap: u64,
+    max_read: u64,
+}
+
+impl<'a> IndexedReader<'a> {
+    /// Open the index's source file for keyed access
+    pub fn new(index: &'a FileIndex) -> Result<Self> {
+        let file = utils::open_seekable(&index.metadata.source_file)?;
+        Ok(Self {
+            index,
+            file,
+            max_gap: 64 * 1024,        // 64KB
+            max_read: 16 * 1024 * 1024, // 16MB
+        })
+    }
+
+    /// Largest gap in bytes between two rows that still gets merged into one read
+    pub fn with_max_gap(mut self, bytes: u64) -> Self {
+        self.max_gap = bytes;
+        self
+    }
+
+    /// Upper bound on the size of a single coalesced read
+    pub fn with_max_read(mut self, bytes: u64) -> Self {
+        self.max_read = bytes.max(1);
+        self
+    }
+
+    /// Fetch the row for a single primary key. With `Keep::All`, a key that
+    /// repeats returns its first row; use [`get_all`](Self::get_all) for the rest.
+    pub fn get(&mut self, key: &str) -> Result<Option<ByteRecord>> {
+        Ok(self.get_many(&[key])?.pop().flatten())
+    }
+
+    /// Fetch every row for a primary key, in file order. Only indices built
+    /// with `Keep::All` can return more than one.
+    pub fn get_all(&mut self, key: &str) -> Result<Vec<ByteRecord>> {
+        let positions = self.index.get_all_positions(key);
+        Ok(self.fetch(positions.len(), positions.into_iter().enumerate().collect())?.into_iter().flatten().collect())
+    }
+
+    /// Fetch rows for many primary keys. The result is in the same order as
+    /// `keys`, with `None` for keys that are not in the index. Like
+    /// [`get`](Self::get), only the first row of a repeated key is returned.
+    pub fn get_many(&mut self, keys: &[&str]) -> Result<Vec<Option<ByteRecord>>> {
+        let index = self.index;
+        let wanted: Vec<(usize, &Position)> = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, key)| index.get_position(key).map(|p| (i, p)))
+            .collect();
+        self.fetch(keys.len(), wanted)
+    }
+
+    /// Read the rows at `wanted` into `count` slots, coalescing nearby rows
+    /// into single reads
+    fn fetch(&mut self, count: usize, mut wanted: Vec<(usize, &Position)>) -> Result<Vec<Option<ByteRecord>>> {
+        let mut results: Vec<Option<ByteRecord>> = vec![None; count];
+        wanted.sort_by_key(|(_, p)| p.offset);
+
+        let mut buffer = Vec::new();
+        let mut run_start = 0;
+        while run_start < wanted.len() {
+            // Extend the run while the next row is close enough to the current end
+            let first_offset = wanted[run_start].1.offset;
+            let mut run_end_offset = first_offset + wanted[run_start].1.length as u64;
+            let mut run_end = run_start + 1;
+            while run_end < wanted.len() {
+                let next = wanted[run_end].1;
+                let next_end = next.offset + next.length as u64;
+                if next.offset > run_end_offset + self.max_gap
+                    || next_end.saturating_sub(first_offset) > self.max_read
+                {
+                    break;
                 }
+                run_end_offset = run_end_offset.max(next_end);
+                run_end += 1;
+            }
+
+            self.read_range(first_offset, run_end_offset, &mut buffer)?;
+            for &(key_idx, position) in &wanted[run_start..run_end] {
+                let start = (position.offset - first_offset) as usize;
+                let end = start + position.length as usize;
+                results[key_idx] = parse_row(&buffer[start..end])?;
             }
+
+            run_start = run_end;
         }
 
-        // Update progress
-        #[cfg(feature = "progress-bars")]
-        progress.set_position(reader.stream_position()?);
+        Ok(results)
+    }
 
-        row_number += 1;
-        line.clear();
+    fn read_range(&mut self, start: u64, end: u64, buffer: &mut Vec<u8>) -> Result<()> {
+        let path = &self.index.metadata.source_file;
+        buffer.resize((end - start) as usize, 0);
+        self.file
+            .seek(SeekFrom::Start(start))
+            .map_err(|e| ExtractorError::io_error(e, path))?;
+        self.file
+            .read_exact(buffer)
+            .map_err(|e| ExtractorError::io_error(e, path))
     }
+}
 
-    #[cfg(feature = "progress-bars")]
-    progress.finish_with_message("Index built successfully");
+/// Parse a single raw row into a record
+fn parse_row(bytes: &[u8]) -> Result<Option<ByteRecord>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+    let mut record = ByteRecord::new();
+    Ok(reader.read_byte_record(&mut record)?.then_some(record))
+}
 
-    // Validate index
-    if self.positions.is_empty() {
-        return Err(ExtractorError::Index {
-            kind: IndexErrorKind::BuildError("No valid rows found for indexing".into()),
-            path: None,
-        });
+/// Split `data[body_start..]` into roughly `chunk_size` byte ranges that each end
+/// at a row boundary (or at end of file), never inside a quoted field.
+fn chunk_bounds(data: &[u8], body_start: usize, chunk_size: usize) -> Vec<(usize, usize)> {
+    let mut bounds = Vec::new();
+    let mut start = body_start;
+    while start < data.len() {
+        let end = utils::next_row_boundary(data, start, start + chunk_size.max(1));
+        bounds.push((start, end));
+        start = end;
     }
+    bounds
+}
 
-    Ok(())
+/// Trimmed, non-empty key for the field at `idx`
+#[inline]
+fn field_key(record: &ByteRecord, idx: usize) -> Option<String> {
+    let value = record.get(idx)?;
+    let key = String::from_utf8_lossy(value).trim().to_string();
+    (!key.is_empty()).then_some(key)
 }
 
-    fn calculate_checksum(&self) -> Result<u64> {
-        let mut buffer = [0u8; 8192];
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        let mut handle = self.file.try_clone()
-            .map_err(|e| ExtractorError::io_error(e, "Failed to clone file handle"))?;
-            
-        handle.seek(SeekFrom::Start(0))
-            .map_err(|e| ExtractorError::io_error(e, "Failed to seek to start"))?;
-            
-        let bytes_read = handle.read(&mut buffer)
-            .map_err(|e| ExtractorError::io_error(e, "Failed to read file"))?;
-            
-        use std::hash::Hasher;
-        hasher.write(&buffer[..bytes_read]);
-        Ok(hasher.finish())
+/// Record a primary key position, applying the duplicate key policy
+fn insert_primary(
+    positions: &mut HashMap<String, Position>,
+    duplicate_positions: &mut HashMap<String, Vec<Position>>,
+    policy: Keep,
+    primary_key: String,
+    position: Position,
+) -> Result<()> {
+    match positions.entry(primary_key) {
+        Entry::Vacant(entry) => {
+            entry.insert(position);
+            Ok(())
+        }
+        Entry::Occupied(entry) => match policy {
+            Keep::First => Ok(()),
+            Keep::All => {
+                duplicate_positions.entry(entry.key().clone()).or_default().push(position);
+                Ok(())
+            }
+            Keep::Error => Err(duplicate_key_error(entry.key())),
+        },
+    }
+}
+
+fn duplicate_key_error(primary_key: &str) -> ExtractorError {
+    ExtractorError::Index {
+        kind: IndexErrorKind::BuildError(
+            format!("Duplicate primary key found: {}", primary_key)
+        ),
+        path: None,
     }
 }
 
@@ -423,4 +1143,301 @@ mod tests {
         assert_eq!(loaded.primary_column, "id");
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_key_summaries() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,chrom,gene").unwrap();
+        for i in 0..100 {
+            writeln!(temp_file, "rs{i},chr{},{}", i % 7 % 3, if i % 10 == 0 { "" } else { "APOE" }).unwrap();
+        }
+
+        let index = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .add_secondary_index("chrom".to_string())
+            .add_secondary_index("gene".to_string())
+            .with_top_keys(2)
+            .build()?;
+        let chrom = index.column_summary("chrom").expect("chrom is indexed");
+        assert_eq!((chrom.cardinality, chrom.rows), (3, 100));
+        assert_eq!(chrom.top_keys, [("chr0".to_string(), 43), ("chr1".to_string(), 29)]);
+        assert_eq!(index.column_summary("gene").map(|gene| gene.rows), Some(90));
+        assert!(index.column_summary("id").is_none());
+
+        // Indices saved before key summaries existed get them on load
+        let mut old = index.clone();
+        old.metadata.key_summaries.clear();
+        let saved = NamedTempFile::new().unwrap();
+        old.save(saved.path())?;
+        let loaded = FileIndex::load(saved.path())?;
+        assert_eq!(loaded.column_summary("chrom").map(|chrom| chrom.cardinality), Some(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_is_atomic_and_checked() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,chrom").unwrap();
+        for i in 0..200 {
+            writeln!(temp_file, "rs{i},chr{}", i % 5).unwrap();
+        }
+        let index = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .add_secondary_index("chrom".to_string())
+            .build()?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("input.idx");
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| index.save(&path).unwrap());
+            }
+        });
+        assert_eq!(FileIndex::load(&path)?.positions.len(), 200);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1, "temporary files are cleaned up");
+
+        let saved = std::fs::read(&path)?;
+        std::fs::write(&path, &saved[..saved.len() / 2])?;
+        assert!(matches!(FileIndex::load(&path), Err(ExtractorError::Index { kind: IndexErrorKind::InvalidFormat, .. })));
+        let mut altered = saved.clone();
+        altered[10] ^= 1;
+        std::fs::write(&path, altered)?;
+        assert!(matches!(FileIndex::load(&path), Err(ExtractorError::Index { kind: IndexErrorKind::Corrupted, .. })));
+
+        // Indices saved without a footer still load
+        std::fs::write(&path, serde_json::to_vec(&index)?)?;
+        assert_eq!(FileIndex::load(&path)?.positions.len(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_versions() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,chrom\nrs1,chr1\nrs2,chr2").unwrap();
+        let index = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .add_secondary_index("chrom".to_string())
+            .build()?;
+        let saved = NamedTempFile::new().unwrap();
+        let path = saved.path();
+
+        // The unversioned layout: no version, footer or key summaries
+        let mut old = serde_json::to_value(&index)?;
+        old.as_object_mut().unwrap().remove("format_version");
+        old["metadata"].as_object_mut().unwrap().remove("key_summaries");
+        std::fs::write(path, serde_json::to_vec(&old)?)?;
+        let loaded = FileIndex::load(path)?;
+        assert_eq!(loaded.format_version, INDEX_FORMAT_VERSION);
+        assert_eq!(loaded.column_summary("chrom").map(|chrom| chrom.cardinality), Some(2));
+        assert!(FileIndex::upgrade(path)?);
+        assert!(!FileIndex::upgrade(path)?);
+        assert_eq!(format_version(split_footer(&std::fs::read(path)?).unwrap().0)?, INDEX_FORMAT_VERSION);
+
+        let mut newer = serde_json::to_value(&index)?;
+        newer["format_version"] = (INDEX_FORMAT_VERSION + 1).into();
+        std::fs::write(path, serde_json::to_vec(&newer)?)?;
+        assert!(matches!(
+            FileIndex::load(path),
+            Err(ExtractorError::Index { kind: IndexErrorKind::UnsupportedVersion { found, .. }, .. })
+                if found == INDEX_FORMAT_VERSION + 1
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_build_matches_single_chunk() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,chrom,value").unwrap();
+        for i in 0..500 {
+            writeln!(temp_file, "rs{},chr{},{}", i, i % 3, i * 10).unwrap();
+        }
+
+        let single = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .add_secondary_index("chrom".to_string())
+            .build()?;
+        let chunked = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .add_secondary_index("chrom".to_string())
+            .with_chunk_size(64)
+            .with_num_threads(4)
+            .build()?;
+
+        assert_eq!(chunked.metadata.row_count, 500);
+        for (key, position) in &single.positions {
+            let other = chunked.get_position(key).unwrap();
+            assert_eq!(position.offset, other.offset);
+            assert_eq!(position.length, other.length);
+            assert_eq!(position.row_number, other.row_number);
+        }
+
+        let first = chunked.get_position("rs0").unwrap();
+        assert_eq!(first.row_number, 1);
+        assert_eq!(first.offset, "id,chrom,value\n".len() as u64);
+        assert_eq!(first.length, "rs0,chr0,0\n".len() as u32);
+
+        let chr1: Vec<u64> = chunked
+            .get_secondary_positions("chrom", "chr1")
+            .unwrap()
+            .iter()
+            .map(|p| p.row_number)
+            .collect();
+        let expected: Vec<u64> = (0..500u64).filter(|i| i % 3 == 1).map(|i| i + 1).collect();
+        assert_eq!(chr1, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_split_outside_quoted_newlines() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,\"multi\nline note\"").unwrap();
+        for i in 0..50 {
+            writeln!(temp_file, "rs{},\"first line\nrs_fake{},second\"", i, i).unwrap();
+        }
+
+        let index = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .with_chunk_size(16)
+            .with_num_threads(4)
+            .build()?;
+
+        assert_eq!(index.metadata.row_count, 50);
+        assert!(index.get_position("rs_fake0").is_none());
+        let header_len = "id,\"multi\nline note\"\n".len() as u64;
+        let row_len = "rs0,\"first line\nrs_fake0,second\"\n".len() as u64;
+        let first = index.get_position("rs0").unwrap();
+        assert_eq!((first.offset, first.length as u64, first.row_number), (header_len, row_len, 1));
+        let last = index.get_position("rs49").unwrap();
+        assert_eq!(last.row_number, 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_key_across_chunks() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,value").unwrap();
+        for i in 0..100 {
+            writeln!(temp_file, "rs{},{}", i, i).unwrap();
+        }
+        writeln!(temp_file, "rs0,999").unwrap();
+
+        let result = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .with_chunk_size(32)
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_build_progress_and_cancellation() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,value").unwrap();
+        for i in 0..200 {
+            writeln!(temp_file, "rs{},{}", i, i).unwrap();
+        }
+        let size = temp_file.as_file().metadata()?.len();
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .with_chunk_size(128)
+            .on_progress(move |done, total| seen.lock().unwrap().push((done, total)))
+            .build()?;
+        let reports = reports.lock().unwrap();
+        assert!(reports.len() > 1);
+        assert!(reports.iter().all(|&(done, total)| total == size && done <= size));
+        assert_eq!(reports.iter().map(|&(done, _)| done).max(), Some(size));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .with_cancellation(token)
+            .build();
+        assert!(matches!(result, Err(ExtractorError::Cancelled(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_policies() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "rsid,study").unwrap();
+        for i in 0..60 {
+            writeln!(temp_file, "rs{},study{}", i % 20, i).unwrap();
+        }
+
+        let first = FileIndex::builder(temp_file.path().to_owned(), "rsid".to_string())
+            .with_chunk_size(48)
+            .on_duplicate(Keep::First)
+            .build()?;
+        assert_eq!(first.positions.len(), 20);
+        assert!(first.duplicate_positions.is_empty());
+        assert_eq!(first.get_position("rs3").unwrap().row_number, 4);
+
+        let all = FileIndex::builder(temp_file.path().to_owned(), "rsid".to_string())
+            .with_chunk_size(48)
+            .on_duplicate(Keep::All)
+            .build()?;
+        assert_eq!(all.metadata.row_count, 60);
+        let rows: Vec<u64> = all.get_all_positions("rs3").iter().map(|p| p.row_number).collect();
+        assert_eq!(rows, vec![4, 24, 44]);
+        let mut reader = IndexedReader::new(&all)?;
+        let studies: Vec<Vec<u8>> = reader.get_all("rs3")?.iter().map(|row| row[1].to_vec()).collect();
+        assert_eq!(studies, [&b"study3"[..], b"study23", b"study43"]);
+        assert_eq!(&reader.get("rs3")?.unwrap()[1], b"study3");
+        assert!(reader.get_all("rs99")?.is_empty());
+
+        let error = FileIndex::builder(temp_file.path().to_owned(), "rsid".to_string())
+            .on_duplicate(Keep::Error)
+            .build();
+        assert!(error.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_access_by_number() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "id,value").unwrap();
+        for i in 1..=300 {
+            writeln!(temp_file, "row{},{}", i, i * 2).unwrap();
+        }
+
+        let index = FileIndex::builder(temp_file.path().to_owned(), "id".to_string())
+            .with_chunk_size(100)
+            .with_checkpoint_interval(7)
+            .build()?;
+
+        let row = index.get_row(123)?.unwrap();
+        assert_eq!(&row[0], b"row123");
+        assert_eq!(&row[1], b"246");
+
+        let mut reader = RowReader::new(&index)?;
+        let page = reader.get_rows(100..105)?;
+        let ids: Vec<&[u8]> = page.iter().map(|r| &r[0]).collect();
+        assert_eq!(ids, vec![&b"row100"[..], b"row101", b"row102", b"row103", b"row104"]);
+
+        assert_eq!(reader.get_rows(298..310)?.len(), 3);
+        assert!(reader.get_row(301)?.is_none());
+        assert!(reader.get_row(0)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_many_coalesces_and_keeps_order() -> Result<()> {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "gene_id,symbol").unwrap();
+        for i in 0..1000 {
+            writeln!(temp_file, "ENSG{:05},SYM{}", i, i).unwrap();
+        }
+
+        let index = FileIndex::builder(temp_file.path().to_owned(), "gene_id".to_string())
+            .build()?;
+
+        for max_gap in [0, 64, 1024 * 1024] {
+            let mut reader = IndexedReader::new(&index)?.with_max_gap(max_gap);
+            let rows = reader.get_many(&["ENSG00999", "missing", "ENSG00002", "ENSG00500"])?;
+
+            assert_eq!(rows.len(), 4);
+            assert_eq!(&rows[0].as_ref().unwrap()[1], b"SYM999");
+            assert!(rows[1].is_none());
+            assert_eq!(&rows[2].as_ref().unwrap()[1], b"SYM2");
+            assert_eq!(&rows[3].as_ref().unwrap()[1], b"SYM500");
+        }
+
+        let mut small_reads = IndexedReader::new(&index)?.with_max_read(32);
+        assert_eq!(&small_reads.get("ENSG00010")?.unwrap()[0], b"ENSG00010");
+        Ok(())
+    }
+}
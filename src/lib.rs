@@ -9,41 +9,327 @@
 #![deny(clippy::all)]
 #![deny(rustdoc::broken_intra_doc_links)]
 
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "compression")]
+mod bgzf;
+pub mod bench;
+pub mod blocks;
+pub mod chrom;
+pub mod clump;
+pub mod column_cache;
+pub mod compression;
 pub mod core;
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+pub mod database;
+pub mod dialect;
+pub mod digest;
+pub mod encoding;
 pub mod error;
+pub mod explain;
+#[cfg(feature = "ffi")]
+mod ffi;
 pub mod filters;
+pub mod fixed_width;
+pub mod formats;
+pub mod gene_ids;
+pub mod group;
+pub mod harmonize;
+pub mod hgvs;
 pub mod index;
+#[cfg(feature = "liftover")]
+pub mod liftover;
+pub mod lookup;
+pub mod observers;
+pub mod output;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod profile;
+pub mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "r")]
+mod r;
+#[cfg(feature = "object-store")]
+pub mod remote;
+mod retry;
+pub mod schema;
+#[cfg(feature = "sql")]
+pub mod sql;
+pub mod testdata;
+pub mod tools;
+pub mod transform;
 pub mod utils;
+pub mod validate;
+pub mod variant_ids;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 // Re-export commonly used items
-pub use crate::core::BioFilter;
-pub use crate::error::ExtractorError;
-pub use crate::filters::{Filter, FilterCondition};
+pub use crate::bench::{Bench, BenchReport};
+pub use crate::blocks::BlockIndex;
+pub use crate::column_cache::ColumnCache;
+pub use crate::compression::{OutputCompression, TabixColumns};
+pub use crate::core::{BioFilter, Pipeline, Preview};
+pub use crate::dialect::Dialect;
+pub use crate::digest::DigestAlgorithm;
+pub use crate::encoding::InputEncoding;
+pub use crate::error::{ExtractorError, RowErrorPolicy, RowErrorSummary};
+pub use crate::explain::{ExplainTarget, RowExplanation};
+pub use crate::filters::{register_predicate, Filter, FilterCondition, FilterStats, ImpactFilter, NumberFormat};
+pub use crate::fixed_width::FixedWidth;
+pub use crate::formats::bed::BedRecord;
+pub use crate::formats::extract::FastaSink;
+pub use crate::formats::fai::{FaiIndex, FastaSlice};
+pub use crate::formats::fastq::QualityEncoding;
+pub use crate::formats::json::JsonSink;
+pub use crate::formats::motif::Motif;
+pub use crate::formats::seq_filter::{SeqCondition, SeqFilter};
+pub use crate::hgvs::{Consequence, Impact};
 pub use crate::index::FileIndex;
+pub use crate::observers::{ColumnObserver, ColumnSummary, DistinctValues, GeneSummary, GeneSummaryObserver};
+pub use crate::output::{ExistingOutput, OutputMode, QuoteEscape, QuoteStyle, Quoting, RowSink, ThreadOutput, WriteMode};
+pub use crate::schema::{FieldType, HeaderFingerprint, Schema};
+pub use crate::tools::concat::{ConcatSummary, TableConcat};
+pub use crate::tools::diff::{DiffSummary, TableDiff};
+pub use crate::tools::intervals::RegionSet;
+pub use crate::tools::reshape::Reshape;
+pub use crate::transform::{RowHash, RowTransform};
+pub use crate::progress::{CallbackProgress, NullProgress, ProgressCallback, ProgressSink};
+pub use crate::utils::{CancellationToken, ThreadPoolSource};
+pub use crate::validate::ValidationIssue;
 
-/// Configuration options for the Extractor
-#[derive(Debug, Clone)]
+/// Configuration options for the Extractor. Serializable, so shared settings
+/// can live in profile files (see `Config::from_path`); fields left out of a
+/// file keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     /// CSV delimiter character (default: ',')
+    #[serde(with = "profile::delimiter")]
     pub delimiter: u8,
     /// Whether the CSV file has headers (default: true)
     pub has_headers: bool,
+    /// Column names for input without a header row, so filters can refer to
+    /// columns by name; outputs then start with these names (default: None)
+    pub column_names: Option<Vec<String>>,
+    /// Character encoding of the input; anything but UTF-8 needs the
+    /// `encoding` feature (default: Auto)
+    pub encoding: InputEncoding,
+    /// Column layout of fixed-width or space-aligned input, whose fields are
+    /// then written out with `delimiter`; None for delimited input
+    /// (default: None)
+    pub fixed_width: Option<FixedWidth>,
+    /// Canonical column names mapped to the header variants they stand for
+    /// (e.g. `p_value` for `P`, `pval`, `P-VALUE`), matched ignoring case, so
+    /// filters can use one name across sources (default: empty)
+    pub column_aliases: HashMap<String, Vec<String>>,
+    /// How numbers are written in columns that aren't plain `1234.5`, e.g.
+    /// `NumberFormat::european()` for `1.234,56`, used by the builder's
+    /// numeric and range filters on those columns (default: empty)
+    pub number_formats: HashMap<String, NumberFormat>,
+    /// Column types checked against a sample of the input when the filter
+    /// is built (default: None)
+    pub schema: Option<Schema>,
     /// Size of processing chunks in bytes (default: 1MB)
     pub chunk_size: usize,
-    /// Enable parallel processing (default: true)
+    /// Enable parallel processing; without the `parallel` feature every run
+    /// is sequential (default: true)
     pub parallel: bool,
     /// Use indexed mode for faster access (default: false)
     pub use_index: bool,
+    /// Index the input on this column while filtering it, for
+    /// `BioFilter::built_index`. Needs a local UTF-8 input and a single
+    /// output file (default: None)
+    pub index_column: Option<String>,
     /// Number of worker threads for parallel processing (default: num_cpus)
     pub num_threads: Option<usize>,
-    /// Progress bar configuration
+    /// Pool that parallel processing runs on (default: a dedicated pool per
+    /// run). Not read from or written to profiles.
+    #[serde(skip)]
+    pub thread_pool: ThreadPoolSource,
+    /// Progress bar and progress sinks
     pub progress: ProgressConfig,
+    /// Chunk sizing and backpressure for parallel processing
+    pub chunking: ChunkingConfig,
+    /// Write parallel output in input order, byte-for-byte identical to a
+    /// sequential run. Without it, chunks are written as they finish, which
+    /// keeps workers busier behind a slow chunk (default: true)
+    pub preserve_order: bool,
+    /// Send matched rows to one writer, or have each worker thread write its
+    /// own file, concatenated or left as shards (default: Shared)
+    pub thread_output: ThreadOutput,
+    /// Write matched rows to one file per distinct value of this column.
+    /// The output path is then treated as a directory (default: None)
+    pub partition_by: Option<String>,
+    /// Start a new output shard after this many rows. With either shard limit
+    /// set, output goes to `<stem>.part-0001.<ext>`, `<stem>.part-0002.<ext>`, ...
+    /// next to the output path, listed in `<stem>.manifest.json` (default: None)
+    pub max_output_rows: Option<u64>,
+    /// Start a new output shard before it would grow past this many bytes (default: None)
+    pub max_output_bytes: Option<u64>,
+    /// Copy matched rows byte-for-byte or re-serialize them (default: Passthrough)
+    pub output_mode: OutputMode,
+    /// Quote style, quote character and escaping of re-serialized rows;
+    /// anything but the default re-serializes every row (default: quote
+    /// only where necessary, with doubled `"`)
+    pub quoting: Quoting,
+    /// Cross-check parallel output against the sequential path after each run (default: Off)
+    pub verify_mode: VerifyMode,
+    /// Write outputs in place or via a temporary file that is renamed on success (default: Direct)
+    pub write_mode: WriteMode,
+    /// Replace, refuse or append to an output file that already exists (default: Error)
+    pub on_existing_output: ExistingOutput,
+    /// Compress the output file with gzip, zstd or bgzip (`compression` feature) (default: None)
+    pub output_compression: OutputCompression,
+    /// Digest the output file while it is written and report it in
+    /// `ProcessingStats::output_digest` (default: None)
+    pub digest: Option<DigestAlgorithm>,
+    /// Also digest the input file while it is read (default: false)
+    pub digest_input: bool,
+    /// Whether a row with bad data stops the run or is skipped and counted in
+    /// `ProcessingStats::row_errors` (default: Fail)
+    pub on_row_error: RowErrorPolicy,
+    /// Retries and read sizes for remote inputs (`object-store` and `http` features)
+    pub remote: RemoteConfig,
+    /// Retries of transient I/O errors when opening and streaming local input
+    /// and loading indices
+    pub io_retry: IoRetryConfig,
+    /// Warn, or stop the run, if no row has matched once part of the input
+    /// has been read (default: None)
+    pub zero_match_guard: Option<ZeroMatchGuard>,
+    /// JSON file holding the header fingerprint of the previous run over
+    /// this dataset. A run whose input columns were added, dropped or
+    /// reordered since then warns before it starts, and the file is updated
+    /// after each successful run (default: None)
+    pub header_baseline: Option<PathBuf>,
+    /// Columns appended from the input file name, such as the phenotype of
+    /// per-trait summary statistics, when the input lacks them. The first
+    /// entry whose pattern matches applies (default: empty)
+    pub filename_columns: Vec<FilenameColumns>,
+    /// Columns left out of the output, by name or alias, including columns
+    /// transforms add. Names the output lacks are ignored, so one list can
+    /// serve several layouts (default: empty)
+    pub exclude_columns: Vec<String>,
+    /// Written in place of every output field that counts as empty (`""`,
+    /// `NA`, `N/A`, `.`, `null`, ...; see `filters::DEFAULT_EMPTY_TOKENS`),
+    /// so downstream parsers see one missing-value marker. Only the default
+    /// tokens are replaced, not those a filter was given with
+    /// `ColumnFilter::with_empty_tokens` (default: None)
+    pub output_na_token: Option<String>,
+}
+
+/// How a parallel run is checked against the sequential reference path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VerifyMode {
+    /// No verification (default)
+    #[default]
+    Off,
+    /// Re-run the sequential path and require byte-identical output
+    Full,
+    /// Compare an order-independent hash of the matched rows, restricted to the
+    /// rows whose content hash falls in a 1-in-`every` sample. Only the sampled
+    /// rows are filtered again, but the input is still read in full.
+    Sampled {
+        /// Sampling period; 1 checks every row
+        every: u64,
+    },
+}
+
+/// Configuration for splitting the input into chunks during parallel processing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkingConfig {
+    /// Resize chunks from measured throughput, starting at `chunk_size` (default: true)
+    pub adaptive: bool,
+    /// Smallest chunk an adaptive run will use, in bytes (default: 64KB)
+    pub min_chunk_size: usize,
+    /// Largest chunk an adaptive run will use, in bytes (default: 16MB)
+    pub max_chunk_size: usize,
+    /// Processing time each chunk should take in milliseconds (default: 50)
+    pub target_chunk_ms: u64,
+    /// Chunks dispatched but not yet written to the output, which bounds the
+    /// memory held by finished chunks (default: twice the worker count)
+    pub max_in_flight: Option<usize>,
+}
+
+/// Configuration for reading object store and HTTP(S) inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Times a failed request is retried, including reconnecting after a
+    /// scan's connection drops (default: 5)
+    pub max_retries: usize,
+    /// Wait before the first retry in milliseconds; doubles on each attempt (default: 100)
+    pub initial_backoff_ms: u64,
+    /// Longest wait between retries in milliseconds (default: 10000)
+    pub max_backoff_ms: u64,
+    /// Bytes fetched per ranged GET for random access (default: 8MB)
+    pub block_size: u64,
+}
+
+/// Retries of transient local I/O errors (timeouts, stale NFS handles and
+/// similar). Errors such as a missing file fail at once. Reads of
+/// memory-mapped input in parallel mode are not retried.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IoRetryConfig {
+    /// Times a failed open or read is retried (default: 3)
+    pub max_retries: usize,
+    /// Wait before the first retry in milliseconds; doubles on each attempt (default: 200)
+    pub initial_backoff_ms: u64,
+    /// Longest wait between retries in milliseconds (default: 5000)
+    pub max_backoff_ms: u64,
+}
+
+/// Catches filters that can never match, e.g. `Chr1` where the input has
+/// `chr1`, before a long scan writes an empty output
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZeroMatchGuard {
+    /// Share of the input in percent, by bytes read, after which the check
+    /// is made (default: 10)
+    pub after_percent: f64,
+    /// Stop the run with `ExtractorError::NoMatches` instead of adding a
+    /// warning to `ProcessingStats::warnings` (default: false)
+    pub abort: bool,
+}
+
+impl Default for ZeroMatchGuard {
+    fn default() -> Self {
+        Self { after_percent: 10.0, abort: false }
+    }
+}
+
+/// Column values derived from input file names matching a pattern. In a
+/// profile:
+///
+/// ```toml
+/// [[filename_columns]]
+/// pattern = "*_*.tsv.gz"
+/// columns = { Phenotype = "{1}", Study = "{2}" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilenameColumns {
+    /// File name pattern, where `*` matches any text
+    pub pattern: String,
+    /// Value of each column, appended in name order. `{1}`, `{2}`, ... stand
+    /// for the text matched by the first, second, ... `*` of the pattern.
+    pub columns: BTreeMap<String, String>,
 }
 
 /// Configuration for progress reporting
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ProgressConfig {
     /// Enable progress bar (default: true if feature enabled)
     pub enabled: bool,
@@ -51,6 +337,10 @@ pub struct ProgressConfig {
     pub refresh_rate: u64,
     /// Show ETA (default: true)
     pub show_eta: bool,
+    /// Sinks that receive progress besides the bar, e.g. a `CallbackProgress`
+    /// (default: none). Not read from or written to profiles.
+    #[serde(skip)]
+    pub sinks: Vec<Arc<dyn ProgressSink>>,
 }
 
 impl Default for Config {
@@ -58,11 +348,41 @@ impl Default for Config {
         Self {
             delimiter: b',',
             has_headers: true,
+            column_names: None,
+            encoding: InputEncoding::default(),
+            fixed_width: None,
+            column_aliases: HashMap::new(),
+            number_formats: HashMap::new(),
+            schema: None,
             chunk_size: 1024 * 1024, // 1MB
             parallel: true,
             use_index: false,
+            index_column: None,
             num_threads: None,
+            thread_pool: ThreadPoolSource::default(),
             progress: ProgressConfig::default(),
+            chunking: ChunkingConfig::default(),
+            preserve_order: true,
+            thread_output: ThreadOutput::default(),
+            partition_by: None,
+            max_output_rows: None,
+            max_output_bytes: None,
+            output_mode: OutputMode::default(),
+            quoting: Quoting::default(),
+            verify_mode: VerifyMode::default(),
+            write_mode: WriteMode::default(),
+            on_existing_output: ExistingOutput::default(),
+            output_compression: OutputCompression::default(),
+            digest: None,
+            digest_input: false,
+            on_row_error: RowErrorPolicy::default(),
+            remote: RemoteConfig::default(),
+            io_retry: IoRetryConfig::default(),
+            zero_match_guard: None,
+            header_baseline: None,
+            filename_columns: Vec::new(),
+            exclude_columns: Vec::new(),
+            output_na_token: None,
         }
     }
 }
@@ -73,6 +393,40 @@ impl Default for ProgressConfig {
             enabled: cfg!(feature = "progress-bars"),
             refresh_rate: 100,
             show_eta: true,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 10_000,
+            block_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for IoRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 5_000,
+        }
+    }
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            adaptive: true,
+            min_chunk_size: 64 * 1024,
+            max_chunk_size: 16 * 1024 * 1024,
+            target_chunk_ms: 50,
+            max_in_flight: None,
         }
     }
 }
@@ -93,6 +447,81 @@ pub struct ProcessingStats {
     pub input_size: u64,
     /// Output file size in bytes
     pub output_size: u64,
+    /// Per-pipeline results when running several pipelines in one pass
+    pub pipelines: Vec<PipelineStats>,
+    /// Pair counts when filtering paired-end reads with `SeqFilter`
+    pub pairs: Option<PairStats>,
+    /// Statistics for observed columns of the matched rows, keyed by column name
+    pub column_stats: HashMap<String, ColumnSummary>,
+    /// Summary of the matched rows per gene, with `BioFilter::set_gene_summary`
+    pub gene_summary: BTreeMap<String, GeneSummary>,
+    /// Encoding the input was read as (`Auto` for `process_stream`)
+    pub encoding: InputEncoding,
+    /// Whether the input started with a byte-order mark, which is not copied to outputs
+    pub bom: bool,
+    /// Hex digest of the input file, with `Config::digest_input`
+    pub input_digest: Option<String>,
+    /// Hex digest of the output file as written, with `Config::digest`. Only
+    /// single-file outputs are digested.
+    pub output_digest: Option<String>,
+    /// Configuration the run used, as from `Config::to_json`, so it can be
+    /// reproduced (`None` for `process_stream` and `process_into`)
+    pub config: Option<serde_json::Value>,
+    /// Rows skipped under `RowErrorPolicy::Skip`, grouped by error category
+    /// and column
+    pub row_errors: Vec<RowErrorSummary>,
+    /// Warnings raised during the run, e.g. by `Config::zero_match_guard`
+    pub warnings: Vec<String>,
+    /// Columns of the input's header row (`None` without one, and for
+    /// `process_stream` and `process_into`)
+    pub header: Option<HeaderFingerprint>,
+    /// High-water marks of the buffers the run held
+    pub memory: MemoryStats,
+}
+
+/// Memory a run held at its peak, for tuning `chunk_size`, `num_threads` and
+/// `chunking.max_in_flight`. Chunk and output figures come from parallel runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Peak resident set size of the process so far, where the OS reports it
+    /// (Linux). Covers the whole process, not only this run: the peak never
+    /// goes down, so a run after a bigger one reports the earlier peak.
+    pub peak_rss_bytes: Option<u64>,
+    /// Most chunks dispatched but not yet merged at once
+    pub peak_chunks_in_flight: usize,
+    /// Most input bytes in those chunks at once
+    pub peak_in_flight_bytes: u64,
+    /// Most matched-row bytes of finished chunks waiting to be written at once
+    pub peak_buffered_output_bytes: u64,
+    /// Approximate size of the index built while filtering, with `index_column`
+    pub index_bytes: u64,
+}
+
+/// Statistics for one named pipeline of a multi-pipeline run
+#[derive(Debug, Clone)]
+pub struct PipelineStats {
+    /// Pipeline name
+    pub name: String,
+    /// Where the pipeline's rows were written
+    pub output_path: PathBuf,
+    /// Number of rows the pipeline matched
+    pub rows_matched: u64,
+    /// Output file size in bytes
+    pub output_size: u64,
+}
+
+/// Outcome of filtering paired-end reads, where a pair is kept only if both
+/// mates pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PairStats {
+    /// Number of pairs read
+    pub pairs_processed: u64,
+    /// Number of pairs written to both outputs
+    pub pairs_kept: u64,
+    /// Pairs dropped although the first mate (R1) passed
+    pub orphaned_first: u64,
+    /// Pairs dropped although the second mate (R2) passed
+    pub orphaned_second: u64,
 }
 
 /// A builder for configuring and creating a BioFilter instance
@@ -102,6 +531,10 @@ pub struct ExtractorBuilder {
     input_path: PathBuf,
     output_path: PathBuf,
     index_path: Option<PathBuf>,
+    block_index_path: Option<PathBuf>,
+    column_cache_path: Option<PathBuf>,
+    /// Column filters, built by `build`
+    filters: Vec<(String, FilterCondition)>,
 }
 
 impl ExtractorBuilder {
@@ -112,6 +545,9 @@ impl ExtractorBuilder {
             input_path: input_path.into(),
             output_path: output_path.into(),
             index_path: None,
+            block_index_path: None,
+            column_cache_path: None,
+            filters: Vec::new(),
         }
     }
 
@@ -121,6 +557,14 @@ impl ExtractorBuilder {
         self
     }
 
+    /// Replace the configuration with the defaults, overlaid with `file` if
+    /// given and then with `EXTRACTOR_*` environment variables. Builder
+    /// methods called after this override all of them.
+    pub fn layered_config<P: AsRef<std::path::Path>>(mut self, file: Option<P>) -> Result<Self> {
+        self.config = Config::layered(file)?;
+        Ok(self)
+    }
+
     /// Set the index file path
     pub fn with_index<P: Into<PathBuf>>(mut self, index_path: P) -> Self {
         self.index_path = Some(index_path.into());
@@ -128,9 +572,379 @@ impl ExtractorBuilder {
         self
     }
 
+    /// Skip blocks of rows the filters rule out using the block index saved
+    /// at `path` (see `BioFilter::set_block_index`)
+    pub fn with_block_index<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.block_index_path = Some(path.into());
+        self
+    }
+
+    /// Test numeric filters against the column cache sidecar at `path`,
+    /// built by the first run (see `BioFilter::set_column_cache`)
+    pub fn with_column_cache<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.column_cache_path = Some(path.into());
+        self
+    }
+
+    /// Index the input on `column` during each run, so later lookups in the
+    /// same file don't need an indexing pass of their own. The index is then
+    /// available from `BioFilter::built_index`.
+    pub fn build_index_while_filtering<S: Into<String>>(mut self, column: S) -> Self {
+        self.config.index_column = Some(column.into());
+        self
+    }
+
+    /// Run parallel processing on an existing pool instead of building one per run
+    #[cfg(feature = "parallel")]
+    pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.config.thread_pool = ThreadPoolSource::Shared(pool);
+        self
+    }
+
+    /// Show a progress bar on stderr; needs the `progress-bars` feature
+    /// (default: on with the feature)
+    pub fn progress_bar(mut self, enabled: bool) -> Self {
+        self.config.progress.enabled = enabled;
+        self
+    }
+
+    /// Report progress to `sink` as well, alongside any bar or other sinks
+    pub fn progress_sink(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.config.progress.sinks.push(sink);
+        self
+    }
+
+    /// Call `callback` with the bytes processed so far and the input size
+    /// as chunks finish
+    pub fn on_progress<F>(self, callback: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        self.progress_sink(Arc::new(CallbackProgress::new(callback)))
+    }
+
+    /// Run parallel processing on rayon's global pool; `num_threads` is then ignored
+    pub fn use_global_thread_pool(mut self) -> Self {
+        self.config.thread_pool = ThreadPoolSource::Global;
+        self
+    }
+
+    /// Set how parallel processing sizes chunks and how many it keeps in flight
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.config.chunking = chunking;
+        self
+    }
+
+    /// Set how transient I/O errors on local input and indices are retried
+    pub fn with_io_retry(mut self, retry: IoRetryConfig) -> Self {
+        self.config.io_retry = retry;
+        self
+    }
+
+    /// Write matched rows into one file per distinct value of `column`
+    /// (e.g. `chr1.csv` … `chrX.csv`) plus a `manifest.json`, inside the output path
+    pub fn partition_by<S: Into<String>>(mut self, column: S) -> Self {
+        self.config.partition_by = Some(column.into());
+        self
+    }
+
+    /// Split the output into shards of at most `rows` rows, each with the
+    /// header, plus a manifest listing them
+    pub fn max_output_rows(mut self, rows: u64) -> Self {
+        self.config.max_output_rows = Some(rows);
+        self
+    }
+
+    /// Split the output into shards of at most `bytes` bytes, each with the
+    /// header, plus a manifest listing them
+    pub fn max_output_bytes(mut self, bytes: u64) -> Self {
+        self.config.max_output_bytes = Some(bytes);
+        self
+    }
+
+    /// Treat the input as headerless and name its columns
+    pub fn column_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.has_headers = false;
+        self.config.column_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Read the input as `dialect`: sets the delimiter and column aliases,
+    /// and names the columns of headerless formats. Call after `with_config`,
+    /// which replaces them.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        dialect.apply(&mut self.config);
+        self
+    }
+
+    /// Read the input as `encoding` instead of detecting it
+    pub fn encoding(mut self, encoding: InputEncoding) -> Self {
+        self.config.encoding = encoding;
+        self
+    }
+
+    /// Read the input as fixed-width or space-aligned columns laid out as
+    /// `layout`. Outputs are delimited with the configured delimiter.
+    pub fn fixed_width(mut self, layout: FixedWidth) -> Self {
+        self.config.fixed_width = Some(layout);
+        self
+    }
+
+    /// Let filters refer to a column by a canonical name whatever the input
+    /// calls it, e.g. `[("p_value", ["P", "pval", "P-VALUE"])]`. Header
+    /// names are matched ignoring case.
+    pub fn with_column_aliases<I, K, V, S>(mut self, aliases: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for (canonical, variants) in aliases {
+            self.config
+                .column_aliases
+                .entry(canonical.into())
+                .or_default()
+                .extend(variants.into_iter().map(Into::into));
+        }
+        self
+    }
+
+    /// Read numbers in `column` as written in `format` for numeric and range
+    /// filters, e.g. `NumberFormat::european()` for `1.234,56`
+    pub fn number_format<S: Into<String>>(mut self, column: S, format: NumberFormat) -> Self {
+        self.config.number_formats.insert(column.into(), format);
+        self
+    }
+
+    /// Declare column types; `build()` fails with the first column and row
+    /// whose value doesn't match
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.config.schema = Some(schema);
+        self
+    }
+
+    /// Quote output fields as `quoting` says, e.g. `Quoting::always()` for
+    /// importers that reject bare values. Rows are then re-serialized.
+    pub fn quoting(mut self, quoting: Quoting) -> Self {
+        self.config.quoting = quoting;
+        self
+    }
+
+    /// Choose whether matched rows keep their original bytes or are re-serialized
+    pub fn output_mode(mut self, mode: OutputMode) -> Self {
+        self.config.output_mode = mode;
+        self
+    }
+
+    /// Keep parallel output in input order, or write chunks as they finish
+    pub fn preserve_order(mut self, preserve: bool) -> Self {
+        self.config.preserve_order = preserve;
+        self
+    }
+
+    /// Have each worker thread write its own output file instead of sending
+    /// rows to a single writer
+    pub fn thread_output(mut self, output: ThreadOutput) -> Self {
+        self.config.thread_output = output;
+        self
+    }
+
+    /// Cross-check parallel runs against the sequential reference path
+    pub fn verify_mode(mut self, mode: VerifyMode) -> Self {
+        self.config.verify_mode = mode;
+        self
+    }
+
+    /// Write outputs to a temporary file and rename it into place once the
+    /// run succeeds
+    pub fn atomic_output(mut self, atomic: bool) -> Self {
+        self.config.write_mode = match (atomic, self.config.write_mode) {
+            (false, _) => WriteMode::Direct,
+            (true, WriteMode::Direct) => WriteMode::Atomic { fsync: false },
+            (true, mode) => mode,
+        };
+        self
+    }
+
+    /// Fsync atomic outputs before the rename and their directory after it.
+    /// Only applies once [`atomic_output`](Self::atomic_output) is on; direct
+    /// writes are never synced.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        if let WriteMode::Atomic { fsync: synced } = &mut self.config.write_mode {
+            *synced = fsync;
+        }
+        self
+    }
+
+    /// Allow replacing an existing output file. Off by default: the run
+    /// fails instead of clobbering it.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.config.on_existing_output = if overwrite {
+            ExistingOutput::Overwrite
+        } else {
+            ExistingOutput::Error
+        };
+        self
+    }
+
+    /// Append matched rows to an existing output file instead of replacing
+    /// it. The header is not repeated and must match the input's.
+    pub fn append(mut self, append: bool) -> Self {
+        if append {
+            self.config.on_existing_output = ExistingOutput::Append;
+        } else if self.config.on_existing_output == ExistingOutput::Append {
+            self.config.on_existing_output = ExistingOutput::default();
+        }
+        self
+    }
+
+    /// Compress the output file. Bgzip output can also get a tabix index,
+    /// written to `<output>.tbi`.
+    pub fn output_compression(mut self, compression: OutputCompression) -> Self {
+        self.config.output_compression = compression;
+        self
+    }
+
+    /// Report a digest of the output file, and of the input file when
+    /// `input` is set, in the processing stats
+    pub fn digest(mut self, algorithm: DigestAlgorithm, input: bool) -> Self {
+        self.config.digest = Some(algorithm);
+        self.config.digest_input = input;
+        self
+    }
+
+    /// Skip rows with bad data instead of failing, and summarize them in the stats
+    pub fn skip_bad_rows(mut self) -> Self {
+        self.config.on_row_error = RowErrorPolicy::Skip;
+        self
+    }
+
+    /// Compare the input's columns with those of the previous run recorded
+    /// in `path`, warning if they changed, and record this run's columns there
+    pub fn header_baseline<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config.header_baseline = Some(path.into());
+        self
+    }
+
+    /// Warn, or stop the run if `abort` is set, when no row has matched after
+    /// `after_percent` percent of the input has been read
+    pub fn zero_match_guard(mut self, after_percent: f64, abort: bool) -> Self {
+        self.config.zero_match_guard = Some(ZeroMatchGuard { after_percent, abort });
+        self
+    }
+
+    /// Append `columns` to the output when the input file name matches
+    /// `pattern` and the input lacks them; see `FilenameColumns`
+    pub fn filename_columns<I, K, V>(mut self, pattern: &str, columns: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.config.filename_columns.push(FilenameColumns {
+            pattern: pattern.to_string(),
+            columns: columns.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+        });
+        self
+    }
+
+    /// Leave these columns out of the output
+    pub fn exclude_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.exclude_columns.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Write `token`, e.g. `NA`, for every empty field or default
+    /// missing-value token of the output
+    pub fn output_na_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.config.output_na_token = Some(token.into());
+        self
+    }
+
+    /// Add a column filter. It is built with the BioFilter, so `validate` can
+    /// report a bad condition such as a regex that doesn't compile.
+    pub fn filter<S: Into<String>>(mut self, column: S, condition: FilterCondition) -> Self {
+        self.filters.push((column.into(), condition));
+        self
+    }
+
+    /// Dry-run the setup: check the input and its format, that filtered and
+    /// partition columns are in the header, that filters and the index are
+    /// valid, and that the output can be written. Returns every issue found;
+    /// an empty list means `build` and `process` shouldn't fail on setup.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let index = self.index_path.as_ref().and_then(|path| match FileIndex::load(path) {
+            Ok(index) => Some(Arc::new(index)),
+            Err(e) => {
+                issues.push(ValidationIssue::Index(e.to_string()));
+                None
+            }
+        });
+        let mut config = self.config.clone();
+        // An index that failed to load is reported once, above
+        config.use_index &= index.is_some() || self.index_path.is_none();
+        let mut filter = BioFilter::unchecked(self.input_path.clone(), self.output_path.clone(), config, index);
+        if let Some(path) = &self.block_index_path {
+            match BlockIndex::load(path) {
+                Ok(blocks) => filter.set_block_index(blocks),
+                Err(e) => issues.push(ValidationIssue::Index(e.to_string())),
+            }
+        }
+        if let Some(path) = &self.column_cache_path {
+            filter.set_column_cache(path.clone());
+        }
+        for (column, condition) in &self.filters {
+            match self.column_filter(column.clone(), condition.clone()) {
+                Ok(column_filter) => filter.add_filter(Box::new(column_filter)),
+                Err(e) => issues.push(ValidationIssue::InvalidFilter { column: column.clone(), message: e.to_string() }),
+            }
+        }
+        issues.extend(filter.validate());
+        issues
+    }
+
     /// Build the BioFilter instance
     pub fn build(self) -> Result<BioFilter> {
-        BioFilter::new(self.input_path, self.output_path, self.config, self.index_path)
+        let column_filters = self
+            .filters
+            .iter()
+            .map(|(column, condition)| self.column_filter(column.clone(), condition.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let block_index = self
+            .block_index_path
+            .map(|path| retry::retry(&self.config.io_retry, || BlockIndex::load(&path)))
+            .transpose()?;
+        let mut filter = BioFilter::new(self.input_path, self.output_path, self.config, self.index_path)?;
+        if let Some(blocks) = block_index {
+            filter.set_block_index(blocks);
+        }
+        if let Some(path) = self.column_cache_path {
+            filter.set_column_cache(path);
+        }
+        for column_filter in column_filters {
+            filter.add_filter(Box::new(column_filter));
+        }
+        Ok(filter)
+    }
+
+    /// A filter on `column`, reading numbers in the column's configured format
+    fn column_filter(&self, column: String, condition: FilterCondition) -> Result<filters::ColumnFilter> {
+        let format = self.config.number_formats.get(&column).copied();
+        let column_filter = filters::ColumnFilter::new(column, condition)?;
+        Ok(match format {
+            Some(format) => column_filter.with_number_format(format),
+            None => column_filter,
+        })
     }
 }
 
@@ -154,6 +968,34 @@ mod tests {
         assert!(filter.is_ok());
     }
 
+    #[test]
+    fn test_validate() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.csv");
+        std::fs::write(&input, "gene,score\nBRCA1,5\n")?;
+
+        let issues = builder(input.clone(), dir.path().join("out.csv"))
+            .filter("score", FilterCondition::NotEmpty)
+            .validate();
+        assert!(issues.is_empty(), "{issues:?}");
+
+        let issues = builder(input, dir.path().join("missing").join("out.csv"))
+            .with_index(dir.path().join("input.idx"))
+            .filter("gene", FilterCondition::Regex("BRCA(".to_string()))
+            .filter("pvalue", FilterCondition::NotEmpty)
+            .validate();
+        assert!(matches!(
+            issues.as_slice(),
+            [
+                ValidationIssue::Index(_),
+                ValidationIssue::InvalidFilter { .. },
+                ValidationIssue::UnknownColumn { column, .. },
+                ValidationIssue::Output { .. },
+            ] if column == "pvalue"
+        ), "{issues:?}");
+        Ok(())
+    }
+
     #[test]
     fn test_default_config() {
         let config = Config::default();
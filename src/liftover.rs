@@ -0,0 +1,309 @@
+//! Convert coordinates between genome builds (e.g. hg19 → hg38) with a UCSC
+//! chain file, as a row transform.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::ByteRecord;
+
+use crate::error::ExtractorError;
+use crate::transform::{replace_field, RowTransform};
+use crate::Result;
+
+/// One gap-free aligned block of a chain, in 0-based half-open coordinates
+#[derive(Debug, Clone)]
+struct Block {
+    start: u64,
+    end: u64,
+    /// Position in the target build that `start` maps to, on the chain's strand
+    target_start: u64,
+    target: Arc<str>,
+    target_size: u64,
+    reverse: bool,
+    score: f64,
+}
+
+/// Aligned blocks of a UCSC chain file, indexed by source chromosome
+#[derive(Debug, Default)]
+pub struct ChainFile {
+    /// Blocks sorted by start, with the largest end seen so far at each index
+    chroms: HashMap<String, (Vec<Block>, Vec<u64>)>,
+}
+
+impl ChainFile {
+    /// Load a chain file such as `hg19ToHg38.over.chain.gz`; `.gz` files are
+    /// decompressed
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+            Box::new(flate2::read::MultiGzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Self::parse(BufReader::new(reader))
+    }
+
+    /// Parse chain file text
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let invalid = |line: usize, message: &str| {
+            ExtractorError::Config(format!("Invalid chain file at line {line}: {message}"))
+        };
+        let mut blocks: HashMap<String, Vec<Block>> = HashMap::new();
+        // (source chromosome, next source offset, next target offset, template block)
+        let mut current: Option<(String, u64, u64, Block)> = None;
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = number + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => current = None,
+                ["chain", score, t_name, _t_size, _t_strand, t_start, _t_end, q_name, q_size, q_strand, q_start, ..] => {
+                    let parse = |value: &str| value.parse::<u64>().map_err(|_| invalid(number, "bad number"));
+                    let template = Block {
+                        start: 0,
+                        end: 0,
+                        target_start: 0,
+                        target: Arc::from(*q_name),
+                        target_size: parse(q_size)?,
+                        reverse: *q_strand == "-",
+                        score: score.parse().map_err(|_| invalid(number, "bad score"))?,
+                    };
+                    current = Some((t_name.to_string(), parse(t_start)?, parse(q_start)?, template));
+                }
+                [size, gaps @ ..] if gaps.is_empty() || gaps.len() == 2 => {
+                    let (chrom, source, target, template) =
+                        current.as_mut().ok_or_else(|| invalid(number, "alignment data before a chain header"))?;
+                    let parse = |value: &str| value.parse::<u64>().map_err(|_| invalid(number, "bad number"));
+                    let size = parse(size)?;
+                    blocks.entry(chrom.clone()).or_default().push(Block {
+                        start: *source,
+                        end: *source + size,
+                        target_start: *target,
+                        ..template.clone()
+                    });
+                    *source += size;
+                    *target += size;
+                    if let [source_gap, target_gap] = gaps {
+                        *source += parse(source_gap)?;
+                        *target += parse(target_gap)?;
+                    } else {
+                        current = None;
+                    }
+                }
+                _ => return Err(invalid(number, "unexpected line")),
+            }
+        }
+
+        let chroms = blocks
+            .into_iter()
+            .map(|(chrom, mut blocks)| {
+                blocks.sort_by_key(|block| block.start);
+                let max_end = blocks
+                    .iter()
+                    .scan(0, |max, block| {
+                        *max = block.end.max(*max);
+                        Some(*max)
+                    })
+                    .collect();
+                (strip_chr(&chrom).to_string(), (blocks, max_end))
+            })
+            .collect();
+        Ok(Self { chroms })
+    }
+
+    /// Map a 1-based position to the target build, returning the target
+    /// chromosome and position. Where chains overlap the highest-scoring one wins.
+    pub fn map(&self, chrom: &str, pos: u64) -> Option<(&str, u64)> {
+        let pos = pos.checked_sub(1)?;
+        let (blocks, max_end) = self.chroms.get(strip_chr(chrom))?;
+        let candidates = blocks.partition_point(|block| block.start <= pos);
+        let block = (0..candidates)
+            .rev()
+            .take_while(|&i| max_end[i] > pos)
+            .map(|i| &blocks[i])
+            .filter(|block| pos < block.end)
+            .max_by(|a, b| a.score.total_cmp(&b.score))?;
+
+        let offset = block.target_start + (pos - block.start);
+        let mapped = if block.reverse { block.target_size - 1 - offset } else { offset };
+        Some((&block.target, mapped + 1))
+    }
+}
+
+fn strip_chr(chrom: &str) -> &str {
+    let chrom = chrom.trim();
+    match chrom.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("chr") => &chrom[3..],
+        _ => chrom,
+    }
+}
+
+/// What happens to rows whose coordinates don't map to the target build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unmapped {
+    /// Leave them out of the output
+    Drop,
+    /// Keep them with their original coordinates and add a column with this
+    /// name holding `mapped` or `unmapped` for every row
+    Flag(String),
+}
+
+/// Row transform that rewrites a chromosome and position column (and
+/// optionally an end column) into another genome build
+pub struct Liftover {
+    chain: Arc<ChainFile>,
+    chrom_column: String,
+    pos_column: String,
+    end_column: Option<String>,
+    unmapped: Unmapped,
+}
+
+impl Liftover {
+    /// Convert `chrom_column`/`pos_column` (1-based) with `chain`, dropping
+    /// unmapped rows
+    pub fn new<S: Into<String>>(chain: Arc<ChainFile>, chrom_column: S, pos_column: S) -> Self {
+        Self {
+            chain,
+            chrom_column: chrom_column.into(),
+            pos_column: pos_column.into(),
+            end_column: None,
+            unmapped: Unmapped::Drop,
+        }
+    }
+
+    /// Also convert an end coordinate; the row is unmapped unless start and
+    /// end land on the same target chromosome
+    pub fn with_end_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.end_column = Some(column.into());
+        self
+    }
+
+    /// Choose what happens to rows that can't be converted
+    pub fn unmapped(mut self, unmapped: Unmapped) -> Self {
+        self.unmapped = unmapped;
+        self
+    }
+
+    fn column(&self, name: &str, headers: &HashMap<String, usize>) -> Result<usize> {
+        headers
+            .get(name)
+            .copied()
+            .ok_or_else(|| ExtractorError::column_not_in_headers(name, headers))
+    }
+
+    /// New chromosome and coordinates for a row, or `None` if it doesn't map
+    fn lift(&self, row: &ByteRecord, chrom: usize, coords: &[usize]) -> Option<(String, Vec<u64>)> {
+        let source = std::str::from_utf8(row.get(chrom)?).ok()?;
+        let mut target_chrom = None;
+        let mut mapped = Vec::with_capacity(coords.len());
+        for &idx in coords {
+            let pos = std::str::from_utf8(row.get(idx)?).ok()?.trim().parse().ok()?;
+            let (chrom, pos) = self.chain.map(source, pos)?;
+            if target_chrom.is_some_and(|seen| seen != chrom) {
+                return None;
+            }
+            target_chrom = Some(chrom);
+            mapped.push(pos);
+        }
+        // Keep the input's naming style: `chr7` stays prefixed, `7` does not
+        let target = target_chrom?;
+        let target = if source.trim() == strip_chr(source) { strip_chr(target) } else { target };
+        if let [start, end] = mapped[..] {
+            mapped = vec![start.min(end), start.max(end)];
+        }
+        Some((target.to_string(), mapped))
+    }
+}
+
+impl RowTransform for Liftover {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let Unmapped::Flag(column) = &self.unmapped else { return None };
+        let mut header = header.clone();
+        header.push_field(column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let chrom = self.column(&self.chrom_column, headers)?;
+        let mut coords = vec![self.column(&self.pos_column, headers)?];
+        if let Some(end) = &self.end_column {
+            coords.push(self.column(end, headers)?);
+        }
+
+        let lifted = self.lift(row, chrom, &coords);
+        if let Some((target, positions)) = &lifted {
+            *row = replace_field(row, chrom, target.as_bytes());
+            for (&idx, pos) in coords.iter().zip(positions) {
+                *row = replace_field(row, idx, pos.to_string().as_bytes());
+            }
+        }
+        match &self.unmapped {
+            Unmapped::Drop => Ok(lifted.is_some()),
+            Unmapped::Flag(_) => {
+                row.push_field(if lifted.is_some() { b"mapped" } else { b"unmapped" });
+                Ok(true)
+            }
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("liftover {}:{}", self.chrom_column, self.pos_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chr1:1000-1100 maps to chr1:2000-2100 with a 10bp source gap at 1050;
+    // chr2:0-100 maps to the reverse strand of chr5 (size 1000)
+    const CHAIN: &str = "\
+chain 1000 chr1 5000 + 1000 1110 chr1 6000 + 2000 2100 1
+50 10 0
+50
+
+chain 500 chr2 3000 + 0 100 chr5 1000 - 0 100 2
+100
+";
+
+    #[test]
+    fn test_chain_mapping() -> Result<()> {
+        let chain = ChainFile::parse(CHAIN.as_bytes())?;
+        assert_eq!(chain.map("chr1", 1001), Some(("chr1", 2001)));
+        assert_eq!(chain.map("1", 1050), Some(("chr1", 2050)));
+        // Inside the gap, and past the end of the chain
+        assert_eq!(chain.map("chr1", 1055), None);
+        assert_eq!(chain.map("chr1", 1061), Some(("chr1", 2051)));
+        assert_eq!(chain.map("chr1", 1111), None);
+        assert_eq!(chain.map("chr2", 1), Some(("chr5", 1000)));
+        assert_eq!(chain.map("chr3", 1), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_liftover_transform() -> Result<()> {
+        let chain = Arc::new(ChainFile::parse(CHAIN.as_bytes())?);
+        let headers: HashMap<String, usize> =
+            [("chrom".to_string(), 0), ("pos".to_string(), 1)].into_iter().collect();
+
+        let drop = Liftover::new(chain.clone(), "chrom", "pos");
+        let mut row = ByteRecord::from(vec!["1", "1001", "rs1"]);
+        assert!(drop.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["1", "2001", "rs1"]));
+        assert!(!drop.apply(&mut ByteRecord::from(vec!["chr1", "1055", "rs2"]), &headers)?);
+
+        let flag = Liftover::new(chain, "chrom", "pos").unmapped(Unmapped::Flag("lifted".into()));
+        assert_eq!(
+            flag.header(&ByteRecord::from(vec!["chrom", "pos", "rsid"])),
+            Some(ByteRecord::from(vec!["chrom", "pos", "rsid", "lifted"]))
+        );
+        let mut row = ByteRecord::from(vec!["chr1", "NA", "rs3"]);
+        assert!(flag.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["chr1", "NA", "rs3", "unmapped"]));
+        Ok(())
+    }
+}
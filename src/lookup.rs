@@ -0,0 +1,263 @@
+//! Hash joins against a small reference CSV: keep rows whose key is (or is
+//! not) in the table, or append the table's columns to matched rows.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::transform::RowTransform;
+use crate::Result;
+
+/// A reference CSV held in memory, keyed on one column. Keys are trimmed;
+/// when a key repeats the first row wins.
+#[derive(Debug)]
+pub struct LookupTable {
+    key_column: String,
+    /// Names of the non-key columns, in file order
+    columns: Vec<String>,
+    /// Non-key values of each row by key
+    rows: HashMap<Vec<u8>, ByteRecord>,
+}
+
+impl LookupTable {
+    /// Load `path`, which must have a header row containing `key_column`
+    pub fn load(path: &Path, key_column: &str, delimiter: u8) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Self::from_reader(file, key_column, delimiter)
+    }
+
+    /// Read a table from CSV with a header row
+    pub fn from_reader<R: Read>(input: R, key_column: &str, delimiter: u8) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let names: Vec<String> = header_names(reader.byte_headers()?).collect();
+        let key_idx = names
+            .iter()
+            .position(|name| name == key_column)
+            .ok_or_else(|| ExtractorError::column_not_found(key_column, &names))?;
+
+        let mut rows = HashMap::new();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let key = record.get(key_idx).unwrap_or_default().trim_ascii().to_vec();
+            let values = (0..names.len())
+                .filter(|&i| i != key_idx)
+                .map(|i| record.get(i).unwrap_or_default())
+                .collect();
+            rows.entry(key).or_insert(values);
+        }
+
+        let columns = names
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, name)| (i != key_idx).then_some(name))
+            .collect();
+        Ok(Self { key_column: key_column.to_string(), columns, rows })
+    }
+
+    /// Load a key list such as withdrawn rsIDs: one key per line, no header.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn load_keys(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+        Self::from_keys(std::io::BufReader::new(file), &name)
+    }
+
+    /// Read a key list, naming it `name` in filter descriptions
+    pub fn from_keys<R: BufRead>(input: R, name: &str) -> Result<Self> {
+        let mut rows = HashMap::new();
+        for line in input.split(b'\n') {
+            let line = line?;
+            let key = line.trim_ascii();
+            if !key.is_empty() && !key.starts_with(b"#") {
+                rows.entry(key.to_vec()).or_insert_with(ByteRecord::new);
+            }
+        }
+        Ok(Self { key_column: name.to_string(), columns: Vec::new(), rows })
+    }
+
+    /// Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether the table has no rows
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Names of the columns other than the key
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Non-key values of the row with this key
+    pub fn get(&self, key: &[u8]) -> Option<&ByteRecord> {
+        self.rows.get(key.trim_ascii())
+    }
+}
+
+/// Index of `column` in the main input, or `ColumnNotFound`
+fn column_index(column: &str, headers: &HashMap<String, usize>) -> Result<usize> {
+    headers
+        .get(column)
+        .copied()
+        .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))
+}
+
+/// Keeps rows whose `column` value is a key of the table (a semi-join), or
+/// with `absent`, rows whose value is not (an anti-join)
+pub struct LookupFilter {
+    column: String,
+    table: Arc<LookupTable>,
+    present: bool,
+}
+
+impl LookupFilter {
+    /// Keep rows whose `column` value is in `table`
+    pub fn present(column: String, table: Arc<LookupTable>) -> Self {
+        Self { column, table, present: true }
+    }
+
+    /// Keep rows whose `column` value is not in `table`
+    pub fn absent(column: String, table: Arc<LookupTable>) -> Self {
+        Self { column, table, present: false }
+    }
+}
+
+impl Filter for LookupFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = row.get(column_index(&self.column, headers)?).unwrap_or_default();
+        Ok(self.table.get(value).is_some() == self.present)
+    }
+
+    fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    fn description(&self) -> String {
+        let op = if self.present { "in" } else { "not in" };
+        format!("{} {op} lookup on {} ({} keys)", self.column, self.table.key_column, self.table.len())
+    }
+}
+
+/// Row transform that appends the table's columns for the row's key; rows
+/// without a match get empty values unless the join is `inner`
+pub struct LookupJoin {
+    column: String,
+    table: Arc<LookupTable>,
+    /// Table columns to append, as indices into `table.columns`
+    selected: Vec<usize>,
+    inner: bool,
+}
+
+impl LookupJoin {
+    /// Append every non-key column of `table`, joined on `column`
+    pub fn new(column: String, table: Arc<LookupTable>) -> Self {
+        let selected = (0..table.columns.len()).collect();
+        Self { column, table, selected, inner: false }
+    }
+
+    /// Append only these table columns, in this order
+    pub fn columns<I, S>(mut self, columns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.selected = columns
+            .into_iter()
+            .map(|name| {
+                let name = name.as_ref();
+                self.table
+                    .columns
+                    .iter()
+                    .position(|column| column == name)
+                    .ok_or_else(|| ExtractorError::column_not_found(name, &self.table.columns))
+            })
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    /// Drop rows whose key is not in the table
+    pub fn inner(mut self, inner: bool) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl RowTransform for LookupJoin {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        for &i in &self.selected {
+            header.push_field(self.table.columns[i].as_bytes());
+        }
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = row.get(column_index(&self.column, headers)?).unwrap_or_default();
+        let Some(found) = self.table.get(value) else {
+            for _ in &self.selected {
+                row.push_field(b"");
+            }
+            return Ok(!self.inner);
+        };
+        for &i in &self.selected {
+            row.push_field(found.get(i).unwrap_or_default());
+        }
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("join {} on {}", self.column, self.table.key_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHWAYS: &str = "\
+gene,pathway,source
+TP53,p53 signaling,KEGG
+APOE,lipid transport,Reactome
+TP53,apoptosis,GO
+";
+
+    #[test]
+    fn test_lookup_join() -> Result<()> {
+        let table = Arc::new(LookupTable::from_reader(PATHWAYS.as_bytes(), "gene", b',')?);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.columns(), ["pathway", "source"]);
+
+        let headers: HashMap<String, usize> = [("symbol".to_string(), 0)].into_iter().collect();
+        let member = LookupFilter::present("symbol".to_string(), table.clone());
+        assert!(member.apply(&ByteRecord::from(vec![" TP53"]), &headers)?);
+        assert!(!LookupFilter::absent("symbol".to_string(), table.clone())
+            .apply(&ByteRecord::from(vec!["APOE"]), &headers)?);
+
+        let join = LookupJoin::new("symbol".to_string(), table.clone()).columns(["pathway"])?;
+        assert_eq!(
+            join.header(&ByteRecord::from(vec!["symbol"])),
+            Some(ByteRecord::from(vec!["symbol", "pathway"]))
+        );
+        let mut row = ByteRecord::from(vec!["TP53"]);
+        assert!(join.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["TP53", "p53 signaling"]));
+        let mut row = ByteRecord::from(vec!["BRCA1"]);
+        assert!(join.apply(&mut row, &headers)?);
+        assert_eq!(row, ByteRecord::from(vec!["BRCA1", ""]));
+        assert!(!join.inner(true).apply(&mut ByteRecord::from(vec!["BRCA1"]), &headers)?);
+
+        assert!(LookupJoin::new("symbol".to_string(), table).columns(["pubmed"]).is_err());
+        Ok(())
+    }
+}
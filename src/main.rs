@@ -0,0 +1,58 @@
+//! Command-line entry point. The library is the main interface; this only
+//! exposes the self-benchmark so it can run from ops scripts:
+//!
+//! ```text
+//! extractor bench --input file.csv [--delimiter '\t'] [--iterations 3]
+//! ```
+//!
+//! which prints the `BenchReport` as one line of JSON.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use extractor::{Bench, Config};
+
+const USAGE: &str = "usage: extractor bench --input <file> [--delimiter <char>] [--iterations <n>]";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(json) => {
+            println!("{json}");
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the command in `args`, returning the JSON report
+fn run(args: Vec<String>) -> Result<String, String> {
+    let mut args = args.into_iter();
+    if args.next().as_deref() != Some("bench") {
+        return Err(USAGE.to_string());
+    }
+    let (mut input, mut config, mut iterations) = (None, Config::default(), 3);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{flag} needs a value\n{USAGE}"))?;
+        match flag.as_str() {
+            "--input" => input = Some(PathBuf::from(value)),
+            "--delimiter" => config.delimiter = delimiter(&value)?,
+            "--iterations" => iterations = value.parse().map_err(|_| format!("invalid --iterations: {value}"))?,
+            _ => return Err(format!("unknown option {flag}\n{USAGE}")),
+        }
+    }
+    let input = input.ok_or_else(|| USAGE.to_string())?;
+    let report = Bench::new(input).with_config(config).iterations(iterations).run().map_err(|e| e.to_string())?;
+    report.to_json().map_err(|e| e.to_string())
+}
+
+/// A single-byte delimiter, accepting `\t` for a tab
+fn delimiter(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        b"\\t" => Ok(b'\t'),
+        [byte] => Ok(*byte),
+        _ => Err(format!("invalid --delimiter: {value}")),
+    }
+}
@@ -0,0 +1,437 @@
+//! Column observers for collecting statistics during filtering.
+//! Observers see every matched row, so a single pass can both filter the data
+//! and summarize selected columns of the output, or summarize it per gene.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str;
+
+use csv::ByteRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ExtractorError;
+use crate::hgvs::Consequence;
+use crate::output::{ExistingOutput, OutputFile, WriteMode};
+use crate::Result;
+
+/// Requests statistics for one column of the matched rows
+#[derive(Debug, Clone)]
+pub struct ColumnObserver {
+    column: String,
+    max_unique: usize,
+}
+
+impl ColumnObserver {
+    /// Observe `column`, counting up to 100,000 distinct values
+    pub fn new<S: Into<String>>(column: S) -> Self {
+        Self {
+            column: column.into(),
+            max_unique: 100_000,
+        }
+    }
+
+    /// Stop tracking distinct values after this many (0 disables unique counting).
+    /// Bounds memory on high-cardinality columns such as rsIDs.
+    pub fn with_max_unique(mut self, max_unique: usize) -> Self {
+        self.max_unique = max_unique;
+        self
+    }
+
+    /// Get the name of the observed column
+    pub fn column_name(&self) -> &str {
+        &self.column
+    }
+}
+
+/// Statistics for one observed column over the matched rows
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSummary {
+    /// Number of matched rows with a non-empty value
+    pub count: u64,
+    /// Number of matched rows where the value was empty, missing or a
+    /// missing-value token such as `NA`
+    pub empty: u64,
+    /// Number of values that parsed as finite numbers
+    pub numeric_count: u64,
+    /// Smallest numeric value
+    pub min: Option<f64>,
+    /// Largest numeric value
+    pub max: Option<f64>,
+    /// Mean of the numeric values
+    pub mean: Option<f64>,
+    /// Number of distinct non-empty values (a lower bound if `unique_capped`)
+    pub unique_count: u64,
+    /// True if the distinct value limit was hit
+    pub unique_capped: bool,
+}
+
+/// Requests a summary of the matched rows per gene, for gene summary cards:
+/// rows per phenotype, the smallest p-value and the most severe consequence
+#[derive(Debug, Clone)]
+pub struct GeneSummaryObserver {
+    gene_column: String,
+    phenotype_column: Option<String>,
+    p_value_column: Option<String>,
+    consequence_column: Option<String>,
+    output: Option<PathBuf>,
+}
+
+impl GeneSummaryObserver {
+    /// Summarize rows by the gene in `gene_column`
+    pub fn new<S: Into<String>>(gene_column: S) -> Self {
+        Self {
+            gene_column: gene_column.into(),
+            phenotype_column: None,
+            p_value_column: None,
+            consequence_column: None,
+            output: None,
+        }
+    }
+
+    /// Count rows per phenotype in this column
+    pub fn phenotype_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.phenotype_column = Some(column.into());
+        self
+    }
+
+    /// Track the smallest p-value in this column
+    pub fn p_value_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.p_value_column = Some(column.into());
+        self
+    }
+
+    /// Track the most severe consequence in this column, read as a protein
+    /// HGVS change or an effect term (see `Consequence::infer`)
+    pub fn consequence_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.consequence_column = Some(column.into());
+        self
+    }
+
+    /// Also write the summary as compact JSON to `path` after each run
+    pub fn write_to<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Every column the observer reads
+    pub(crate) fn columns(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.gene_column)
+            .chain(&self.phenotype_column)
+            .chain(&self.p_value_column)
+            .chain(&self.consequence_column)
+            .map(String::as_str)
+    }
+
+    /// Where the summary is written, if anywhere
+    pub(crate) fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+}
+
+/// Summary of the matched rows of one gene
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeneSummary {
+    /// Number of matched rows
+    pub variants: u64,
+    /// Matched rows per phenotype, without rows whose phenotype is empty
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phenotypes: BTreeMap<String, u64>,
+    /// Smallest p-value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_p_value: Option<f64>,
+    /// Most severe consequence recognised
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub most_severe_consequence: Option<Consequence>,
+}
+
+impl GeneSummary {
+    fn merge(&mut self, other: GeneSummary) {
+        self.variants += other.variants;
+        for (phenotype, rows) in other.phenotypes {
+            *self.phenotypes.entry(phenotype).or_default() += rows;
+        }
+        self.min_p_value = match (self.min_p_value, other.min_p_value) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        };
+        self.most_severe_consequence = match (self.most_severe_consequence, other.most_severe_consequence) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        };
+    }
+}
+
+/// Write per-gene summaries as compact JSON, keyed by gene
+pub(crate) fn save_gene_summary(path: &Path, summary: &BTreeMap<String, GeneSummary>) -> Result<()> {
+    let (output, file) = OutputFile::create(path, WriteMode::Atomic { fsync: false }, ExistingOutput::Overwrite)?;
+    serde_json::to_writer(file, summary)?;
+    output.commit()
+}
+
+/// Distinct values of one column over the whole input, from
+/// `BioFilter::distinct_values`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistinctValues {
+    /// Non-empty values with the number of rows holding each, most common first
+    pub values: Vec<(String, u64)>,
+    /// True if the column has more distinct values than the limit
+    pub capped: bool,
+}
+
+impl DistinctValues {
+    /// Sort counted values, most common first, and keep at most `limit`
+    pub(crate) fn from_counts(counts: HashMap<String, u64>, limit: usize, capped: bool) -> Self {
+        let mut values: Vec<(String, u64)> = counts.into_iter().collect();
+        values.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+        let capped = capped || values.len() > limit;
+        values.truncate(limit);
+        Self { values, capped }
+    }
+}
+
+/// Per-run (or per-chunk) accumulators for a set of observers
+#[derive(Debug, Default)]
+pub(crate) struct ObserverSet {
+    accumulators: Vec<ColumnAccumulator>,
+    genes: Option<GeneAccumulator>,
+}
+
+/// Per-gene summaries so far, with the indices of the columns they read
+#[derive(Debug)]
+struct GeneAccumulator {
+    /// Gene, phenotype, p-value and consequence column
+    columns: [Option<usize>; 4],
+    genes: HashMap<String, GeneSummary>,
+}
+
+#[derive(Debug)]
+struct ColumnAccumulator {
+    column: String,
+    idx: usize,
+    max_unique: usize,
+    summary: ColumnSummary,
+    sum: f64,
+    uniques: HashSet<Vec<u8>>,
+}
+
+impl ObserverSet {
+    /// Resolve observer columns against the headers
+    pub(crate) fn new(
+        observers: &[ColumnObserver],
+        gene_summary: Option<&GeneSummaryObserver>,
+        headers: &HashMap<String, usize>,
+    ) -> Result<Self> {
+        let index = |column: &String| {
+            headers
+                .get(column)
+                .copied()
+                .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))
+        };
+        let genes = gene_summary
+            .map(|observer| -> Result<_> {
+                Ok(GeneAccumulator {
+                    columns: [
+                        Some(index(&observer.gene_column)?),
+                        observer.phenotype_column.as_ref().map(index).transpose()?,
+                        observer.p_value_column.as_ref().map(index).transpose()?,
+                        observer.consequence_column.as_ref().map(index).transpose()?,
+                    ],
+                    genes: HashMap::new(),
+                })
+            })
+            .transpose()?;
+        let accumulators = observers
+            .iter()
+            .map(|observer| {
+                let idx = *headers
+                    .get(&observer.column)
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(&observer.column, headers))?;
+                Ok(ColumnAccumulator {
+                    column: observer.column.clone(),
+                    idx,
+                    max_unique: observer.max_unique,
+                    summary: ColumnSummary::default(),
+                    sum: 0.0,
+                    uniques: HashSet::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { accumulators, genes })
+    }
+
+    /// Record a matched row
+    #[inline]
+    pub(crate) fn observe(&mut self, record: &ByteRecord) {
+        for acc in &mut self.accumulators {
+            acc.observe(record.get(acc.idx).unwrap_or_default());
+        }
+        if let Some(genes) = &mut self.genes {
+            genes.observe(record);
+        }
+    }
+
+    /// Fold another set (e.g. from a later chunk) into this one
+    pub(crate) fn merge(&mut self, other: ObserverSet) {
+        if self.accumulators.is_empty() && self.genes.is_none() {
+            *self = other;
+            return;
+        }
+        for (acc, other) in self.accumulators.iter_mut().zip(other.accumulators) {
+            acc.merge(other);
+        }
+        if let (Some(genes), Some(other)) = (&mut self.genes, other.genes) {
+            for (gene, summary) in other.genes {
+                genes.genes.entry(gene).or_default().merge(summary);
+            }
+        }
+    }
+
+    /// Produce the final column summaries keyed by column name, and the
+    /// summaries keyed by gene
+    pub(crate) fn finish(self) -> (HashMap<String, ColumnSummary>, BTreeMap<String, GeneSummary>) {
+        let genes = self.genes.map(|genes| genes.genes.into_iter().collect()).unwrap_or_default();
+        let columns = self
+            .accumulators
+            .into_iter()
+            .map(|acc| {
+                let mut summary = acc.summary;
+                if summary.numeric_count > 0 {
+                    summary.mean = Some(acc.sum / summary.numeric_count as f64);
+                }
+                summary.unique_count = acc.uniques.len() as u64;
+                (acc.column, summary)
+            })
+            .collect();
+        (columns, genes)
+    }
+}
+
+impl GeneAccumulator {
+    fn observe(&mut self, record: &ByteRecord) {
+        let [gene, phenotype, p_value, consequence] =
+            self.columns.map(|idx| idx.map(|idx| String::from_utf8_lossy(record.get(idx).unwrap_or_default())));
+        let Some(gene) = gene.as_deref().map(str::trim).filter(|gene| !gene.is_empty()) else { return };
+        let summary = self.genes.entry(gene.to_string()).or_default();
+        summary.variants += 1;
+        if let Some(phenotype) = phenotype.as_deref().map(str::trim).filter(|phenotype| !phenotype.is_empty()) {
+            *summary.phenotypes.entry(phenotype.to_string()).or_default() += 1;
+        }
+        let p_value = p_value.and_then(|p| p.trim().parse::<f64>().ok()).filter(|p| !p.is_nan());
+        let consequence = consequence.and_then(|value| Consequence::infer(&value));
+        summary.merge(GeneSummary { min_p_value: p_value, most_severe_consequence: consequence, ..GeneSummary::default() });
+    }
+}
+
+impl ColumnAccumulator {
+    fn observe(&mut self, value: &[u8]) {
+        let value = value.trim_ascii();
+        if crate::filters::is_default_empty(value) {
+            self.summary.empty += 1;
+            return;
+        }
+        self.summary.count += 1;
+
+        let number = str::from_utf8(value)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|x| x.is_finite());
+        if let Some(x) = number {
+            self.summary.numeric_count += 1;
+            self.sum += x;
+            self.summary.min = Some(self.summary.min.map_or(x, |m| m.min(x)));
+            self.summary.max = Some(self.summary.max.map_or(x, |m| m.max(x)));
+        }
+
+        if !self.summary.unique_capped && !self.uniques.contains(value) {
+            if self.uniques.len() < self.max_unique {
+                self.uniques.insert(value.to_vec());
+            } else {
+                self.summary.unique_capped = true;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: ColumnAccumulator) {
+        let (a, b) = (&mut self.summary, &other.summary);
+        a.count += b.count;
+        a.empty += b.empty;
+        a.numeric_count += b.numeric_count;
+        a.min = match (a.min, b.min) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        };
+        a.max = match (a.max, b.max) {
+            (Some(x), Some(y)) => Some(x.max(y)),
+            (x, y) => x.or(y),
+        };
+        self.sum += other.sum;
+
+        a.unique_capped |= b.unique_capped;
+        for value in other.uniques {
+            if self.uniques.len() >= self.max_unique {
+                if !self.uniques.contains(&value) {
+                    a.unique_capped = true;
+                }
+                continue;
+            }
+            self.uniques.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> HashMap<String, usize> {
+        [("gene".to_string(), 0), ("p".to_string(), 1)].into_iter().collect()
+    }
+
+    #[test]
+    fn test_observe_and_merge() -> Result<()> {
+        let observers = [ColumnObserver::new("gene"), ColumnObserver::new("p")];
+        let mut first = ObserverSet::new(&observers, None, &headers())?;
+        let mut second = ObserverSet::new(&observers, None, &headers())?;
+
+        first.observe(&ByteRecord::from(vec!["TP53", "0.5"]));
+        first.observe(&ByteRecord::from(vec!["TP53", "NA"]));
+        second.observe(&ByteRecord::from(vec!["APOE", "1.5"]));
+        second.observe(&ByteRecord::from(vec!["", " "]));
+        second.observe(&ByteRecord::from(vec!["BRCA1", "inf"]));
+        first.merge(second);
+
+        let (stats, _) = first.finish();
+        let p = &stats["p"];
+        assert_eq!(p.count, 3);
+        assert_eq!(p.empty, 2);
+        assert_eq!(p.numeric_count, 2);
+        assert_eq!(p.min, Some(0.5));
+        assert_eq!(p.max, Some(1.5));
+        assert_eq!(p.mean, Some(1.0));
+
+        let gene = &stats["gene"];
+        assert_eq!(gene.unique_count, 3);
+        assert_eq!(gene.numeric_count, 0);
+        assert!(gene.mean.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_cap() -> Result<()> {
+        let observers = [ColumnObserver::new("gene").with_max_unique(2)];
+        let mut set = ObserverSet::new(&observers, None, &headers())?;
+        for gene in ["A", "B", "A", "C"] {
+            set.observe(&ByteRecord::from(vec![gene, "1"]));
+        }
+        let (stats, _) = set.finish();
+        assert_eq!(stats["gene"].unique_count, 2);
+        assert!(stats["gene"].unique_capped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let observers = [ColumnObserver::new("beta")];
+        assert!(ObserverSet::new(&observers, None, &headers()).is_err());
+    }
+}
@@ -0,0 +1,1023 @@
+//! Output sinks for matched rows.
+//! Handles writing filtered records somewhere other than a single CSV file.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use csv::{ByteRecord, Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::chrom::compare_chrom;
+use crate::error::ExtractorError;
+use crate::Result;
+
+/// How matched rows are written to the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutputMode {
+    /// Copy each row's original bytes, keeping quoting, delimiters and number
+    /// formatting exactly as in the input (default)
+    #[default]
+    Passthrough,
+    /// Re-serialize each row with the configured delimiter and minimal quoting
+    Normalized,
+}
+
+/// How re-serialized fields are quoted, for importers that require a
+/// strict CSV dialect. Anything but the default re-serializes every row, as
+/// `OutputMode::Normalized` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quoting {
+    /// Which fields are quoted (default: Necessary)
+    pub style: QuoteStyle,
+    /// Quote character (default: `"`)
+    #[serde(with = "crate::profile::delimiter")]
+    pub quote: u8,
+    /// How a quote character inside a field is written (default: Doubled)
+    pub escape: QuoteEscape,
+}
+
+impl Default for Quoting {
+    fn default() -> Self {
+        Self { style: QuoteStyle::default(), quote: b'"', escape: QuoteEscape::default() }
+    }
+}
+
+impl Quoting {
+    /// Quote every field, e.g. for importers that reject bare values
+    pub fn always() -> Self {
+        Self { style: QuoteStyle::Always, ..Self::default() }
+    }
+
+    /// A csv writer quoting this way
+    pub(crate) fn writer<W: Write>(&self, inner: W, delimiter: u8) -> Writer<W> {
+        let mut builder = WriterBuilder::new();
+        builder.delimiter(delimiter).quote(self.quote).quote_style(match self.style {
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        });
+        if self.escape == QuoteEscape::Backslash {
+            builder.double_quote(false).escape(b'\\');
+        }
+        builder.from_writer(inner)
+    }
+}
+
+/// Which fields are quoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum QuoteStyle {
+    /// Only fields holding the delimiter, the quote character or a line
+    /// break (default)
+    #[default]
+    Necessary,
+    /// Every field, the header's included
+    Always,
+    /// Every field that isn't a number
+    NonNumeric,
+    /// No field, even where that makes the row ambiguous
+    Never,
+}
+
+/// How a quote character inside a quoted field is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum QuoteEscape {
+    /// Twice, as in RFC 4180: `"a ""b"""` (default)
+    #[default]
+    Doubled,
+    /// After a backslash: `"a \"b\""`
+    Backslash,
+}
+
+/// How output files are put in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WriteMode {
+    /// Write straight to the output path (default)
+    #[default]
+    Direct,
+    /// Write to `<output>.tmp` and rename it over the output path once the
+    /// run succeeds, so a failed or cancelled run never leaves a truncated file
+    Atomic {
+        /// fsync the file before the rename and its directory after it
+        fsync: bool,
+    },
+}
+
+/// What happens when an output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExistingOutput {
+    /// Replace it
+    Overwrite,
+    /// Fail before anything is written (default)
+    #[default]
+    Error,
+    /// Add the matched rows to its end without repeating the header. The
+    /// existing header must match the input's.
+    Append,
+}
+
+/// Where the workers of a parallel run write matched rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ThreadOutput {
+    /// Send every chunk to a single writer (default)
+    #[default]
+    Shared,
+    /// Each worker thread writes its own scratch file; the files are copied
+    /// into the output path one after another once all chunks are done.
+    /// Rows are not in input order.
+    Concatenated,
+    /// Each worker thread writes its own shard, `<stem>.part-0001.<ext>`,
+    /// ..., listed in `<stem>.manifest.json`; the output path itself is not
+    /// written. Rows are not in input order.
+    Partitioned,
+}
+
+/// An output file being written. In atomic mode it only appears at its final
+/// path on `commit`; if dropped before that, the temporary file is removed.
+pub(crate) struct OutputFile {
+    path: PathBuf,
+    temp: Option<PathBuf>,
+    fsync: bool,
+    appending: bool,
+}
+
+impl OutputFile {
+    /// Create the file that output for `path` is written to
+    pub(crate) fn create(path: &Path, mode: WriteMode, existing: ExistingOutput) -> Result<(Self, File)> {
+        let io_error = |e| ExtractorError::io_error(e, path);
+        let existing_len = fs::metadata(path).ok().filter(|meta| meta.is_file()).map(|meta| meta.len());
+        if existing == ExistingOutput::Error && existing_len.is_some() {
+            return Err(ExtractorError::Config(format!(
+                "Output {} already exists; enable overwrite or append to write to it",
+                path.display()
+            )));
+        }
+        let appending = existing == ExistingOutput::Append && existing_len.is_some_and(|len| len > 0);
+
+        let (temp, fsync) = match mode {
+            WriteMode::Direct => (None, false),
+            WriteMode::Atomic { fsync } => {
+                let mut name = path.file_name().unwrap_or_default().to_os_string();
+                name.push(".tmp");
+                (Some(path.with_file_name(name)), fsync)
+            }
+        };
+        let output = Self { path: path.to_path_buf(), temp, fsync, appending };
+        let target = output.path();
+
+        let file = if appending {
+            if target != path {
+                fs::copy(path, target).map_err(io_error)?;
+            }
+            // Nothing is written yet: the header is checked first, and an
+            // `AppendWriter` starts the rows on a new line
+            fs::OpenOptions::new()
+                .read(true)
+                .append(true)
+                .open(target)
+                .map_err(|e| ExtractorError::io_error(e, target))?
+        } else {
+            File::create(target).map_err(|e| ExtractorError::io_error(e, target))?
+        };
+        Ok((output, file))
+    }
+
+    /// Header row already in the file when appending, which the run must not repeat
+    pub(crate) fn existing_header(&self, delimiter: u8) -> Result<Option<ByteRecord>> {
+        if !self.appending {
+            return Ok(None);
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_path(self.path())?;
+        let mut header = ByteRecord::new();
+        reader.read_byte_record(&mut header)?;
+        Ok(Some(header))
+    }
+
+    /// Where the output is currently being written
+    pub(crate) fn path(&self) -> &Path {
+        self.temp.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Move the finished file to its final path
+    pub(crate) fn commit(mut self) -> Result<()> {
+        let Some(temp) = self.temp.take() else {
+            return Ok(());
+        };
+        let io_error = |e| ExtractorError::io_error(e, &temp);
+        if self.fsync {
+            File::open(&temp).and_then(|file| file.sync_all()).map_err(io_error)?;
+        }
+        fs::rename(&temp, &self.path).map_err(io_error)?;
+        if self.fsync {
+            // Persist the rename itself; directories can't be opened for syncing on Windows
+            #[cfg(unix)]
+            if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                File::open(dir).and_then(|dir| dir.sync_all()).map_err(|e| ExtractorError::io_error(e, dir))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+/// Whether `file` is non-empty and doesn't end with a line break
+pub(crate) fn ends_mid_line(mut file: &File) -> io::Result<bool> {
+    if file.seek(SeekFrom::End(0))? == 0 {
+        return Ok(false);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] != b'\n')
+}
+
+/// Writes to a file being appended to, starting with a line break when the
+/// file doesn't end with one. The break is only written with the first
+/// bytes, so a run that writes nothing, e.g. because its header doesn't
+/// match, leaves the file as it was.
+pub(crate) struct AppendWriter<W: Write> {
+    inner: W,
+    separate: bool,
+}
+
+impl<W: Write> AppendWriter<W> {
+    /// Write to `inner`, first writing a line break if `separate`
+    pub(crate) fn new(inner: W, separate: bool) -> Self {
+        Self { inner, separate }
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for AppendWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.separate && !buf.is_empty() {
+            self.inner.write_all(b"\n")?;
+            self.separate = false;
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl From<AppendWriter<File>> for File {
+    fn from(writer: AppendWriter<File>) -> Self {
+        writer.into_inner()
+    }
+}
+
+/// Fail unless `header` names the same columns as the header of the file being appended to
+pub(crate) fn check_appended_header(existing: &ByteRecord, header: &ByteRecord) -> Result<()> {
+    let trimmed = |record: &ByteRecord| -> Vec<String> { crate::core::header_names(record).collect() };
+    if trimmed(existing) != trimmed(header) {
+        return Err(ExtractorError::Config(format!(
+            "Cannot append: output columns {:?} differ from input columns {:?}",
+            trimmed(existing),
+            trimmed(header)
+        )));
+    }
+    Ok(())
+}
+
+/// Writes rows verbatim or re-serialized, depending on the output mode
+pub(crate) enum RowWriter<W: Write> {
+    Passthrough { inner: W, delimiter: u8 },
+    Normalized(Box<Writer<W>>),
+}
+
+impl<W: Write> RowWriter<W> {
+    pub(crate) fn new(inner: W, mode: OutputMode, delimiter: u8, quoting: Quoting) -> Self {
+        match mode {
+            OutputMode::Passthrough if quoting == Quoting::default() => RowWriter::Passthrough { inner, delimiter },
+            _ => RowWriter::Normalized(Box::new(quoting.writer(inner, delimiter))),
+        }
+    }
+
+    /// Write a row. `span` is the input bytes the reader consumed for it; in
+    /// normalized mode, or when no span is available, the record is serialized.
+    #[inline]
+    pub(crate) fn write(&mut self, record: &ByteRecord, span: &[u8]) -> Result<()> {
+        match self {
+            RowWriter::Passthrough { inner, .. } if !span.is_empty() => {
+                write_raw_row(inner, span)?;
+            }
+            RowWriter::Passthrough { inner, delimiter } => {
+                let mut writer = WriterBuilder::new().delimiter(*delimiter).from_writer(inner);
+                writer.write_byte_record(record)?;
+                writer.flush()?;
+            }
+            RowWriter::Normalized(writer) => writer.write_byte_record(record)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        match self {
+            RowWriter::Passthrough { inner, .. } => inner.flush(),
+            RowWriter::Normalized(writer) => writer.flush(),
+        }
+    }
+
+    /// Flush buffered rows and return the underlying writer
+    pub(crate) fn into_inner(self) -> Result<W> {
+        match self {
+            RowWriter::Passthrough { inner, .. } => Ok(inner),
+            RowWriter::Normalized(writer) => writer
+                .into_inner()
+                .map_err(|e| ExtractorError::Other(format!("Failed to flush output: {e}"))),
+        }
+    }
+}
+
+/// Offsets of the row content inside a span reported by the csv reader.
+///
+/// The reader attributes blank lines and the LF of a CRLF pair to the
+/// *following* record, so a span can start with stray CR/LF bytes and end
+/// with only the CR of its own terminator.
+#[inline]
+pub(crate) fn row_content(span: &[u8]) -> (usize, usize) {
+    let is_term = |b: &u8| *b == b'\r' || *b == b'\n';
+    let start = span.iter().take_while(|b| is_term(b)).count();
+    let end = span.len() - span[start..].iter().rev().take_while(|b| is_term(b)).count();
+    (start, end)
+}
+
+/// Copy a row's bytes with a complete line terminator (CRLF stays CRLF)
+#[inline]
+fn write_raw_row<W: Write>(out: &mut W, span: &[u8]) -> io::Result<()> {
+    let (start, end) = row_content(span);
+    out.write_all(&span[start..end])?;
+    if span[end..].starts_with(b"\r") {
+        out.write_all(b"\r\n")
+    } else {
+        out.write_all(b"\n")
+    }
+}
+
+/// Read adapter that keeps the bytes it has passed on, so rows parsed by a
+/// streaming csv reader can still be copied verbatim
+pub(crate) struct RawCapture<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// Stream offset of `buf[0]`
+    base: u64,
+}
+
+impl<R> RawCapture<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            base: 0,
+        }
+    }
+
+    /// Bytes between two absolute stream offsets that have not been released
+    #[inline]
+    pub(crate) fn span(&self, start: u64, end: u64) -> &[u8] {
+        &self.buf[(start - self.base) as usize..(end - self.base) as usize]
+    }
+
+    /// Allow bytes before `pos` to be dropped; compacts in 64KB steps
+    #[inline]
+    pub(crate) fn release(&mut self, pos: u64) {
+        let consumed = (pos - self.base) as usize;
+        if consumed >= 64 * 1024 {
+            self.buf.drain(..consumed);
+            self.base = pos;
+        }
+    }
+}
+
+impl<R: Read> Read for RawCapture<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(out)?;
+        self.buf.extend_from_slice(&out[..n]);
+        Ok(n)
+    }
+}
+
+/// Destination for matched rows other than a CSV file, used with
+/// `BioFilter::process_into`
+pub trait RowSink {
+    /// Called once before any row with the column names of the input
+    fn begin(&mut self, header: &ByteRecord) -> Result<()>;
+
+    /// Write one matched row
+    fn write_row(&mut self, record: &ByteRecord) -> Result<()>;
+
+    /// Called after the last row; commit or flush here
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Name of the manifest written next to partitioned output
+pub const PARTITION_MANIFEST: &str = "manifest.json";
+
+/// One output file of a partitioned run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionEntry {
+    /// Column value the file holds
+    pub value: String,
+    /// File name, relative to the output directory
+    pub file: PathBuf,
+    /// Number of data rows written
+    pub rows: u64,
+}
+
+/// Manifest describing a partitioned output directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionManifest {
+    /// Column the output was partitioned by
+    pub column: String,
+    /// Partitions, sorted by value in chromosome order (chr1, chr2, …, chr10, chrX)
+    pub partitions: Vec<PartitionEntry>,
+}
+
+impl PartitionManifest {
+    /// Load a manifest from an output directory
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(PARTITION_MANIFEST);
+        let file = File::open(&path).map_err(|e| ExtractorError::io_error(e, &path))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+/// Writes each matched row into a file chosen by the value of one column
+pub struct PartitionedWriter {
+    dir: PathBuf,
+    column: String,
+    column_idx: usize,
+    delimiter: u8,
+    quoting: Quoting,
+    headers: Option<ByteRecord>,
+    header_span: Vec<u8>,
+    mode: OutputMode,
+    write_mode: WriteMode,
+    max_partitions: usize,
+    writers: HashMap<Vec<u8>, Partition>,
+    used_names: HashSet<String>,
+}
+
+struct Partition {
+    writer: RowWriter<BufWriter<File>>,
+    file: OutputFile,
+    entry: PartitionEntry,
+}
+
+impl PartitionedWriter {
+    /// Create a writer that partitions on `column` (at `column_idx`) into `dir`.
+    /// The directory is created if it doesn't exist.
+    pub fn new(
+        dir: &Path,
+        column: &str,
+        column_idx: usize,
+        delimiter: u8,
+        headers: Option<ByteRecord>,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(|e| ExtractorError::io_error(e, dir))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            column: column.to_string(),
+            column_idx,
+            delimiter,
+            quoting: Quoting::default(),
+            headers,
+            header_span: Vec::new(),
+            mode: OutputMode::Normalized,
+            write_mode: WriteMode::Direct,
+            max_partitions: 1024,
+            writers: HashMap::new(),
+            used_names: HashSet::new(),
+        })
+    }
+
+    /// Fail instead of opening more than this many files (default: 1024)
+    pub fn with_max_partitions(mut self, max_partitions: usize) -> Self {
+        self.max_partitions = max_partitions;
+        self
+    }
+
+    /// Choose how rows are written (default: normalized). `header_span` is the
+    /// header line as read from the input, copied verbatim in passthrough mode.
+    pub fn with_output_mode(mut self, mode: OutputMode, header_span: &[u8]) -> Self {
+        self.mode = mode;
+        self.header_span = header_span.to_vec();
+        self
+    }
+
+    /// Choose how re-serialized fields are quoted (default: only where necessary)
+    pub fn with_quoting(mut self, quoting: Quoting) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Choose how partition files and the manifest are put in place (default: Direct)
+    pub fn with_write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Write a record to the partition for its column value
+    pub fn write(&mut self, record: &ByteRecord) -> Result<()> {
+        self.write_row(record, &[])
+    }
+
+    /// Write a record along with the input bytes it was parsed from, which
+    /// are copied verbatim in passthrough mode
+    pub fn write_row(&mut self, record: &ByteRecord, span: &[u8]) -> Result<()> {
+        let value = record.get(self.column_idx).unwrap_or_default();
+
+        if !self.writers.contains_key(value) {
+            let partition = self.open_partition(value)?;
+            self.writers.insert(value.to_vec(), partition);
+        }
+
+        let partition = self.writers.get_mut(value).expect("partition opened above");
+        partition.writer.write(record, span)?;
+        partition.entry.rows += 1;
+        Ok(())
+    }
+
+    fn open_partition(&mut self, value: &[u8]) -> Result<Partition> {
+        if self.writers.len() >= self.max_partitions {
+            return Err(ExtractorError::ResourceExhaustion(format!(
+                "Partitioning by '{}' would create more than {} output files",
+                self.column, self.max_partitions
+            )));
+        }
+
+        let value = String::from_utf8_lossy(value).into_owned();
+        let mut name = partition_file_stem(&value);
+        let mut suffix = 1;
+        while self.used_names.contains(&name) {
+            name = format!("{}-{}", partition_file_stem(&value), suffix);
+            suffix += 1;
+        }
+        self.used_names.insert(name.clone());
+
+        let file_name = PathBuf::from(format!("{name}.csv"));
+        let (output, file) = OutputFile::create(&self.dir.join(&file_name), self.write_mode, ExistingOutput::Overwrite)?;
+        let mut writer = RowWriter::new(BufWriter::new(file), self.mode, self.delimiter, self.quoting);
+        if let Some(headers) = &self.headers {
+            writer.write(headers, &self.header_span)?;
+        }
+
+        Ok(Partition {
+            writer,
+            file: output,
+            entry: PartitionEntry {
+                value,
+                file: file_name,
+                rows: 0,
+            },
+        })
+    }
+
+    /// Flush all partitions and write the manifest. Returns the manifest and
+    /// the total size in bytes of the partition files.
+    pub fn finish(self) -> Result<(PartitionManifest, u64)> {
+        let mut partitions = Vec::with_capacity(self.writers.len());
+        let mut total_size = 0;
+
+        let mut files = Vec::with_capacity(self.writers.len());
+        for (_, mut partition) in self.writers {
+            partition.writer.flush().map_err(|e| ExtractorError::io_error(e, &self.dir))?;
+            let path = partition.file.path();
+            total_size += path.metadata().map_err(|e| ExtractorError::io_error(e, path))?.len();
+            files.push(partition.file);
+            partitions.push(partition.entry);
+        }
+        for file in files {
+            file.commit()?;
+        }
+        partitions.sort_by(|a, b| compare_chrom(&a.value, &b.value));
+
+        let manifest = PartitionManifest {
+            column: self.column,
+            partitions,
+        };
+        let (output, file) =
+            OutputFile::create(&self.dir.join(PARTITION_MANIFEST), self.write_mode, ExistingOutput::Overwrite)?;
+        serde_json::to_writer_pretty(file, &manifest)?;
+        output.commit()?;
+
+        Ok((manifest, total_size))
+    }
+}
+
+/// One output file of a sharded run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardEntry {
+    /// File name, relative to the directory of the output path
+    pub file: PathBuf,
+    /// Number of data rows written
+    pub rows: u64,
+    /// Size of the file in bytes, header included
+    pub bytes: u64,
+}
+
+/// Manifest listing the shards of a size-limited output, in row order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardManifest {
+    /// Shards in the order their rows were written
+    pub shards: Vec<ShardEntry>,
+}
+
+impl ShardManifest {
+    /// Path of the manifest for output path `output`: `<stem>.manifest.json`
+    pub fn path(output: &Path) -> PathBuf {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        output.with_file_name(format!("{stem}.manifest.json"))
+    }
+
+    /// Load the manifest written for output path `output`
+    pub fn load(output: &Path) -> Result<Self> {
+        let path = Self::path(output);
+        let file = File::open(&path).map_err(|e| ExtractorError::io_error(e, &path))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+/// Path of shard `number` (from 1) of output path `output`, e.g.
+/// `output.part-0001.csv` for `output.csv`
+pub fn shard_path(output: &Path, number: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}.part-{number:04}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.part-{number:04}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Writes matched rows into numbered shards of at most `max_rows` rows and
+/// `max_bytes` bytes, each starting with the header. A shard always holds at
+/// least one row, so a single row larger than `max_bytes` gets a shard of its own.
+pub(crate) struct ShardedWriter {
+    output: PathBuf,
+    max_rows: Option<u64>,
+    max_bytes: Option<u64>,
+    mode: OutputMode,
+    delimiter: u8,
+    quoting: Quoting,
+    write_mode: WriteMode,
+    /// Header line as written to every shard, or empty
+    header: Vec<u8>,
+    current: Option<Shard>,
+    finished: Vec<(OutputFile, ShardEntry)>,
+    /// The row being written, rendered before it is placed in a shard
+    scratch: Vec<u8>,
+}
+
+struct Shard {
+    writer: BufWriter<File>,
+    file: OutputFile,
+    entry: ShardEntry,
+}
+
+impl ShardedWriter {
+    /// Create a writer for shards of `output`. `header_span` is the header
+    /// line as read from the input, copied verbatim in passthrough mode.
+    pub(crate) fn new(
+        output: &Path,
+        max_rows: Option<u64>,
+        max_bytes: Option<u64>,
+        mode: OutputMode,
+        delimiter: u8,
+        quoting: Quoting,
+        header: Option<(&ByteRecord, &[u8])>,
+    ) -> Result<Self> {
+        if max_rows == Some(0) || max_bytes == Some(0) {
+            return Err(ExtractorError::Config("Output shard limits must be at least 1".to_string()));
+        }
+        let header = match header {
+            Some((record, span)) => {
+                let mut writer = RowWriter::new(Vec::new(), mode, delimiter, quoting);
+                writer.write(record, span)?;
+                writer.into_inner()?
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            output: output.to_path_buf(),
+            max_rows,
+            max_bytes,
+            mode,
+            delimiter,
+            quoting,
+            write_mode: WriteMode::Direct,
+            header,
+            current: None,
+            finished: Vec::new(),
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Choose how shards and the manifest are put in place (default: Direct)
+    pub(crate) fn with_write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Write a record along with the input bytes it was parsed from, starting
+    /// a new shard first if it would not fit in the current one
+    pub(crate) fn write_row(&mut self, record: &ByteRecord, span: &[u8]) -> Result<()> {
+        let mut writer = RowWriter::new(std::mem::take(&mut self.scratch), self.mode, self.delimiter, self.quoting);
+        writer.write(record, span)?;
+        self.scratch = writer.into_inner()?;
+
+        let full = self.current.as_ref().is_some_and(|shard| {
+            shard.entry.rows > 0
+                && (self.max_rows.is_some_and(|max| shard.entry.rows >= max)
+                    || self.max_bytes.is_some_and(|max| shard.entry.bytes + self.scratch.len() as u64 > max))
+        });
+        if full {
+            self.close_shard()?;
+        }
+        if self.current.is_none() {
+            self.current = Some(self.open_shard()?);
+        }
+        let shard = self.current.as_mut().expect("shard opened above");
+        shard.writer.write_all(&self.scratch).map_err(|e| ExtractorError::io_error(e, shard.file.path()))?;
+        shard.entry.rows += 1;
+        shard.entry.bytes += self.scratch.len() as u64;
+        self.scratch.clear();
+        Ok(())
+    }
+
+    fn open_shard(&self) -> Result<Shard> {
+        let path = shard_path(&self.output, self.finished.len() + 1);
+        let (file, output) = OutputFile::create(&path, self.write_mode, ExistingOutput::Overwrite)?;
+        let mut writer = BufWriter::new(output);
+        writer.write_all(&self.header).map_err(|e| ExtractorError::io_error(e, &path))?;
+        Ok(Shard {
+            writer,
+            file,
+            entry: ShardEntry {
+                file: PathBuf::from(path.file_name().unwrap_or_default()),
+                rows: 0,
+                bytes: self.header.len() as u64,
+            },
+        })
+    }
+
+    fn close_shard(&mut self) -> Result<()> {
+        if let Some(mut shard) = self.current.take() {
+            shard.writer.flush().map_err(|e| ExtractorError::io_error(e, shard.file.path()))?;
+            self.finished.push((shard.file, shard.entry));
+        }
+        Ok(())
+    }
+
+    /// Flush the last shard and write the manifest. Returns the manifest and
+    /// the total size in bytes of the shards. A run without matches still
+    /// writes one shard holding the header.
+    pub(crate) fn finish(mut self) -> Result<(ShardManifest, u64)> {
+        if self.current.is_none() && self.finished.is_empty() {
+            self.current = Some(self.open_shard()?);
+        }
+        self.close_shard()?;
+
+        let mut shards = Vec::with_capacity(self.finished.len());
+        for (file, entry) in self.finished {
+            file.commit()?;
+            shards.push(entry);
+        }
+        let total_size = shards.iter().map(|shard| shard.bytes).sum();
+
+        let manifest = ShardManifest { shards };
+        let (output, file) =
+            OutputFile::create(&ShardManifest::path(&self.output), self.write_mode, ExistingOutput::Overwrite)?;
+        serde_json::to_writer_pretty(file, &manifest)?;
+        output.commit()?;
+        Ok((manifest, total_size))
+    }
+}
+
+/// One file per worker thread, written without a shared writer. Files are
+/// opened on first use and numbered in that order.
+pub(crate) struct ThreadFiles {
+    output: PathBuf,
+    /// Header line each file starts with, or empty
+    header: Vec<u8>,
+    write_mode: WriteMode,
+    files: Vec<Mutex<Option<Shard>>>,
+    opened: AtomicUsize,
+}
+
+impl ThreadFiles {
+    /// Scratch files for `threads` workers, which `concatenate` copies into
+    /// one output. They are removed when dropped.
+    pub(crate) fn scratch(output: &Path, threads: usize) -> Self {
+        Self::shards(output, threads, Vec::new(), WriteMode::Atomic { fsync: false })
+    }
+
+    /// Shards of `output` for `threads` workers, each starting with `header`
+    pub(crate) fn shards(output: &Path, threads: usize, header: Vec<u8>, write_mode: WriteMode) -> Self {
+        Self {
+            output: output.to_path_buf(),
+            header,
+            write_mode,
+            files: (0..threads.max(1)).map(|_| Mutex::new(None)).collect(),
+            opened: AtomicUsize::new(0),
+        }
+    }
+
+    /// Write `rows` rendered rows to the file of worker `thread`
+    pub(crate) fn write(&self, thread: usize, data: &[u8], rows: u64) -> Result<()> {
+        let mut slot = self.files[thread % self.files.len()].lock().unwrap_or_else(|e| e.into_inner());
+        if slot.is_none() {
+            *slot = Some(self.open()?);
+        }
+        let shard = slot.as_mut().expect("file opened above");
+        shard.writer.write_all(data).map_err(|e| ExtractorError::io_error(e, shard.file.path()))?;
+        shard.entry.rows += rows;
+        shard.entry.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Shard> {
+        let path = shard_path(&self.output, self.opened.fetch_add(1, Ordering::Relaxed) + 1);
+        let (file, output) = OutputFile::create(&path, self.write_mode, ExistingOutput::Overwrite)?;
+        let mut writer = BufWriter::new(output);
+        writer.write_all(&self.header).map_err(|e| ExtractorError::io_error(e, &path))?;
+        Ok(Shard {
+            writer,
+            file,
+            entry: ShardEntry {
+                file: PathBuf::from(path.file_name().unwrap_or_default()),
+                rows: 0,
+                bytes: self.header.len() as u64,
+            },
+        })
+    }
+
+    /// Flushed files in the order they were opened
+    fn into_shards(self) -> Result<Vec<Shard>> {
+        let mut shards: Vec<Shard> = self
+            .files
+            .into_iter()
+            .filter_map(|slot| slot.into_inner().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        shards.sort_by(|a, b| a.entry.file.cmp(&b.entry.file));
+        for shard in &mut shards {
+            shard.writer.flush().map_err(|e| ExtractorError::io_error(e, shard.file.path()))?;
+        }
+        Ok(shards)
+    }
+
+    /// Copy every file into `out`, then remove them
+    pub(crate) fn concatenate(self, out: &mut impl Write) -> Result<()> {
+        for shard in self.into_shards()? {
+            let path = shard.file.path();
+            let mut file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+            io::copy(&mut file, out).map_err(|e| ExtractorError::io_error(e, path))?;
+        }
+        Ok(())
+    }
+
+    /// Keep the files as shards and write their manifest. Returns the
+    /// manifest and the total size of the shards. A run without matches
+    /// still writes one shard holding the header.
+    pub(crate) fn finish(self) -> Result<(ShardManifest, u64)> {
+        if self.opened.load(Ordering::Relaxed) == 0 {
+            self.write(0, &[], 0)?;
+        }
+        let (output, write_mode) = (self.output.clone(), self.write_mode);
+        let mut shards = Vec::new();
+        for shard in self.into_shards()? {
+            shard.file.commit()?;
+            shards.push(shard.entry);
+        }
+        let total_size = shards.iter().map(|shard| shard.bytes).sum();
+
+        let manifest = ShardManifest { shards };
+        let (file, out) = OutputFile::create(&ShardManifest::path(&output), write_mode, ExistingOutput::Overwrite)?;
+        serde_json::to_writer_pretty(out, &manifest)?;
+        file.commit()?;
+        Ok((manifest, total_size))
+    }
+}
+
+/// File-system safe file stem for a partition value
+fn partition_file_stem(value: &str) -> String {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return "_empty".to_string();
+    }
+    trimmed
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_file_stem() {
+        assert_eq!(partition_file_stem("chr1"), "chr1");
+        assert_eq!(partition_file_stem(" HLA/DRB1 "), "HLA_DRB1");
+        assert_eq!(partition_file_stem(""), "_empty");
+    }
+
+    #[test]
+    fn test_partitioned_writer() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let headers = ByteRecord::from(vec!["rsid", "chrom"]);
+        let mut writer = PartitionedWriter::new(dir.path(), "chrom", 1, b',', Some(headers))?;
+
+        writer.write(&ByteRecord::from(vec!["rs1", "chr1"]))?;
+        writer.write(&ByteRecord::from(vec!["rs2", "chrX"]))?;
+        writer.write(&ByteRecord::from(vec!["rs3", "chr1"]))?;
+        writer.write(&ByteRecord::from(vec!["rs4", "chr/1"]))?;
+        writer.write(&ByteRecord::from(vec!["rs5", "chr_1"]))?;
+        let (manifest, _) = writer.finish()?;
+
+        let chr1 = fs::read_to_string(dir.path().join("chr1.csv")).unwrap();
+        assert_eq!(chr1, "rsid,chrom\nrs1,chr1\nrs3,chr1\n");
+
+        let loaded = PartitionManifest::load(dir.path())?;
+        assert_eq!(loaded.column, "chrom");
+        assert_eq!(manifest.partitions.len(), 4);
+        let rows: Vec<u64> = loaded.partitions.iter().map(|p| p.rows).collect();
+        assert_eq!(rows.iter().sum::<u64>(), 5);
+
+        let files: HashSet<_> = loaded.partitions.iter().map(|p| p.file.clone()).collect();
+        assert_eq!(files.len(), 4, "sanitized names must not collide");
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_writer_modes() -> Result<()> {
+        let record = ByteRecord::from(vec!["rs1", "1.50"]);
+        let span = b"\n\"rs1\";1.50\r";
+
+        let mut passthrough = RowWriter::new(Vec::new(), OutputMode::Passthrough, b';', Quoting::default());
+        passthrough.write(&record, span)?;
+        assert_eq!(passthrough.into_inner()?, b"\"rs1\";1.50\r\n");
+
+        let mut normalized = RowWriter::new(Vec::new(), OutputMode::Normalized, b',', Quoting::default());
+        normalized.write(&record, span)?;
+        assert_eq!(normalized.into_inner()?, b"rs1,1.50\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_writer_quoting() -> Result<()> {
+        let record = ByteRecord::from(vec!["rs1", "1.50", "a 'b'"]);
+        let write = |quoting: Quoting| -> Result<String> {
+            // Passthrough is overridden by any non-default quoting
+            let mut writer = RowWriter::new(Vec::new(), OutputMode::Passthrough, b',', quoting);
+            writer.write(&record, b"rs1,1.50,a 'b'\n")?;
+            Ok(String::from_utf8_lossy(&writer.into_inner()?).into_owned())
+        };
+
+        assert_eq!(write(Quoting::always())?, "\"rs1\",\"1.50\",\"a 'b'\"\n");
+        let single = Quoting { quote: b'\'', ..Quoting::default() };
+        assert_eq!(write(single)?, "rs1,1.50,'a ''b'''\n");
+        let non_numeric = Quoting { style: QuoteStyle::NonNumeric, quote: b'\'', escape: QuoteEscape::Backslash };
+        assert_eq!(write(non_numeric)?, "'rs1',1.50,'a \\'b\\''\n");
+
+        let config: crate::Config = serde_json::from_str(r#"{"quoting": {"style": "Always", "quote": "'"}}"#)?;
+        assert_eq!(config.quoting, Quoting { quote: b'\'', ..Quoting::always() });
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_limit() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = PartitionedWriter::new(dir.path(), "id", 0, b',', None)?
+            .with_max_partitions(2);
+
+        writer.write(&ByteRecord::from(vec!["a"]))?;
+        writer.write(&ByteRecord::from(vec!["b"]))?;
+        assert!(writer.write(&ByteRecord::from(vec!["c"])).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+//! Parquet input, enabled with the `parquet` feature, for variant tables
+//! written by Spark and other columnar tools.
+//!
+//! Rows are rendered as delimited text and streamed through the sequential
+//! path, so filters, transforms and outputs behave as they do for CSV. Row
+//! groups whose column statistics show that no row could pass a numeric
+//! filter are skipped without being read.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use parquet::basic::{ConvertedType, Type as PhysicalType};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::RowGroupMetaData;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::serialized_reader::ReadOptionsBuilder;
+use parquet::file::statistics::Statistics;
+use parquet::record::reader::RowIter;
+use parquet::record::Field;
+use crate::blocks::ColumnZone;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::Result;
+
+/// Rendered rows buffered per refill of a `ParquetRows` reader
+const BUFFER_BYTES: usize = 64 * 1024;
+
+/// Whether `path` names a Parquet file, by its `.parquet` or `.parq` extension
+pub fn is_parquet(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet") || ext.eq_ignore_ascii_case("parq"))
+}
+
+/// A Parquet file opened for filtering
+pub struct ParquetInput {
+    path: PathBuf,
+    reader: SerializedFileReader<File>,
+}
+
+impl ParquetInput {
+    /// Open `path` and read its footer
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let reader = SerializedFileReader::new(file).map_err(|e| parquet_error(e, path))?;
+        Ok(Self { path: path.to_path_buf(), reader })
+    }
+
+    /// Top-level column names, in schema order
+    pub fn column_names(&self) -> Vec<String> {
+        let schema = self.reader.metadata().file_metadata().schema_descr();
+        schema.root_schema().get_fields().iter().map(|field| field.name().to_string()).collect()
+    }
+
+    /// Number of row groups
+    pub fn num_row_groups(&self) -> usize {
+        self.reader.num_row_groups()
+    }
+
+    /// Number of rows
+    pub fn num_rows(&self) -> u64 {
+        self.reader.metadata().file_metadata().num_rows().max(0) as u64
+    }
+
+    /// What the statistics of row group `row_group` say about top-level
+    /// column `column`. Only plain integer and floating point columns
+    /// without nulls get a numeric range; anything else is marked as
+    /// non-numeric, so it never rules the row group out.
+    pub fn zone(&self, row_group: usize, column: usize) -> ColumnZone {
+        let unknown = ColumnZone { non_numeric: true, ..ColumnZone::default() };
+        let Some(leaf) = self.leaf(column) else {
+            return unknown;
+        };
+        match numeric_range(self.reader.metadata().row_group(row_group), leaf) {
+            Some((min, max)) => ColumnZone { min: Some(min), max: Some(max), ..ColumnZone::default() },
+            None => unknown,
+        }
+    }
+
+    /// Row groups in which a row could pass every filter, each given with the
+    /// index of its top-level column
+    pub fn candidate_row_groups(&self, filters: &[(usize, &dyn Filter)]) -> Vec<usize> {
+        (0..self.num_row_groups())
+            .filter(|&row_group| {
+                filters.iter().all(|&(column, filter)| {
+                    // Statistics leave NaN out, so they can't rule out a
+                    // filter that NaN passes
+                    let floating = self.leaf(column).is_some_and(|leaf| {
+                        let physical = self.reader.metadata().row_group(row_group).column(leaf).column_type();
+                        matches!(physical, PhysicalType::FLOAT | PhysicalType::DOUBLE)
+                    });
+                    if floating && filter.test_number(f64::NAN) != Some(false) {
+                        return true;
+                    }
+                    filter.may_match(&self.zone(row_group, column))
+                })
+            })
+            .collect()
+    }
+
+    /// The rows of `row_groups` as delimited text, starting with a header row
+    /// when `header` is set
+    pub fn rows(self, row_groups: Vec<usize>, delimiter: u8, header: bool) -> Result<ParquetRows> {
+        let names = self.column_names();
+        let file = File::open(&self.path).map_err(|e| ExtractorError::io_error(e, &self.path))?;
+        let options = ReadOptionsBuilder::new()
+            .with_predicate(Box::new(move |_: &RowGroupMetaData, i| row_groups.binary_search(&i).is_ok()))
+            .build();
+        let reader = SerializedFileReader::new_with_options(file, options).map_err(|e| parquet_error(e, &self.path))?;
+        let mut format = csv::WriterBuilder::new();
+        format.delimiter(delimiter);
+        let mut buffer = Vec::new();
+        if header {
+            let mut writer = format.from_writer(&mut buffer);
+            writer.write_record(&names)?;
+            writer.flush()?;
+        }
+        Ok(ParquetRows {
+            path: self.path,
+            rows: RowIter::from_file_into(Box::new(reader)),
+            format,
+            buffer,
+            consumed: 0,
+        })
+    }
+
+    /// Index of the leaf column holding top-level column `column`, `None`
+    /// for nested columns
+    fn leaf(&self, column: usize) -> Option<usize> {
+        let schema = self.reader.metadata().file_metadata().schema_descr();
+        let field = schema.root_schema().get_fields().get(column)?;
+        if !field.is_primitive() {
+            return None;
+        }
+        (0..schema.num_columns()).find(|&i| schema.column(i).path().parts() == [field.name()])
+    }
+}
+
+/// Minimum and maximum of a leaf column in a row group, if its statistics
+/// hold them, it has no nulls and its values render as plain numbers
+fn numeric_range(row_group: &RowGroupMetaData, leaf: usize) -> Option<(f64, f64)> {
+    let column = row_group.column(leaf);
+    let plain = matches!(
+        column.column_descr().converted_type(),
+        ConvertedType::NONE | ConvertedType::INT_8 | ConvertedType::INT_16 | ConvertedType::INT_32 | ConvertedType::INT_64
+    );
+    let statistics = column.statistics().filter(|_| plain)?;
+    if statistics.null_count_opt() != Some(0) {
+        return None;
+    }
+    let (min, max) = match statistics {
+        Statistics::Int32(s) => (*s.min_opt()? as f64, *s.max_opt()? as f64),
+        Statistics::Int64(s) => (*s.min_opt()? as f64, *s.max_opt()? as f64),
+        Statistics::Float(s) => (*s.min_opt()? as f64, *s.max_opt()? as f64),
+        Statistics::Double(s) => (*s.min_opt()?, *s.max_opt()?),
+        _ => return None,
+    };
+    (min.is_finite() && max.is_finite()).then_some((min, max))
+}
+
+fn parquet_error(error: ParquetError, path: &Path) -> ExtractorError {
+    ExtractorError::io_error(io::Error::new(io::ErrorKind::InvalidData, error), path)
+}
+
+/// Rows of a Parquet file rendered as delimited text, from `ParquetInput::rows`.
+/// Nulls become empty fields and floating point numbers are written in
+/// their shortest form that reads back the same.
+pub struct ParquetRows {
+    path: PathBuf,
+    rows: RowIter<'static>,
+    format: csv::WriterBuilder,
+    /// Rendered rows not yet read, from `consumed` on
+    buffer: Vec<u8>,
+    consumed: usize,
+}
+
+impl ParquetRows {
+    /// Render rows into the buffer until it holds `BUFFER_BYTES` or the rows
+    /// run out
+    fn refill(&mut self) -> io::Result<()> {
+        self.buffer.clear();
+        self.consumed = 0;
+        let mut writer = self.format.from_writer(&mut self.buffer);
+        let mut fields: Vec<String> = Vec::new();
+        for row in self.rows.by_ref() {
+            let row = row.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fields.clear();
+            fields.extend(row.get_column_iter().map(|(_, field)| render(field)));
+            writer.write_record(&fields)?;
+            // Rows still in the writer's own buffer aren't counted
+            if writer.get_ref().len() >= BUFFER_BYTES {
+                break;
+            }
+        }
+        writer.flush()
+    }
+}
+
+impl Read for ParquetRows {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.consumed == self.buffer.len() {
+            self.refill().map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", self.path.display())))?;
+        }
+        let pending = &self.buffer[self.consumed..];
+        let n = pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&pending[..n]);
+        self.consumed += n;
+        Ok(n)
+    }
+}
+
+/// A field's value as text
+fn render(field: &Field) -> String {
+    match field {
+        Field::Null => String::new(),
+        Field::Str(value) => value.clone(),
+        Field::Bytes(value) => String::from_utf8_lossy(value.data()).into_owned(),
+        // Debug formatting switches to exponents for very small and large
+        // values, as p-values need
+        Field::Float(value) => format!("{value:?}"),
+        Field::Double(value) => format!("{value:?}"),
+        field => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use tempfile::TempDir;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+    use crate::Config;
+
+    /// Write one row group per chunk of `rows`
+    fn write_variants(path: &Path, row_groups: &[&[(&str, i64, Option<f64>)]]) {
+        let schema = Arc::new(
+            parse_message_type(
+                "message variants { required binary rsid (UTF8); required int64 pos; optional double p; }",
+            )
+            .unwrap(),
+        );
+        let file = File::create(path).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build())).unwrap();
+        for rows in row_groups {
+            let mut group = writer.next_row_group().unwrap();
+            let ids: Vec<ByteArray> = rows.iter().map(|row| ByteArray::from(row.0)).collect();
+            let mut column = group.next_column().unwrap().unwrap();
+            column.typed::<ByteArrayType>().write_batch(&ids, None, None).unwrap();
+            column.close().unwrap();
+            let positions: Vec<i64> = rows.iter().map(|row| row.1).collect();
+            let mut column = group.next_column().unwrap().unwrap();
+            column.typed::<Int64Type>().write_batch(&positions, None, None).unwrap();
+            column.close().unwrap();
+            let p: Vec<f64> = rows.iter().filter_map(|row| row.2).collect();
+            let levels: Vec<i16> = rows.iter().map(|row| row.2.is_some() as i16).collect();
+            let mut column = group.next_column().unwrap().unwrap();
+            column.typed::<DoubleType>().write_batch(&p, Some(&levels), None).unwrap();
+            column.close().unwrap();
+            group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_parquet_input() -> Result<()> {
+        let dir = TempDir::new()?;
+        let input = dir.path().join("variants.parquet");
+        write_variants(
+            &input,
+            &[
+                &[("rs1", 100, Some(0.5)), ("rs2", 200, Some(0.04))],
+                &[("rs3", 300, Some(3e-9)), ("rs4", 400, Some(0.2))],
+                &[("rs5", 500, None), ("rs6", 600, Some(1e-3))],
+            ],
+        );
+
+        let parquet = ParquetInput::open(&input)?;
+        assert_eq!(parquet.column_names(), ["rsid", "pos", "p"]);
+        assert_eq!((parquet.num_row_groups(), parquet.num_rows()), (3, 6));
+        let significant = ColumnFilter::new(
+            "p".to_string(),
+            FilterCondition::Numeric(NumericCondition::LessThan(1e-6)),
+        )?;
+        // The third row group has a null p-value, so its statistics can't rule it out
+        assert_eq!(parquet.candidate_row_groups(&[(2, &significant)]), [1, 2]);
+
+        let output = dir.path().join("hits.tsv");
+        let stats = crate::builder(&input, &output)
+            .with_config(Config { delimiter: b'\t', ..Config::default() })
+            .filter("p", FilterCondition::Numeric(NumericCondition::LessThan(0.05)))
+            .filter("pos", FilterCondition::Numeric(NumericCondition::GreaterThan(150.0)))
+            .skip_bad_rows()
+            .build()?
+            .process()?;
+        assert_eq!((stats.rows_matched, stats.row_errors.len()), (3, 1));
+        assert_eq!(
+            std::fs::read_to_string(&output)?,
+            "rsid\tpos\tp\nrs2\t200\t0.04\nrs3\t300\t3e-9\nrs6\t600\t0.001\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! Filter plugins loaded from shared libraries, enabled with the `plugins`
+//! feature, so filters such as proprietary scores can be added to a fixed
+//! build without forking the crate.
+//!
+//! A plugin exports `extractor_plugin_v1`, a function returning a pointer to
+//! a static `PluginVTable` (declared for C in `include/extractor_plugin.h`).
+//! The table names the plugin and creates, runs and destroys filter
+//! instances; each instance is configured from a string, such as JSON, that
+//! the plugin defines.
+
+// Loading libraries and calling through the plugin's table is unsafe code
+#![allow(unsafe_code)]
+
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use csv::ByteRecord;
+use libloading::Library;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::Result;
+
+/// ABI version this build expects in `PluginVTable::abi_version`
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol a plugin library exports
+pub const PLUGIN_ENTRY_POINT: &str = "extractor_plugin_v1";
+
+/// Function table a plugin exports. Every function must be thread-safe:
+/// `test` is called on one instance from several threads at once.
+#[repr(C)]
+pub struct PluginVTable {
+    /// `PLUGIN_ABI_VERSION` of the headers the plugin was built with
+    pub abi_version: u32,
+    /// Plugin name, NUL-terminated
+    pub name: *const c_char,
+    /// Create an instance from a NUL-terminated configuration string.
+    /// Returns null if the configuration is invalid.
+    pub create: unsafe extern "C" fn(config: *const c_char) -> *mut c_void,
+    /// Test one field of `len` bytes: 1 to keep the row, 0 to drop it, and
+    /// a negative value if the field is invalid
+    pub test: unsafe extern "C" fn(instance: *mut c_void, field: *const u8, len: usize) -> i32,
+    /// Free an instance from `create`
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+}
+
+// SAFETY: the table is immutable and `name` points to static data
+unsafe impl Sync for PluginVTable {}
+
+/// Type of the `extractor_plugin_v1` entry point
+type EntryPoint = unsafe extern "C" fn() -> *const PluginVTable;
+
+/// A loaded plugin. Filters created from it keep the library loaded.
+#[derive(Clone)]
+pub struct Plugin {
+    vtable: &'static PluginVTable,
+    name: String,
+    /// `None` for plugins linked into the program
+    _library: Option<Arc<Library>>,
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugin").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+impl Plugin {
+    /// Load the plugin library at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let invalid = |message: String| ExtractorError::Config(format!("Plugin {}: {message}", path.display()));
+        // SAFETY: loading runs the library's initializers; plugins are trusted code
+        let library = unsafe { Library::new(path) }.map_err(|e| invalid(e.to_string()))?;
+        // SAFETY: the entry point has the documented signature
+        let vtable = unsafe {
+            let entry = library
+                .get::<EntryPoint>(PLUGIN_ENTRY_POINT.as_bytes())
+                .map_err(|_| invalid(format!("no {PLUGIN_ENTRY_POINT} symbol")))?;
+            entry()
+        };
+        // SAFETY: the table is static data of the library, which `Plugin` keeps loaded
+        let vtable = unsafe { vtable.as_ref() }.ok_or_else(|| invalid(format!("{PLUGIN_ENTRY_POINT} returned null")))?;
+        Self::new(vtable, Some(Arc::new(library))).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Use a plugin linked into the program, e.g. a Rust crate that exports
+    /// its table for both uses
+    pub fn from_vtable(vtable: &'static PluginVTable) -> Result<Self> {
+        Self::new(vtable, None)
+    }
+
+    fn new(vtable: &'static PluginVTable, library: Option<Arc<Library>>) -> Result<Self> {
+        if vtable.abi_version != PLUGIN_ABI_VERSION {
+            return Err(ExtractorError::Config(format!(
+                "plugin ABI version {} is not supported (expected {PLUGIN_ABI_VERSION})",
+                vtable.abi_version
+            )));
+        }
+        if vtable.name.is_null() {
+            return Err(ExtractorError::Config("plugin has no name".to_string()));
+        }
+        // SAFETY: checked for null; the plugin provides a NUL-terminated string
+        let name = unsafe { CStr::from_ptr(vtable.name) }.to_string_lossy().into_owned();
+        Ok(Self { vtable, name, _library: library })
+    }
+
+    /// Name the plugin gives itself
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Create a filter on `column` from the plugin's `config` string
+    pub fn filter(&self, column: String, config: &str) -> Result<PluginFilter> {
+        let invalid = || ExtractorError::Config(format!("Plugin {} rejected its configuration for '{column}'", self.name));
+        let config_c = CString::new(config).map_err(|_| invalid())?;
+        // SAFETY: `config_c` is NUL-terminated and outlives the call
+        let instance = unsafe { (self.vtable.create)(config_c.as_ptr()) };
+        if instance.is_null() {
+            return Err(invalid());
+        }
+        Ok(PluginFilter {
+            plugin: self.clone(),
+            instance,
+            column,
+            config: config.to_string(),
+            col_idx: OnceLock::new(),
+        })
+    }
+}
+
+/// A filter implemented by a plugin, from `Plugin::filter`
+pub struct PluginFilter {
+    plugin: Plugin,
+    instance: *mut c_void,
+    column: String,
+    config: String,
+    col_idx: OnceLock<usize>,
+}
+
+// SAFETY: plugins must make `test` thread-safe (see `PluginVTable`), and the
+// instance is only destroyed on drop
+unsafe impl Send for PluginFilter {}
+unsafe impl Sync for PluginFilter {}
+
+impl Drop for PluginFilter {
+    fn drop(&mut self) {
+        // SAFETY: `instance` came from `create` of the same table and is freed once
+        unsafe { (self.plugin.vtable.destroy)(self.instance) };
+    }
+}
+
+impl Filter for PluginFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let idx = match self.col_idx.get() {
+            Some(&idx) => idx,
+            None => {
+                let idx = *headers
+                    .get(&self.column)
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(&self.column, headers))?;
+                *self.col_idx.get_or_init(|| idx)
+            }
+        };
+        let field = row.get(idx).unwrap_or_default();
+        // SAFETY: `field` is valid for `len` bytes during the call
+        match unsafe { (self.plugin.vtable.test)(self.instance, field.as_ptr(), field.len()) } {
+            0 => Ok(false),
+            code if code > 0 => Ok(true),
+            code => Err(ExtractorError::InvalidDataFormat {
+                column: self.column.clone(),
+                message: format!("plugin {} rejected the value (code {code})", self.plugin.name),
+                row: None,
+                snippet: None,
+            }),
+        }
+    }
+
+    fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    fn description(&self) -> String {
+        format!("{}({}; {})", self.plugin.name, self.column, self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin keeping fields whose number is at least the configured threshold
+    mod at_least {
+        use super::*;
+
+        unsafe extern "C" fn create(config: *const c_char) -> *mut c_void {
+            // SAFETY: the caller passes a NUL-terminated string
+            let config = unsafe { CStr::from_ptr(config) }.to_str().unwrap_or_default();
+            match config.parse::<f64>() {
+                Ok(threshold) => Box::into_raw(Box::new(threshold)).cast(),
+                Err(_) => std::ptr::null_mut(),
+            }
+        }
+
+        unsafe extern "C" fn test(instance: *mut c_void, field: *const u8, len: usize) -> i32 {
+            // SAFETY: `instance` came from `create` and `field` holds `len` bytes
+            let (threshold, field) = unsafe { (*instance.cast::<f64>(), std::slice::from_raw_parts(field, len)) };
+            match std::str::from_utf8(field).ok().and_then(|s| s.parse::<f64>().ok()) {
+                Some(x) => (x >= threshold) as i32,
+                None => -1,
+            }
+        }
+
+        unsafe extern "C" fn destroy(instance: *mut c_void) {
+            // SAFETY: `instance` came from `create`
+            drop(unsafe { Box::from_raw(instance.cast::<f64>()) });
+        }
+
+        pub static VTABLE: PluginVTable = PluginVTable {
+            abi_version: PLUGIN_ABI_VERSION,
+            name: c"at_least".as_ptr(),
+            create,
+            test,
+            destroy,
+        };
+    }
+
+    #[test]
+    fn test_plugin_filter() -> Result<()> {
+        let plugin = Plugin::from_vtable(&at_least::VTABLE)?;
+        assert_eq!(plugin.name(), "at_least");
+        assert!(plugin.filter("score".to_string(), "not a number").is_err());
+
+        let filter = plugin.filter("score".to_string(), "20")?;
+        let headers: HashMap<String, usize> = [("score".to_string(), 1)].into_iter().collect();
+        assert!(filter.apply(&ByteRecord::from(vec!["rs1", "25.3"]), &headers)?);
+        assert!(!filter.apply(&ByteRecord::from(vec!["rs2", "3"]), &headers)?);
+        assert!(filter.apply(&ByteRecord::from(vec!["rs3", "NA"]), &headers).is_err());
+        assert_eq!(filter.description(), "at_least(score; 20)");
+
+        assert!(Plugin::load(Path::new("/nonexistent/libplugin.so")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+//! Loading and saving `Config` as profile files, so teams can share delimiter,
+//! chunking and thread settings without code changes. JSON is always
+//! supported; TOML and YAML need the `profiles` feature.
+//!
+//! A file either holds one config, or several named ones under `profiles`:
+//!
+//! ```toml
+//! [profiles.gwas-default]
+//! delimiter = "\t"
+//! num_threads = 8
+//!
+//! [profiles.rnaseq-qc]
+//! chunk_size = 4194304
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::ExtractorError;
+use crate::{Config, Result};
+
+/// Prefix of environment variables that override config fields
+pub const ENV_PREFIX: &str = "EXTRACTOR_";
+
+/// File formats a profile can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Format from the file extension
+    fn of(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(ExtractorError::Config(format!(
+                "Unknown profile format for {}; use .toml, .yaml or .json",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Parse a config, or the profile called `name` from the `profiles` table
+    fn parse(self, text: &str, name: Option<&str>) -> std::result::Result<Config, String> {
+        match self {
+            Format::Json => {
+                let document: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+                serde_json::from_value(select(document, name)?).map_err(|e| e.to_string())
+            }
+            #[cfg(feature = "profiles")]
+            Format::Toml => {
+                let document: toml::Value = toml::from_str(text).map_err(|e| e.to_string())?;
+                select(document, name)?.try_into().map_err(|e: toml::de::Error| e.to_string())
+            }
+            #[cfg(feature = "profiles")]
+            Format::Yaml => {
+                let document: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+                serde_yaml::from_value(select(document, name)?).map_err(|e| e.to_string())
+            }
+            #[cfg(not(feature = "profiles"))]
+            Format::Toml | Format::Yaml => Err("TOML and YAML profiles need the `profiles` feature".to_string()),
+        }
+    }
+
+    fn render(self, config: &Config) -> std::result::Result<String, String> {
+        match self {
+            Format::Json => serde_json::to_string_pretty(config).map_err(|e| e.to_string()),
+            #[cfg(feature = "profiles")]
+            Format::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+            #[cfg(feature = "profiles")]
+            Format::Yaml => serde_yaml::to_string(config).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "profiles"))]
+            Format::Toml | Format::Yaml => Err("TOML and YAML profiles need the `profiles` feature".to_string()),
+        }
+    }
+}
+
+/// Parsed document of one of the profile formats
+trait Document: Sized {
+    /// Take entry `key` out of a table
+    fn take(&mut self, key: &str) -> Option<Self>;
+
+    /// Keys of a table
+    fn keys(&self) -> Vec<String>;
+}
+
+impl Document for serde_json::Value {
+    fn take(&mut self, key: &str) -> Option<Self> {
+        self.as_object_mut()?.remove(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.as_object().map(|map| map.keys().cloned().collect()).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "profiles")]
+impl Document for toml::Value {
+    fn take(&mut self, key: &str) -> Option<Self> {
+        self.as_table_mut()?.remove(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.as_table().map(|table| table.keys().cloned().collect()).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "profiles")]
+impl Document for serde_yaml::Value {
+    fn take(&mut self, key: &str) -> Option<Self> {
+        self.as_mapping_mut()?.remove(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let keys = self.as_mapping().map(|map| map.keys().filter_map(|key| key.as_str()).map(String::from).collect());
+        keys.unwrap_or_default()
+    }
+}
+
+/// The profile called `name` from the `profiles` table of `document`, or the
+/// whole document without a name
+fn select<D: Document>(mut document: D, name: Option<&str>) -> std::result::Result<D, String> {
+    let Some(name) = name else {
+        return Ok(document);
+    };
+    let Some(mut profiles) = document.take("profiles") else {
+        return Err("no profiles table".to_string());
+    };
+    profiles
+        .take(name)
+        .ok_or_else(|| format!("no profile {name}; it has {}", profiles.keys().join(", ")))
+}
+
+/// Load a config from `path`, or its profile called `name`
+fn load(path: &Path, name: Option<&str>) -> Result<Config> {
+    let format = Format::of(path)?;
+    let text = fs::read_to_string(path).map_err(|e| ExtractorError::io_error(e, path))?;
+    format
+        .parse(&text, name)
+        .map_err(|e| ExtractorError::Config(format!("{}: {e}", path.display())))
+}
+
+impl Config {
+    /// Load a config from a `.toml`, `.yaml`/`.yml` or `.json` file. Fields
+    /// the file leaves out keep their defaults; unknown fields are an error.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        load(path.as_ref(), None)
+    }
+
+    /// Load the profile called `name` from the `profiles` table of a file
+    pub fn from_profile<P: AsRef<Path>>(path: P, name: &str) -> Result<Self> {
+        load(path.as_ref(), Some(name))
+    }
+
+    /// Defaults, overlaid with `file` if given, then with `EXTRACTOR_*`
+    /// environment variables (see `with_env`)
+    pub fn layered<P: AsRef<Path>>(file: Option<P>) -> Result<Self> {
+        let config = match file {
+            Some(path) => Self::from_path(path)?,
+            None => Self::default(),
+        };
+        config.with_env()
+    }
+
+    /// Override fields from `EXTRACTOR_<FIELD>` environment variables, e.g.
+    /// `EXTRACTOR_NUM_THREADS=8`. Nested fields are joined with `__`
+    /// (`EXTRACTOR_CHUNKING__ADAPTIVE=false`); values are read as JSON where
+    /// they parse, so enums are written `EXTRACTOR_OUTPUT_MODE=Normalized` or
+    /// `EXTRACTOR_VERIFY_MODE='{"Sampled":{"every":100}}'`. Unknown fields are an error.
+    pub fn with_env(self) -> Result<Self> {
+        self.with_vars(std::env::vars())
+    }
+
+    /// `with_env` over the given variables
+    pub(crate) fn with_vars<I: IntoIterator<Item = (String, String)>>(self, vars: I) -> Result<Self> {
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase(), value)))
+            .filter(|(name, _)| !name.is_empty())
+            .collect();
+        if vars.is_empty() {
+            return Ok(self);
+        }
+        vars.sort();
+
+        let mut document = self.to_json();
+        for (name, raw) in &vars {
+            let mut target = &mut document;
+            for key in name.split("__") {
+                let Some(table) = target.as_object_mut() else {
+                    return Err(ExtractorError::Config(format!("{ENV_PREFIX}{}: {key} is not a table", name.to_uppercase())));
+                };
+                target = table.entry(key).or_insert(serde_json::Value::Null);
+            }
+            // Fields already holding a string keep the raw value; unset optional
+            // strings that look like JSON need quoting (EXTRACTOR_PARTITION_BY='"1"')
+            *target = match target {
+                serde_json::Value::String(_) => serde_json::Value::String(raw.clone()),
+                _ => serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.clone())),
+            };
+        }
+        let thread_pool = self.thread_pool;
+        let config: Self = serde_json::from_value(document)
+            .map_err(|e| ExtractorError::Config(format!("{ENV_PREFIX}* environment variables: {e}")))?;
+        Ok(Self { thread_pool, ..config })
+    }
+
+    /// The resolved configuration as JSON, for run reports. The thread pool
+    /// is left out.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("Config serializes to JSON")
+    }
+
+    /// Save the config as a profile, in the format of the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let text = Format::of(path)?
+            .render(self)
+            .map_err(|e| ExtractorError::Config(format!("{}: {e}", path.display())))?;
+        fs::write(path, text).map_err(|e| ExtractorError::io_error(e, path))
+    }
+}
+
+/// The delimiter as a one-character string, e.g. `","` or `"\t"`
+pub(crate) mod delimiter {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(delimiter: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&char::from(*delimiter).to_string())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        match text.as_bytes() {
+            // A tab is hard to type in environment variables and shells
+            b"\\t" | b"tab" => Ok(b'\t'),
+            [byte] if byte.is_ascii() => Ok(*byte),
+            _ => Err(de::Error::custom(format!("delimiter must be a single ASCII character, not {text:?}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutputMode, VerifyMode};
+
+    #[test]
+    fn test_profiles() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = Config {
+            delimiter: b'\t',
+            num_threads: Some(8),
+            verify_mode: VerifyMode::Sampled { every: 100 },
+            output_mode: OutputMode::Normalized,
+            ..Config::default()
+        };
+        #[cfg(feature = "profiles")]
+        let formats = ["profile.json", "profile.toml", "profile.yaml"];
+        #[cfg(not(feature = "profiles"))]
+        let formats = ["profile.json"];
+        for name in formats {
+            let path = dir.path().join(name);
+            config.save(&path)?;
+            let loaded = Config::from_path(&path)?;
+            assert_eq!(loaded.delimiter, b'\t');
+            assert_eq!(loaded.num_threads, Some(8));
+            assert_eq!(loaded.verify_mode, VerifyMode::Sampled { every: 100 });
+            assert_eq!(loaded.output_mode, OutputMode::Normalized);
+        }
+
+        let path = dir.path().join("profiles.json");
+        fs::write(
+            &path,
+            r#"{"profiles": {"gwas-default": {"delimiter": "\t", "chunking": {"adaptive": false}}, "rnaseq-qc": {}}}"#,
+        )?;
+        let gwas = Config::from_profile(&path, "gwas-default")?;
+        assert_eq!(gwas.delimiter, b'\t');
+        assert!(!gwas.chunking.adaptive);
+        assert_eq!(gwas.chunk_size, Config::default().chunk_size);
+        assert!(matches!(Config::from_profile(&path, "missing"), Err(ExtractorError::Config(_))));
+
+        fs::write(&path, r#"{"delimiter": ",", "chunk_sise": 10}"#)?;
+        assert!(matches!(Config::from_path(&path), Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_overrides() -> Result<()> {
+        let vars = |vars: &[(&str, &str)]| -> Vec<(String, String)> {
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        let config = Config { chunk_size: 4096, ..Config::default() }.with_vars(vars(&[
+            ("EXTRACTOR_NUM_THREADS", "8"),
+            ("EXTRACTOR_DELIMITER", "tab"),
+            ("EXTRACTOR_PARTITION_BY", "chrom"),
+            ("EXTRACTOR_CHUNKING__ADAPTIVE", "false"),
+            ("EXTRACTOR_OUTPUT_MODE", "Normalized"),
+            ("PATH", "/usr/bin"),
+        ]))?;
+        assert_eq!(config.chunk_size, 4096);
+        assert_eq!(config.num_threads, Some(8));
+        assert_eq!(config.delimiter, b'\t');
+        assert_eq!(config.partition_by.as_deref(), Some("chrom"));
+        assert!(!config.chunking.adaptive);
+        assert_eq!(config.output_mode, OutputMode::Normalized);
+        assert_eq!(config.to_json()["num_threads"], 8);
+
+        let typo = Config::default().with_vars(vars(&[("EXTRACTOR_NUM_THREDS", "8")]));
+        assert!(matches!(typo, Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+}
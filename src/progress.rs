@@ -0,0 +1,175 @@
+//! Progress reporting for long runs: filtering and index building report
+//! bytes done to every `ProgressSink` of their `ProgressConfig`, so a
+//! terminal bar and a service's callback can follow the same run.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "progress-bars")]
+use std::sync::Mutex;
+
+use crate::ProgressConfig;
+
+/// Called with the bytes processed so far and the total, from whichever
+/// worker thread finished a chunk
+pub type ProgressCallback = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Receives the progress of runs. Calls can come from any worker thread.
+pub trait ProgressSink: Send + Sync {
+    /// A run over `total` bytes has started
+    fn start(&self, _total: u64, _message: &str) {}
+
+    /// `delta` more bytes are done, `done` of `total` in all
+    fn advance(&self, delta: u64, done: u64, total: u64);
+
+    /// The run has finished
+    fn finish(&self) {}
+}
+
+impl fmt::Debug for dyn ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
+
+/// Sink that ignores progress
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullProgress;
+
+impl ProgressSink for NullProgress {
+    fn advance(&self, _delta: u64, _done: u64, _total: u64) {}
+}
+
+/// Sink that calls a function with the bytes done and the total
+pub struct CallbackProgress(ProgressCallback);
+
+impl CallbackProgress {
+    /// Report progress to `callback`
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+impl ProgressSink for CallbackProgress {
+    fn advance(&self, _delta: u64, done: u64, total: u64) {
+        (self.0)(done, total)
+    }
+}
+
+/// Sink that draws a terminal progress bar on stderr, one per run
+#[cfg(feature = "progress-bars")]
+pub struct IndicatifBar {
+    bar: Mutex<Option<indicatif::ProgressBar>>,
+    refresh_rate: u64,
+    show_eta: bool,
+}
+
+#[cfg(feature = "progress-bars")]
+impl IndicatifBar {
+    /// A bar redrawn at most every `refresh_rate` milliseconds
+    pub fn new(refresh_rate: u64, show_eta: bool) -> Self {
+        Self { bar: Mutex::new(None), refresh_rate, show_eta }
+    }
+
+    fn bar(&self) -> std::sync::MutexGuard<'_, Option<indicatif::ProgressBar>> {
+        self.bar.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "progress-bars")]
+impl ProgressSink for IndicatifBar {
+    fn start(&self, total: u64, message: &str) {
+        let template = if self.show_eta { "{msg} [{bar:40}] {pos}/{len} ({eta})" } else { "{msg} [{bar:40}] {pos}/{len}" };
+        let hz = (1000 / self.refresh_rate.max(1)).clamp(1, u8::MAX as u64) as u8;
+        let bar = indicatif::ProgressBar::with_draw_target(Some(total), indicatif::ProgressDrawTarget::stderr_with_hz(hz));
+        bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(template)
+                .expect("valid progress template")
+                .progress_chars("=> "),
+        );
+        bar.set_message(message.to_string());
+        *self.bar() = Some(bar);
+    }
+
+    fn advance(&self, delta: u64, _done: u64, _total: u64) {
+        if let Some(bar) = self.bar().as_ref() {
+            bar.inc(delta);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar().take() {
+            bar.finish();
+        }
+    }
+}
+
+/// Progress of one run, fanned out to the configured sinks
+pub(crate) struct Progress {
+    sinks: Vec<Arc<dyn ProgressSink>>,
+    done: AtomicU64,
+    total: u64,
+}
+
+impl Progress {
+    /// Start a run over `total` bytes: the configured sinks, plus a bar if
+    /// `config.enabled` and the `progress-bars` feature is on
+    pub(crate) fn start(config: &ProgressConfig, total: u64, message: &str) -> Self {
+        #[allow(unused_mut)]
+        let mut sinks = config.sinks.clone();
+        #[cfg(feature = "progress-bars")]
+        if config.enabled {
+            sinks.push(Arc::new(IndicatifBar::new(config.refresh_rate, config.show_eta)));
+        }
+        for sink in &sinks {
+            sink.start(total, message);
+        }
+        Self { sinks, done: AtomicU64::new(0), total }
+    }
+
+    /// Record `delta` more bytes done
+    pub(crate) fn inc(&self, delta: u64) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        let done = self.done.fetch_add(delta, Ordering::Relaxed) + delta;
+        for sink in &self.sinks {
+            sink.advance(delta, done, self.total);
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        for sink in &self.sinks {
+            sink.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_fans_out() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let config = ProgressConfig {
+            enabled: false,
+            sinks: vec![
+                Arc::new(NullProgress),
+                Arc::new(CallbackProgress::new(move |done, total| seen.lock().unwrap().push((done, total)))),
+            ],
+            ..ProgressConfig::default()
+        };
+        let progress = Progress::start(&config, 10, "test");
+        progress.inc(4);
+        progress.inc(6);
+        progress.finish();
+        assert_eq!(*reports.lock().unwrap(), vec![(4, 10), (10, 10)]);
+    }
+}
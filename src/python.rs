@@ -0,0 +1,574 @@
+//! Python bindings, built with maturin when the `python` feature is enabled.
+//! Statistics come back as plain dicts and lists so they load straight into pandas.
+
+// The glue generated by pyo3's macros is unsafe code
+#![allow(unsafe_code)]
+
+use std::path::PathBuf;
+
+use csv::ByteRecord;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::core::BioFilter;
+use crate::error::ExtractorError as Error;
+use crate::filters::{ColumnFilter, DateOp, FilterCondition, FilterStats, NumericCondition};
+use crate::index::{FileIndex, IndexedReader, RowReader};
+use crate::observers::{ColumnObserver, ColumnSummary};
+use crate::output::{ExistingOutput, OutputMode};
+use crate::{Config, ProcessingStats};
+
+create_exception!(extractor, ExtractorError, PyException, "Error reported by the extractor library");
+
+/// Map library errors onto the closest built-in Python exception
+fn to_py_err(err: Error) -> PyErr {
+    match &err {
+        Error::Io { .. } => PyIOError::new_err(err.to_string()),
+        Error::ColumnNotFound { .. } => PyKeyError::new_err(err.to_string()),
+        Error::Config(_) => PyValueError::new_err(err.to_string()),
+        _ => ExtractorError::new_err(err.to_string()),
+    }
+}
+
+fn record_to_strings(record: &ByteRecord) -> Vec<String> {
+    record.iter().map(|field| String::from_utf8_lossy(field).into_owned()).collect()
+}
+
+/// A condition on a single column, created with the static constructors
+#[pyclass(name = "FilterCondition", module = "extractor", frozen)]
+#[derive(Clone)]
+pub struct PyFilterCondition {
+    inner: FilterCondition,
+}
+
+impl From<FilterCondition> for PyFilterCondition {
+    fn from(inner: FilterCondition) -> Self {
+        Self { inner }
+    }
+}
+
+fn numeric(cond: NumericCondition) -> PyFilterCondition {
+    FilterCondition::Numeric(cond).into()
+}
+
+#[pymethods]
+impl PyFilterCondition {
+    #[staticmethod]
+    fn equals(value: String) -> Self {
+        FilterCondition::Equals(value).into()
+    }
+
+    #[staticmethod]
+    fn contains(substring: String) -> Self {
+        FilterCondition::Contains(substring).into()
+    }
+
+    #[staticmethod]
+    fn regex(pattern: String) -> Self {
+        FilterCondition::Regex(pattern).into()
+    }
+
+    #[staticmethod]
+    fn one_of(values: Vec<String>) -> Self {
+        FilterCondition::OneOf(values).into()
+    }
+
+    #[staticmethod]
+    fn empty() -> Self {
+        FilterCondition::Empty.into()
+    }
+
+    #[staticmethod]
+    fn not_empty() -> Self {
+        FilterCondition::NotEmpty.into()
+    }
+
+    #[staticmethod]
+    fn greater_than(value: f64) -> Self {
+        numeric(NumericCondition::GreaterThan(value))
+    }
+
+    #[staticmethod]
+    fn greater_or_equal(value: f64) -> Self {
+        numeric(NumericCondition::GreaterOrEqual(value))
+    }
+
+    #[staticmethod]
+    fn less_than(value: f64) -> Self {
+        numeric(NumericCondition::LessThan(value))
+    }
+
+    #[staticmethod]
+    fn less_or_equal(value: f64) -> Self {
+        numeric(NumericCondition::LessOrEqual(value))
+    }
+
+    #[staticmethod]
+    fn equal_to(value: f64) -> Self {
+        numeric(NumericCondition::Equal(value))
+    }
+
+    #[staticmethod]
+    fn between(min: f64, max: f64) -> Self {
+        numeric(NumericCondition::Between(min, max))
+    }
+
+    #[staticmethod]
+    fn abs_greater_than(value: f64) -> Self {
+        numeric(NumericCondition::AbsGreaterThan(value))
+    }
+
+    #[staticmethod]
+    fn abs_greater_or_equal(value: f64) -> Self {
+        numeric(NumericCondition::AbsGreaterOrEqual(value))
+    }
+
+    #[staticmethod]
+    fn abs_less_than(value: f64) -> Self {
+        numeric(NumericCondition::AbsLessThan(value))
+    }
+
+    #[staticmethod]
+    fn is_finite() -> Self {
+        numeric(NumericCondition::IsFinite)
+    }
+
+    /// `op` is one of "before", "after", "on_or_before", "on_or_after", "on"
+    /// or "between" (which needs `end`)
+    #[staticmethod]
+    #[pyo3(signature = (op, value, format = "%Y-%m-%d".to_string(), end = None))]
+    fn date(op: &str, value: String, format: String, end: Option<String>) -> PyResult<Self> {
+        let op = match (op, end) {
+            ("before", None) => DateOp::Before,
+            ("after", None) => DateOp::After,
+            ("on_or_before", None) => DateOp::OnOrBefore,
+            ("on_or_after", None) => DateOp::OnOrAfter,
+            ("on", None) => DateOp::On,
+            ("between", Some(end)) => DateOp::Between { end },
+            ("between", None) => return Err(PyValueError::new_err("'between' needs an end date")),
+            (op, _) => return Err(PyValueError::new_err(format!("Unknown date comparison '{op}'"))),
+        };
+        Ok(FilterCondition::Date { op, value, format }.into())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FilterCondition({})", self.inner.description("value"))
+    }
+}
+
+/// A condition bound to a column. Invalid conditions (e.g. a bad regex) are
+/// rejected when the filter is created.
+#[pyclass(name = "ColumnFilter", module = "extractor", frozen)]
+#[derive(Clone)]
+pub struct PyColumnFilter {
+    column: String,
+    condition: FilterCondition,
+    empty_tokens: Option<Vec<String>>,
+}
+
+impl PyColumnFilter {
+    fn build(&self) -> crate::Result<ColumnFilter> {
+        let filter = ColumnFilter::new(self.column.clone(), self.condition.clone())?;
+        Ok(match &self.empty_tokens {
+            Some(tokens) => filter.with_empty_tokens(tokens.clone()),
+            None => filter,
+        })
+    }
+}
+
+#[pymethods]
+impl PyColumnFilter {
+    #[new]
+    #[pyo3(signature = (column, condition, empty_tokens = None))]
+    fn new(column: String, condition: PyFilterCondition, empty_tokens: Option<Vec<String>>) -> PyResult<Self> {
+        let filter = Self { column, condition: condition.inner, empty_tokens };
+        filter.build().map_err(to_py_err)?;
+        Ok(filter)
+    }
+
+    #[getter]
+    fn column(&self) -> &str {
+        &self.column
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ColumnFilter({})", self.condition.description(&self.column))
+    }
+}
+
+/// Result of a run. `to_dict()` and the dict/list getters load directly
+/// into `pandas.DataFrame`.
+#[pyclass(name = "ProcessingStats", module = "extractor", frozen)]
+pub struct PyProcessingStats {
+    inner: ProcessingStats,
+}
+
+fn summary_dict<'py>(py: Python<'py>, summary: &ColumnSummary) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("count", summary.count)?;
+    dict.set_item("empty", summary.empty)?;
+    dict.set_item("numeric_count", summary.numeric_count)?;
+    dict.set_item("min", summary.min)?;
+    dict.set_item("max", summary.max)?;
+    dict.set_item("mean", summary.mean)?;
+    dict.set_item("unique_count", summary.unique_count)?;
+    dict.set_item("unique_capped", summary.unique_capped)?;
+    Ok(dict)
+}
+
+fn filter_stats_dict<'py>(py: Python<'py>, stats: &FilterStats) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("position", stats.position)?;
+    dict.set_item("description", &stats.description)?;
+    dict.set_item("evaluated", stats.evaluated)?;
+    dict.set_item("rejected", stats.rejected)?;
+    dict.set_item("errors", stats.errors)?;
+    dict.set_item("rejection_rate", stats.rejection_rate())?;
+    dict.set_item("avg_eval_ns", stats.avg_eval_ns)?;
+    Ok(dict)
+}
+
+#[pymethods]
+impl PyProcessingStats {
+    #[getter]
+    fn rows_processed(&self) -> u64 {
+        self.inner.rows_processed
+    }
+
+    #[getter]
+    fn rows_matched(&self) -> u64 {
+        self.inner.rows_matched
+    }
+
+    #[getter]
+    fn processing_time_ms(&self) -> u64 {
+        self.inner.processing_time_ms
+    }
+
+    #[getter]
+    fn input_size(&self) -> u64 {
+        self.inner.input_size
+    }
+
+    #[getter]
+    fn output_size(&self) -> u64 {
+        self.inner.output_size
+    }
+
+    /// Observed column statistics as `{column: {statistic: value}}`; use
+    /// `pandas.DataFrame.from_dict(stats.column_stats, orient="index")`
+    #[getter]
+    fn column_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (column, summary) in &self.inner.column_stats {
+            dict.set_item(column, summary_dict(py, summary)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// One dict per pipeline of a multi-pipeline run
+    #[getter]
+    fn pipelines<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner
+            .pipelines
+            .iter()
+            .map(|pipeline| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("name", &pipeline.name)?;
+                dict.set_item("output_path", &pipeline.output_path)?;
+                dict.set_item("rows_matched", pipeline.rows_matched)?;
+                dict.set_item("output_size", pipeline.output_size)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Buffer high-water marks of the run, in bytes unless named otherwise
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let memory = &self.inner.memory;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("peak_rss_bytes", memory.peak_rss_bytes)?;
+        dict.set_item("peak_chunks_in_flight", memory.peak_chunks_in_flight)?;
+        dict.set_item("peak_in_flight_bytes", memory.peak_in_flight_bytes)?;
+        dict.set_item("peak_buffered_output_bytes", memory.peak_buffered_output_bytes)?;
+        dict.set_item("index_bytes", memory.index_bytes)?;
+        Ok(dict)
+    }
+
+    /// The scalar counters as a flat dict
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("rows_processed", self.inner.rows_processed)?;
+        dict.set_item("rows_matched", self.inner.rows_matched)?;
+        dict.set_item("processing_time_ms", self.inner.processing_time_ms)?;
+        dict.set_item("input_size", self.inner.input_size)?;
+        dict.set_item("output_size", self.inner.output_size)?;
+        dict.set_item("input_digest", &self.inner.input_digest)?;
+        dict.set_item("output_digest", &self.inner.output_digest)?;
+        let config = self.inner.config.as_ref().map(|config| config.to_string());
+        dict.set_item("config", config)?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ProcessingStats(rows_processed={}, rows_matched={})",
+            self.inner.rows_processed, self.inner.rows_matched
+        )
+    }
+}
+
+/// Filters a CSV file into `output`. Keyword arguments mirror `Config`.
+#[pyclass(name = "BioFilter", module = "extractor")]
+pub struct PyBioFilter {
+    inner: BioFilter,
+}
+
+#[pymethods]
+impl PyBioFilter {
+    #[new]
+    #[pyo3(signature = (
+        input,
+        output,
+        *,
+        delimiter = ",",
+        has_headers = true,
+        parallel = true,
+        num_threads = None,
+        chunk_size = None,
+        index = None,
+        partition_by = None,
+        output_mode = "passthrough",
+        overwrite = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        input: PathBuf,
+        output: PathBuf,
+        delimiter: &str,
+        has_headers: bool,
+        parallel: bool,
+        num_threads: Option<usize>,
+        chunk_size: Option<usize>,
+        index: Option<PathBuf>,
+        partition_by: Option<String>,
+        output_mode: &str,
+        overwrite: bool,
+    ) -> PyResult<Self> {
+        let &[delimiter] = delimiter.as_bytes() else {
+            return Err(PyValueError::new_err("delimiter must be a single ASCII character"));
+        };
+        let output_mode = match output_mode {
+            "passthrough" => OutputMode::Passthrough,
+            "normalized" => OutputMode::Normalized,
+            other => return Err(PyValueError::new_err(format!("Unknown output mode '{other}'"))),
+        };
+        let defaults = Config::default();
+        let config = Config {
+            delimiter,
+            has_headers,
+            parallel,
+            num_threads,
+            chunk_size: chunk_size.unwrap_or(defaults.chunk_size),
+            use_index: index.is_some(),
+            partition_by,
+            output_mode,
+            on_existing_output: if overwrite { ExistingOutput::Overwrite } else { ExistingOutput::Error },
+            ..defaults
+        };
+        let inner = BioFilter::new(input, output, config, index).map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    fn add_filter(&mut self, filter: PyColumnFilter) -> PyResult<()> {
+        let filter = filter.build().map_err(to_py_err)?;
+        self.inner.add_filter(Box::new(filter));
+        Ok(())
+    }
+
+    /// Collect statistics for `column` over the matched rows
+    #[pyo3(signature = (column, max_unique = None))]
+    fn add_observer(&mut self, column: String, max_unique: Option<usize>) {
+        let observer = ColumnObserver::new(column);
+        self.inner.add_observer(match max_unique {
+            Some(max_unique) => observer.with_max_unique(max_unique),
+            None => observer,
+        });
+    }
+
+    /// Run the extraction. The GIL is released while the file is processed.
+    fn process(&self, py: Python<'_>) -> PyResult<PyProcessingStats> {
+        let inner = py.allow_threads(|| self.inner.process()).map_err(to_py_err)?;
+        Ok(PyProcessingStats { inner })
+    }
+
+    /// Run the extraction and check the output against the sequential path
+    fn process_verified(&self, py: Python<'_>) -> PyResult<PyProcessingStats> {
+        let inner = py.allow_threads(|| self.inner.process_verified()).map_err(to_py_err)?;
+        Ok(PyProcessingStats { inner })
+    }
+
+    /// The first `n` selected rows without writing output, as a dict with
+    /// `header` (a list, or None), `rows` (lists of strings) and `rows_scanned`
+    #[pyo3(signature = (n = 50))]
+    fn preview<'py>(&self, py: Python<'py>, n: usize) -> PyResult<Bound<'py, PyDict>> {
+        let preview = py.allow_threads(|| self.inner.preview(n)).map_err(to_py_err)?;
+        let strings = |record: &csv::StringRecord| record.iter().map(str::to_string).collect::<Vec<_>>();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("header", preview.header.as_ref().map(strings))?;
+        dict.set_item("rows", preview.rows.iter().map(strings).collect::<Vec<_>>())?;
+        dict.set_item("rows_scanned", preview.rows_scanned)?;
+        Ok(dict)
+    }
+
+    /// Distinct non-empty values of `column` and their row counts, as a dict
+    /// with `values` (a list of `(value, count)`, most common first) and `capped`
+    #[pyo3(signature = (column, limit = 1000))]
+    fn distinct_values<'py>(&self, py: Python<'py>, column: &str, limit: usize) -> PyResult<Bound<'py, PyDict>> {
+        let distinct = py.allow_threads(|| self.inner.distinct_values(column, limit)).map_err(to_py_err)?;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("values", distinct.values)?;
+        dict.set_item("capped", distinct.capped)?;
+        Ok(dict)
+    }
+
+    /// Per-filter counters from the last run, one dict per filter
+    fn filter_stats<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner
+            .filter_stats()
+            .iter()
+            .map(|stats| filter_stats_dict(py, stats))
+            .collect()
+    }
+
+    fn filter_report(&self) -> String {
+        self.inner.filter_report()
+    }
+
+    /// Reorder filters by sampled selectivity; returns the new order
+    #[pyo3(signature = (sample_rows = 1000))]
+    fn optimize_filter_order(&mut self, py: Python<'_>, sample_rows: usize) -> PyResult<Vec<usize>> {
+        let inner = &mut self.inner;
+        py.allow_threads(|| inner.optimize_filter_order(sample_rows)).map_err(to_py_err)
+    }
+}
+
+/// Primary/secondary key index over a CSV file
+#[pyclass(name = "FileIndex", module = "extractor", frozen)]
+pub struct PyFileIndex {
+    inner: FileIndex,
+}
+
+#[pymethods]
+impl PyFileIndex {
+    #[staticmethod]
+    #[pyo3(signature = (source, primary_column, secondary_columns = Vec::new()))]
+    fn build(
+        py: Python<'_>,
+        source: PathBuf,
+        primary_column: String,
+        secondary_columns: Vec<String>,
+    ) -> PyResult<Self> {
+        let inner = py
+            .allow_threads(|| {
+                secondary_columns
+                    .into_iter()
+                    .fold(FileIndex::builder(source, primary_column), |builder, column| {
+                        builder.add_secondary_index(column)
+                    })
+                    .build()
+            })
+            .map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let inner = FileIndex::load(&path).map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        self.inner.save(&path).map_err(to_py_err)
+    }
+
+    #[getter]
+    fn primary_column(&self) -> &str {
+        &self.inner.primary_column
+    }
+
+    #[getter]
+    fn columns(&self) -> Vec<String> {
+        self.inner.columns.clone()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.positions.len()
+    }
+
+    /// Row for a primary key as a list of strings, or None
+    fn get(&self, key: &str) -> PyResult<Option<Vec<String>>> {
+        let row = IndexedReader::new(&self.inner)
+            .and_then(|mut reader| reader.get(key))
+            .map_err(to_py_err)?;
+        Ok(row.as_ref().map(record_to_strings))
+    }
+
+    /// Rows for many primary keys, in the order of `keys`
+    fn get_many(&self, py: Python<'_>, keys: Vec<String>) -> PyResult<Vec<Option<Vec<String>>>> {
+        let rows = py
+            .allow_threads(|| {
+                let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+                IndexedReader::new(&self.inner)?.get_many(&keys)
+            })
+            .map_err(to_py_err)?;
+        Ok(rows.iter().map(|row| row.as_ref().map(record_to_strings)).collect())
+    }
+
+    /// Row by row number (the first data row is 1)
+    fn get_row(&self, row_number: u64) -> PyResult<Option<Vec<String>>> {
+        let row = RowReader::new(&self.inner)
+            .and_then(|mut reader| reader.get_row(row_number))
+            .map_err(to_py_err)?;
+        Ok(row.as_ref().map(record_to_strings))
+    }
+
+    /// Row numbers matching `value` in a secondary index column
+    fn secondary_rows(&self, column: &str, value: &str) -> Vec<u64> {
+        self.inner
+            .get_secondary_positions(column, value)
+            .map(|positions| positions.iter().map(|p| p.row_number).collect())
+            .unwrap_or_default()
+    }
+
+    /// Cardinality, row count and most frequent keys of a secondary index
+    /// column, or None if the column isn't indexed
+    fn column_summary<'py>(&self, py: Python<'py>, column: &str) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.inner
+            .column_summary(column)
+            .map(|summary| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("cardinality", summary.cardinality)?;
+                dict.set_item("rows", summary.rows)?;
+                dict.set_item("top_keys", summary.top_keys.clone())?;
+                Ok(dict)
+            })
+            .transpose()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "extractor")]
+fn extractor_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFilterCondition>()?;
+    m.add_class::<PyColumnFilter>()?;
+    m.add_class::<PyBioFilter>()?;
+    m.add_class::<PyFileIndex>()?;
+    m.add_class::<PyProcessingStats>()?;
+    m.add("ExtractorError", m.py().get_type_bound::<ExtractorError>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! R bindings, built by the package in `r/` when the `r` feature is enabled.
+//! Results come back as R lists and data frames for use in R Markdown.
+
+// The glue generated by extendr's macros is unsafe code
+#![allow(unsafe_code)]
+
+use extendr_api::prelude::*;
+
+use crate::core::BioFilter;
+use crate::error::ExtractorError;
+use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+use crate::Config;
+
+fn to_r_err(err: ExtractorError) -> Error {
+    Error::Other(err.to_string())
+}
+
+/// Build one condition from a `list(op = ..., value = ...)` spec
+fn condition(column: &str, spec: &Robj) -> Result<FilterCondition> {
+    let spec = spec
+        .as_list()
+        .ok_or_else(|| Error::Other(format!("filter for '{column}' must be list(op = ..., value = ...)")))?
+        .into_hashmap();
+    let op = spec
+        .get("op")
+        .and_then(|op| op.as_str())
+        .ok_or_else(|| Error::Other(format!("filter for '{column}' has no op")))?;
+    let value = spec.get("value");
+
+    let number = || {
+        value
+            .and_then(|v| v.as_real())
+            .ok_or_else(|| Error::Other(format!("op '{op}' on '{column}' needs a numeric value")))
+    };
+    let text = || {
+        value
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| Error::Other(format!("op '{op}' on '{column}' needs a character value")))
+    };
+
+    let numeric = FilterCondition::Numeric;
+    Ok(match op {
+        "equals" => FilterCondition::Equals(text()?),
+        "contains" => FilterCondition::Contains(text()?),
+        "regex" => FilterCondition::Regex(text()?),
+        "one_of" => FilterCondition::OneOf(
+            value
+                .and_then(|v| v.as_string_vector())
+                .ok_or_else(|| Error::Other(format!("op 'one_of' on '{column}' needs a character vector")))?,
+        ),
+        "empty" => FilterCondition::Empty,
+        "not_empty" => FilterCondition::NotEmpty,
+        "<" => numeric(NumericCondition::LessThan(number()?)),
+        "<=" => numeric(NumericCondition::LessOrEqual(number()?)),
+        ">" => numeric(NumericCondition::GreaterThan(number()?)),
+        ">=" => numeric(NumericCondition::GreaterOrEqual(number()?)),
+        "==" => numeric(NumericCondition::Equal(number()?)),
+        "!=" => numeric(NumericCondition::NotEqual(number()?)),
+        "abs>" => numeric(NumericCondition::AbsGreaterThan(number()?)),
+        "abs>=" => numeric(NumericCondition::AbsGreaterOrEqual(number()?)),
+        "between" => match value.and_then(|v| v.as_real_vector()).as_deref() {
+            Some(&[min, max]) => numeric(NumericCondition::Between(min, max)),
+            _ => return Err(Error::Other(format!("op 'between' on '{column}' needs c(min, max)"))),
+        },
+        _ => return Err(Error::Other(format!("unknown op '{op}' for '{column}'"))),
+    })
+}
+
+/// Filter a CSV file.
+///
+/// `filters` is a named list keyed by column, each entry
+/// `list(op = ..., value = ...)`, e.g.
+/// `list(p_value = list(op = "<", value = 5e-8), gene = list(op = "one_of", value = c("TP53", "APOE")))`.
+/// Returns the run statistics as a list; `filter_stats` is a data frame with
+/// one row per filter.
+/// @export
+#[extendr]
+fn filter_csv(
+    input: &str,
+    output: &str,
+    filters: List,
+    #[default = "\",\""] delimiter: &str,
+    #[default = "TRUE"] parallel: bool,
+) -> Result<List> {
+    let delimiter = match delimiter.as_bytes() {
+        [byte] => *byte,
+        _ => return Err(Error::Other(format!("delimiter must be a single character, got '{delimiter}'"))),
+    };
+    let config = Config { delimiter, parallel, ..Config::default() };
+    let mut extractor =
+        BioFilter::new(input.into(), output.into(), config, None).map_err(to_r_err)?;
+
+    for (column, spec) in filters.iter() {
+        let filter = ColumnFilter::new(column.to_string(), condition(column, &spec)?).map_err(to_r_err)?;
+        extractor.add_filter(Box::new(filter));
+    }
+
+    let stats = extractor.process().map_err(to_r_err)?;
+    let filter_stats = extractor.filter_stats();
+    let filter_frame = data_frame!(
+        position = filter_stats.iter().map(|s| s.position as i32).collect::<Vec<_>>(),
+        description = filter_stats.iter().map(|s| s.description.clone()).collect::<Vec<_>>(),
+        evaluated = filter_stats.iter().map(|s| s.evaluated as f64).collect::<Vec<_>>(),
+        rejected = filter_stats.iter().map(|s| s.rejected as f64).collect::<Vec<_>>(),
+        errors = filter_stats.iter().map(|s| s.errors as f64).collect::<Vec<_>>(),
+        avg_eval_ns = filter_stats.iter().map(|s| s.avg_eval_ns).collect::<Vec<_>>()
+    );
+
+    // R has no 64-bit integers, so counts come back as doubles
+    Ok(list!(
+        rows_processed = stats.rows_processed as f64,
+        rows_matched = stats.rows_matched as f64,
+        processing_time_ms = stats.processing_time_ms as f64,
+        input_size = stats.input_size as f64,
+        output_size = stats.output_size as f64,
+        filter_stats = filter_frame
+    ))
+}
+
+extendr_module! {
+    mod extractor;
+    fn filter_csv;
+}
@@ -0,0 +1,428 @@
+//! Remote input from object stores and web servers, enabled with the
+//! `object-store` feature (and `http` for `http://` / `https://` URLs).
+//!
+//! Inputs given as URLs are read in place instead of being staged locally:
+//! scans use one streaming GET that resumes where it left off if the
+//! connection drops, and indexed lookups use ranged GETs. Credentials and
+//! regions come from the usual environment variables (`AWS_ACCESS_KEY_ID`,
+//! `GOOGLE_SERVICE_ACCOUNT`, ...).
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use flate2::read::MultiGzDecoder;
+use futures::stream::{BoxStream, StreamExt};
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+#[cfg(feature = "http")]
+use object_store::http::HttpBuilder;
+#[cfg(feature = "http")]
+use object_store::ClientOptions;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::{
+    BackoffConfig, GetOptions, GetRange, ObjectMeta, ObjectStore, ObjectStoreScheme, RetryConfig,
+};
+use tokio::runtime::{Builder, Runtime};
+use url::Url;
+
+use crate::error::ExtractorError;
+use crate::{RemoteConfig, Result};
+
+/// URL schemes that are read remotely
+const SCHEMES: &[&str] = &[
+    "s3", "s3a", "gs", "az", "adl", "azure", "abfs", "abfss", "file",
+    #[cfg(feature = "http")]
+    "http",
+    #[cfg(feature = "http")]
+    "https",
+];
+
+/// Whether `path` is a URL that is read remotely, such as `s3://bucket/key.csv`
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str()
+        .and_then(|path| path.split_once("://"))
+        .is_some_and(|(scheme, _)| SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+}
+
+/// An object in S3, GCS or Azure, or a file on a web server, addressed by URL
+#[derive(Clone)]
+pub struct RemoteObject {
+    url: PathBuf,
+    store: Arc<dyn ObjectStore>,
+    location: ObjectPath,
+    meta: ObjectMeta,
+    config: RemoteConfig,
+    runtime: Arc<Runtime>,
+}
+
+impl RemoteObject {
+    /// Resolve `url` and fetch the object's metadata
+    pub fn open(url: &Path, config: &RemoteConfig) -> Result<Self> {
+        let io_error = |e: io::Error| ExtractorError::io_error(e, url);
+        let store_error = |e: &dyn std::fmt::Display| ExtractorError::Config(format!("{}: {e}", url.display()));
+        let parsed = url
+            .to_str()
+            .and_then(|url| Url::parse(url).ok())
+            .ok_or_else(|| ExtractorError::Config(format!("Invalid URL: {}", url.display())))?;
+
+        let retry = RetryConfig {
+            backoff: BackoffConfig {
+                init_backoff: Duration::from_millis(config.initial_backoff_ms),
+                max_backoff: Duration::from_millis(config.max_backoff_ms),
+                base: 2.0,
+            },
+            max_retries: config.max_retries,
+            ..RetryConfig::default()
+        };
+        let (scheme, location) = ObjectStoreScheme::parse(&parsed).map_err(|e| store_error(&e))?;
+        let store: Arc<dyn ObjectStore> = match scheme {
+            ObjectStoreScheme::Local => Arc::new(LocalFileSystem::new()),
+            ObjectStoreScheme::AmazonS3 => Arc::new(
+                AmazonS3Builder::from_env().with_url(parsed.as_str()).with_retry(retry).build().map_err(|e| store_error(&e))?,
+            ),
+            ObjectStoreScheme::GoogleCloudStorage => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_url(parsed.as_str())
+                    .with_retry(retry)
+                    .build()
+                    .map_err(|e| store_error(&e))?,
+            ),
+            ObjectStoreScheme::MicrosoftAzure => Arc::new(
+                MicrosoftAzureBuilder::from_env()
+                    .with_url(parsed.as_str())
+                    .with_retry(retry)
+                    .build()
+                    .map_err(|e| store_error(&e))?,
+            ),
+            #[cfg(feature = "http")]
+            ObjectStoreScheme::Http => Arc::new(
+                HttpBuilder::new()
+                    .with_url(&parsed[..url::Position::BeforePath])
+                    .with_client_options(ClientOptions::new().with_allow_http(parsed.scheme() == "http"))
+                    .with_retry(retry)
+                    .build()
+                    .map_err(|e| store_error(&e))?,
+            ),
+            other => {
+                return Err(ExtractorError::Config(format!(
+                    "{}: {other:?} URLs are not supported in this build",
+                    url.display()
+                )))
+            }
+        };
+
+        let runtime = Builder::new_current_thread().enable_all().build().map_err(io_error)?;
+        let meta = runtime
+            .block_on(store.head(&location))
+            .map_err(|e| io_error(e.into()))?;
+
+        Ok(Self {
+            url: url.to_path_buf(),
+            store,
+            location,
+            meta,
+            config: config.clone(),
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Size of the object in bytes
+    pub fn size(&self) -> u64 {
+        self.meta.size
+    }
+
+    /// Last modification time as seconds and nanoseconds since the Unix epoch
+    pub fn modified(&self) -> Duration {
+        let modified = self.meta.last_modified;
+        Duration::new(modified.timestamp().max(0) as u64, modified.timestamp_subsec_nanos())
+    }
+
+    /// Fetch `range` with one GET
+    pub fn read_range(&self, range: Range<u64>) -> Result<Bytes> {
+        self.runtime
+            .block_on(self.store.get_range(&self.location, range))
+            .map_err(|e| ExtractorError::io_error(e.into(), &self.url))
+    }
+
+    /// Fetch the whole object into memory
+    pub fn read_all(&self) -> Result<Bytes> {
+        self.read_range(0..self.size())
+    }
+
+    /// Seekable reader that fetches the object with one ranged GET per block
+    pub fn reader(&self) -> ObjectReader {
+        ObjectReader {
+            object: self.clone(),
+            pos: 0,
+            block: Bytes::new(),
+            block_start: 0,
+            block_size: self.config.block_size.max(1),
+        }
+    }
+
+    /// Reader over the object's content for a single sequential pass.
+    /// Objects ending in `.gz` are decompressed.
+    pub fn stream(&self) -> Box<dyn Read + Send> {
+        let reader = GetStream {
+            object: self.clone(),
+            body: None,
+            chunk: Bytes::new(),
+            pos: 0,
+        };
+        if self.location.extension() == Some("gz") {
+            Box::new(MultiGzDecoder::new(reader))
+        } else {
+            Box::new(reader)
+        }
+    }
+}
+
+/// Streaming GET that reconnects from the current offset when the body fails
+struct GetStream {
+    object: RemoteObject,
+    body: Option<BoxStream<'static, object_store::Result<Bytes>>>,
+    chunk: Bytes,
+    pos: u64,
+}
+
+impl GetStream {
+    /// Next chunk of the body, or `None` at the end of the object
+    fn next_chunk(&mut self) -> object_store::Result<Option<Bytes>> {
+        let object = &self.object;
+        if self.body.is_none() {
+            // Resuming must not splice in bytes from a newer version of the object
+            let resume = self.pos > 0;
+            let options = GetOptions {
+                range: resume.then_some(GetRange::Offset(self.pos)),
+                if_match: object.meta.e_tag.clone().filter(|_| resume),
+                ..GetOptions::default()
+            };
+            let result = object.runtime.block_on(object.store.get_opts(&object.location, options))?;
+            self.body = Some(result.into_stream());
+        }
+        let body = self.body.as_mut().expect("body opened above");
+        object.runtime.block_on(body.next()).transpose()
+    }
+}
+
+impl Read for GetStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let config = self.object.config.clone();
+        let mut backoff = Duration::from_millis(config.initial_backoff_ms);
+        let mut retries = 0;
+        while self.chunk.is_empty() {
+            if self.pos >= self.object.size() {
+                return Ok(0);
+            }
+            let chunk = self.next_chunk().map_err(io::Error::from).and_then(|chunk| {
+                chunk.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed early"))
+            });
+            match chunk {
+                Ok(chunk) => self.chunk = chunk,
+                Err(_) if retries < config.max_retries => {
+                    // Drop the broken connection and resume from `pos`
+                    self.body = None;
+                    retries += 1;
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(Duration::from_millis(config.max_backoff_ms));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk.split_to(n));
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// `Read + Seek` over a `RemoteObject`, buffering one ranged GET at a time
+pub struct ObjectReader {
+    object: RemoteObject,
+    pos: u64,
+    block: Bytes,
+    block_start: u64,
+    block_size: u64,
+}
+
+impl ObjectReader {
+    /// Bytes fetched per GET (default: `RemoteConfig::block_size`). Smaller
+    /// blocks suit sparse random access.
+    pub fn with_block_size(mut self, bytes: u64) -> Self {
+        self.block_size = bytes.max(1);
+        self
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.object.size();
+        if buf.is_empty() || self.pos >= size {
+            return Ok(0);
+        }
+
+        let block_end = self.block_start + self.block.len() as u64;
+        if self.pos < self.block_start || self.pos >= block_end {
+            let end = size.min(self.pos + self.block_size);
+            self.block = self
+                .object
+                .runtime
+                .block_on(self.object.store.get_range(&self.object.location, self.pos..end))?;
+            self.block_start = self.pos;
+        }
+
+        let offset = (self.pos - self.block_start) as usize;
+        let n = buf.len().min(self.block.len() - offset);
+        buf[..n].copy_from_slice(&self.block[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ObjectReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.object.size().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the object")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_object_reader_ranges() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        write!(file, "gene,p\nTP53,0.01\nAPOE,0.5\n")?;
+        let url = PathBuf::from(format!("file://{}", file.path().display()));
+        assert!(is_remote(&url) && !is_remote(file.path()));
+
+        let object = RemoteObject::open(&url, &RemoteConfig::default())?;
+        assert_eq!(object.size(), 26);
+        assert_eq!(&object.read_range(7..11)?[..], b"TP53");
+
+        let mut reader = object.reader().with_block_size(4);
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, "gene,p\nTP53,0.01\nAPOE,0.5\n");
+
+        reader.seek(SeekFrom::End(-4))?;
+        let mut tail = String::new();
+        reader.read_to_string(&mut tail)?;
+        assert_eq!(tail, "0.5\n");
+        Ok(())
+    }
+
+    /// Serve `body` over HTTP, cutting the first full GET off halfway
+    #[cfg(feature = "http")]
+    fn flaky_server(body: &'static [u8]) -> String {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut cut = false;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let (mut start, mut end) = (0, body.len());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some(range) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (first, last) = range.trim().split_once('-').unwrap();
+                        start = first.parse().unwrap();
+                        end = last.parse::<usize>().map_or(end, |last| last + 1);
+                    }
+                    request.push_str(&line);
+                }
+                let rest = &body[start..end];
+                let status = if (start, end) != (0, body.len()) {
+                    format!("206 Partial Content\r\nContent-Range: bytes {start}-{}/{}", end - 1, body.len())
+                } else {
+                    "200 OK".to_string()
+                };
+                let head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", rest.len());
+                stream.write_all(head.as_bytes()).unwrap();
+                if request.starts_with("GET") {
+                    if start == 0 && !cut {
+                        cut = true;
+                        stream.write_all(&rest[..rest.len() / 2]).unwrap();
+                        continue;
+                    }
+                    stream.write_all(rest).unwrap();
+                }
+            }
+        });
+        format!("http://{addr}/variants.csv")
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_scan_resumes_after_dropped_connection() -> Result<()> {
+        let body: &[u8] = b"gene,p\nTP53,0.01\nAPOE,0.5\nBRCA1,0.2\n";
+        let url = PathBuf::from(flaky_server(body));
+        assert!(is_remote(&url));
+
+        let object = RemoteObject::open(&url, &RemoteConfig { initial_backoff_ms: 1, ..RemoteConfig::default() })?;
+        assert_eq!(object.size(), body.len() as u64);
+        let mut content = Vec::new();
+        object.stream().read_to_end(&mut content)?;
+        assert_eq!(content, body);
+        assert_eq!(&object.read_range(7..11)?[..], b"TP53");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_gzip_input_and_index() -> Result<()> {
+        use crate::core::BioFilter;
+        use crate::filters::{ColumnFilter, FilterCondition};
+        use crate::index::{FileIndex, RowReader};
+        use crate::Config;
+        use flate2::write::GzEncoder;
+
+        let dir = tempfile::tempdir()?;
+        let csv = "gene,p\nTP53,0.01\nAPOE,0.5\n";
+        let plain = dir.path().join("hits.csv");
+        std::fs::write(&plain, csv)?;
+        let mut gz = GzEncoder::new(File::create(dir.path().join("hits.csv.gz"))?, Default::default());
+        gz.write_all(csv.as_bytes())?;
+        gz.finish()?;
+
+        let output = dir.path().join("out.csv");
+        let url = PathBuf::from(format!("file://{}", dir.path().join("hits.csv.gz").display()));
+        let config = Config { parallel: true, ..Config::default() };
+        let mut filter = BioFilter::new(url, output.clone(), config, None)?;
+        filter.add_filter(Box::new(ColumnFilter::new("gene".to_string(), FilterCondition::Equals("APOE".to_string()))?));
+        let stats = filter.process()?;
+        assert_eq!((stats.rows_processed, stats.rows_matched), (2, 1));
+        assert_eq!(std::fs::read_to_string(&output)?, "gene,p\nAPOE,0.5\n");
+
+        let url = PathBuf::from(format!("file://{}", plain.display()));
+        let index = FileIndex::builder(url, "gene".to_string()).build()?;
+        let row = RowReader::new(&index)?.get_row(2)?.unwrap();
+        assert_eq!(&row[0], b"APOE");
+        Ok(())
+    }
+}
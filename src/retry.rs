@@ -0,0 +1,134 @@
+//! Retries of transient local I/O failures, such as a network filesystem that
+//! stalls or a stale NFS handle, with exponential backoff.
+
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+use crate::{IoRetryConfig, Result};
+
+/// Whether an I/O error may go away if the operation is tried again
+pub(crate) fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+    )
+}
+
+/// Waits between attempts, doubling up to the configured maximum
+struct Backoff<'a> {
+    config: &'a IoRetryConfig,
+    wait: Duration,
+    retries: usize,
+}
+
+impl<'a> Backoff<'a> {
+    fn new(config: &'a IoRetryConfig) -> Self {
+        Self { config, wait: Duration::from_millis(config.initial_backoff_ms), retries: 0 }
+    }
+
+    /// Sleep before the next attempt, or `false` when retries are used up
+    fn wait(&mut self) -> bool {
+        if self.retries >= self.config.max_retries {
+            return false;
+        }
+        self.retries += 1;
+        thread::sleep(self.wait);
+        self.wait = (self.wait * 2).min(Duration::from_millis(self.config.max_backoff_ms));
+        true
+    }
+}
+
+/// Run `op` until it succeeds, fails with an error that isn't retryable, or
+/// the retries run out
+pub(crate) fn retry<T, F: FnMut() -> Result<T>>(config: &IoRetryConfig, mut op: F) -> Result<T> {
+    let mut backoff = Backoff::new(config);
+    loop {
+        match op() {
+            Err(e) if e.is_retryable() && backoff.wait() => {}
+            result => return result,
+        }
+    }
+}
+
+/// Reader that retries reads failing with a transient error. A failed read
+/// consumes nothing, so the retry continues from the same position.
+pub(crate) struct RetryingReader<R> {
+    inner: R,
+    config: IoRetryConfig,
+}
+
+impl<R: Read> RetryingReader<R> {
+    pub(crate) fn new(inner: R, config: IoRetryConfig) -> Self {
+        Self { inner, config }
+    }
+}
+
+impl<R: Read> Read for RetryingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut backoff = Backoff::new(&self.config);
+        loop {
+            match self.inner.read(buf) {
+                Err(e) if is_transient(&e) && backoff.wait() => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ExtractorError;
+
+    /// Fails with `kind` the first `failures` reads
+    struct Flaky {
+        failures: usize,
+        kind: io::ErrorKind,
+        data: &'static [u8],
+    }
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::new(self.kind, "flaky"));
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_retries() -> Result<()> {
+        let config = IoRetryConfig { max_retries: 2, initial_backoff_ms: 1, max_backoff_ms: 1 };
+        let flaky = |failures, kind| Flaky { failures, kind, data: b"rs1,0.01\n" };
+
+        let mut out = String::new();
+        RetryingReader::new(flaky(2, io::ErrorKind::TimedOut), config.clone()).read_to_string(&mut out)?;
+        assert_eq!(out, "rs1,0.01\n");
+        let mut reader = RetryingReader::new(flaky(3, io::ErrorKind::TimedOut), config.clone());
+        assert!(reader.read_to_string(&mut out).is_err());
+        let mut reader = RetryingReader::new(flaky(1, io::ErrorKind::PermissionDenied), config.clone());
+        assert!(reader.read_to_string(&mut out).is_err());
+
+        let mut attempts = 0;
+        let opened = retry(&config, || {
+            attempts += 1;
+            match attempts {
+                1 => Err(ExtractorError::io_error(io::Error::from(io::ErrorKind::StaleNetworkFileHandle), "a.csv")),
+                _ => Ok(attempts),
+            }
+        })?;
+        assert_eq!(opened, 2);
+        let fatal = retry(&config, || -> Result<()> { Err(ExtractorError::config("bad")) });
+        assert!(matches!(fatal, Err(ExtractorError::Config(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+//! Declared column types, checked against a sample of the input when a
+//! `BioFilter` is built so type problems surface before a long run starts.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use csv::ByteRecord;
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::error::ExtractorError;
+use crate::filters::is_default_empty;
+use crate::output::{ExistingOutput, OutputFile, WriteMode};
+use crate::Result;
+
+/// Expected type of a column's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    /// Whole numbers that fit in an `i64`
+    Integer,
+    /// Any number, including integers and scientific notation
+    Float,
+    /// Any UTF-8 text
+    String,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Integer => write!(f, "integer"),
+            FieldType::Float => write!(f, "float"),
+            FieldType::String => write!(f, "string"),
+        }
+    }
+}
+
+/// Columns the input must have, with the type of their values. Empty and NA
+/// values are accepted for every type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schema {
+    columns: Vec<(String, FieldType)>,
+    sample_rows: usize,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schema {
+    /// An empty schema that checks the first 10,000 rows
+    pub fn new() -> Self {
+        Self {
+            columns: Vec::new(),
+            sample_rows: 10_000,
+        }
+    }
+
+    /// Declare a column and its type
+    pub fn col<S: Into<String>>(mut self, name: S, ty: FieldType) -> Self {
+        self.columns.push((name.into(), ty));
+        self
+    }
+
+    /// Check this many rows (0 checks the whole file)
+    pub fn sample_rows(mut self, rows: usize) -> Self {
+        self.sample_rows = rows;
+        self
+    }
+
+    /// Declared columns, in order
+    pub fn columns(&self) -> &[(String, FieldType)] {
+        &self.columns
+    }
+
+    /// Check that every declared column exists and that its values in the
+    /// sampled rows parse as the declared type. Rows are numbered from 1,
+    /// not counting the header.
+    pub(crate) fn validate<R: Read>(
+        &self,
+        reader: &mut csv::Reader<R>,
+        headers: &HashMap<String, usize>,
+    ) -> Result<()> {
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for (name, ty) in &self.columns {
+            let idx = *headers
+                .get(name)
+                .ok_or_else(|| ExtractorError::column_not_in_headers(name, headers))?;
+            if *ty != FieldType::String {
+                columns.push((name, idx, *ty));
+            }
+        }
+
+        let mut record = ByteRecord::new();
+        let mut row = 0;
+        while (self.sample_rows == 0 || row < self.sample_rows as u64)
+            && reader.read_byte_record(&mut record)?
+        {
+            row += 1;
+            for &(name, idx, ty) in &columns {
+                let Some(field) = record.get(idx) else { continue };
+                let value = String::from_utf8_lossy(field);
+                let value = value.trim();
+                let valid = is_default_empty(field)
+                    || match ty {
+                        FieldType::Integer => value.parse::<i64>().is_ok(),
+                        FieldType::Float => value.parse::<f64>().is_ok(),
+                        FieldType::String => true,
+                    };
+                if !valid {
+                    return Err(ExtractorError::InvalidDataFormat {
+                        column: name.clone(),
+                        message: format!("expected {ty}, found {value:?}"),
+                        row: Some(row),
+                        snippet: None,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Column names of an input's header row and a digest of them, kept between
+/// runs to notice when a provider adds, drops or reorders columns
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderFingerprint {
+    /// Column names in file order, trimmed
+    pub columns: Vec<String>,
+    /// Hex xxh64 of the column names
+    pub digest: String,
+}
+
+impl HeaderFingerprint {
+    /// Fingerprint of a header row
+    pub fn of(header: &ByteRecord) -> Self {
+        let columns: Vec<String> = crate::core::header_names(header).collect();
+        let digest = format!("{:016x}", xxh64(columns.join("\0").as_bytes(), 0));
+        Self { columns, digest }
+    }
+
+    /// Load a fingerprint saved with `save`, or `None` if there is no file yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match File::open(path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(file)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ExtractorError::io_error(e, path)),
+        }
+    }
+
+    /// Save the fingerprint as JSON, replacing the file in one step
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let (output, file) = OutputFile::create(path, WriteMode::Atomic { fsync: false }, ExistingOutput::Overwrite)?;
+        serde_json::to_writer_pretty(file, self)?;
+        output.commit()
+    }
+
+    /// How the columns differ from `previous`, or `None` if they are the same
+    pub fn drift_from(&self, previous: &Self) -> Option<String> {
+        if self.digest == previous.digest && self.columns == previous.columns {
+            return None;
+        }
+        let missing = |from: &Self, other: &Self| -> Vec<String> {
+            from.columns.iter().filter(|column| !other.columns.contains(column)).cloned().collect()
+        };
+        let (added, removed) = (missing(self, previous), missing(previous, self));
+        let mut changes = Vec::new();
+        if !added.is_empty() {
+            changes.push(format!("added {added:?}"));
+        }
+        if !removed.is_empty() {
+            changes.push(format!("removed {removed:?}"));
+        }
+        // Order of the columns both headers have
+        let kept = |from: &Self, other: &Self| -> Vec<String> {
+            from.columns.iter().filter(|column| other.columns.contains(column)).cloned().collect()
+        };
+        if kept(self, previous) != kept(previous, self) {
+            changes.push(format!("reordered from {:?} to {:?}", previous.columns, self.columns));
+        }
+        if changes.is_empty() {
+            changes.push(format!("changed from {:?} to {:?}", previous.columns, self.columns));
+        }
+        Some(format!("input columns differ from the last run: {}", changes.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(schema: &Schema, data: &str) -> Result<()> {
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let headers = crate::core::header_map(reader.byte_headers()?);
+        schema.validate(&mut reader, &headers)
+    }
+
+    #[test]
+    fn test_schema_validation() -> Result<()> {
+        let schema = Schema::new()
+            .col("chrom", FieldType::String)
+            .col("pos", FieldType::Integer)
+            .col("p_value", FieldType::Float);
+        check(&schema, "chrom,pos,p_value\nchr1,12345,5e-8\nchrX,NA,\nchrY,n/a,Null\n")?;
+
+        match check(&schema, "chrom,pos,p_value\nchr1,1,0.1\nchr2,2,0.2\nchr3,3.5,0.3\n") {
+            Err(ExtractorError::InvalidDataFormat { column, row, .. }) => {
+                assert_eq!(column, "pos");
+                assert_eq!(row, Some(3));
+            }
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+        // Rows past the sample are not checked
+        check(&schema.clone().sample_rows(2), "chrom,pos,p_value\nchr1,1,0.1\nchr2,2,0.2\nchr3,3.5,0.3\n")?;
+
+        assert!(matches!(
+            check(&schema, "chrom,position,p_value\nchr1,1,0.1\n"),
+            Err(ExtractorError::ColumnNotFound { column, .. }) if column == "pos"
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,528 @@
+//! SQL front-end for single CSV files.
+//! Parses a restricted `SELECT ... FROM ... WHERE ... ORDER BY ... LIMIT`
+//! statement and runs it as column filters plus projection, sorting and a row limit.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+
+use csv::{ByteRecord, ReaderBuilder};
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Query,
+    SelectItem, SetExpr, Statement, TableFactor, UnaryOperator, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::chrom::compare_chrom;
+use crate::core::resolve_columns;
+use crate::error::ExtractorError;
+use crate::filters::{ColumnFilter, Filter, FilterCondition, NumericCondition};
+use crate::{Config, ProcessingStats, Result};
+
+/// A parsed query, ready to run against a CSV file.
+///
+/// Supported: `SELECT *` or a list of columns (optionally `AS` aliases), a
+/// single table in `FROM`, a `WHERE` clause made of `AND`-ed comparisons
+/// (`=`, `!=`, `<`, `<=`, `>`, `>=`, `ABS(col) >`/`>=`/`<`, `IN (...)`,
+/// `BETWEEN`, `LIKE`, `IS [NOT] NULL`), `ORDER BY` columns and `LIMIT`.
+/// Comparisons against values that are not numbers (e.g. `NA`) are false,
+/// as with SQL `NULL`.
+#[derive(Debug)]
+pub struct SqlQuery {
+    table: String,
+    /// `(column, output name)` pairs; `None` selects every column
+    columns: Option<Vec<(String, String)>>,
+    filters: Vec<ColumnFilter>,
+    order_by: Vec<SortKey>,
+    limit: Option<u64>,
+}
+
+#[derive(Debug)]
+struct SortKey {
+    column: String,
+    descending: bool,
+}
+
+fn query_error<S: Into<String>>(message: S) -> ExtractorError {
+    ExtractorError::Query(message.into())
+}
+
+fn unsupported(what: impl std::fmt::Display) -> ExtractorError {
+    query_error(format!("unsupported in queries: {what}"))
+}
+
+impl SqlQuery {
+    /// Parse a single `SELECT` statement
+    pub fn parse(sql: &str) -> Result<Self> {
+        let mut statements =
+            Parser::parse_sql(&GenericDialect {}, sql).map_err(|e| query_error(e.to_string()))?;
+        if statements.len() != 1 {
+            return Err(query_error("expected exactly one statement"));
+        }
+        match statements.remove(0) {
+            Statement::Query(query) => Self::from_query(*query),
+            other => Err(unsupported(other)),
+        }
+    }
+
+    fn from_query(query: Query) -> Result<Self> {
+        if query.with.is_some() || query.offset.is_some() || query.fetch.is_some() {
+            return Err(unsupported("WITH, OFFSET and FETCH"));
+        }
+        let select = match *query.body {
+            SetExpr::Select(select) => select,
+            other => return Err(unsupported(other)),
+        };
+        let grouped = !matches!(&select.group_by, GroupByExpr::Expressions(exprs, _) if exprs.is_empty());
+        if select.distinct.is_some() || grouped || select.having.is_some() {
+            return Err(unsupported("DISTINCT, GROUP BY and HAVING"));
+        }
+
+        let table = match select.from.as_slice() {
+            [from] if from.joins.is_empty() => match &from.relation {
+                TableFactor::Table { name, .. } => {
+                    name.0.iter().map(|part| part.value.as_str()).collect::<Vec<_>>().join(".")
+                }
+                other => return Err(unsupported(other)),
+            },
+            _ => return Err(query_error("FROM must name exactly one table")),
+        };
+
+        let columns = match select.projection.as_slice() {
+            [SelectItem::Wildcard(_)] => None,
+            items => Some(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        SelectItem::UnnamedExpr(expr) => {
+                            let column = column_name(expr)?;
+                            Ok((column.clone(), column))
+                        }
+                        SelectItem::ExprWithAlias { expr, alias } => Ok((column_name(expr)?, alias.value.clone())),
+                        other => Err(unsupported(other)),
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+        };
+
+        let mut filters = Vec::new();
+        if let Some(selection) = &select.selection {
+            compile_predicate(selection, &mut filters)?;
+        }
+
+        let order_by = match &query.order_by {
+            Some(order_by) => order_by
+                .exprs
+                .iter()
+                .map(|key| {
+                    Ok(SortKey {
+                        column: column_name(&key.expr)?,
+                        descending: key.asc == Some(false),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        let limit = match &query.limit {
+            Some(expr) => match literal(expr)? {
+                Literal::Number(n) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+                _ => return Err(query_error(format!("LIMIT must be a non-negative integer, got {expr}"))),
+            },
+            None => None,
+        };
+
+        Ok(Self { table, columns, filters, order_by, limit })
+    }
+
+    /// Table named in `FROM`, e.g. a file path written as a quoted identifier
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Run the query against the file named in `FROM`
+    pub fn run<P: AsRef<Path>>(&self, output: P, config: &Config) -> Result<ProcessingStats> {
+        self.execute(&self.table, output, config)
+    }
+
+    /// Run the query against `input`, whatever `FROM` names.
+    ///
+    /// Output rows are re-serialized with the configured delimiter. Without
+    /// `ORDER BY`, reading stops once `LIMIT` rows are written; with it, only
+    /// the best `LIMIT` rows are kept while the input is scanned.
+    /// `rows_matched` counts the rows written.
+    pub fn execute<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input: P,
+        output: Q,
+        config: &Config,
+    ) -> Result<ProcessingStats> {
+        let start = Instant::now();
+        let (input, output) = (input.as_ref(), output.as_ref());
+        let header_names = match (&config.column_names, config.has_headers) {
+            (_, true) => None,
+            (Some(names), false) => Some(names.iter().collect::<ByteRecord>()),
+            (None, false) => {
+                return Err(query_error("queries refer to columns by name and need a header row or column_names"));
+            }
+        };
+
+        let mut reader = ReaderBuilder::new()
+            .delimiter(config.delimiter)
+            .has_headers(config.has_headers)
+            .flexible(true)
+            .from_path(input)?;
+        let header = match header_names {
+            Some(names) => names,
+            None => reader.byte_headers()?.clone(),
+        };
+        let headers = resolve_columns(&header, &config.column_aliases)?;
+        let index = |name: &str| {
+            headers
+                .get(name)
+                .copied()
+                .ok_or_else(|| ExtractorError::column_not_in_headers(name, &headers))
+        };
+
+        let (projection, names): (Vec<usize>, Vec<&[u8]>) = match &self.columns {
+            None => ((0..header.len()).collect(), header.iter().collect()),
+            Some(columns) => columns
+                .iter()
+                .map(|(column, name)| Ok((index(column)?, name.as_bytes())))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .unzip(),
+        };
+        let sort_keys = self
+            .order_by
+            .iter()
+            .map(|key| Ok((index(&key.column)?, key.descending)))
+            .collect::<Result<Vec<_>>>()?;
+        for filter in &self.filters {
+            index(filter.column_name())?;
+        }
+
+        let file = File::create(output).map_err(|e| ExtractorError::io_error(e, output))?;
+        let mut writer = config.quoting.writer(file, config.delimiter);
+        writer.write_record(&names)?;
+
+        let limit = self.limit.map_or(usize::MAX, |n| n as usize);
+        let compare = |a: &ByteRecord, b: &ByteRecord| compare_rows(a, b, &sort_keys);
+        let mut projected = ByteRecord::new();
+
+        let mut stats = ProcessingStats {
+            input_size: input.metadata().map_err(|e| ExtractorError::io_error(e, input))?.len(),
+            ..ProcessingStats::default()
+        };
+        let mut kept: Vec<ByteRecord> = Vec::new();
+        let mut written = 0;
+        let mut record = ByteRecord::new();
+        while written < limit && reader.read_byte_record(&mut record)? {
+            stats.rows_processed += 1;
+            if !self.matches(&record, &headers)? {
+                continue;
+            }
+            if sort_keys.is_empty() {
+                project(&record, &projection, &mut projected);
+                writer.write_byte_record(&projected)?;
+                written += 1;
+            } else {
+                kept.push(record.clone());
+                // Bound memory for ORDER BY ... LIMIT; the sort is stable, so
+                // ties keep their input order across truncations
+                if kept.len() >= limit.saturating_mul(2).max(4096) {
+                    kept.sort_by(compare);
+                    kept.truncate(limit);
+                }
+            }
+        }
+
+        if !sort_keys.is_empty() {
+            kept.sort_by(compare);
+            kept.truncate(limit);
+            for row in &kept {
+                project(row, &projection, &mut projected);
+                writer.write_byte_record(&projected)?;
+            }
+            written = kept.len();
+        }
+        writer.flush()?;
+
+        stats.rows_matched = written as u64;
+        stats.output_size = output.metadata().map_err(|e| ExtractorError::io_error(e, output))?.len();
+        stats.processing_time_ms = start.elapsed().as_millis() as u64;
+        Ok(stats)
+    }
+
+    /// All `WHERE` filters hold; a value that can't be compared is a non-match
+    fn matches(&self, record: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for filter in &self.filters {
+            match filter.apply(record, headers) {
+                Ok(true) => {}
+                Ok(false) => return Ok(false),
+                Err(err) if err.is_data_error() => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Copy the selected fields of `record` into `out`
+fn project(record: &ByteRecord, projection: &[usize], out: &mut ByteRecord) {
+    out.clear();
+    for &i in projection {
+        out.push_field(record.get(i).unwrap_or_default());
+    }
+}
+
+/// Order two rows by the sort keys: numbers numerically and before text,
+/// chromosome names naturally (chr2 before chr10, chrX after chr22), other
+/// text bytewise
+fn compare_rows(a: &ByteRecord, b: &ByteRecord, keys: &[(usize, bool)]) -> Ordering {
+    let number = |value: &[u8]| std::str::from_utf8(value).ok().and_then(|s| s.trim().parse::<f64>().ok());
+    for &(i, descending) in keys {
+        let (x, y) = (a.get(i).unwrap_or_default(), b.get(i).unwrap_or_default());
+        let order = match (number(x), number(y)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => match (std::str::from_utf8(x), std::str::from_utf8(y)) {
+                (Ok(x), Ok(y)) => compare_chrom(x, y),
+                _ => x.cmp(y),
+            },
+        };
+        let order = if descending { order.reverse() } else { order };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A column reference in a comparison
+enum Operand {
+    Column(String),
+    Abs(String),
+}
+
+enum Literal {
+    Number(f64),
+    Text(String),
+}
+
+fn column_name(expr: &Expr) -> Result<String> {
+    match expr {
+        Expr::Identifier(ident) => Ok(ident.value.clone()),
+        Expr::Nested(inner) => column_name(inner),
+        other => Err(query_error(format!("expected a column name, got {other}"))),
+    }
+}
+
+fn operand(expr: &Expr) -> Option<Operand> {
+    match expr {
+        Expr::Identifier(ident) => Some(Operand::Column(ident.value.clone())),
+        Expr::Nested(inner) => operand(inner),
+        Expr::Function(function) if function.name.to_string().eq_ignore_ascii_case("abs") => {
+            match &function.args {
+                FunctionArguments::List(list) => match list.args.as_slice() {
+                    [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg))] => {
+                        column_name(arg).ok().map(Operand::Abs)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn literal(expr: &Expr) -> Result<Literal> {
+    let number = |text: &str| {
+        text.parse::<f64>()
+            .map_err(|_| query_error(format!("invalid number {text}")))
+    };
+    match expr {
+        Expr::Value(Value::Number(text, _)) => Ok(Literal::Number(number(text)?)),
+        Expr::Value(Value::SingleQuotedString(text)) => Ok(Literal::Text(text.clone())),
+        Expr::UnaryOp { op: UnaryOperator::Minus, expr } => match literal(expr)? {
+            Literal::Number(n) => Ok(Literal::Number(-n)),
+            Literal::Text(_) => Err(unsupported(expr)),
+        },
+        Expr::UnaryOp { op: UnaryOperator::Plus, expr } => literal(expr),
+        Expr::Nested(inner) => literal(inner),
+        other => Err(query_error(format!("expected a literal, got {other}"))),
+    }
+}
+
+fn number_literal(expr: &Expr) -> Result<f64> {
+    match literal(expr)? {
+        Literal::Number(n) => Ok(n),
+        Literal::Text(_) => Err(query_error(format!("expected a number, got {expr}"))),
+    }
+}
+
+fn text_literal(expr: &Expr) -> Result<String> {
+    match literal(expr)? {
+        Literal::Text(text) => Ok(text),
+        // Numbers in IN lists match their text as written, e.g. chromosome 1
+        Literal::Number(_) => Ok(expr.to_string()),
+    }
+}
+
+/// Translate a `LIKE` pattern into an anchored regex
+fn like_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Compile a `WHERE` expression into filters that must all hold
+fn compile_predicate(expr: &Expr, filters: &mut Vec<ColumnFilter>) -> Result<()> {
+    let mut push = |column: String, condition: FilterCondition| -> Result<()> {
+        filters.push(ColumnFilter::new(column, condition)?);
+        Ok(())
+    };
+    match expr {
+        Expr::Nested(inner) => compile_predicate(inner, filters),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            compile_predicate(left, filters)?;
+            compile_predicate(right, filters)
+        }
+        Expr::BinaryOp { left, op, right } => {
+            // Accept `5e-8 > p` as well as `p < 5e-8`
+            let (operand, op, value) = match (operand(left), operand(right)) {
+                (Some(operand), None) => (operand, op.clone(), literal(right)?),
+                (None, Some(operand)) => {
+                    let flipped = match op {
+                        BinaryOperator::Lt => BinaryOperator::Gt,
+                        BinaryOperator::LtEq => BinaryOperator::GtEq,
+                        BinaryOperator::Gt => BinaryOperator::Lt,
+                        BinaryOperator::GtEq => BinaryOperator::LtEq,
+                        other => other.clone(),
+                    };
+                    (operand, flipped, literal(left)?)
+                }
+                _ => return Err(unsupported(expr)),
+            };
+            let (column, condition) = match (operand, &op, value) {
+                (Operand::Column(column), BinaryOperator::Eq, Literal::Text(text)) => {
+                    (column, FilterCondition::Equals(text))
+                }
+                (Operand::Column(column), op, Literal::Number(n)) => {
+                    let condition = match op {
+                        BinaryOperator::Eq => NumericCondition::Equal(n),
+                        BinaryOperator::NotEq => NumericCondition::NotEqual(n),
+                        BinaryOperator::Lt => NumericCondition::LessThan(n),
+                        BinaryOperator::LtEq => NumericCondition::LessOrEqual(n),
+                        BinaryOperator::Gt => NumericCondition::GreaterThan(n),
+                        BinaryOperator::GtEq => NumericCondition::GreaterOrEqual(n),
+                        _ => return Err(unsupported(expr)),
+                    };
+                    (column, FilterCondition::Numeric(condition))
+                }
+                (Operand::Abs(column), op, Literal::Number(n)) => {
+                    let condition = match op {
+                        BinaryOperator::Gt => NumericCondition::AbsGreaterThan(n),
+                        BinaryOperator::GtEq => NumericCondition::AbsGreaterOrEqual(n),
+                        BinaryOperator::Lt => NumericCondition::AbsLessThan(n),
+                        _ => return Err(unsupported(expr)),
+                    };
+                    (column, FilterCondition::Numeric(condition))
+                }
+                _ => return Err(unsupported(expr)),
+            };
+            push(column, condition)
+        }
+        Expr::IsNull(inner) => push(column_name(inner)?, FilterCondition::Empty),
+        Expr::IsNotNull(inner) => push(column_name(inner)?, FilterCondition::NotEmpty),
+        Expr::InList { expr: inner, list, negated: false } => push(
+            column_name(inner)?,
+            FilterCondition::OneOf(list.iter().map(text_literal).collect::<Result<_>>()?),
+        ),
+        Expr::Between { expr: inner, negated: false, low, high } => push(
+            column_name(inner)?,
+            FilterCondition::Numeric(NumericCondition::Between(number_literal(low)?, number_literal(high)?)),
+        ),
+        Expr::Like { negated: false, any: false, expr: inner, pattern, escape_char: None } => {
+            let condition = match literal(pattern)? {
+                Literal::Text(pattern) => FilterCondition::Regex(like_regex(&pattern)),
+                Literal::Number(_) => return Err(unsupported(expr)),
+            };
+            push(column_name(inner)?, condition)
+        }
+        other => Err(unsupported(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn gwas() -> Result<NamedTempFile> {
+        let mut input = NamedTempFile::new()?;
+        write!(
+            input,
+            "variant,gene,chrom,p,beta\n\
+             rs1,TP53,17,1e-9,0.5\n\
+             rs2,APOE,19,0.2,-1.5\n\
+             rs3,BRCA1,17,NA,2.0\n\
+             rs4,TP53,17,3e-8,-0.1\n\
+             rs5,LDLR,19,4e-12,1.1\n"
+        )?;
+        Ok(input)
+    }
+
+    fn run(sql: &str) -> Result<(ProcessingStats, String)> {
+        let input = gwas()?;
+        let output = NamedTempFile::new()?;
+        let stats = SqlQuery::parse(sql)?.execute(input.path(), output.path(), &Config::default())?;
+        Ok((stats, std::fs::read_to_string(output.path())?))
+    }
+
+    #[test]
+    fn test_select_where_order_limit() -> Result<()> {
+        let (stats, output) = run(
+            "SELECT gene, p AS p_value FROM gwas WHERE p < 5e-8 AND chrom IN (17, 19) ORDER BY p LIMIT 2",
+        )?;
+        assert_eq!(output, "gene,p_value\nLDLR,4e-12\nTP53,1e-9\n");
+        assert_eq!((stats.rows_processed, stats.rows_matched), (5, 2));
+
+        let (_, output) = run("SELECT * FROM gwas WHERE ABS(beta) >= 1 ORDER BY chrom DESC, variant")?;
+        assert_eq!(output, "variant,gene,chrom,p,beta\nrs2,APOE,19,0.2,-1.5\nrs5,LDLR,19,4e-12,1.1\nrs3,BRCA1,17,NA,2.0\n");
+
+        // Without ORDER BY the scan stops at the limit
+        let (stats, output) = run("SELECT variant FROM gwas WHERE gene LIKE 'T%' AND 1e-7 > p LIMIT 1")?;
+        assert_eq!(output, "variant\nrs1\n");
+        assert_eq!(stats.rows_processed, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_queries() -> Result<()> {
+        for sql in [
+            "SELECT gene FROM a JOIN b ON a.x = b.x",
+            "SELECT gene FROM gwas WHERE p < 0.1 OR beta > 1",
+            "SELECT COUNT(*) FROM gwas",
+            "SELECT gene FROM gwas GROUP BY gene",
+            "DELETE FROM gwas",
+        ] {
+            assert!(matches!(SqlQuery::parse(sql), Err(ExtractorError::Query(_))), "{sql}");
+        }
+        assert!(matches!(run("SELECT nope FROM gwas"), Err(ExtractorError::ColumnNotFound { .. })));
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+//! Reproducible synthetic datasets for benchmarks, tests and demos.
+//! Generates GWAS summary statistics or gene expression tables of any size from a seed.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::ExtractorError;
+use crate::Result;
+
+/// Column layout of a generated dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    /// GWAS summary statistics: one row per variant
+    Gwas,
+    /// Gene expression results: one row per gene measurement
+    Expression,
+}
+
+impl Schema {
+    /// All columns of the schema in output order
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Schema::Gwas => &[
+                "variant_id", "chromosome", "position", "ref", "alt", "gene",
+                "effect_allele_frequency", "beta", "standard_error", "p_value", "n",
+            ],
+            Schema::Expression => &[
+                "gene_id", "gene_name", "chromosome", "start", "end", "strand", "gene_type",
+                "tpm", "fpkm", "expression", "log2fc", "p_value", "condition",
+            ],
+        }
+    }
+}
+
+/// Builder for a synthetic CSV dataset. The same settings and seed always
+/// produce the same bytes.
+#[derive(Debug, Clone)]
+pub struct SyntheticData {
+    schema: Schema,
+    rows: usize,
+    seed: u64,
+    genes: Option<usize>,
+    chromosomes: usize,
+    conditions: usize,
+    signal_fraction: f64,
+    columns: Option<Vec<String>>,
+    extra_columns: usize,
+    delimiter: u8,
+}
+
+const GENE_TYPES: [&str; 5] = ["protein_coding", "lncRNA", "miRNA", "pseudogene", "other"];
+const BASES: [&str; 4] = ["A", "C", "G", "T"];
+
+impl SyntheticData {
+    /// A dataset with `rows` rows of the given schema
+    pub fn new(schema: Schema, rows: usize) -> Self {
+        Self {
+            schema,
+            rows,
+            seed: 42,
+            genes: None,
+            chromosomes: 23,
+            conditions: 2,
+            signal_fraction: 0.01,
+            columns: None,
+            extra_columns: 0,
+            delimiter: b',',
+        }
+    }
+
+    /// GWAS summary statistics with `rows` variants
+    pub fn gwas(rows: usize) -> Self {
+        Self::new(Schema::Gwas, rows)
+    }
+
+    /// Gene expression results with `rows` rows
+    pub fn expression(rows: usize) -> Self {
+        Self::new(Schema::Expression, rows)
+    }
+
+    /// Seed for the random values (default: 42)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Number of distinct genes (default: one per row for expression data,
+    /// one per 50 variants for GWAS data)
+    pub fn with_genes(mut self, genes: usize) -> Self {
+        self.genes = Some(genes.max(1));
+        self
+    }
+
+    /// Number of distinct chromosomes, counting 23, 24 and 25 as X, Y and MT (default: 23)
+    pub fn with_chromosomes(mut self, chromosomes: usize) -> Self {
+        self.chromosomes = chromosomes.clamp(1, 25);
+        self
+    }
+
+    /// Number of distinct values in the expression `condition` column (default: 2)
+    pub fn with_conditions(mut self, conditions: usize) -> Self {
+        self.conditions = conditions.max(1);
+        self
+    }
+
+    /// Fraction of rows drawn as strong associations with very small p-values (default: 0.01)
+    pub fn with_signal_fraction(mut self, fraction: f64) -> Self {
+        self.signal_fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Only write these schema columns, in this order
+    pub fn with_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Append `count` filler columns (`extra_1`, `extra_2`, ...) to widen the rows
+    pub fn with_extra_columns(mut self, count: usize) -> Self {
+        self.extra_columns = count;
+        self
+    }
+
+    /// Field delimiter (default: ',')
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Header names of the generated file
+    pub fn headers(&self) -> Result<Vec<String>> {
+        let mut headers: Vec<String> = self
+            .selected()?
+            .into_iter()
+            .map(|i| self.schema.columns()[i].to_string())
+            .collect();
+        headers.extend((1..=self.extra_columns).map(|i| format!("extra_{i}")));
+        Ok(headers)
+    }
+
+    /// Write the dataset, header first, to `writer`
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let selected = self.selected()?;
+        let mut writer = BufWriter::new(writer);
+        let delimiter = [self.delimiter];
+
+        for (n, name) in self.headers()?.iter().enumerate() {
+            if n > 0 {
+                writer.write_all(&delimiter)?;
+            }
+            writer.write_all(name.as_bytes())?;
+        }
+        writer.write_all(b"\n")?;
+
+        let mut rng = SplitMix64::new(self.seed);
+        let mut fields = Vec::with_capacity(self.schema.columns().len());
+        for i in 0..self.rows {
+            fields.clear();
+            match self.schema {
+                Schema::Gwas => self.gwas_row(i, &mut rng, &mut fields),
+                Schema::Expression => self.expression_row(i, &mut rng, &mut fields),
+            }
+            for (n, &col) in selected.iter().enumerate() {
+                if n > 0 {
+                    writer.write_all(&delimiter)?;
+                }
+                writer.write_all(fields[col].as_bytes())?;
+            }
+            for n in 0..self.extra_columns {
+                if n > 0 || !selected.is_empty() {
+                    writer.write_all(&delimiter)?;
+                }
+                write!(writer, "{:08x}", rng.next_u64() as u32)?;
+            }
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the dataset to a new file at `path`
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_to(File::create(path)?)
+    }
+
+    /// Generate the dataset in memory
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Indices into the schema columns that will be written
+    fn selected(&self) -> Result<Vec<usize>> {
+        let all = self.schema.columns();
+        match &self.columns {
+            None => Ok((0..all.len()).collect()),
+            Some(columns) => columns
+                .iter()
+                .map(|name| {
+                    all.iter()
+                        .position(|c| c == name)
+                        .ok_or_else(|| ExtractorError::column_not_found(name, all))
+                })
+                .collect(),
+        }
+    }
+
+    fn gene_count(&self) -> usize {
+        match (self.genes, self.schema) {
+            (Some(genes), _) => genes,
+            (None, Schema::Expression) => self.rows.max(1),
+            (None, Schema::Gwas) => self.rows.div_ceil(50).max(1),
+        }
+    }
+
+    fn chromosome(&self, gene: usize) -> String {
+        match gene % self.chromosomes + 1 {
+            23 => "chrX".to_string(),
+            24 => "chrY".to_string(),
+            25 => "chrMT".to_string(),
+            n => format!("chr{n}"),
+        }
+    }
+
+    fn p_value(&self, rng: &mut SplitMix64) -> f64 {
+        if rng.next_f64() < self.signal_fraction {
+            10f64.powf(-(5.0 + 15.0 * rng.next_f64()))
+        } else {
+            rng.next_f64().max(f64::MIN_POSITIVE)
+        }
+    }
+
+    fn gwas_row(&self, i: usize, rng: &mut SplitMix64, fields: &mut Vec<String>) {
+        let gene = i * self.gene_count() / self.rows.max(1);
+        let reference = rng.below(4);
+        let alt = (reference + 1 + rng.below(3)) % 4;
+        let frequency = 0.01 + 0.98 * rng.next_f64();
+        let n = 1000 + rng.below(499_000);
+        let standard_error = 1.0 / (2.0 * frequency * (1.0 - frequency) * n as f64).sqrt();
+        let beta = rng.normal() * standard_error;
+
+        fields.push(format!("rs{}", 1000 + i));
+        fields.push(self.chromosome(gene));
+        fields.push((10_000 + i * 1_000 + rng.below(1_000)).to_string());
+        fields.push(BASES[reference].to_string());
+        fields.push(BASES[alt].to_string());
+        fields.push(format!("GENE_{gene}"));
+        fields.push(format!("{frequency:.4}"));
+        fields.push(format!("{beta:.5}"));
+        fields.push(format!("{standard_error:.5}"));
+        fields.push(format!("{:.3e}", self.p_value(rng)));
+        fields.push(n.to_string());
+    }
+
+    fn expression_row(&self, i: usize, rng: &mut SplitMix64, fields: &mut Vec<String>) {
+        let gene = i % self.gene_count();
+        let start = gene * 1_000 + 1;
+        let tpm = 1000.0 * rng.next_f64().powi(3) + 0.1;
+        let log2fc = 2.0 * rng.normal();
+
+        fields.push(format!("ENSG{gene:011}"));
+        fields.push(format!("GENE_{gene}"));
+        fields.push(self.chromosome(gene));
+        fields.push(start.to_string());
+        fields.push((start + 999).to_string());
+        fields.push(if gene % 2 == 1 { "-" } else { "+" }.to_string());
+        fields.push(GENE_TYPES[gene % GENE_TYPES.len()].to_string());
+        fields.push(format!("{tpm:.2}"));
+        fields.push(format!("{:.2}", tpm * 1.2));
+        fields.push(format!("{:.2}", 100.0 * rng.next_f64()));
+        fields.push(format!("{log2fc:.3}"));
+        fields.push(format!("{:.3e}", self.p_value(rng)));
+        fields.push(format!("condition_{}", i % self.conditions + 1));
+    }
+}
+
+/// Small deterministic generator so output never depends on an external RNG crate
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in 0..n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+
+    /// Standard normal via Box-Muller
+    fn normal(&mut self) -> f64 {
+        let u = self.next_f64().max(f64::MIN_POSITIVE);
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn read(bytes: &[u8]) -> (csv::StringRecord, Vec<csv::StringRecord>) {
+        let mut reader = csv::Reader::from_reader(bytes);
+        let headers = reader.headers().unwrap().clone();
+        let rows = reader.records().map(|r| r.unwrap()).collect();
+        (headers, rows)
+    }
+
+    #[test]
+    fn test_reproducible() -> Result<()> {
+        let data = SyntheticData::gwas(500);
+        assert_eq!(data.to_bytes()?, data.to_bytes()?);
+        assert_ne!(data.to_bytes()?, data.clone().with_seed(7).to_bytes()?);
+
+        let (headers, rows) = read(&data.to_bytes()?);
+        assert_eq!(headers.len(), Schema::Gwas.columns().len());
+        assert_eq!(rows.len(), 500);
+        for row in &rows {
+            let p: f64 = row[9].parse().unwrap();
+            assert!(p > 0.0 && p <= 1.0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cardinality() -> Result<()> {
+        let bytes = SyntheticData::expression(1000)
+            .with_genes(10)
+            .with_chromosomes(3)
+            .with_conditions(4)
+            .to_bytes()?;
+        let (_, rows) = read(&bytes);
+        let distinct = |col: usize| rows.iter().map(|r| r[col].to_string()).collect::<HashSet<_>>().len();
+        assert_eq!(distinct(0), 10);
+        assert_eq!(distinct(2), 3);
+        assert_eq!(distinct(12), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selection() -> Result<()> {
+        let data = SyntheticData::expression(20)
+            .with_columns(["gene_id", "p_value"])
+            .with_extra_columns(2)
+            .with_delimiter(b'\t');
+        let bytes = data.to_bytes()?;
+        let text = String::from_utf8(bytes).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("gene_id\tp_value\textra_1\textra_2"));
+        assert!(lines.all(|line| line.split('\t').count() == 4));
+
+        let unknown = SyntheticData::gwas(1).with_columns(["nope"]);
+        assert!(matches!(unknown.to_bytes(), Err(ExtractorError::ColumnNotFound { .. })));
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! Row-level differences between two releases of a table: rows added,
+//! removed and changed, matched on key columns.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use csv::{ByteRecord, Reader, ReaderBuilder, WriterBuilder};
+use serde::Serialize;
+
+use crate::core::{header_names, resolve_columns};
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::{Config, Result};
+
+/// Compares an old and a new release of a table row by row. Rows are matched
+/// on the key columns, which must be unique in each file, and compared on
+/// the columns both files have, by name (or alias), byte for byte.
+///
+/// The report has the new file's columns, preceded by `change` (`added`,
+/// `removed` or `changed`) and `changed_columns` (`;`-separated). Added and
+/// changed rows come first, in the new file's order, then removed rows, in
+/// the old file's order. Removed rows are empty in columns the new file added.
+pub struct TableDiff {
+    keys: Vec<String>,
+    config: Config,
+    filters: Vec<Box<dyn Filter>>,
+    ignored: Vec<String>,
+}
+
+/// Counts from `TableDiff::run`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffSummary {
+    /// Keys only in the new file
+    pub added: u64,
+    /// Keys only in the old file
+    pub removed: u64,
+    /// Keys in both files whose compared values differ
+    pub changed: u64,
+    /// Keys in both files with the same values
+    pub unchanged: u64,
+    /// Columns only in the new file
+    pub added_columns: Vec<String>,
+    /// Columns only in the old file
+    pub removed_columns: Vec<String>,
+}
+
+/// One side of a diff
+struct Table {
+    reader: Reader<BufReader<File>>,
+    header: ByteRecord,
+    headers: HashMap<String, usize>,
+}
+
+impl TableDiff {
+    /// Match rows on `keys`, e.g. `["MarkerID"]` or `["chrom", "pos", "ref", "alt"]`
+    pub fn new<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            config: Config::default(),
+            filters: Vec::new(),
+            ignored: Vec::new(),
+        }
+    }
+
+    /// Read both files with `config`'s delimiter, header setting and column aliases
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Only compare rows that pass `filter`. It is applied to each file on
+    /// its own, so a row that stops passing counts as removed.
+    pub fn filter(mut self, filter: Box<dyn Filter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Leave these columns out of the comparison, e.g. a release date
+    pub fn ignore_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored.extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Compare `old` with `new` and write the differences to `output`
+    pub fn run(&self, old: &Path, new: &Path, output: &Path) -> Result<DiffSummary> {
+        let mut old = self.open(old)?;
+        let mut new = self.open(new)?;
+        let old_keys = self.key_indices(&old.headers)?;
+        let new_keys = self.key_indices(&new.headers)?;
+
+        // Where each new column is in the old file
+        let counterparts: Vec<Option<usize>> = header_names(&new.header)
+            .enumerate()
+            .map(|(idx, name)| {
+                old.headers.get(&name).copied().or_else(|| {
+                    self.config
+                        .column_aliases
+                        .keys()
+                        .find(|canonical| new.headers.get(*canonical) == Some(&idx))
+                        .and_then(|canonical| old.headers.get(canonical).copied())
+                })
+            })
+            .collect();
+        let ignored: Vec<usize> = self.ignored.iter().filter_map(|column| new.headers.get(column).copied()).collect();
+        let mut summary = DiffSummary {
+            added_columns: header_names(&new.header)
+                .zip(&counterparts)
+                .filter(|(_, old_idx)| old_idx.is_none())
+                .map(|(name, _)| name)
+                .collect(),
+            removed_columns: header_names(&old.header)
+                .enumerate()
+                .filter(|(idx, _)| !counterparts.contains(&Some(*idx)))
+                .map(|(_, name)| name)
+                .collect(),
+            ..DiffSummary::default()
+        };
+
+        // The old rows that pass the filters, by key, in file order
+        let mut old_rows: Vec<(ByteRecord, bool)> = Vec::new();
+        let mut by_key: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut record = ByteRecord::new();
+        while old.reader.read_byte_record(&mut record)? {
+            if self.passes(&record, &old.headers)? {
+                let key = row_key(&record, &old_keys);
+                if by_key.insert(key, old_rows.len()).is_some() {
+                    return Err(self.duplicate_key(&record, &old_keys));
+                }
+                old_rows.push((record.clone(), false));
+            }
+        }
+
+        let file = File::create(output).map_err(|e| ExtractorError::io_error(e, output))?;
+        let mut writer = WriterBuilder::new().delimiter(self.config.delimiter).from_writer(file);
+        let mut report_header = ByteRecord::from(vec!["change", "changed_columns"]);
+        report_header.extend(header_names(&new.header));
+        writer.write_byte_record(&report_header)?;
+        let write = |writer: &mut csv::Writer<File>, change: &str, changed: &str, values: &mut dyn Iterator<Item = &[u8]>| {
+            let mut row = ByteRecord::from(vec![change, changed]);
+            row.extend(values);
+            writer.write_byte_record(&row)
+        };
+
+        let mut new_seen = HashSet::new();
+        while new.reader.read_byte_record(&mut record)? {
+            if !self.passes(&record, &new.headers)? {
+                continue;
+            }
+            let key = row_key(&record, &new_keys);
+            let Some(&old_idx) = by_key.get(&key) else {
+                if !new_seen.insert(key) {
+                    return Err(self.duplicate_key(&record, &new_keys));
+                }
+                summary.added += 1;
+                write(&mut writer, "added", "", &mut record.iter())?;
+                continue;
+            };
+            let (old_row, seen) = &mut old_rows[old_idx];
+            if std::mem::replace(seen, true) {
+                return Err(self.duplicate_key(&record, &new_keys));
+            }
+            let changed: Vec<String> = header_names(&new.header)
+                .enumerate()
+                .filter(|(idx, _)| !ignored.contains(idx))
+                .filter_map(|(idx, name)| {
+                    let old_value = old_row.get(counterparts[idx]?).unwrap_or_default();
+                    (record.get(idx).unwrap_or_default() != old_value).then_some(name)
+                })
+                .collect();
+            if changed.is_empty() {
+                summary.unchanged += 1;
+            } else {
+                summary.changed += 1;
+                write(&mut writer, "changed", &changed.join(";"), &mut record.iter())?;
+            }
+        }
+
+        for (old_row, _) in old_rows.iter().filter(|(_, seen)| !seen) {
+            summary.removed += 1;
+            let mut values = counterparts
+                .iter()
+                .map(|old_idx| old_idx.and_then(|idx| old_row.get(idx)).unwrap_or_default());
+            write(&mut writer, "removed", "", &mut values)?;
+        }
+        writer.flush().map_err(|e| ExtractorError::io_error(e, output))?;
+        Ok(summary)
+    }
+
+    fn open(&self, path: &Path) -> Result<Table> {
+        let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .flexible(true)
+            .from_reader(BufReader::new(file));
+        let header = match &self.config.column_names {
+            Some(names) => names.iter().collect(),
+            None => reader.byte_headers()?.clone(),
+        };
+        let headers = resolve_columns(&header, &self.config.column_aliases)?;
+        Ok(Table { reader, header, headers })
+    }
+
+    /// Indices of the key columns in a file
+    fn key_indices(&self, headers: &HashMap<String, usize>) -> Result<Vec<usize>> {
+        self.keys
+            .iter()
+            .map(|key| {
+                headers
+                    .get(key)
+                    .copied()
+                    .ok_or_else(|| ExtractorError::column_not_in_headers(key, headers))
+            })
+            .collect()
+    }
+
+    fn passes(&self, record: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for filter in &self.filters {
+            if !filter.apply(record, headers)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn duplicate_key(&self, record: &ByteRecord, keys: &[usize]) -> ExtractorError {
+        let values: Vec<String> =
+            keys.iter().map(|&idx| String::from_utf8_lossy(record.get(idx).unwrap_or_default()).into_owned()).collect();
+        ExtractorError::InvalidDataFormat {
+            column: self.keys.join(","),
+            message: format!("key '{}' appears more than once", values.join(",")),
+            row: record.position().map(|position| position.line().saturating_sub(1)),
+            snippet: None,
+        }
+    }
+}
+
+/// The key columns' values, separated by the ASCII unit separator
+fn row_key(record: &ByteRecord, keys: &[usize]) -> Vec<u8> {
+    let mut key = Vec::new();
+    for (i, &idx) in keys.iter().enumerate() {
+        if i > 0 {
+            key.push(0x1f);
+        }
+        key.extend_from_slice(record.get(idx).unwrap_or_default());
+    }
+    key
+}
+
+/// Compare two releases of a table on one key column, writing the report to `output`
+pub fn diff(old: &Path, new: &Path, key: &str, output: &Path) -> Result<DiffSummary> {
+    TableDiff::new([key]).run(old, new, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{ColumnFilter, FilterCondition, NumericCondition};
+
+    #[test]
+    fn test_table_diff() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (old, new, report) = (dir.path().join("old.csv"), dir.path().join("new.csv"), dir.path().join("diff.csv"));
+        std::fs::write(&old, "MarkerID,P,beta,release\nrs1,0.01,0.5,2024\nrs2,0.02,0.1,2024\nrs3,0.03,0.2,2024\nrs9,0.9,0.0,2024\n")?;
+        std::fs::write(&new, "MarkerID,beta,pval,n,release\nrs4,0.3,0.04,100,2025\nrs3,0.25,0.03,100,2025\nrs2,0.1,0.02,100,2025\n")?;
+
+        let summary = TableDiff::new(["MarkerID"])
+            .with_config(Config {
+                column_aliases: [("p".to_string(), vec!["P".to_string(), "pval".to_string()])].into_iter().collect(),
+                ..Config::default()
+            })
+            .filter(Box::new(ColumnFilter::new(
+                "p".to_string(),
+                FilterCondition::Numeric(NumericCondition::LessThan(0.5)),
+            )?))
+            .ignore_columns(["release"])
+            .run(&old, &new, &report)?;
+
+        assert_eq!((summary.added, summary.removed, summary.changed, summary.unchanged), (1, 1, 1, 1));
+        assert_eq!(summary.added_columns, ["n"]);
+        assert!(summary.removed_columns.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&report)?,
+            "change,changed_columns,MarkerID,beta,pval,n,release\n\
+             added,,rs4,0.3,0.04,100,2025\n\
+             changed,beta,rs3,0.25,0.03,100,2025\n\
+             removed,,rs1,0.5,0.01,,2024\n"
+        );
+
+        std::fs::write(&new, "MarkerID\nrs1\nrs1\n")?;
+        assert!(diff(&old, &new, "MarkerID", &report).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+//! Interval algebra on BED region sets: merge, intersect and subtract, for
+//! preparing the regions a filtering run is restricted to.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use crate::chrom::{compare_chrom, ChromKey};
+use crate::error::ExtractorError;
+use crate::formats::bed::{BedReader, BedRecord};
+use crate::Result;
+
+/// Sorted `(start, end)` intervals of one chromosome, 0-based and end-exclusive
+type Intervals = Vec<(u64, u64)>;
+
+/// Regions grouped by chromosome, in natural chromosome order, each
+/// chromosome's intervals sorted and neither overlapping nor touching.
+/// Chromosomes match across sets however they are written (`chr7`, `7`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionSet {
+    chroms: Vec<(String, Intervals)>,
+}
+
+impl RegionSet {
+    /// Build a set from BED records, merging overlapping and adjacent ones.
+    /// Names and extra columns are dropped, as are empty intervals.
+    pub fn from_records<I: IntoIterator<Item = BedRecord>>(records: I) -> Self {
+        let records: Vec<BedRecord> = records.into_iter().collect();
+        let mut groups: HashMap<ChromKey, (&str, Intervals)> = HashMap::new();
+        for record in records.iter().filter(|record| !record.is_empty()) {
+            groups
+                .entry(ChromKey::parse(&record.chrom))
+                .or_insert_with(|| (record.chrom.as_str(), Vec::new()))
+                .1
+                .push((record.start, record.end));
+        }
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by_key(|(key, _)| *key);
+        let chroms = groups
+            .into_iter()
+            .map(|(_, (name, mut intervals))| {
+                intervals.sort_unstable();
+                (name.to_string(), merge_sorted(intervals, 0))
+            })
+            .collect();
+        Self { chroms }
+    }
+
+    /// Read a BED file
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::from_records(BedReader::from_path(path)?.collect::<Result<Vec<_>>>()?))
+    }
+
+    /// Write the set as a three-column BED file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = String::new();
+        for (chrom, start, end) in self.iter() {
+            let _ = writeln!(text, "{chrom}\t{start}\t{end}");
+        }
+        std::fs::write(path, text).map_err(|e| ExtractorError::io_error(e, path))
+    }
+
+    /// Intervals as `(chrom, start, end)`, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64, u64)> {
+        self.chroms
+            .iter()
+            .flat_map(|(chrom, intervals)| intervals.iter().map(move |&(start, end)| (chrom.as_str(), start, end)))
+    }
+
+    /// Number of intervals
+    pub fn len(&self) -> usize {
+        self.chroms.iter().map(|(_, intervals)| intervals.len()).sum()
+    }
+
+    /// Whether the set has no intervals
+    pub fn is_empty(&self) -> bool {
+        self.chroms.is_empty()
+    }
+
+    /// Number of bases covered
+    pub fn total_bases(&self) -> u64 {
+        self.iter().map(|(_, start, end)| end - start).sum()
+    }
+
+    /// Also merge intervals separated by at most `gap` bases
+    pub fn merge_within(&self, gap: u64) -> Self {
+        self.map(|_, intervals| merge_sorted(intervals.to_vec(), gap))
+    }
+
+    /// Bases covered by both sets
+    pub fn intersect(&self, other: &RegionSet) -> Self {
+        self.map(|chrom, intervals| {
+            let Some(others) = other.get(chrom) else { return Vec::new() };
+            let (mut i, mut j, mut out) = (0, 0, Vec::new());
+            while i < intervals.len() && j < others.len() {
+                let ((a_start, a_end), (b_start, b_end)) = (intervals[i], others[j]);
+                let (start, end) = (a_start.max(b_start), a_end.min(b_end));
+                if start < end {
+                    out.push((start, end));
+                }
+                if a_end < b_end {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+            out
+        })
+    }
+
+    /// Bases of this set not covered by `other`, e.g. with blacklisted
+    /// regions removed
+    pub fn subtract(&self, other: &RegionSet) -> Self {
+        self.map(|chrom, intervals| {
+            let Some(others) = other.get(chrom) else { return intervals.to_vec() };
+            let mut out = Vec::new();
+            let mut j = 0;
+            for &(start, end) in intervals {
+                // Skip removed intervals that end before this one starts
+                while j < others.len() && others[j].1 <= start {
+                    j += 1;
+                }
+                let mut cursor = start;
+                for &(cut_start, cut_end) in others[j..].iter().take_while(|(cut_start, _)| *cut_start < end) {
+                    if cut_start > cursor {
+                        out.push((cursor, cut_start));
+                    }
+                    cursor = cursor.max(cut_end);
+                }
+                if cursor < end {
+                    out.push((cursor, end));
+                }
+            }
+            out
+        })
+    }
+
+    /// Intervals of `chrom`, however it is written
+    fn get(&self, chrom: &str) -> Option<&[(u64, u64)]> {
+        self.chroms
+            .binary_search_by(|(name, _)| compare_chrom(name, chrom))
+            .ok()
+            .map(|i| self.chroms[i].1.as_slice())
+    }
+
+    /// Apply `op` to each chromosome's intervals, dropping chromosomes left empty
+    fn map(&self, mut op: impl FnMut(&str, &[(u64, u64)]) -> Intervals) -> Self {
+        let chroms = self
+            .chroms
+            .iter()
+            .map(|(chrom, intervals)| (chrom.clone(), op(chrom, intervals)))
+            .filter(|(_, intervals)| !intervals.is_empty())
+            .collect();
+        Self { chroms }
+    }
+}
+
+/// Merge sorted intervals separated by at most `gap` bases
+fn merge_sorted(intervals: Intervals, gap: u64) -> Intervals {
+    let mut merged: Intervals = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(gap) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Merge the intervals of a BED file that overlap or lie within `gap`
+/// bases of each other, writing the result to `output`
+pub fn merge(input: &Path, output: &Path, gap: u64) -> Result<RegionSet> {
+    let merged = RegionSet::load(input)?.merge_within(gap);
+    merged.save(output)?;
+    Ok(merged)
+}
+
+/// Write the bases covered by both BED files to `output`
+pub fn intersect(a: &Path, b: &Path, output: &Path) -> Result<RegionSet> {
+    let common = RegionSet::load(a)?.intersect(&RegionSet::load(b)?);
+    common.save(output)?;
+    Ok(common)
+}
+
+/// Write the regions of `input` outside those of `blacklist` to `output`
+pub fn subtract(input: &Path, blacklist: &Path, output: &Path) -> Result<RegionSet> {
+    let kept = RegionSet::load(input)?.subtract(&RegionSet::load(blacklist)?);
+    kept.save(output)?;
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_region_algebra() -> Result<()> {
+        let dir = TempDir::new()?;
+        let (peaks, blacklist, output) = (dir.path().join("peaks.bed"), dir.path().join("bl.bed"), dir.path().join("out.bed"));
+        std::fs::write(&peaks, "chr2\t0\t10\nchr1\t50\t60\nchr1\t10\t20\nchr1\t15\t30\nchr1\t30\t35\nchr10\t5\t6\n")?;
+        std::fs::write(&blacklist, "1\t12\t14\n1\t25\t55\nchrX\t0\t100\n")?;
+
+        let set = merge(&peaks, &output, 0)?;
+        assert_eq!(std::fs::read_to_string(&output)?, "chr1\t10\t35\nchr1\t50\t60\nchr2\t0\t10\nchr10\t5\t6\n");
+        assert_eq!((set.len(), set.total_bases()), (4, 46));
+        assert_eq!(set.merge_within(15).len(), 3);
+
+        let kept = subtract(&peaks, &blacklist, &output)?;
+        let kept: Vec<_> = kept.iter().collect();
+        assert_eq!(kept, [("chr1", 10, 12), ("chr1", 14, 25), ("chr1", 55, 60), ("chr2", 0, 10), ("chr10", 5, 6)]);
+
+        let common = intersect(&peaks, &blacklist, &output)?;
+        assert_eq!(common.iter().collect::<Vec<_>>(), [("chr1", 12, 14), ("chr1", 25, 35), ("chr1", 50, 55)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+//! File-level tools built on the format readers
+
+pub mod concat;
+pub mod diff;
+pub mod intervals;
+pub mod reshape;
@@ -0,0 +1,247 @@
+//! Reshaping between wide and long layouts, e.g. per-population allele
+//! frequency columns (`AF_EUR`, `AF_AFR`, ...) to `population` and `af`
+//! columns, and back.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::transform::wildcard_captures;
+use crate::{Config, Result};
+
+/// Values of the columns that identify a wide row
+type RowId = Vec<Vec<u8>>;
+
+/// Which way to reshape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Longer,
+    Wider,
+}
+
+/// Reshapes a table between a wide layout, with one column per name such as
+/// `AF_EUR`, and a long one, with a names column and a values column. The
+/// wide columns are given by a pattern with one `*` standing for the name.
+pub struct Reshape {
+    direction: Direction,
+    pattern: String,
+    names_column: String,
+    values_column: String,
+    config: Config,
+    missing_value: String,
+    skip_empty: bool,
+}
+
+impl Reshape {
+    /// Turn each column matching `pattern` into a row of its own, keeping
+    /// the other columns. `AF_*` turns `rs1,0.1,0.2` under `rsid,AF_EUR,AF_AFR`
+    /// into `rs1,EUR,0.1` and `rs1,AFR,0.2` under `rsid,name,value`.
+    pub fn longer(pattern: &str) -> Self {
+        Self::new(Direction::Longer, pattern)
+    }
+
+    /// Turn rows that share every column but the names and values columns
+    /// into one row, with a column per name named by `pattern`. Rows come
+    /// out in the order their first row was read, and new columns in the
+    /// order their names were first seen.
+    pub fn wider(pattern: &str) -> Self {
+        Self::new(Direction::Wider, pattern)
+    }
+
+    fn new(direction: Direction, pattern: &str) -> Self {
+        Self {
+            direction,
+            pattern: pattern.to_string(),
+            names_column: "name".to_string(),
+            values_column: "value".to_string(),
+            config: Config::default(),
+            missing_value: String::new(),
+            skip_empty: false,
+        }
+    }
+
+    /// Name the long layout's column of names (default: `name`)
+    pub fn names_column(mut self, column: &str) -> Self {
+        self.names_column = column.to_string();
+        self
+    }
+
+    /// Name the long layout's column of values (default: `value`)
+    pub fn values_column(mut self, column: &str) -> Self {
+        self.values_column = column.to_string();
+        self
+    }
+
+    /// Read and write with `config`'s delimiter
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the value of wide cells with no long row (default: empty)
+    pub fn missing_value(mut self, value: &str) -> Self {
+        self.missing_value = value.to_string();
+        self
+    }
+
+    /// Leave out long rows whose value is empty (default: false)
+    pub fn skip_empty(mut self, skip: bool) -> Self {
+        self.skip_empty = skip;
+        self
+    }
+
+    /// Reshape `input` into `output`, returning the number of rows written
+    pub fn run(&self, input: &Path, output: &Path) -> Result<u64> {
+        if self.pattern.matches('*').count() != 1 {
+            return Err(ExtractorError::Config(format!(
+                "Reshape pattern '{}' must contain exactly one '*'",
+                self.pattern
+            )));
+        }
+        let file = File::open(input).map_err(|e| ExtractorError::io_error(e, input))?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.config.delimiter)
+            .flexible(true)
+            .from_reader(BufReader::new(file));
+        let names: Vec<String> = header_names(reader.byte_headers()?).collect();
+        let file = File::create(output).map_err(|e| ExtractorError::io_error(e, output))?;
+        let mut writer = WriterBuilder::new().delimiter(self.config.delimiter).from_writer(file);
+
+        let rows = match self.direction {
+            Direction::Longer => self.to_longer(&mut reader, &names, &mut writer)?,
+            Direction::Wider => self.to_wider(&mut reader, &names, &mut writer)?,
+        };
+        writer.flush().map_err(|e| ExtractorError::io_error(e, output))?;
+        Ok(rows)
+    }
+
+    fn to_longer<R: std::io::Read, W: std::io::Write>(
+        &self,
+        reader: &mut csv::Reader<R>,
+        names: &[String],
+        writer: &mut csv::Writer<W>,
+    ) -> Result<u64> {
+        // Wide columns with their names, and the columns kept on every row
+        let wide: Vec<(usize, String)> = names
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, column)| Some((idx, wildcard_captures(&self.pattern, column)?.first()?.to_string())))
+            .collect();
+        if wide.is_empty() {
+            return Err(ExtractorError::Config(format!("No column matches the reshape pattern '{}'", self.pattern)));
+        }
+        let kept: Vec<usize> = (0..names.len()).filter(|idx| !wide.iter().any(|(wide_idx, _)| wide_idx == idx)).collect();
+
+        let mut header: ByteRecord = kept.iter().map(|&idx| names[idx].as_str()).collect();
+        header.push_field(self.names_column.as_bytes());
+        header.push_field(self.values_column.as_bytes());
+        writer.write_byte_record(&header)?;
+
+        let mut written = 0;
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            for (idx, name) in &wide {
+                let value = record.get(*idx).unwrap_or_default();
+                if self.skip_empty && value.trim_ascii().is_empty() {
+                    continue;
+                }
+                let mut row: ByteRecord = kept.iter().map(|&idx| record.get(idx).unwrap_or_default()).collect();
+                row.push_field(name.as_bytes());
+                row.push_field(value);
+                writer.write_byte_record(&row)?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+
+    fn to_wider<R: std::io::Read, W: std::io::Write>(
+        &self,
+        reader: &mut csv::Reader<R>,
+        names: &[String],
+        writer: &mut csv::Writer<W>,
+    ) -> Result<u64> {
+        let position = |column: &str| {
+            names
+                .iter()
+                .position(|name| name == column)
+                .ok_or_else(|| ExtractorError::column_not_found(column, names))
+        };
+        let (names_idx, values_idx) = (position(&self.names_column)?, position(&self.values_column)?);
+        let kept: Vec<usize> = (0..names.len()).filter(|&idx| idx != names_idx && idx != values_idx).collect();
+
+        // Rows by the values of the kept columns, each with its value per name
+        let mut rows: Vec<(RowId, HashMap<String, Vec<u8>>)> = Vec::new();
+        let mut by_id: HashMap<RowId, usize> = HashMap::new();
+        let mut wide_names: Vec<String> = Vec::new();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let id: RowId = kept.iter().map(|&idx| record.get(idx).unwrap_or_default().to_vec()).collect();
+            let name = String::from_utf8_lossy(record.get(names_idx).unwrap_or_default()).trim().to_string();
+            let value = record.get(values_idx).unwrap_or_default().to_vec();
+            if !wide_names.contains(&name) {
+                wide_names.push(name.clone());
+            }
+            let row = *by_id.entry(id.clone()).or_insert_with(|| {
+                rows.push((id, HashMap::new()));
+                rows.len() - 1
+            });
+            if rows[row].1.insert(name.clone(), value).is_some() {
+                return Err(ExtractorError::InvalidDataFormat {
+                    column: self.names_column.clone(),
+                    message: format!("'{name}' appears more than once for the same row"),
+                    row: record.position().map(|position| position.line().saturating_sub(1)),
+                    snippet: None,
+                });
+            }
+        }
+
+        let mut header: ByteRecord = kept.iter().map(|&idx| names[idx].as_str()).collect();
+        for name in &wide_names {
+            header.push_field(self.pattern.replacen('*', name, 1).as_bytes());
+        }
+        writer.write_byte_record(&header)?;
+        for (id, values) in &rows {
+            let mut row: ByteRecord = id.iter().collect();
+            for name in &wide_names {
+                row.push_field(values.get(name).map_or(self.missing_value.as_bytes(), Vec::as_slice));
+            }
+            writer.write_byte_record(&row)?;
+        }
+        Ok(rows.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reshape_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (wide, long, back) = (dir.path().join("wide.csv"), dir.path().join("long.csv"), dir.path().join("back.csv"));
+        std::fs::write(&wide, "rsid,AF_EUR,gene,AF_AFR\nrs1,0.1,APOE,0.2\nrs2,0.3,TP53,\n")?;
+
+        let rows = Reshape::longer("AF_*").names_column("population").values_column("af").run(&wide, &long)?;
+        assert_eq!(rows, 4);
+        assert_eq!(
+            std::fs::read_to_string(&long)?,
+            "rsid,gene,population,af\nrs1,APOE,EUR,0.1\nrs1,APOE,AFR,0.2\nrs2,TP53,EUR,0.3\nrs2,TP53,AFR,\n"
+        );
+
+        let rows = Reshape::longer("AF_*").skip_empty(true).run(&wide, &long)?;
+        assert_eq!(rows, 3);
+
+        let rows = Reshape::wider("AF_*").missing_value("NA").run(&long, &back)?;
+        assert_eq!(rows, 2);
+        assert_eq!(std::fs::read_to_string(&back)?, "rsid,gene,AF_EUR,AF_AFR\nrs1,APOE,0.1,0.2\nrs2,TP53,0.3,NA\n");
+
+        assert!(Reshape::longer("AF").run(&wide, &long).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+//! Row transforms: rewrite matched rows before they are written, e.g. to
+//! convert coordinates between genome builds.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use csv::ByteRecord;
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::core::resolve_columns;
+use crate::error::ExtractorError;
+use crate::{FilenameColumns, Result};
+
+/// Rewrites matched rows. Transforms run in the order they were added, after
+/// every filter has matched; a rewritten row is re-serialized on output.
+pub trait RowTransform: Send + Sync {
+    /// The output header for the input `header`, or `None` to keep it
+    fn header(&self, _header: &ByteRecord) -> Option<ByteRecord> {
+        None
+    }
+
+    /// Rewrite `row` in place; return false to drop it from the output
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool>;
+
+    /// Get a description of the transform
+    fn description(&self) -> String;
+}
+
+/// Copy `record` with the field at `idx` replaced by `value`
+pub(crate) fn replace_field(record: &ByteRecord, idx: usize, value: &[u8]) -> ByteRecord {
+    record
+        .iter()
+        .enumerate()
+        .map(|(i, field)| if i == idx { value } else { field })
+        .collect()
+}
+
+/// Appends columns with fixed values to every row, skipping columns the
+/// input already has
+pub(crate) struct ConstantColumns {
+    columns: Vec<(String, String)>,
+    /// `Config::column_aliases`, so a column present under an alias isn't added
+    aliases: HashMap<String, Vec<String>>,
+}
+
+impl ConstantColumns {
+    /// The columns the first of `entries` whose pattern matches the file
+    /// name of `path` gives it, or `None` if none matches
+    pub(crate) fn for_input(
+        entries: &[FilenameColumns],
+        path: &Path,
+        aliases: &HashMap<String, Vec<String>>,
+    ) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy();
+        entries.iter().find_map(|entry| {
+            let captures = wildcard_captures(&entry.pattern, &name)?;
+            let columns = entry
+                .columns
+                .iter()
+                .map(|(column, value)| {
+                    let value = captures
+                        .iter()
+                        .enumerate()
+                        .fold(value.clone(), |value, (i, capture)| value.replace(&format!("{{{}}}", i + 1), capture));
+                    (column.clone(), value)
+                })
+                .collect();
+            Some(Self { columns, aliases: aliases.clone() })
+        })
+    }
+}
+
+impl RowTransform for ConstantColumns {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let present = resolve_columns(header, &self.aliases).unwrap_or_default();
+        let mut header = header.clone();
+        for (column, _) in self.columns.iter().filter(|(column, _)| !present.contains_key(column)) {
+            header.push_field(column.as_bytes());
+        }
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        for (_, value) in self.columns.iter().filter(|(column, _)| !headers.contains_key(column)) {
+            row.push_field(value.as_bytes());
+        }
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|(column, value)| format!("{column}={value}")).collect();
+        format!("append {}", columns.join(", "))
+    }
+}
+
+/// Appends a stable hash of the named columns to every row: the xxh64 of
+/// their values, in the given order, as 16 hex digits. The hash depends only
+/// on those values, not on the other columns or where the columns are in the
+/// file, so it identifies a row across runs and data releases.
+pub struct RowHash {
+    columns: Vec<String>,
+    output_column: String,
+}
+
+impl RowHash {
+    /// Hash `columns` into a `row_hash` column
+    pub fn new<I, S>(columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { columns: columns.into_iter().map(Into::into).collect(), output_column: "row_hash".to_string() }
+    }
+
+    /// Name the appended column (default: `row_hash`)
+    pub fn named(mut self, output_column: &str) -> Self {
+        self.output_column = output_column.to_string();
+        self
+    }
+}
+
+impl RowTransform for RowHash {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.output_column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let mut hasher = Xxh64::new(0);
+        for column in &self.columns {
+            let idx = *headers
+                .get(column)
+                .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))?;
+            let value = row.get(idx).unwrap_or_default();
+            // Length-prefixed, so ("ab", "c") and ("a", "bc") differ
+            hasher.update(&(value.len() as u64).to_le_bytes());
+            hasher.update(value);
+        }
+        row.push_field(format!("{:016x}", hasher.digest()).as_bytes());
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("append hash of {} as {}", self.columns.join(", "), self.output_column)
+    }
+}
+
+/// The text each `*` of `pattern` matches in `text`, or `None` if it doesn't
+/// match. Each `*` takes the shortest text it can, so `*_*` splits
+/// `LDL_GLGC_2013` at the first underscore.
+pub(crate) fn wildcard_captures<'t>(pattern: &str, text: &'t str) -> Option<Vec<&'t str>> {
+    let Some((literal, rest)) = pattern.split_once('*') else {
+        return (pattern == text).then(Vec::new);
+    };
+    let text = text.strip_prefix(literal)?;
+    text.char_indices().map(|(i, _)| i).chain([text.len()]).find_map(|end| {
+        let mut captures = wildcard_captures(rest, &text[end..])?;
+        captures.insert(0, &text[..end]);
+        Some(captures)
+    })
+}
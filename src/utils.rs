@@ -2,13 +2,104 @@
 //! This module provides common functionality used across the library.
 
 use std::fs::File;
+use std::hash::Hasher;
 use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "parallel")]
 use memmap2::{Mmap, MmapOptions};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh64::Xxh64;
 use crate::error::ExtractorError;
 use crate::Result;
 
+/// Thread pool that parallel work runs on
+#[derive(Debug, Clone, Default)]
+pub enum ThreadPoolSource {
+    /// Build a new pool for each run, sized by `num_threads` (default)
+    #[default]
+    Dedicated,
+    /// Use rayon's global pool, or the pool the caller is already running in
+    Global,
+    /// Use a caller-owned pool, e.g. one shared by every extraction in a server
+    #[cfg(feature = "parallel")]
+    Shared(Arc<rayon::ThreadPool>),
+}
+
+#[cfg(feature = "parallel")]
+impl ThreadPoolSource {
+    /// Run `op` on the selected pool. `num_threads` only sizes a dedicated pool.
+    pub fn install<R, F>(&self, num_threads: Option<usize>, op: F) -> Result<R>
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        match self {
+            ThreadPoolSource::Dedicated => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads.unwrap_or_else(num_cpus::get))
+                    .build()
+                    .map_err(|e| ExtractorError::Parallel(e.to_string()))?;
+                Ok(pool.install(op))
+            }
+            ThreadPoolSource::Global => Ok(op()),
+            ThreadPoolSource::Shared(pool) => Ok(pool.install(op)),
+        }
+    }
+
+    /// Run `op` on the calling thread while the tasks it spawns run on the
+    /// selected pool, so the caller can consume results as they are produced
+    pub fn in_place_scope<'scope, R, F>(&self, num_threads: Option<usize>, op: F) -> Result<R>
+    where
+        F: FnOnce(&rayon::Scope<'scope>) -> R,
+    {
+        match self {
+            ThreadPoolSource::Dedicated => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads.unwrap_or_else(num_cpus::get))
+                    .build()
+                    .map_err(|e| ExtractorError::Parallel(e.to_string()))?;
+                Ok(pool.in_place_scope(op))
+            }
+            ThreadPoolSource::Global => Ok(rayon::in_place_scope(op)),
+            ThreadPoolSource::Shared(pool) => Ok(pool.in_place_scope(op)),
+        }
+    }
+
+    /// Number of workers the selected pool runs with
+    pub fn num_threads(&self, num_threads: Option<usize>) -> usize {
+        match self {
+            ThreadPoolSource::Dedicated => num_threads.unwrap_or_else(num_cpus::get),
+            ThreadPoolSource::Global => rayon::current_num_threads(),
+            ThreadPoolSource::Shared(pool) => pool.current_num_threads(),
+        }
+    }
+}
+
+/// Flag that stops a long run from another thread. Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that hasn't been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every run holding a clone of this token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// Memory mapping options with safety checks
+#[cfg(feature = "parallel")]
 #[derive(Debug, Clone)]
 pub struct SafeMmapOptions {
     /// Maximum file size to memory map (in bytes)
@@ -17,6 +108,7 @@ pub struct SafeMmapOptions {
     pub read_only: bool,
 }
 
+#[cfg(feature = "parallel")]
 impl Default for SafeMmapOptions {
     fn default() -> Self {
         Self {
@@ -27,6 +119,7 @@ impl Default for SafeMmapOptions {
 }
 
 /// Safely create a memory map for a file
+#[cfg(feature = "parallel")]
 pub fn create_mmap(file: &File, options: &SafeMmapOptions) -> Result<Mmap> {
     let file_size = file.metadata()
         .map_err(|e| ExtractorError::io_error(e, "Failed to get file metadata"))?
@@ -69,6 +162,21 @@ pub fn find_line_end(data: &[u8], mut pos: usize) -> usize {
     pos
 }
 
+/// Find the first row boundary at or after `target`, scanning from `start`,
+/// which must itself be a row boundary. Line breaks inside quoted fields are
+/// skipped, so the result is always the start of a row (or `data.len()`).
+pub fn next_row_boundary(data: &[u8], start: usize, target: usize) -> usize {
+    let mut in_quotes = false;
+    for (i, &byte) in data.iter().enumerate().skip(start) {
+        match byte {
+            b'"' => in_quotes = !in_quotes,
+            b'\n' if !in_quotes && i + 1 >= target => return i + 1,
+            _ => {}
+        }
+    }
+    data.len()
+}
+
 /// Check if a file is likely to be CSV based on content
 pub fn is_csv_file(path: &Path) -> Result<bool> {
     let file = File::open(path)
@@ -91,28 +199,126 @@ pub fn is_csv_file(path: &Path) -> Result<bool> {
     Ok(has_commas && has_newlines && consistent_fields)
 }
 
+/// Algorithm used to fingerprint a file for index validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ChecksumAlgorithm {
+    /// Hash of the first 8KB only. This is what indices written before the
+    /// algorithm was recorded used, so it stays the deserialization default.
+    #[default]
+    FirstBlock,
+    /// xxHash64 of the full file contents
+    XxHash64,
+    /// xxHash64 of the file size plus `blocks` evenly spaced 64KB blocks
+    Sampled {
+        /// Number of blocks to sample (first and last block always included)
+        blocks: u32,
+    },
+    /// File size and modification time only; no content is read
+    SizeMtime,
+}
+
+/// Readable and seekable input, either a local file or a remote object
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Open `path` for random access. With the `object-store` feature, object
+/// store URLs are read with ranged GETs.
+pub fn open_seekable(path: &Path) -> Result<Box<dyn ReadSeek>> {
+    #[cfg(feature = "object-store")]
+    if crate::remote::is_remote(path) {
+        return Ok(Box::new(crate::remote::RemoteObject::open(path, &crate::RemoteConfig::default())?.reader()));
+    }
+    let file = File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+    Ok(Box::new(file))
+}
+
 /// Calculate file checksums for index validation
-pub fn calculate_file_checksum(path: &Path) -> Result<u64> {
-    let mut file = File::open(path)
+pub fn calculate_file_checksum(path: &Path, algorithm: ChecksumAlgorithm) -> Result<u64> {
+    let file = File::open(path)
         .map_err(|e| ExtractorError::io_error(e, path))?;
-    
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-    let mut buffer = [0u8; 8192];
-    
-    loop {
-        let bytes_read = file.read(&mut buffer)
-            .map_err(|e| ExtractorError::io_error(e, path))?;
-        
-        if bytes_read == 0 {
-            break;
+    checksum_file(&file, path, algorithm)
+}
+
+/// Calculate the checksum of an already open file. `path` is only used for error reporting.
+pub fn checksum_file(file: &File, path: &Path, algorithm: ChecksumAlgorithm) -> Result<u64> {
+    let metadata = file.metadata().map_err(|e| ExtractorError::io_error(e, path))?;
+    let modified = metadata
+        .modified()
+        .map(|time| time.duration_since(std::time::UNIX_EPOCH).unwrap_or_default());
+    checksum_reader(file, metadata.len(), modified, path, algorithm)
+}
+
+/// Calculate the checksum of any seekable source of `file_size` bytes, e.g. a
+/// remote object. `modified` is only needed for `SizeMtime`.
+pub(crate) fn checksum_reader<R: Read + Seek>(
+    mut handle: R,
+    file_size: u64,
+    modified: io::Result<std::time::Duration>,
+    path: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<u64> {
+    const SAMPLE_BLOCK: u64 = 64 * 1024;
+
+    let io_error = |e| ExtractorError::io_error(e, path);
+
+    match algorithm {
+        ChecksumAlgorithm::FirstBlock => {
+            let mut buffer = Vec::with_capacity(8192);
+            handle.seek(SeekFrom::Start(0)).map_err(io_error)?;
+            (&mut handle).take(8192).read_to_end(&mut buffer).map_err(io_error)?;
+
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            hasher.write(&buffer);
+            Ok(hasher.finish())
+        }
+        ChecksumAlgorithm::XxHash64 => {
+            let mut hasher = Xxh64::new(0);
+            let mut buffer = vec![0u8; SAMPLE_BLOCK as usize];
+            handle.seek(SeekFrom::Start(0)).map_err(io_error)?;
+            loop {
+                let bytes_read = handle.read(&mut buffer).map_err(io_error)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..bytes_read]);
+            }
+            Ok(hasher.digest())
+        }
+        ChecksumAlgorithm::Sampled { blocks } => {
+            let mut hasher = Xxh64::new(0);
+            hasher.update(&file_size.to_le_bytes());
+
+            let mut buffer = Vec::with_capacity(SAMPLE_BLOCK as usize);
+            let last_start = file_size.saturating_sub(SAMPLE_BLOCK);
+            for i in 0..blocks as u64 {
+                let offset = if blocks > 1 { last_start * i / (blocks as u64 - 1) } else { 0 };
+                buffer.clear();
+                handle.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+                (&mut handle).take(SAMPLE_BLOCK).read_to_end(&mut buffer).map_err(io_error)?;
+                hasher.update(&buffer);
+            }
+            Ok(hasher.digest())
+        }
+        ChecksumAlgorithm::SizeMtime => {
+            let modified = modified.map_err(io_error)?;
+
+            let mut hasher = Xxh64::new(0);
+            hasher.update(&file_size.to_le_bytes());
+            hasher.update(&modified.as_secs().to_le_bytes());
+            hasher.update(&modified.subsec_nanos().to_le_bytes());
+            Ok(hasher.digest())
         }
-        
-        use std::hash::Hasher;
-        hasher.write(&buffer[..bytes_read]);
     }
-    
-    use std::hash::Hasher;
-    Ok(hasher.finish())
+}
+
+/// Peak resident set size of this process in bytes since it started, from
+/// `VmHWM` in `/proc/self/status`; `None` where that isn't available
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kb: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kb * 1024)
 }
 
 /// Get the field count for a CSV row
@@ -143,37 +349,6 @@ fn check_consistent_fields(data: &[u8]) -> bool {
     }
 }
 
-/// Progress tracking helper
-#[cfg(feature = "progress-bars")]
-pub struct Progress {
-    bar: indicatif::ProgressBar,
-    total: u64,
-}
-
-#[cfg(feature = "progress-bars")]
-impl Progress {
-    pub fn new(total: u64, message: &str) -> Self {
-        let bar = indicatif::ProgressBar::new(total);
-        bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template("{msg} [{bar:40}] {pos}/{len} ({eta})")
-                .unwrap()
-                .progress_chars("=> ")
-        );
-        bar.set_message(message.to_string());
-        
-        Self { bar, total }
-    }
-
-    pub fn inc(&self, delta: u64) {
-        self.bar.inc(delta);
-    }
-
-    pub fn finish(&self) {
-        self.bar.finish();
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +362,15 @@ mod tests {
         assert_eq!(find_line_end(data, 15), 21);
     }
 
+    #[test]
+    fn test_next_row_boundary_skips_quoted_newlines() {
+        let data = b"a,\"x\ny\"\nb,c\n";
+        assert_eq!(next_row_boundary(data, 0, 1), 8);
+        assert_eq!(next_row_boundary(data, 0, 8), 8);
+        assert_eq!(next_row_boundary(data, 8, 9), data.len());
+        assert_eq!(next_row_boundary(b"a,b", 0, 1), 3);
+    }
+
     #[test]
     fn test_csv_detection() -> Result<()> {
         let mut file = NamedTempFile::new().unwrap();
@@ -202,4 +386,45 @@ mod tests {
         let line = b"field1,field2,\"field,3\",field4";
         assert_eq!(get_field_count(line), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_checksum_algorithms_detect_late_changes() -> Result<()> {
+        let mut file = NamedTempFile::new().unwrap();
+        let row = "x".repeat(99) + "\n";
+        for _ in 0..2000 {
+            file.write_all(row.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let algorithms = [
+            ChecksumAlgorithm::FirstBlock,
+            ChecksumAlgorithm::XxHash64,
+            ChecksumAlgorithm::Sampled { blocks: 4 },
+        ];
+        let before: Vec<u64> = algorithms
+            .iter()
+            .map(|a| calculate_file_checksum(file.path(), *a))
+            .collect::<Result<_>>()?;
+
+        // Change a byte near the end of the file, well past the first 8KB
+        file.as_file_mut().seek(SeekFrom::End(-10)).unwrap();
+        file.write_all(b"y").unwrap();
+        file.flush().unwrap();
+
+        let after: Vec<u64> = algorithms
+            .iter()
+            .map(|a| calculate_file_checksum(file.path(), *a))
+            .collect::<Result<_>>()?;
+
+        assert_eq!(before[0], after[0], "first block hash only sees the first 8KB");
+        assert_ne!(before[1], after[1]);
+        assert_ne!(before[2], after[2]);
+
+        // Stable when nothing changes
+        assert_eq!(
+            calculate_file_checksum(file.path(), ChecksumAlgorithm::SizeMtime)?,
+            calculate_file_checksum(file.path(), ChecksumAlgorithm::SizeMtime)?,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Dry-run validation of a filter setup. Everything that would make a run fail
+//! part-way, from a missing input to an unwritable output directory, is
+//! collected up front as a list of issues.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::output::ExistingOutput;
+
+/// A problem found by `ExtractorBuilder::validate` or `BioFilter::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The input is missing or can't be read
+    Input(String),
+    /// The input's header can't be parsed, or its first rows don't fit the schema
+    InputFormat(String),
+    /// A column used by a filter or setting isn't in the input's header
+    UnknownColumn {
+        /// Column name
+        column: String,
+        /// What refers to it, e.g. a filter description or `partition_by`
+        used_by: String,
+    },
+    /// A filter that can't be built, e.g. because its regex doesn't compile
+    InvalidFilter {
+        /// Column the filter applies to
+        column: String,
+        /// Why it was rejected
+        message: String,
+    },
+    /// The index can't be loaded or no longer matches the input
+    Index(String),
+    /// An output location that can't be written
+    Output {
+        /// File or directory that would be written
+        path: PathBuf,
+        /// Why it can't be
+        message: String,
+    },
+    /// Settings that can't be used together
+    Config(String),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Input(message) => write!(f, "input: {message}"),
+            ValidationIssue::InputFormat(message) => write!(f, "input format: {message}"),
+            ValidationIssue::UnknownColumn { column, used_by } => {
+                write!(f, "column '{column}' used by {used_by} is not in the input header")
+            }
+            ValidationIssue::InvalidFilter { column, message } => {
+                write!(f, "filter on '{column}': {message}")
+            }
+            ValidationIssue::Index(message) => write!(f, "index: {message}"),
+            ValidationIssue::Output { path, message } => write!(f, "output {}: {message}", path.display()),
+            ValidationIssue::Config(message) => write!(f, "config: {message}"),
+        }
+    }
+}
+
+/// Check that a single output file can be created at `path`
+pub(crate) fn check_output_file(path: &Path, existing: ExistingOutput) -> Option<ValidationIssue> {
+    let issue = |message: String| Some(ValidationIssue::Output { path: path.to_path_buf(), message });
+    if path.is_dir() {
+        return issue("is a directory".to_string());
+    }
+    if path.exists() && existing == ExistingOutput::Error {
+        return issue("already exists; enable overwrite or append to write to it".to_string());
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return issue(format!("directory {} does not exist", dir.display()));
+    }
+    // Opening without `create` leaves a missing file missing
+    match fs::OpenOptions::new().append(true).open(path) {
+        Err(e) if path.exists() => return issue(format!("can't be opened for writing: {e}")),
+        _ => {}
+    }
+    probe_dir(dir).and_then(issue)
+}
+
+/// Check that a directory of output files can be created at `path`
+pub(crate) fn check_output_dir(path: &Path) -> Option<ValidationIssue> {
+    let issue = |message: String| Some(ValidationIssue::Output { path: path.to_path_buf(), message });
+    // The directory and any missing parents are created by the run
+    let existing = path.ancestors().find(|dir| dir.as_os_str().is_empty() || dir.exists())?;
+    let existing = if existing.as_os_str().is_empty() { Path::new(".") } else { existing };
+    if !existing.is_dir() {
+        return issue(format!("{} is not a directory", existing.display()));
+    }
+    probe_dir(existing).and_then(issue)
+}
+
+/// Create and remove a file in `dir`, returning why that failed
+fn probe_dir(dir: &Path) -> Option<String> {
+    let probe = dir.join(format!(".extractor-validate-{}", std::process::id()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            None
+        }
+        Err(e) => Some(format!("directory {} is not writable: {e}", dir.display())),
+    }
+}
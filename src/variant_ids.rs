@@ -0,0 +1,310 @@
+//! Variant identifiers as GWAS sources write them (`rs429358`,
+//! `chr19:44908684:T:C`, `19_44908684_T_C`, `19-44908684-T-C`), normalized so
+//! that one variant compares equal however it is spelled.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::chrom::ChromKey;
+use crate::core::header_names;
+use crate::error::ExtractorError;
+use crate::filters::Filter;
+use crate::transform::RowTransform;
+use crate::Result;
+
+/// A parsed variant identifier
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VariantId {
+    /// dbSNP reference SNP ID
+    Rsid(u64),
+    /// Chromosome, 1-based position and alleles. The chromosome has no `chr`
+    /// prefix (`MT` for the mitochondrial one) and alleles are upper case.
+    Locus {
+        /// Chromosome
+        chrom: String,
+        /// Position
+        pos: u64,
+        /// Reference allele
+        ref_allele: String,
+        /// Alternate allele
+        alt_allele: String,
+    },
+}
+
+impl VariantId {
+    /// Parse an rsID or a `chrom:pos:ref:alt` ID whose fields are separated
+    /// by `:`, `_`, `-` or `/`. Returns `None` for anything else.
+    pub fn parse(id: &str) -> Option<Self> {
+        let id = id.trim();
+        if let Some(number) = id.get(..2).filter(|prefix| prefix.eq_ignore_ascii_case("rs")).and(id.get(2..)) {
+            return number.parse().ok().map(VariantId::Rsid);
+        }
+        let mut fields = id.rsplitn(4, [':', '_', '-', '/']);
+        let (alt, ref_, pos, chrom) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+        let allele = |allele: &str| {
+            let valid = !allele.is_empty() && allele.bytes().all(|b| b"ACGTNacgtn".contains(&b));
+            valid.then(|| allele.to_ascii_uppercase())
+        };
+        Some(VariantId::Locus {
+            chrom: chrom_name(chrom)?,
+            pos: pos.parse().ok()?,
+            ref_allele: allele(ref_)?,
+            alt_allele: allele(alt)?,
+        })
+    }
+}
+
+/// Chromosome name without a `chr` prefix, with `MT` for the mitochondrial
+/// chromosome; `None` if empty
+pub(crate) fn chrom_name(chrom: &str) -> Option<String> {
+    match ChromKey::parse(chrom) {
+        ChromKey::Autosome(n) => Some(n.to_string()),
+        ChromKey::X => Some("X".to_string()),
+        ChromKey::Y => Some("Y".to_string()),
+        ChromKey::M => Some("MT".to_string()),
+        ChromKey::Other(name) if !name.is_empty() => Some(name.to_string()),
+        ChromKey::Other(_) => None,
+    }
+}
+
+/// Normalized form: `rs429358` or `19:44908684:T:C`
+impl fmt::Display for VariantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantId::Rsid(number) => write!(f, "rs{number}"),
+            VariantId::Locus { chrom, pos, ref_allele, alt_allele } => {
+                write!(f, "{chrom}:{pos}:{ref_allele}:{alt_allele}")
+            }
+        }
+    }
+}
+
+/// rsIDs and the loci they name, e.g. from a dbSNP VCF converted to a table,
+/// so an rsID and a `chrom:pos:ref:alt` ID of the same variant match. An rsID
+/// may name several loci (one per alternate allele).
+#[derive(Debug, Default)]
+pub struct RsidMap {
+    loci: HashMap<u64, Vec<VariantId>>,
+    rsids: HashMap<VariantId, u64>,
+}
+
+impl RsidMap {
+    /// Load a table with a header row and rsID, chromosome, position,
+    /// reference and alternate allele columns, named in that order by `columns`
+    pub fn load(path: &Path, delimiter: u8, columns: [&str; 5]) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| ExtractorError::io_error(e, path))?;
+        Self::from_reader(file, delimiter, columns)
+    }
+
+    /// Read a table with a header row; see `load`. Rows that don't parse are skipped.
+    pub fn from_reader<R: Read>(input: R, delimiter: u8, columns: [&str; 5]) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let names: Vec<String> = header_names(reader.byte_headers()?).collect();
+        let indices = columns
+            .iter()
+            .map(|&column| {
+                names
+                    .iter()
+                    .position(|name| name == column)
+                    .ok_or_else(|| ExtractorError::column_not_found(column, &names))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut map = Self::default();
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let field = |i: usize| String::from_utf8_lossy(record.get(indices[i]).unwrap_or_default()).into_owned();
+            let (Some(VariantId::Rsid(rsid)), Some(locus)) = (
+                VariantId::parse(&field(0)),
+                VariantId::parse(&format!("{}:{}:{}:{}", field(1).trim(), field(2).trim(), field(3).trim(), field(4).trim())),
+            ) else {
+                continue;
+            };
+            map.rsids.entry(locus.clone()).or_insert(rsid);
+            map.loci.entry(rsid).or_default().push(locus);
+        }
+        Ok(map)
+    }
+
+    /// Loci of an rsID
+    pub fn loci(&self, rsid: u64) -> &[VariantId] {
+        self.loci.get(&rsid).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// rsID of a locus
+    pub fn rsid(&self, locus: &VariantId) -> Option<u64> {
+        self.rsids.get(locus).copied()
+    }
+
+    /// Every known form of `id`: itself, and its loci or rsID
+    fn equivalents(&self, id: VariantId) -> Vec<VariantId> {
+        let mut forms = match &id {
+            VariantId::Rsid(rsid) => self.loci(*rsid).to_vec(),
+            locus => self.rsid(locus).map(VariantId::Rsid).into_iter().collect(),
+        };
+        forms.push(id);
+        forms
+    }
+}
+
+/// Index of `column` in the main input, or `ColumnNotFound`
+fn column_index(column: &str, headers: &HashMap<String, usize>) -> Result<usize> {
+    headers
+        .get(column)
+        .copied()
+        .ok_or_else(|| ExtractorError::column_not_in_headers(column, headers))
+}
+
+/// Keeps rows whose `column` is one of the variants, however either is
+/// written. With an `RsidMap`, rsIDs also match their loci and vice versa.
+/// Values that don't parse as a variant never match.
+pub struct VariantFilter {
+    column: String,
+    variants: HashSet<VariantId>,
+    /// The variants as given, for the description
+    names: Vec<String>,
+}
+
+impl VariantFilter {
+    /// Keep rows whose `column` is one of `variants`. Fails if one of them
+    /// doesn't parse.
+    pub fn new<I, S>(column: String, variants: I, rsids: Option<&RsidMap>) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter = Self { column, variants: HashSet::new(), names: Vec::new() };
+        for variant in variants {
+            let variant = variant.as_ref().trim();
+            let id = VariantId::parse(variant).ok_or_else(|| {
+                ExtractorError::Config(format!("'{variant}' is not an rsID or chrom:pos:ref:alt variant"))
+            })?;
+            match rsids {
+                Some(rsids) => filter.variants.extend(rsids.equivalents(id)),
+                None => {
+                    filter.variants.insert(id);
+                }
+            }
+            filter.names.push(variant.to_string());
+        }
+        Ok(filter)
+    }
+}
+
+impl Filter for VariantFilter {
+    fn apply(&self, row: &ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = String::from_utf8_lossy(row.get(column_index(&self.column, headers)?).unwrap_or_default());
+        Ok(VariantId::parse(&value).is_some_and(|id| self.variants.contains(&id)))
+    }
+
+    fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    fn description(&self) -> String {
+        format!("{} is variant {}", self.column, self.names.join(" or "))
+    }
+}
+
+/// Row transform that appends the normalized form of the variant in `column`,
+/// or an empty value if it doesn't parse. With an `RsidMap`, rsIDs with a
+/// single locus are written as that locus.
+pub struct NormalizedVariant {
+    column: String,
+    rsids: Option<Arc<RsidMap>>,
+    output_column: String,
+}
+
+impl NormalizedVariant {
+    /// Append the normalized ID of `column` as `variant_id`
+    pub fn new(column: String) -> Self {
+        Self { column, rsids: None, output_column: "variant_id".to_string() }
+    }
+
+    /// Write rsIDs as their locus
+    pub fn with_rsids(mut self, rsids: Arc<RsidMap>) -> Self {
+        self.rsids = Some(rsids);
+        self
+    }
+
+    /// Name the appended column (default: `variant_id`)
+    pub fn named(mut self, output_column: &str) -> Self {
+        self.output_column = output_column.to_string();
+        self
+    }
+}
+
+impl RowTransform for NormalizedVariant {
+    fn header(&self, header: &ByteRecord) -> Option<ByteRecord> {
+        let mut header = header.clone();
+        header.push_field(self.output_column.as_bytes());
+        Some(header)
+    }
+
+    fn apply(&self, row: &mut ByteRecord, headers: &HashMap<String, usize>) -> Result<bool> {
+        let value = String::from_utf8_lossy(row.get(column_index(&self.column, headers)?).unwrap_or_default());
+        let normalized = match VariantId::parse(&value) {
+            Some(VariantId::Rsid(rsid)) => match self.rsids.as_deref().map(|rsids| rsids.loci(rsid)) {
+                Some([locus]) => locus.to_string(),
+                _ => VariantId::Rsid(rsid).to_string(),
+            },
+            Some(locus) => locus.to_string(),
+            None => String::new(),
+        };
+        row.push_field(normalized.as_bytes());
+        Ok(true)
+    }
+
+    fn description(&self) -> String {
+        format!("append normalized variant of {} as {}", self.column, self.output_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_ids() -> Result<()> {
+        let apoe = VariantId::parse("chr19:44908684:T:C").expect("parses");
+        for spelling in ["19_44908684_T_C", "19-44908684-t-c", " 19:44908684:T/C", "CHR19:44908684_T_C"] {
+            assert_eq!(VariantId::parse(spelling).as_ref(), Some(&apoe), "{spelling}");
+        }
+        assert_eq!(apoe.to_string(), "19:44908684:T:C");
+        assert_eq!(VariantId::parse("chrM:73:A:G").map(|id| id.to_string()).as_deref(), Some("MT:73:A:G"));
+        assert_eq!(VariantId::parse("RS429358"), Some(VariantId::Rsid(429358)));
+        for invalid in ["rsX", "19:44908684:T", "19:pos:T:C", "19:44908684:T:<DEL>", ""] {
+            assert_eq!(VariantId::parse(invalid), None, "{invalid}");
+        }
+
+        let table = "rsid\tchrom\tpos\tref\talt\nrs429358\t19\t44908684\tT\tC\nrs7412\t19\t44908822\tC\tT\n";
+        let rsids = Arc::new(RsidMap::from_reader(table.as_bytes(), b'\t', ["rsid", "chrom", "pos", "ref", "alt"])?);
+        assert_eq!(rsids.rsid(&apoe), Some(429358));
+
+        let headers: HashMap<String, usize> = [("SNP".to_string(), 0)].into_iter().collect();
+        let filter = VariantFilter::new("SNP".to_string(), ["rs429358"], Some(&rsids))?;
+        for (value, kept) in [("rs429358", true), ("chr19_44908684_T_C", true), ("19:44908822:C:T", false), ("NA", false)] {
+            assert_eq!(filter.apply(&ByteRecord::from(vec![value]), &headers)?, kept, "{value}");
+        }
+        let plain = VariantFilter::new("SNP".to_string(), ["19:44908684:T:C"], None)?;
+        assert!(!plain.apply(&ByteRecord::from(vec!["rs429358"]), &headers)?);
+        assert!(VariantFilter::new("SNP".to_string(), ["APOE e4"], None).is_err());
+
+        let normalize = NormalizedVariant::new("SNP".to_string()).with_rsids(rsids);
+        let mut row = ByteRecord::from(vec!["rs7412"]);
+        normalize.apply(&mut row, &headers)?;
+        assert_eq!(row, ByteRecord::from(vec!["rs7412", "19:44908822:C:T"]));
+        let mut row = ByteRecord::from(vec!["chr1_12345_a_g"]);
+        normalize.apply(&mut row, &headers)?;
+        assert_eq!(row, ByteRecord::from(vec!["chr1_12345_a_g", "1:12345:A:G"]));
+        Ok(())
+    }
+}
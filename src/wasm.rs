@@ -0,0 +1,109 @@
+//! Browser bindings, built with wasm-pack when the `wasm` feature is enabled.
+//! Uploads are filtered in memory on the streaming path; build with
+//! `--no-default-features --features wasm` so rayon, crossbeam and memmap2 are
+//! left out.
+
+// The glue generated by wasm-bindgen's macros is unsafe code
+#![allow(unsafe_code)]
+
+use wasm_bindgen::prelude::*;
+
+use crate::core::BioFilter;
+use crate::filters::{ColumnFilter, FilterCondition};
+use crate::output::OutputMode;
+use crate::{Config, ExtractorError};
+
+fn to_js_err(err: ExtractorError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Filters a CSV file held in memory, e.g. one the user is about to upload
+#[wasm_bindgen(js_name = CsvFilter)]
+pub struct WasmFilter {
+    inner: BioFilter,
+}
+
+#[wasm_bindgen(js_class = CsvFilter)]
+impl WasmFilter {
+    /// `delimiter` is a single ASCII character (default ","). With
+    /// `normalize` set, rows are re-serialized instead of copied verbatim.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        delimiter: Option<String>,
+        has_headers: Option<bool>,
+        normalize: Option<bool>,
+    ) -> Result<WasmFilter, JsError> {
+        let mut config = Config {
+            has_headers: has_headers.unwrap_or(true),
+            parallel: false,
+            ..Config::default()
+        };
+        if let Some(delimiter) = delimiter {
+            match delimiter.as_bytes() {
+                [byte] => config.delimiter = *byte,
+                _ => {
+                    return Err(to_js_err(ExtractorError::Config(format!(
+                        "delimiter must be a single ASCII character, got {delimiter:?}"
+                    ))))
+                }
+            }
+        }
+        if normalize.unwrap_or(false) {
+            config.output_mode = OutputMode::Normalized;
+        }
+        Ok(Self {
+            inner: BioFilter::streaming(config),
+        })
+    }
+
+    /// Add a filter on `column`. `condition` is a `FilterCondition` as JSON,
+    /// e.g. `{"Numeric":{"LessThan":5e-8}}`, `{"Equals":"TP53"}` or `"NotEmpty"`.
+    #[wasm_bindgen(js_name = addFilter)]
+    pub fn add_filter(&mut self, column: String, condition: &str) -> Result<(), JsError> {
+        let condition: FilterCondition = serde_json::from_str(condition)
+            .map_err(|e| to_js_err(ExtractorError::Config(format!("invalid filter condition: {e}"))))?;
+        let filter = ColumnFilter::new(column, condition).map_err(to_js_err)?;
+        self.inner.add_filter(Box::new(filter));
+        Ok(())
+    }
+
+    /// Filter `input` and return the matched rows, header included
+    pub fn run(&self, input: &[u8]) -> Result<FilterResult, JsError> {
+        let mut output = Vec::with_capacity(input.len());
+        let stats = self.inner.process_stream(input, &mut output).map_err(to_js_err)?;
+        Ok(FilterResult {
+            output,
+            rows_processed: stats.rows_processed as f64,
+            rows_matched: stats.rows_matched as f64,
+        })
+    }
+}
+
+/// Output of `CsvFilter.run`
+#[wasm_bindgen]
+pub struct FilterResult {
+    output: Vec<u8>,
+    rows_processed: f64,
+    rows_matched: f64,
+}
+
+#[wasm_bindgen]
+impl FilterResult {
+    /// Filtered CSV bytes, ready to wrap in a `Blob` for upload
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> Vec<u8> {
+        self.output.clone()
+    }
+
+    /// Number of data rows read
+    #[wasm_bindgen(getter, js_name = rowsProcessed)]
+    pub fn rows_processed(&self) -> f64 {
+        self.rows_processed
+    }
+
+    /// Number of data rows that passed every filter
+    #[wasm_bindgen(getter, js_name = rowsMatched)]
+    pub fn rows_matched(&self) -> f64 {
+        self.rows_matched
+    }
+}
@@ -0,0 +1,315 @@
+//! Watch mode: re-run a filter pipeline whenever an input file changes.
+//! Built on `notify`; only available with the `watch` feature.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{select, unbounded, Receiver, Sender};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::ExtractorError;
+use crate::{ProcessingStats, Result};
+
+/// Shortest wait between checks for settled inputs, so a zero debounce
+/// doesn't spin
+const MIN_TICK: Duration = Duration::from_millis(10);
+
+/// Events emitted by a running watch
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// A changed input settled and the pipeline is about to run on it
+    Started {
+        /// Input that changed
+        path: PathBuf,
+    },
+    /// The pipeline finished successfully
+    Completed {
+        /// Input that was processed
+        path: PathBuf,
+        /// Statistics returned by the pipeline
+        stats: Box<ProcessingStats>,
+    },
+    /// The pipeline returned an error; watching continues
+    Failed {
+        /// Input that was processed
+        path: PathBuf,
+        /// The error returned by the pipeline
+        error: ExtractorError,
+    },
+}
+
+/// Configuration for watching input paths
+#[derive(Debug, Clone)]
+pub struct Watch {
+    paths: Vec<PathBuf>,
+    debounce: Duration,
+    recursive: bool,
+    extensions: Option<Vec<String>>,
+    excluded: Vec<PathBuf>,
+}
+
+impl Watch {
+    /// Watch the given files or directories
+    pub fn new<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            paths: paths.into_iter().map(Into::into).collect(),
+            debounce: Duration::from_secs(2),
+            recursive: false,
+            extensions: None,
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Wait this long after the last change to a file before processing it (default: 2s).
+    /// Large uploads produce a stream of modify events; this avoids reading half-written files.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Watch directories recursively (default: false)
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Only react to files with one of these extensions (e.g. `["csv", "tsv"]`)
+    pub fn with_extensions<S: Into<String>>(mut self, extensions: impl IntoIterator<Item = S>) -> Self {
+        self.extensions = Some(extensions.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Ignore changes to these files, or to anything under these directories.
+    /// Outputs the pipeline writes inside a watched directory must be listed
+    /// here, or each run would trigger the next.
+    pub fn exclude<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.excluded.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Start watching in a background thread.
+    ///
+    /// `pipeline` is called with the path of each changed input once it has been
+    /// quiet for the debounce interval. It usually builds a `BioFilter` for that
+    /// path, adds filters and calls `process()`. Paths passed to
+    /// [`exclude`](Self::exclude) never reach it.
+    pub fn spawn<F>(self, mut pipeline: F) -> Result<WatchHandle>
+    where
+        F: FnMut(&Path) -> Result<ProcessingStats> + Send + 'static,
+    {
+        let (raw_tx, raw_rx) = unbounded::<PathBuf>();
+        let (event_tx, event_rx) = unbounded();
+        let (stop_tx, stop_rx) = unbounded::<()>();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res
+                && (event.kind.is_create() || event.kind.is_modify())
+            {
+                for path in event.paths {
+                    let _ = raw_tx.send(path);
+                }
+            }
+        })
+        .map_err(|e| ExtractorError::Other(format!("Failed to start file watcher: {e}")))?;
+
+        let mode = if self.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for path in &self.paths {
+            watcher.watch(path, mode).map_err(|e| {
+                ExtractorError::Other(format!("Failed to watch {}: {e}", path.display()))
+            })?;
+        }
+
+        let debounce = self.debounce;
+        let tick = (debounce / 4).max(MIN_TICK);
+        let extensions = self.extensions;
+        let excluded = self
+            .excluded
+            .iter()
+            .map(|path| std::path::absolute(path).map_err(|e| ExtractorError::io_error(e, path)))
+            .collect::<Result<Vec<_>>>()?;
+        let thread = std::thread::spawn(move || {
+            let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+            loop {
+                select! {
+                    recv(stop_rx) -> _ => break,
+                    recv(raw_rx) -> path => match path {
+                        Ok(path) => {
+                            if matches_extension(&path, extensions.as_deref())
+                                && !is_excluded(&path, &excluded)
+                            {
+                                pending.insert(path, Instant::now());
+                            }
+                        }
+                        Err(_) => break,
+                    },
+                    default(tick) => {}
+                }
+
+                let ready: Vec<PathBuf> = pending
+                    .iter()
+                    .filter(|(_, changed)| changed.elapsed() >= debounce)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+
+                for path in ready {
+                    pending.remove(&path);
+                    if !path.is_file() {
+                        continue;
+                    }
+                    let _ = event_tx.send(WatchEvent::Started { path: path.clone() });
+                    let event = match pipeline(&path) {
+                        Ok(stats) => WatchEvent::Completed { path, stats: Box::new(stats) },
+                        Err(error) => WatchEvent::Failed { path, error },
+                    };
+                    if event_tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(WatchHandle {
+            events: event_rx,
+            stop: stop_tx,
+            thread: Some(thread),
+            _watcher: watcher,
+        })
+    }
+}
+
+/// Handle to a running watch. Dropping it stops watching.
+pub struct WatchHandle {
+    events: Receiver<WatchEvent>,
+    stop: Sender<()>,
+    thread: Option<JoinHandle<()>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl WatchHandle {
+    /// Channel of pipeline events
+    pub fn events(&self) -> &Receiver<WatchEvent> {
+        &self.events
+    }
+
+    /// Stop watching and wait for an in-progress run to finish
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Whether `path` is one of the absolute `excluded` paths or under one of them
+fn is_excluded(path: &Path, excluded: &[PathBuf]) -> bool {
+    std::path::absolute(path).is_ok_and(|path| excluded.iter().any(|excluded| path.starts_with(excluded)))
+}
+
+fn matches_extension(path: &Path, extensions: Option<&[String]>) -> bool {
+    let Some(extensions) = extensions else {
+        return true;
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_extension_filter() {
+        let csv = ["csv".to_string()];
+        assert!(matches_extension(Path::new("a/b.CSV"), Some(&csv)));
+        assert!(!matches_extension(Path::new("a/b.tsv"), Some(&csv)));
+        assert!(matches_extension(Path::new("a/b.tsv"), None));
+    }
+
+    #[test]
+    fn test_reprocess_on_change() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("gwas.csv");
+
+        let handle = Watch::new([dir.path()])
+            .with_debounce(Duration::from_millis(100))
+            .spawn(|path| {
+                Ok(ProcessingStats {
+                    input_size: path.metadata().map(|m| m.len()).unwrap_or(0),
+                    ..ProcessingStats::default()
+                })
+            })?;
+
+        let mut file = std::fs::File::create(&input).unwrap();
+        writeln!(file, "id,value\nrs1,0.5").unwrap();
+        file.sync_all().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut completed = None;
+        while Instant::now() < deadline {
+            if let Ok(WatchEvent::Completed { path, stats }) =
+                handle.events().recv_timeout(Duration::from_millis(200))
+            {
+                completed = Some((path, stats));
+                break;
+            }
+        }
+
+        let (path, stats) = completed.expect("pipeline should run after the file changes");
+        assert_eq!(path.file_name(), input.file_name());
+        assert!(stats.input_size > 0);
+        handle.stop();
+        Ok(())
+    }
+
+    #[test]
+    fn test_excluded_outputs_do_not_retrigger() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("gwas.csv"), dir.path().join("hits.csv"));
+
+        let written = output.clone();
+        let handle = Watch::new([dir.path()])
+            .with_debounce(Duration::ZERO)
+            .with_extensions(["csv"])
+            .exclude([&output])
+            .spawn(move |path| {
+                std::fs::copy(path, &written).map_err(|e| ExtractorError::io_error(e, &written))?;
+                Ok(ProcessingStats::default())
+            })?;
+        std::fs::write(&input, "id,value\nrs1,0.5\n").unwrap();
+
+        // Runs until the watch has been quiet for a while, or gives up
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut runs = Vec::new();
+        while Instant::now() < deadline
+            && let Ok(event) = handle.events().recv_timeout(Duration::from_millis(500))
+        {
+            if let WatchEvent::Completed { path, .. } = event {
+                runs.push(path);
+            }
+        }
+        assert!(!runs.is_empty());
+        assert!(runs.iter().all(|path| path.file_name() == input.file_name()));
+        assert!(output.is_file());
+        handle.stop();
+        Ok(())
+    }
+}
@@ -1,248 +1,219 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_function_one() {
-        // Arrange
-        let input = "test input";
-        let expected = "expected output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_one(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a valid input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a valid input");
-        assert_ne!(result, "unexpected output", "Result should not be 'unexpected output' for a valid input");
-    }
-
-    #[test]
-    fn test_function_two() {
-        // Arrange
-        let input = 42;
-        let expected = 84;
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_two(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a positive input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(result > 0, "Result should be positive for a positive input");
-        assert_ne!(result, 0, "Result should not be zero for a non-zero input");
-    }
-
-    #[test]
-    fn test_function_three() {
-        // Arrange
-        let input = vec![1, 2, 3];
-        let expected = vec![2, 4, 6];
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_three(&input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a non-empty vector");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a non-empty vector input");
-        assert_eq!(result.len(), input.len(), "Result length should match input length");
-        assert_ne!(result, vec![1, 2, 3], "Result should not be the same as input vector");
-    }
-
-    #[test]
-    fn test_function_four() {
-        // Arrange
-        let input = "another test input";
-        let expected = "another expected output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_four(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a valid input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a valid input");
-        assert_ne!(result, "unexpected output", "Result should not be 'unexpected output' for a valid input");
-    }
-
-    #[test]
-    fn test_function_one_empty_input() {
-        // Arrange
-        let input = "";
-        let expected = "empty output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_one(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for an empty input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(result.is_empty(), "Result should be empty for empty input");
-        assert_ne!(result, "non-empty output", "Result should not be non-empty for empty input");
+//! End-to-end correctness checks: every processing mode must write the same
+//! rows for the same input, whatever quoting, line endings or encoding it uses.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use extractor::filters::ColumnFilter;
+use extractor::testdata::SyntheticData;
+use extractor::{ChunkingConfig, Config, FilterCondition, OutputMode, Result};
+use proptest::prelude::*;
+use tempfile::NamedTempFile;
+
+const HEADER: [&str; 4] = ["id", "gene", "value", "note"];
+
+/// Filters used against the generated tables; none of them can fail on odd input
+fn make_filter(choice: usize, columns: &[String]) -> Result<Option<ColumnFilter>> {
+    let column = |i: usize| columns[i % columns.len()].clone();
+    let filter = match choice {
+        0 => return Ok(None),
+        // Exercises the NA/empty token handling
+        1 => ColumnFilter::new(column(2), FilterCondition::NotEmpty)?,
+        2 => ColumnFilter::new(column(3), FilterCondition::Contains("a".to_string()))?,
+        _ => ColumnFilter::new(column(2), FilterCondition::Regex("^-?[0-9]".to_string()))?,
+    };
+    Ok(Some(filter))
+}
+
+/// Run one extraction and return the number of matched rows and the output bytes
+fn run(input: &Path, config: Config, filter: usize) -> Result<(u64, Vec<u8>)> {
+    let columns: Vec<String> = csv::ReaderBuilder::new()
+        .delimiter(config.delimiter)
+        .from_path(input)?
+        .headers()?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let output = NamedTempFile::new()?;
+    let mut extractor = extractor::builder(input.to_path_buf(), output.path().to_path_buf())
+        .with_config(config)
+        .overwrite(true)
+        .build()?;
+    if let Some(filter) = make_filter(filter, &columns)? {
+        extractor.add_filter(Box::new(filter));
     }
-
-    #[test]
-    fn test_function_two_negative_input() {
-        // Arrange
-        let input = -42;
-        let expected = -84;
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_two(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for negative input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(result.is_negative(), "Result should be negative for negative input");
-        assert_ne!(result, 0, "Result should not be zero for non-zero input");
+    let stats = extractor.process()?;
+    Ok((stats.rows_matched, fs::read(output.path())?))
+}
+
+/// Configurations that must all agree with a plain sequential run
+fn parallel_configs(chunk_size: usize) -> Vec<Config> {
+    let fixed = ChunkingConfig { adaptive: false, ..ChunkingConfig::default() };
+    vec![
+        Config { chunk_size, num_threads: Some(1), chunking: fixed.clone(), ..Config::default() },
+        Config { chunk_size, num_threads: Some(4), chunking: fixed, ..Config::default() },
+        Config {
+            chunk_size,
+            num_threads: Some(3),
+            chunking: ChunkingConfig { max_in_flight: Some(1), ..ChunkingConfig::default() },
+            ..Config::default()
+        },
+        Config::default(),
+    ]
+}
+
+fn assert_modes_agree(input: &[u8], filter: usize, chunk_size: usize) -> Result<()> {
+    let mut file = NamedTempFile::new()?;
+    std::io::Write::write_all(&mut file, input)?;
+
+    for output_mode in [OutputMode::Passthrough, OutputMode::Normalized] {
+        let sequential = Config { parallel: false, output_mode, ..Config::default() };
+        let expected = run(file.path(), sequential, filter)?;
+        if filter == 0 && output_mode == OutputMode::Passthrough {
+            assert_eq!(
+                String::from_utf8_lossy(&expected.1),
+                String::from_utf8_lossy(&without_blank_lines(input)),
+                "passthrough without filters should reproduce the input"
+            );
+        }
+
+        for config in parallel_configs(chunk_size) {
+            let config = Config { output_mode, ..config };
+            let described = format!("{output_mode:?} chunk_size={} threads={:?}", config.chunk_size, config.num_threads);
+            let actual = run(file.path(), config, filter)?;
+            assert_eq!(actual.0, expected.0, "matched rows differ: {described}");
+            assert_eq!(
+                String::from_utf8_lossy(&actual.1),
+                String::from_utf8_lossy(&expected.1),
+                "output differs from sequential run: {described}"
+            );
+        }
     }
-
-    #[test]
-    fn test_function_three_empty_vector() {
-        // Arrange
-        let input = vec![];
-        let expected = vec![];
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_three(&input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for an empty vector");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(result.is_empty(), "Result should be empty for an empty vector input");
-        assert_ne!(result, vec![1], "Result should not be a non-empty vector for an empty vector input");
+    Ok(())
+}
+
+/// The input as the extractor reproduces it: blank lines are dropped and a
+/// missing final newline is added
+fn without_blank_lines(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + 1);
+    let mut in_quotes = false;
+    let mut line_start = true;
+    let mut i = 0;
+    while i < input.len() {
+        let byte = input[i];
+        if line_start && !in_quotes {
+            let rest = &input[i..];
+            if rest.starts_with(b"\n") {
+                i += 1;
+                continue;
+            }
+            if rest.starts_with(b"\r\n") {
+                i += 2;
+                continue;
+            }
+        }
+        if byte == b'"' {
+            in_quotes = !in_quotes;
+        }
+        out.push(byte);
+        line_start = byte == b'\n';
+        i += 1;
     }
-
-    #[test]
-    fn test_function_four_special_characters() {
-        // Arrange
-        let input = "!@#$%^&*()";
-        let expected = "special output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_four(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for special characters");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for special characters input");
-        assert!(result.contains("special"), "Result should contain 'special' for special characters input");
-        assert_ne!(result, "unexpected output", "Result should not be 'unexpected output' for special characters input");
+    if !out.is_empty() && !out.ends_with(b"\n") {
+        out.push(b'\n');
     }
-
-    #[test]
-    fn test_function_one_long_input() {
-        // Arrange
-        let input = "a".repeat(1000);
-        let expected = "long output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_one(&input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a long input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a long input");
-        assert_ne!(result, "short output", "Result should not be 'short output' for a long input");
+    out
+}
+
+fn to_csv(rows: &[[String; 4]], crlf: bool, quote_all: bool) -> Vec<u8> {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(if crlf { csv::Terminator::CRLF } else { csv::Terminator::Any(b'\n') })
+        .quote_style(if quote_all { csv::QuoteStyle::Always } else { csv::QuoteStyle::Necessary })
+        .from_writer(Vec::new());
+    writer.write_record(HEADER).unwrap();
+    for row in rows {
+        writer.write_record(row).unwrap();
     }
+    writer.into_inner().unwrap()
+}
+
+fn field() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9 ._-]{0,12}",
+        "-?[0-9]{1,3}\\.[0-9]{1,4}",
+        prop::sample::select(vec!["", "NA", "N/A", "null", ".", "NaN"]).prop_map(str::to_string),
+        // Quotes, delimiters and embedded line breaks
+        "[\"',\r\n ab]{1,10}",
+        "\\PC{1,8}",
+    ]
+}
+
+fn table() -> impl Strategy<Value = Vec<[String; 4]>> {
+    prop::collection::vec([field(), field(), field(), field()], 0..40)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
 
     #[test]
-    fn test_function_two_large_input() {
-        // Arrange
-        let input = 1_000_000;
-        let expected = 2_000_000;
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_two(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a large input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(result > 0, "Result should be positive for a large input");
-        assert_ne!(result, 0, "Result should not be zero for a non-zero input");
+    fn prop_parallel_matches_sequential(
+        rows in table(),
+        crlf in any::<bool>(),
+        quote_all in any::<bool>(),
+        filter in 0..4usize,
+        chunk_size in 1..96usize,
+    ) {
+        assert_modes_agree(&to_csv(&rows, crlf, quote_all), filter, chunk_size).unwrap();
     }
-
-    #[test]
-    fn test_function_three_large_vector() {
-        // Arrange
-        let input: Vec<i32> = (1..=1000).collect();
-        let expected: Vec<i32> = input.iter().map(|&x| x * 2).collect();
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_three(&input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a large vector");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a large vector input");
-        assert_eq!(result.len(), input.len(), "Result length should match input length");
-        assert_ne!(result, input, "Result should not be the same as input vector");
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_corpus_parallel_matches_sequential() -> Result<()> {
+    let files = corpus();
+    assert!(!files.is_empty());
+    for path in files {
+        let input = fs::read(&path)?;
+        for filter in 0..4 {
+            for chunk_size in [1, 7, 32] {
+                assert_modes_agree(&input, filter, chunk_size)?;
+            }
+        }
     }
-
-    #[test]
-    fn test_function_four_unicode_input() {
-        // Arrange
-        let input = "こんにちは";
-        let expected = "unicode output";
-
-        // Act
-        let start_time = std::time::Instant::now();
-        let result = function_four(input);
-        let duration = start_time.elapsed();
-
-        // Assert
-        assert_eq!(result, expected, "The function did not return the expected result for a unicode input");
-        assert!(duration.as_millis() < 10, "The function took too long to execute");
-
-        // Additional checks
-        assert!(!result.is_empty(), "Result should not be empty for a unicode input");
-        assert!(result.contains("unicode"), "Result should contain 'unicode' for a unicode input");
-        assert_ne!(result, "unexpected output", "Result should not be 'unexpected output' for a unicode input");
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_gwas_parallel_matches_sequential() -> Result<()> {
+    let input = SyntheticData::gwas(5_000)
+        .with_columns(["variant_id", "gene", "p_value", "chromosome"])
+        .to_bytes()?;
+    for filter in 0..4 {
+        assert_modes_agree(&input, filter, 4096)?;
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[test]
+fn test_tab_delimited() -> Result<()> {
+    let input = SyntheticData::expression(500).with_delimiter(b'\t').to_bytes()?;
+    let mut file = NamedTempFile::new()?;
+    std::io::Write::write_all(&mut file, &input)?;
+
+    let sequential = run(file.path(), Config { delimiter: b'\t', parallel: false, ..Config::default() }, 0)?;
+    let parallel = run(file.path(), Config { delimiter: b'\t', chunk_size: 512, ..Config::default() }, 0)?;
+    assert_eq!(sequential.0, 500);
+    assert_eq!(sequential, parallel);
+    assert_eq!(sequential.1, input);
+    Ok(())
+}